@@ -1,14 +1,22 @@
-use super::models::{NewAnnotationRequest, PatchAnnotationRequest};
+use super::models::{
+    AnnotationError, AnnotationSearch, ExportFormat, ImportFormat, ImportReport, NewAnnotationRequest,
+    PaginatedAnnotations, PatchAnnotationRequest, VALID_ANNOTATION_TYPES,
+};
 use crate::app::annotations::models::Annotation;
-use crate::app::annotations::service;
+use crate::app::annotations::{export, importers, service};
 use crate::app::authentication::models::AuthToken;
 use crate::app::error::ProsaError;
 use crate::app::server::LOCKS;
 use crate::app::sync::models::{ChangeLogAction, ChangeLogEntityType};
 use crate::app::{books, sync};
-use axum::extract::Path;
-use axum::http::StatusCode;
+use axum::body::Bytes;
+use axum::extract::{Path, Query};
+use axum::http::{StatusCode, header};
 use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::task::spawn_blocking;
 
 pub async fn add_annotation_handler(
     Extension(token): Extension<AuthToken>,
@@ -45,12 +53,63 @@ pub async fn get_annotation_handler(
     Ok(Json(annotation))
 }
 
-pub async fn list_annotations_handler(Path(book_id): Path<String>) -> Result<Json<Vec<String>>, ProsaError> {
+pub async fn list_annotations_handler(
+    Path(book_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Vec<String>>, ProsaError> {
+    let annotation_type = params.get("type").map(String::as_str);
+    if annotation_type.is_some_and(|t| !VALID_ANNOTATION_TYPES.contains(&t)) {
+        return Err(AnnotationError::InvalidAnnotationType.into());
+    }
+
     let lock = LOCKS.get_book_lock(&book_id).await;
     let _guard = lock.read().await;
 
     books::service::get_book(&book_id).await?;
-    let annotations = service::get_annotations(&book_id).await;
+    let annotations = service::get_annotations(&book_id, annotation_type).await;
+
+    Ok(Json(annotations))
+}
+
+pub async fn search_annotations_handler(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<PaginatedAnnotations>, ProsaError> {
+    let page = match params.get("page").map(|t| t.parse::<i64>()) {
+        Some(Ok(p)) => Some(p),
+        None => None,
+        _ => return Err(AnnotationError::InvalidPagination.into()),
+    };
+
+    let size = match params.get("size").map(|t| t.parse::<i64>()) {
+        Some(Ok(s)) => Some(s),
+        None => None,
+        _ => return Err(AnnotationError::InvalidPagination.into()),
+    };
+
+    let annotation_type = params.get("type").map(ToString::to_string);
+    if annotation_type
+        .as_deref()
+        .is_some_and(|t| !VALID_ANNOTATION_TYPES.contains(&t))
+    {
+        return Err(AnnotationError::InvalidAnnotationType.into());
+    }
+
+    let user_id = match params.get("user_id") {
+        Some(id) => id,
+        None => token.role.get_user(),
+    };
+
+    let search = AnnotationSearch {
+        query: params.get("q").map(ToString::to_string),
+        book_id: params.get("book_id").map(ToString::to_string),
+        tag: params.get("tag").map(ToString::to_string),
+        annotation_type,
+        from: parse_timestamp(&params, "from")?,
+        to: parse_timestamp(&params, "to")?,
+    };
+
+    let annotations = service::search_annotations(user_id, search, page, size).await?;
 
     Ok(Json(annotations))
 }
@@ -87,7 +146,7 @@ pub async fn patch_annotation_handler(
 
     let book = books::service::get_book(&book_id).await?;
 
-    service::patch_annotation(&annotation_id, request.note).await?;
+    service::patch_annotation(&annotation_id, request).await?;
 
     sync::service::log_change(
         &book_id,
@@ -100,3 +159,101 @@ pub async fn patch_annotation_handler(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+pub async fn export_book_annotations_handler(
+    Path(book_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), ProsaError> {
+    let format = parse_export_format(&params)?;
+
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.read().await;
+
+    let export = service::export_book_annotations(&book_id).await?;
+    let body = match format {
+        ExportFormat::Markdown => export::to_markdown(std::slice::from_ref(&export)),
+        ExportFormat::Json => to_json(&export),
+        ExportFormat::Csv => export::to_csv(std::slice::from_ref(&export)),
+    };
+
+    Ok(([(header::CONTENT_TYPE, format.media_type())], body))
+}
+
+pub async fn export_annotations_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(token): Extension<AuthToken>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), ProsaError> {
+    let format = parse_export_format(&params)?;
+
+    let user_id = match params.get("user_id") {
+        Some(id) => id,
+        None => token.role.get_user(),
+    };
+
+    let exports = service::export_user_annotations(user_id).await?;
+    let body = match format {
+        ExportFormat::Markdown => export::to_markdown(&exports),
+        ExportFormat::Json => to_json(&exports),
+        ExportFormat::Csv => export::to_csv(&exports),
+    };
+
+    Ok(([(header::CONTENT_TYPE, format.media_type())], body))
+}
+
+pub async fn import_annotations_handler(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+    data: Bytes,
+) -> Result<Json<ImportReport>, ProsaError> {
+    let format = match params.get("format").map(String::as_str) {
+        Some("kindle") => ImportFormat::Kindle,
+        Some("koreader") => ImportFormat::Koreader,
+        Some("calibre") => ImportFormat::Calibre,
+        _ => return Err(AnnotationError::InvalidImportFormat.into()),
+    };
+
+    let user_id = match params.get("user_id") {
+        Some(id) => id,
+        None => token.role.get_user(),
+    };
+
+    let clippings = spawn_blocking(move || {
+        std::str::from_utf8(&data)
+            .ok()
+            .and_then(|data| importers::parse(format, data))
+    })
+    .await
+    .expect("Failed to parse annotations file")
+    .ok_or(AnnotationError::InvalidImportFile)?;
+
+    let book_id = params.get("book_id").map(String::as_str);
+    let report = service::import_annotations(user_id, book_id, clippings, &token.session_id).await?;
+
+    Ok(Json(report))
+}
+
+fn parse_export_format(params: &HashMap<String, String>) -> Result<ExportFormat, AnnotationError> {
+    match params.get("format").map(String::as_str) {
+        None | Some("markdown" | "md") => Ok(ExportFormat::Markdown),
+        Some("json") => Ok(ExportFormat::Json),
+        Some("csv") => Ok(ExportFormat::Csv),
+        _ => Err(AnnotationError::InvalidExportFormat),
+    }
+}
+
+fn parse_timestamp(
+    params: &HashMap<String, String>,
+    key: &str,
+) -> Result<Option<DateTime<Utc>>, AnnotationError> {
+    match params.get(key).map(|t| t.parse::<i64>()) {
+        Some(Ok(t)) => DateTime::from_timestamp_millis(t)
+            .map(Some)
+            .ok_or(AnnotationError::InvalidTimestamp),
+        None => Ok(None),
+        _ => Err(AnnotationError::InvalidTimestamp),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Failed to serialize annotations export")
+}
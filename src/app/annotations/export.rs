@@ -0,0 +1,126 @@
+use super::models::BookAnnotationsExport;
+use std::fmt::Write;
+
+const CSV_HEADER: [&str; 7] = [
+    "Highlight",
+    "Title",
+    "Author",
+    "Note",
+    "Chapter",
+    "Book ID",
+    "Annotation ID",
+];
+
+/// Writes annotations as Markdown, with a heading for every book and chapter, highlights as
+/// quotes and notes as paragraphs. The result can be dropped as is into a notes app like Obsidian.
+pub fn to_markdown(books: &[BookAnnotationsExport]) -> String {
+    let mut markdown = String::new();
+
+    for book in books {
+        if !markdown.is_empty() {
+            markdown.push('\n');
+        }
+
+        let _ = writeln!(markdown, "# {}\n", book.title.as_deref().unwrap_or(&book.book_id));
+
+        let mut details = Vec::new();
+        if let Some(subtitle) = &book.subtitle {
+            details.push(format!("- **Subtitle:** {subtitle}"));
+        }
+        if !book.authors.is_empty() {
+            details.push(format!("- **Authors:** {}", book.authors.join(", ")));
+        }
+        if let Some(publisher) = &book.publisher {
+            details.push(format!("- **Publisher:** {publisher}"));
+        }
+        if let Some(date) = &book.publication_date {
+            details.push(format!("- **Published:** {}", date.format("%Y-%m-%d")));
+        }
+        if let Some(isbn) = &book.isbn {
+            details.push(format!("- **ISBN:** {isbn}"));
+        }
+        if !details.is_empty() {
+            let _ = writeln!(markdown, "{}\n", details.join("\n"));
+        }
+
+        let mut chapter = None;
+        for annotation in &book.annotations {
+            if annotation.text.is_none() && annotation.note.is_none() {
+                continue;
+            }
+
+            if annotation.chapter.is_some() && annotation.chapter != chapter {
+                chapter.clone_from(&annotation.chapter);
+                let _ = writeln!(
+                    markdown,
+                    "## {}\n",
+                    annotation.chapter.as_deref().unwrap_or_default()
+                );
+            }
+
+            if let Some(text) = &annotation.text {
+                let _ = writeln!(markdown, "{}\n", blockquote(text));
+            }
+            if let Some(note) = &annotation.note {
+                let _ = writeln!(markdown, "{}\n", blockquote(&format!("**Note:** {note}")));
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Writes annotations as CSV, one highlight per row, with the columns expected by Readwise imports
+/// followed by the chapter and the IDs of the book and annotation.
+pub fn to_csv(books: &[BookAnnotationsExport]) -> String {
+    let mut csv = CSV_HEADER.join(",") + "\r\n";
+
+    for book in books {
+        let title = book.title.as_deref().unwrap_or_default();
+        let authors = book.authors.join(", ");
+
+        for annotation in &book.annotations {
+            if annotation.text.is_none() && annotation.note.is_none() {
+                continue;
+            }
+
+            let row = [
+                annotation.text.as_deref().unwrap_or_default(),
+                title,
+                &authors,
+                annotation.note.as_deref().unwrap_or_default(),
+                annotation.chapter.as_deref().unwrap_or_default(),
+                &book.book_id,
+                &annotation.annotation_id,
+            ];
+
+            let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push_str("\r\n");
+        }
+    }
+
+    csv
+}
+
+/// Quotes every line of the text, so that multi-line highlights and notes stay in one block.
+fn blockquote(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                ">".to_string()
+            } else {
+                format!("> {line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Clipping, non_empty};
+use crate::app::duplicates::models::BookIdentity;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Collection {
+    Wrapped { annotations: Vec<Annotation> },
+    Plain(Vec<Annotation>),
+}
+
+#[derive(Deserialize)]
+struct Annotation {
+    #[serde(rename = "type")]
+    kind: String,
+    highlighted_text: Option<String>,
+    notes: Option<String>,
+    style: Option<Style>,
+    #[serde(default)]
+    removed: bool,
+}
+
+#[derive(Deserialize)]
+struct Style {
+    kind: Option<String>,
+    which: Option<String>,
+}
+
+/// Reads the annotations exported from the calibre viewer or annotations browser. These do not
+/// say which book they belong to, so they are only matched when the book is given.
+pub fn parse(data: &str) -> Option<Vec<Clipping>> {
+    let annotations = match serde_json::from_str(data).ok()? {
+        Collection::Wrapped { annotations } | Collection::Plain(annotations) => annotations,
+    };
+
+    let clippings = annotations
+        .into_iter()
+        .filter(|a| a.kind == "highlight" && !a.removed)
+        .map(|annotation| {
+            let style = annotation.style.as_ref();
+            let which = style.and_then(|s| s.which.as_deref()).unwrap_or_default();
+
+            // Highlights are either coloured in or drawn as a line under or through the text
+            let (color, style) = match style.and_then(|s| s.kind.as_deref()) {
+                Some("decoration") => (None, decoration_style(which)),
+                _ => (color(which), "Highlight"),
+            };
+
+            Clipping {
+                book: BookIdentity::default(),
+                text: annotation.highlighted_text.as_deref().and_then(non_empty),
+                note: annotation.notes.as_deref().and_then(non_empty),
+                color,
+                style: Some(style.to_string()),
+            }
+        })
+        .collect();
+
+    Some(clippings)
+}
+
+/// The colours of the highlights built into calibre.
+fn color(which: &str) -> Option<String> {
+    let hex = match which {
+        "yellow" => "#FFEB6B",
+        "green" => "#C0ED72",
+        "blue" => "#ADD8FF",
+        "red" => "#FFB0CA",
+        "purple" => "#D9B2FF",
+        _ => return None,
+    };
+
+    Some(hex.to_string())
+}
+
+fn decoration_style(which: &str) -> &'static str {
+    match which {
+        "strikeout" => "Strikethrough",
+        _ => "Underline",
+    }
+}
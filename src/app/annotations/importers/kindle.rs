@@ -0,0 +1,136 @@
+use super::{Clipping, non_empty};
+use crate::app::duplicates::models::BookIdentity;
+
+const SEPARATOR: &str = "==========";
+
+/// Reads a Kindle `My Clippings.txt`, where every clipping is made of the title and author of the
+/// book, a line of details starting with a dash, a blank line and the text, followed by a separator.
+/// Notes are clippings of their own, so they are attached to the highlight they were made on.
+pub fn parse(data: &str) -> Option<Vec<Clipping>> {
+    let mut highlights: Vec<(Clipping, Option<(u32, u32)>)> = Vec::new();
+    let mut notes: Vec<(BookIdentity, String, Option<u32>)> = Vec::new();
+
+    for entry in data.split(SEPARATOR) {
+        let mut lines = entry.lines().map(str::trim).skip_while(|l| l.is_empty());
+        let Some(title_line) = lines.next() else {
+            continue;
+        };
+
+        let details = lines.next()?.strip_prefix('-')?.to_lowercase();
+        if details.contains("bookmark") {
+            continue;
+        }
+
+        let text = non_empty(&lines.collect::<Vec<_>>().join("\n"));
+        let book = read_book(title_line);
+        let location = read_location(&details);
+
+        if details.contains("note") {
+            if let Some(note) = text {
+                notes.push((book, note, location.map(|(start, _)| start)));
+            }
+            continue;
+        }
+
+        let clipping = Clipping {
+            book,
+            text,
+            note: None,
+            color: None,
+            style: None,
+        };
+        highlights.push((clipping, location));
+    }
+
+    let mut clippings = Vec::new();
+    for (book, note, location) in notes {
+        // A note sits at the end of its highlight, or at least within it
+        let candidates: Vec<usize> = highlights
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, (highlight, range))| {
+                highlight.book == book
+                    && highlight.note.is_none()
+                    && range
+                        .zip(location)
+                        .is_some_and(|((start, end), l)| start <= l && l <= end)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let highlight = candidates
+            .iter()
+            .find(|&&i| highlights[i].1.map(|(_, end)| end) == location)
+            .or(candidates.first());
+
+        match highlight {
+            Some(&i) => highlights[i].0.note = Some(note),
+            None => clippings.push(Clipping {
+                book,
+                text: None,
+                note: Some(note),
+                color: None,
+                style: None,
+            }),
+        }
+    }
+
+    let mut highlights: Vec<Clipping> = highlights.into_iter().map(|(clipping, _)| clipping).collect();
+    highlights.append(&mut clippings);
+    Some(highlights)
+}
+
+/// The title line ends with the authors between brackets, separated by semicolons.
+fn read_book(line: &str) -> BookIdentity {
+    let mut depth = 0;
+    let open = line.strip_suffix(')').and_then(|rest| {
+        rest.char_indices().rev().find_map(|(i, c)| match c {
+            ')' => {
+                depth += 1;
+                None
+            }
+            '(' if depth == 0 => Some(i),
+            '(' => {
+                depth -= 1;
+                None
+            }
+            _ => None,
+        })
+    });
+
+    match open {
+        Some(open) => BookIdentity {
+            title: non_empty(&line[..open]),
+            authors: line[open + 1..line.len() - 1]
+                .split(';')
+                .filter_map(non_empty)
+                .collect(),
+            isbn: None,
+        },
+        None => BookIdentity {
+            title: non_empty(line),
+            ..BookIdentity::default()
+        },
+    }
+}
+
+/// Reads a location such as `Location 1406-1407`, or `Loc. 1406-07` on older Kindles, which
+/// leave out the leading digits the end shares with the start.
+fn read_location(details: &str) -> Option<(u32, u32)> {
+    let position = details
+        .find("location ")
+        .map(|i| i + "location ".len())
+        .or_else(|| details.find("loc. ").map(|i| i + "loc. ".len()))?;
+
+    let range = details[position..].split_whitespace().next()?;
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let start_value: u32 = start.parse().ok()?;
+    let end_value: u32 = end.parse().ok()?;
+
+    if end_value >= start_value || end.len() >= start.len() {
+        return Some((start_value, end_value.max(start_value)));
+    }
+
+    let end = format!("{}{end}", &start[..start.len() - end.len()]);
+    Some((start_value, end.parse().ok()?))
+}
@@ -0,0 +1,238 @@
+use super::{Clipping, non_empty};
+use crate::app::duplicates::models::BookIdentity;
+use std::{iter::Peekable, str::Chars};
+
+/// How deeply tables can be nested, far past what `KOReader` writes, so that crafted files cannot
+/// exhaust the stack.
+const MAX_DEPTH: usize = 64;
+
+/// A value of the Lua tables `KOReader` keeps its book settings in. Only strings and tables are
+/// of use, so numbers, booleans and `nil` are all read as other values.
+enum Value {
+    String(String),
+    Table(Vec<(Value, Value)>),
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Value::String(s) if s == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn values(&self) -> impl Iterator<Item = &Value> {
+        let entries = match self {
+            Value::Table(entries) => entries.as_slice(),
+            _ => &[],
+        };
+
+        entries.iter().map(|(_, v)| v)
+    }
+}
+
+/// Reads the `metadata.epub.lua` file of a `KOReader` `.sdr` folder. Recent versions keep highlights
+/// and their notes in `annotations`, while older ones kept highlights in `highlight`, grouped by page.
+pub fn parse(data: &str) -> Option<Vec<Clipping>> {
+    // The settings are returned as a table, after a comment
+    let data = data
+        .lines()
+        .skip_while(|l| l.trim().is_empty() || l.trim_start().starts_with("--"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut chars = data.trim().strip_prefix("return")?.chars().peekable();
+
+    let settings = read_value(&mut chars, 0)?;
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() || !matches!(settings, Value::Table(_)) {
+        return None;
+    }
+
+    let book = read_book(&settings);
+    let highlights: Vec<&Value> = match settings.get("annotations") {
+        // Bookmarks of a page are kept along with highlights, but have no drawer
+        Some(annotations) => annotations
+            .values()
+            .filter(|a| a.get("drawer").is_some())
+            .collect(),
+        None => settings
+            .get("highlight")
+            .map(|pages| pages.values().flat_map(Value::values).collect())
+            .unwrap_or_default(),
+    };
+
+    let clippings = highlights
+        .into_iter()
+        .map(|highlight| Clipping {
+            book: book.clone(),
+            text: highlight.str("text").and_then(non_empty),
+            note: highlight.str("note").and_then(non_empty),
+            color: highlight.str("color").and_then(color),
+            style: highlight.str("drawer").and_then(style),
+        })
+        .collect();
+
+    Some(clippings)
+}
+
+fn read_book(settings: &Value) -> BookIdentity {
+    let properties = settings.get("doc_props").or_else(|| settings.get("stats"));
+    let property = |key: &str| properties.and_then(|p| p.str(key));
+
+    // Identifiers are listed one per line, with their scheme in front
+    let isbn = property("identifiers").and_then(|identifiers| {
+        identifiers
+            .lines()
+            .filter(|l| l.to_lowercase().contains("isbn"))
+            .find_map(|l| non_empty(l.rsplit(':').next().unwrap_or_default()))
+    });
+
+    BookIdentity {
+        title: property("title").and_then(non_empty),
+        authors: property("authors")
+            .map(|authors| authors.lines().filter_map(non_empty).collect())
+            .unwrap_or_default(),
+        isbn,
+    }
+}
+
+/// The colours `KOReader` highlights in, as shown on colour screens.
+fn color(name: &str) -> Option<String> {
+    let hex = match name {
+        "red" => "#FF3300",
+        "orange" => "#FF8800",
+        "yellow" => "#FFFF33",
+        "green" => "#00AA66",
+        "olive" => "#88FF77",
+        "cyan" => "#00FFEE",
+        "blue" => "#0066FF",
+        "purple" => "#EE00FF",
+        "gray" => "#808080",
+        _ => return None,
+    };
+
+    Some(hex.to_string())
+}
+
+fn style(drawer: &str) -> Option<String> {
+    let style = match drawer {
+        "lighten" | "invert" => "Highlight",
+        "underscore" => "Underline",
+        "strikeout" => "Strikethrough",
+        _ => return None,
+    };
+
+    Some(style.to_string())
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn read_value(chars: &mut Peekable<Chars>, depth: usize) -> Option<Value> {
+    skip_whitespace(chars);
+
+    match chars.peek()? {
+        '{' => read_table(chars, depth + 1),
+        '"' | '\'' => read_string(chars).map(Value::String),
+        _ => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+')) {
+                word.push(c);
+            }
+
+            let valid = matches!(word.as_str(), "true" | "false" | "nil") || word.parse::<f64>().is_ok();
+            valid.then_some(Value::Other)
+        }
+    }
+}
+
+fn read_table(chars: &mut Peekable<Chars>, depth: usize) -> Option<Value> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    chars.next();
+    let mut entries = Vec::new();
+
+    loop {
+        skip_whitespace(chars);
+        match chars.peek()? {
+            '}' => {
+                chars.next();
+                return Some(Value::Table(entries));
+            }
+            '[' => {
+                chars.next();
+                let key = read_value(chars, depth)?;
+                skip_whitespace(chars);
+                chars.next().filter(|c| *c == ']')?;
+                skip_whitespace(chars);
+                chars.next().filter(|c| *c == '=')?;
+                entries.push((key, read_value(chars, depth)?));
+            }
+            c if c.is_alphabetic() || *c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+
+                skip_whitespace(chars);
+                if chars.next_if_eq(&'=').is_some() {
+                    entries.push((Value::String(name), read_value(chars, depth)?));
+                } else {
+                    // A bare word without a key is a value of its own, such as `true`
+                    if !matches!(name.as_str(), "true" | "false" | "nil") {
+                        return None;
+                    }
+                    entries.push((Value::Other, Value::Other));
+                }
+            }
+            _ => entries.push((Value::Other, read_value(chars, depth)?)),
+        }
+
+        skip_whitespace(chars);
+        chars.next_if(|c| matches!(c, ',' | ';'));
+    }
+}
+
+fn read_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let quote = chars.next()?;
+    let mut string = String::new();
+
+    loop {
+        match chars.next()? {
+            c if c == quote => return Some(string),
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                '\n' => string.push('\n'),
+                c if c.is_ascii_digit() => {
+                    // Bytes are escaped as up to three decimal digits
+                    let mut code = c.to_digit(10)?;
+                    for _ in 0..2 {
+                        match chars.next_if(char::is_ascii_digit) {
+                            Some(d) => code = code * 10 + d.to_digit(10)?,
+                            None => break,
+                        }
+                    }
+                    string.push(char::from_u32(code)?);
+                }
+                c => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+}
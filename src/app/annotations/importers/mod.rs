@@ -0,0 +1,32 @@
+use super::models::ImportFormat;
+use crate::app::duplicates::models::BookIdentity;
+
+mod calibre;
+mod kindle;
+mod koreader;
+
+/// A highlight or note made in another reader, before it is matched to a book and a position in it.
+pub struct Clipping {
+    pub book: BookIdentity,
+    pub text: Option<String>,
+    pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: Option<String>,
+}
+
+/// Reads the clippings of an annotations file, or `None` if it is not in the given format.
+pub fn parse(format: ImportFormat, data: &str) -> Option<Vec<Clipping>> {
+    let data = data.trim_start_matches('\u{feff}');
+
+    match format {
+        ImportFormat::Kindle => kindle::parse(data),
+        ImportFormat::Koreader => koreader::parse(data),
+        ImportFormat::Calibre => calibre::parse(data),
+    }
+}
+
+/// Trims a text, leaving out those that are blank.
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -1,5 +1,7 @@
 pub mod controller;
-mod models;
+mod export;
+mod importers;
+pub mod models;
 pub mod repository;
 pub mod routes;
 pub mod service;
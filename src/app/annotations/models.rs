@@ -1,3 +1,7 @@
+use chrono::{
+    DateTime, Utc,
+    serde::{ts_milliseconds, ts_milliseconds_option},
+};
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use sqlx::{
@@ -20,6 +24,27 @@ pub enum AnnotationError {
     #[strum(message = "An annotation in this position already exists.")]
     #[strum(props(StatusCode = "409"))]
     AnnotationConflict,
+    #[strum(message = "The requested annotation type is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidAnnotationType,
+    #[strum(message = "The provided tags are invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidTags,
+    #[strum(message = "The requested pagination is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidPagination,
+    #[strum(message = "The provided timestamp is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidTimestamp,
+    #[strum(message = "The requested export format is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidExportFormat,
+    #[strum(message = "The requested import format is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidImportFormat,
+    #[strum(message = "The provided annotations file could not be read.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidImportFile,
     #[strum(message = "Internal error")]
     #[strum(props(StatusCode = "500"))]
     InternalError,
@@ -44,29 +69,175 @@ impl From<&SqliteError> for AnnotationError {
     }
 }
 
+pub const VALID_ANNOTATION_TYPES: [&str; 3] = ["Highlight", "Note", "Bookmark"];
+pub const VALID_HIGHLIGHT_STYLES: [&str; 3] = ["Highlight", "Underline", "Strikethrough"];
+
 #[skip_serializing_none]
 #[derive(FromRow, Serialize)]
 pub struct Annotation {
     pub annotation_id: String,
+    pub annotation_type: String,
     pub source: String,
     pub start_tag: String,
     pub end_tag: String,
     pub start_char: u32,
     pub end_char: u32,
+    pub text: Option<String>,
     pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: Option<String>,
+    #[sqlx(skip)]
+    pub tags: Vec<String>,
+    pub orphaned: bool,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Deserialize)]
 pub struct NewAnnotationRequest {
+    pub annotation_type: Option<String>,
+    pub source: String,
+    pub start_tag: String,
+    pub end_tag: Option<String>,
+    pub start_char: u32,
+    pub end_char: Option<u32>,
+    pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+pub struct NewAnnotation {
+    pub annotation_type: String,
     pub source: String,
     pub start_tag: String,
     pub end_tag: String,
     pub start_char: u32,
     pub end_char: u32,
     pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: Option<String>,
+    pub tags: Vec<String>,
 }
 
 #[derive(Deserialize)]
 pub struct PatchAnnotationRequest {
     pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+pub struct AnnotationSearch {
+    pub query: Option<String>,
+    pub book_id: Option<String>,
+    pub tag: Option<String>,
+    pub annotation_type: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct AnnotationSearchResult {
+    pub book_id: String,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub annotation: Annotation,
+}
+
+#[derive(Serialize)]
+pub struct PaginatedAnnotations {
+    pub annotations: Vec<AnnotationSearchResult>,
+    pub page_size: i64,
+    pub total_elements: i64,
+    pub total_pages: i64,
+    pub current_page: i64,
+}
+
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct BookAnnotationsExport {
+    pub book_id: String,
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    #[serde(with = "ts_milliseconds_option")]
+    pub publication_date: Option<DateTime<Utc>>,
+    pub isbn: Option<String>,
+    pub annotations: Vec<ExportedAnnotation>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct ExportedAnnotation {
+    pub annotation_id: String,
+    pub annotation_type: String,
+    pub chapter: Option<String>,
+    pub text: Option<String>,
+    pub note: Option<String>,
+    pub color: Option<String>,
+    pub style: Option<String>,
+    pub tags: Vec<String>,
+    pub source: String,
+    pub start_tag: String,
+    pub end_tag: String,
+    pub start_char: u32,
+    pub end_char: u32,
+    pub orphaned: bool,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy)]
+pub enum ImportFormat {
+    Kindle,
+    Koreader,
+    Calibre,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub imported: u32,
+    pub existing: u32,
+    pub unmatched: Vec<UnmatchedClipping>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedReason {
+    BookNotFound,
+    TextNotFound,
+    InvalidAnnotation,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct UnmatchedClipping {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub text: Option<String>,
+    pub note: Option<String>,
+    pub reason: UnmatchedReason,
 }
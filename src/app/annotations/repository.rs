@@ -1,35 +1,104 @@
-use super::models::{Annotation, AnnotationError, NewAnnotationRequest};
+use super::models::{Annotation, AnnotationError, AnnotationSearch, AnnotationSearchResult, NewAnnotation};
 use crate::DB_POOL;
+use chrono::Utc;
+use sqlx::{QueryBuilder, Sqlite, Transaction};
+use std::fmt::Write;
 
 pub async fn add_annotation(
     annotation_id: &str,
     book_id: &str,
-    annotation: &NewAnnotationRequest,
+    annotation: &NewAnnotation,
+    text: Option<&str>,
 ) -> Result<(), AnnotationError> {
+    let now = Utc::now();
+    let mut tx = DB_POOL
+        .get()
+        .expect("Failed to get database pool")
+        .begin()
+        .await?;
+
     sqlx::query(
         r"
-        INSERT INTO annotations (annotation_id, book_id, source, start_tag, end_tag, start_char, end_char, note)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        INSERT INTO annotations (annotation_id, book_id, annotation_type, source, start_tag, end_tag, start_char, end_char, text, note, color, style, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
         ",
     )
     .bind(annotation_id)
     .bind(book_id)
+    .bind(&annotation.annotation_type)
     .bind(&annotation.source)
     .bind(&annotation.start_tag)
     .bind(&annotation.end_tag)
     .bind(annotation.start_char)
     .bind(annotation.end_char)
+    .bind(text)
     .bind(&annotation.note)
-    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .bind(&annotation.color)
+    .bind(&annotation.style)
+    .bind(now)
+    .bind(now)
+    .execute(&mut *tx)
     .await?;
 
+    add_tags(&mut tx, annotation_id, &annotation.tags).await?;
+
+    tx.commit().await?;
     Ok(())
 }
 
+async fn add_tags(
+    tx: &mut Transaction<'_, Sqlite>,
+    annotation_id: &str,
+    tags: &[String],
+) -> Result<(), AnnotationError> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::new("INSERT INTO annotation_tags (annotation_id, tag)");
+    query.push_values(tags, |mut b, tag| {
+        b.push_bind(annotation_id).push_bind(tag);
+    });
+    query.build().execute(&mut **tx).await?;
+
+    Ok(())
+}
+
+pub async fn get_tags(annotation_id: &str) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT tag
+        FROM annotation_tags
+        WHERE annotation_id = $1
+        ORDER BY tag
+        ",
+    )
+    .bind(annotation_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve annotation tags")
+}
+
+pub async fn get_book_tags(book_id: &str) -> Vec<(String, String)> {
+    sqlx::query_as(
+        r"
+        SELECT t.annotation_id, t.tag
+        FROM annotation_tags t
+        JOIN annotations a ON a.annotation_id = t.annotation_id
+        WHERE a.book_id = $1
+        ORDER BY t.tag
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve annotation tags")
+}
+
 pub async fn get_annotation(annotation_id: &str) -> Result<Annotation, AnnotationError> {
     let annotation = sqlx::query_as::<_, Annotation>(
         r"
-        SELECT annotation_id, source, start_tag, end_tag, start_char, end_char, note
+        SELECT annotation_id, annotation_type, source, start_tag, end_tag, start_char, end_char, text, note, color, style, orphaned, created_at, updated_at
         FROM annotations
         WHERE annotation_id = $1
         ",
@@ -41,15 +110,16 @@ pub async fn get_annotation(annotation_id: &str) -> Result<Annotation, Annotatio
     Ok(annotation)
 }
 
-pub async fn get_annotations(book_id: &str) -> Vec<String> {
+pub async fn get_annotations(book_id: &str, annotation_type: Option<&str>) -> Vec<String> {
     sqlx::query_scalar(
         r"
         SELECT annotation_id
         FROM annotations
-        WHERE book_id = $1
+        WHERE book_id = $1 AND ($2 IS NULL OR annotation_type = $2)
         ",
     )
     .bind(book_id)
+    .bind(annotation_type)
     .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
     .await
     .expect("Failed to retrieve book annotations")
@@ -74,23 +144,265 @@ pub async fn delete_annotation(annotation_id: &str) -> Result<(), AnnotationErro
     Ok(())
 }
 
-pub async fn patch_annotation(annotation_id: &str, note: Option<String>) -> Result<(), AnnotationError> {
+pub async fn patch_annotation(
+    annotation_id: &str,
+    note: Option<&str>,
+    color: Option<&str>,
+    style: Option<&str>,
+    tags: &[String],
+) -> Result<(), AnnotationError> {
+    let mut tx = DB_POOL
+        .get()
+        .expect("Failed to get database pool")
+        .begin()
+        .await?;
+
     let result = sqlx::query(
         r"
         UPDATE annotations
-        SET note = $1
-        WHERE annotation_id = $2
+        SET note = $1, color = $2, style = $3, updated_at = $4
+        WHERE annotation_id = $5
         ",
     )
     .bind(note)
+    .bind(color)
+    .bind(style)
+    .bind(Utc::now())
     .bind(annotation_id)
-    .execute(DB_POOL.get().expect("Failed to get database pool"))
-    .await
-    .expect("Failed to patch annotation");
+    .execute(&mut *tx)
+    .await?;
 
     if result.rows_affected() == 0 {
         return Err(AnnotationError::AnnotationNotFound);
     }
 
+    sqlx::query("DELETE FROM annotation_tags WHERE annotation_id = ?")
+        .bind(annotation_id)
+        .execute(&mut *tx)
+        .await?;
+    add_tags(&mut tx, annotation_id, tags).await?;
+
+    tx.commit().await?;
     Ok(())
 }
+
+/// Moves annotations to another book. Those it already has at the same place take on the note and
+/// tags of their copy, so that nothing written on them is lost.
+pub async fn move_annotations(from_book_id: &str, to_book_id: &str) -> u64 {
+    let mut tx = DB_POOL
+        .get()
+        .expect("Failed to get database pool")
+        .begin()
+        .await
+        .expect("Failed to start transaction");
+
+    let moved = sqlx::query(
+        r"
+        UPDATE OR IGNORE annotations
+        SET book_id = $1
+        WHERE book_id = $2
+        ",
+    )
+    .bind(to_book_id)
+    .bind(from_book_id)
+    .execute(&mut *tx)
+    .await
+    .expect("Failed to move annotations")
+    .rows_affected();
+
+    // Differing notes are both kept, one after the other
+    let merged = sqlx::query(
+        r"
+        UPDATE annotations AS a
+        SET note = CASE WHEN a.note IS NULL THEN d.note ELSE a.note || char(10) || char(10) || d.note END,
+            updated_at = $1
+        FROM annotations d
+        WHERE a.book_id = $2 AND d.book_id = $3
+            AND a.source = d.source AND a.start_tag = d.start_tag AND a.end_tag = d.end_tag
+            AND a.start_char = d.start_char AND a.end_char = d.end_char
+            AND d.note IS NOT NULL AND (a.note IS NULL OR a.note != d.note)
+        ",
+    )
+    .bind(Utc::now())
+    .bind(to_book_id)
+    .bind(from_book_id)
+    .execute(&mut *tx)
+    .await
+    .expect("Failed to merge annotation notes")
+    .rows_affected();
+
+    let tagged = sqlx::query(
+        r"
+        INSERT OR IGNORE INTO annotation_tags (annotation_id, tag)
+        SELECT a.annotation_id, t.tag
+        FROM annotation_tags t
+        JOIN annotations d ON d.annotation_id = t.annotation_id
+        JOIN annotations a ON a.book_id = $1
+            AND a.source = d.source AND a.start_tag = d.start_tag AND a.end_tag = d.end_tag
+            AND a.start_char = d.start_char AND a.end_char = d.end_char
+        WHERE d.book_id = $2
+        ",
+    )
+    .bind(to_book_id)
+    .bind(from_book_id)
+    .execute(&mut *tx)
+    .await
+    .expect("Failed to merge annotation tags")
+    .rows_affected();
+
+    tx.commit().await.expect("Failed to commit transaction");
+    moved + merged + tagged
+}
+
+pub async fn get_book_annotations(book_id: &str) -> Vec<Annotation> {
+    sqlx::query_as(
+        r"
+        SELECT annotation_id, annotation_type, source, start_tag, end_tag, start_char, end_char, text, note, color, style, orphaned, created_at, updated_at
+        FROM annotations
+        WHERE book_id = $1
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve book annotations")
+}
+
+pub async fn remap_annotation(
+    annotation_id: &str,
+    source: &str,
+    start_tag: &str,
+    end_tag: &str,
+) -> Result<(), AnnotationError> {
+    sqlx::query(
+        r"
+        UPDATE annotations
+        SET source = $1, start_tag = $2, end_tag = $3
+        WHERE annotation_id = $4
+        ",
+    )
+    .bind(source)
+    .bind(start_tag)
+    .bind(end_tag)
+    .bind(annotation_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn orphan_annotation(annotation_id: &str) {
+    sqlx::query(
+        r"
+        UPDATE annotations
+        SET orphaned = TRUE
+        WHERE annotation_id = $1
+        ",
+    )
+    .bind(annotation_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to orphan annotation");
+}
+
+pub async fn get_annotated_books(owner_id: &str) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT DISTINCT b.book_id
+        FROM books b
+        JOIN annotations a ON a.book_id = b.book_id
+        LEFT JOIN metadata m ON m.metadata_id = b.metadata_id
+        WHERE b.owner_id = $1 AND b.deleted_at IS NULL
+        ORDER BY m.title COLLATE NOCASE, b.book_id
+        ",
+    )
+    .bind(owner_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list annotated books")
+}
+
+/// Searches the annotations in the library of a user, newest first. Every word of the query has to
+/// be in the highlighted text or the note.
+pub async fn search_annotations(
+    owner_id: &str,
+    search: &AnnotationSearch,
+    page: i64,
+    page_size: i64,
+) -> (Vec<AnnotationSearchResult>, i64) {
+    let offset = (page - 1) * page_size;
+
+    let mut base_query = r"
+        FROM annotations a
+        INNER JOIN books b ON a.book_id = b.book_id
+        WHERE b.owner_id = $1 AND b.deleted_at IS NULL
+          AND ($2 IS NULL OR a.book_id = $2)
+          AND ($3 IS NULL OR a.annotation_type = $3)
+          AND ($4 IS NULL OR a.created_at >= $4)
+          AND ($5 IS NULL OR a.created_at <= $5)
+          AND ($6 IS NULL OR EXISTS (
+              SELECT 1 FROM annotation_tags t WHERE t.annotation_id = a.annotation_id AND t.tag = $6
+          ))
+    "
+    .to_string();
+
+    let words: Vec<&str> = search
+        .query
+        .as_deref()
+        .map(|q| q.split_whitespace().collect())
+        .unwrap_or_default();
+    for i in 0..words.len() {
+        let param = i + 7;
+        let _ = write!(
+            base_query,
+            " AND (a.text LIKE '%' || ${param} || '%' COLLATE NOCASE OR a.note LIKE '%' || ${param} || '%' COLLATE NOCASE)"
+        );
+    }
+
+    let limit = words.len() + 7;
+    let annotation_query = format!(
+        r"
+        SELECT a.book_id, a.annotation_id, a.annotation_type, a.source, a.start_tag, a.end_tag, a.start_char, a.end_char,
+               a.text, a.note, a.color, a.style, a.orphaned, a.created_at, a.updated_at
+        {base_query}
+        ORDER BY a.created_at DESC, a.annotation_id
+        LIMIT ${limit} OFFSET ${}
+        ",
+        limit + 1
+    );
+    let count_query = format!("SELECT COUNT(*) {base_query}");
+
+    let mut annotation_stmt = sqlx::query_as::<_, AnnotationSearchResult>(&annotation_query)
+        .bind(owner_id)
+        .bind(&search.book_id)
+        .bind(&search.annotation_type)
+        .bind(search.from)
+        .bind(search.to)
+        .bind(&search.tag);
+    let mut count_stmt = sqlx::query_scalar::<_, i64>(&count_query)
+        .bind(owner_id)
+        .bind(&search.book_id)
+        .bind(&search.annotation_type)
+        .bind(search.from)
+        .bind(search.to)
+        .bind(&search.tag);
+
+    for word in &words {
+        annotation_stmt = annotation_stmt.bind(*word);
+        count_stmt = count_stmt.bind(*word);
+    }
+
+    let annotations = annotation_stmt
+        .bind(page_size)
+        .bind(offset)
+        .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+        .await
+        .expect("Failed to search for annotations");
+
+    let total_elements = count_stmt
+        .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+        .await
+        .expect("Failed to count annotations");
+
+    (annotations, total_elements)
+}
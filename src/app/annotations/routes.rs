@@ -1,16 +1,21 @@
 use crate::app::{
     annotations::controller::{
-        add_annotation_handler, delete_annotation_handler, get_annotation_handler, list_annotations_handler,
-        patch_annotation_handler,
+        add_annotation_handler, delete_annotation_handler, export_annotations_handler,
+        export_book_annotations_handler, get_annotation_handler, import_annotations_handler,
+        list_annotations_handler, patch_annotation_handler, search_annotations_handler,
     },
     authentication::middleware::extract_token_middleware,
     authorization::{
-        annotations::{can_read_annotation, can_update_annotation},
+        annotations::{
+            can_export_annotations, can_import_annotations, can_read_annotation, can_search_annotations,
+            can_update_annotation,
+        },
         books::{can_read_book, can_update_book},
     },
 };
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
     middleware::from_fn,
     routing::{delete, get, patch, post},
 };
@@ -33,5 +38,18 @@ pub fn get_routes() -> Router {
         .route("/books/{book_id}/annotations/{annotation_id}", patch(patch_annotation_handler) 
             .route_layer(from_fn(can_update_annotation))
         )
+        .route("/books/{book_id}/annotations/export", get(export_book_annotations_handler)
+            .route_layer(from_fn(can_read_book))
+        )
+        .route("/annotations", get(search_annotations_handler)
+            .route_layer(from_fn(can_search_annotations))
+        )
+        .route("/annotations/export", get(export_annotations_handler)
+            .route_layer(from_fn(can_export_annotations))
+        )
+        .route("/annotations/import", post(import_annotations_handler)
+            .route_layer(from_fn(can_import_annotations))
+            .layer(DefaultBodyLimit::max(15728640))
+        )
         .layer(from_fn(extract_token_middleware))
 }
@@ -1,33 +1,110 @@
-use super::models::{Annotation, AnnotationError, NewAnnotationRequest};
+use super::{
+    importers::Clipping,
+    models::{
+        Annotation, AnnotationError, AnnotationSearch, BookAnnotationsExport, ExportedAnnotation,
+        ImportReport, NewAnnotation, NewAnnotationRequest, PaginatedAnnotations, PatchAnnotationRequest,
+        UnmatchedClipping, UnmatchedReason, VALID_ANNOTATION_TYPES, VALID_HIGHLIGHT_STYLES,
+    },
+};
 use crate::{
     CONFIG,
-    app::{annotations::repository, books, error::ProsaError, server::CACHE},
+    app::{
+        annotations::repository,
+        books::{self, models::BookError},
+        duplicates, epubs,
+        epubs::spans::{KoboSpans, TextPosition},
+        error::ProsaError,
+        metadata,
+        server::{CACHE, LOCKS},
+        sync::{
+            self,
+            models::{ChangeLogAction, ChangeLogEntityType},
+        },
+        users,
+    },
 };
 use epub::doc::EpubDoc;
 use regex::Regex;
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
 use uuid::Uuid;
 
-pub async fn add_annotation(book_id: &str, annotation: NewAnnotationRequest) -> Result<String, ProsaError> {
+pub async fn add_annotation(book_id: &str, request: NewAnnotationRequest) -> Result<String, ProsaError> {
+    let annotation_type = request
+        .annotation_type
+        .unwrap_or_else(|| VALID_ANNOTATION_TYPES[0].to_string());
+
+    // Bookmarks mark a single position, so their end is where they start
+    let (end_tag, end_char) = match (request.end_tag, request.end_char) {
+        (Some(end_tag), Some(end_char)) => (end_tag, end_char),
+        (None, None) if annotation_type == "Bookmark" => (request.start_tag.clone(), request.start_char),
+        _ => return Err(AnnotationError::InvalidAnnotation.into()),
+    };
+
+    let tags = normalize_tags(request.tags.unwrap_or_default())?;
+
+    let annotation = NewAnnotation {
+        annotation_type,
+        source: request.source,
+        start_tag: request.start_tag,
+        end_tag,
+        start_char: request.start_char,
+        end_char,
+        note: request.note,
+        color: request.color,
+        style: request.style,
+        tags,
+    };
+
     let epub_id = books::repository::get_book(book_id).await?.epub_id;
+    let annotation_id = insert_annotation(book_id, &epub_id, &annotation).await?;
 
-    if !validate_annotation(&annotation, &epub_id) {
-        return Err(AnnotationError::InvalidAnnotation.into());
-    }
+    Ok(annotation_id)
+}
 
+async fn insert_annotation(
+    book_id: &str,
+    epub_id: &str,
+    annotation: &NewAnnotation,
+) -> Result<String, AnnotationError> {
+    let Some(text) = validate_annotation(annotation, epub_id) else {
+        return Err(AnnotationError::InvalidAnnotation);
+    };
+
+    // Bookmarks only keep their position, not the text at it
+    let text = Some(text).filter(|t| !t.is_empty() && annotation.annotation_type != "Bookmark");
     let annotation_id = Uuid::new_v4().to_string();
-    repository::add_annotation(&annotation_id, book_id, &annotation).await?;
+    repository::add_annotation(&annotation_id, book_id, annotation, text.as_deref()).await?;
 
     Ok(annotation_id)
 }
 
 pub async fn get_annotation(annotation_id: &str) -> Result<Annotation, ProsaError> {
-    let annotation = repository::get_annotation(annotation_id).await?;
+    let mut annotation = repository::get_annotation(annotation_id).await?;
+    annotation.tags = repository::get_tags(annotation_id).await;
+
     Ok(annotation)
 }
 
-pub async fn get_annotations(book_id: &str) -> Vec<String> {
-    repository::get_annotations(book_id).await
+pub async fn get_annotations(book_id: &str, annotation_type: Option<&str>) -> Vec<String> {
+    repository::get_annotations(book_id, annotation_type).await
+}
+
+/// Gets every annotation of a book, along with its tags.
+pub async fn get_book_annotations(book_id: &str) -> Vec<Annotation> {
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for (annotation_id, tag) in repository::get_book_tags(book_id).await {
+        tags.entry(annotation_id).or_default().push(tag);
+    }
+
+    let mut annotations = repository::get_book_annotations(book_id).await;
+    for annotation in &mut annotations {
+        annotation.tags = tags.remove(&annotation.annotation_id).unwrap_or_default();
+    }
+
+    annotations
 }
 
 pub async fn delete_annotation(annotation_id: &str) -> Result<(), ProsaError> {
@@ -35,15 +112,384 @@ pub async fn delete_annotation(annotation_id: &str) -> Result<(), ProsaError> {
     Ok(())
 }
 
-pub async fn patch_annotation(annotation_id: &str, note: Option<String>) -> Result<(), ProsaError> {
-    let note = note.filter(|n| !n.is_empty());
-    repository::patch_annotation(annotation_id, note).await?;
+pub async fn patch_annotation(
+    annotation_id: &str,
+    request: PatchAnnotationRequest,
+) -> Result<(), ProsaError> {
+    let annotation = repository::get_annotation(annotation_id).await?;
+
+    // Fields left out are kept as they are, while empty ones are cleared
+    let patch = |new: Option<String>, current: Option<String>| match new {
+        Some(value) => Some(value).filter(|v| !v.is_empty()),
+        None => current,
+    };
+
+    let note = patch(request.note, annotation.note);
+    let color = patch(request.color, annotation.color);
+    let style = patch(request.style, annotation.style);
+
+    if !validate_type(
+        &annotation.annotation_type,
+        note.as_deref(),
+        color.as_deref(),
+        style.as_deref(),
+    ) {
+        return Err(AnnotationError::InvalidAnnotation.into());
+    }
+
+    let tags = match request.tags {
+        Some(tags) => normalize_tags(tags)?,
+        None => repository::get_tags(annotation_id).await,
+    };
+
+    repository::patch_annotation(
+        annotation_id,
+        note.as_deref(),
+        color.as_deref(),
+        style.as_deref(),
+        &tags,
+    )
+    .await?;
     Ok(())
 }
 
-fn validate_annotation(annotation: &NewAnnotationRequest, epub_id: &str) -> bool {
-    if !validate_tags(&annotation.start_tag, &annotation.end_tag) {
-        return false;
+/// Moves the annotations of a book to another one with a different file, following the text they
+/// were made on. Annotations whose text is not in the other file are moved, but flagged as orphaned.
+pub async fn move_annotations(
+    from_book_id: &str,
+    to_book_id: &str,
+    from_spans: &KoboSpans,
+    to_spans: &KoboSpans,
+) -> bool {
+    // Annotations are placed in the other file while still on their book, as the two may overlap
+    remap_annotations(from_book_id, from_spans, to_spans).await;
+    repository::move_annotations(from_book_id, to_book_id).await > 0
+}
+
+/// Carries the annotations of a book over to a new file, following the text they were made on.
+/// Annotations whose text is gone are kept, but flagged as orphaned.
+pub async fn remap_annotations(book_id: &str, old_spans: &KoboSpans, new_spans: &KoboSpans) -> bool {
+    let mut changed = false;
+
+    for annotation in repository::get_book_annotations(book_id).await {
+        if annotation.orphaned {
+            continue;
+        }
+
+        let start_text = old_spans.text(&annotation.source, &annotation.start_tag);
+        let end_text = old_spans.text(&annotation.source, &annotation.end_tag);
+
+        if start_text.is_some()
+            && start_text == new_spans.text(&annotation.source, &annotation.start_tag)
+            && end_text == new_spans.text(&annotation.source, &annotation.end_tag)
+        {
+            continue;
+        }
+
+        let remapped = match start_text.zip(end_text) {
+            Some((start, end)) => new_spans.find_range(&annotation.source, start, end),
+            None => None,
+        };
+
+        // Landing on top of another annotation counts as losing the text
+        let remapped = match remapped {
+            Some((source, start_tag, end_tag)) => {
+                repository::remap_annotation(&annotation.annotation_id, &source, &start_tag, &end_tag)
+                    .await
+                    .is_ok()
+            }
+            None => false,
+        };
+
+        if !remapped {
+            repository::orphan_annotation(&annotation.annotation_id).await;
+        }
+
+        changed = true;
+    }
+
+    changed
+}
+
+/// Gathers the annotations of a book in reading order, with the text they highlight, the chapter
+/// they are in and the details of the book.
+pub async fn export_book_annotations(book_id: &str) -> Result<BookAnnotationsExport, ProsaError> {
+    let book = books::service::get_book(book_id).await?;
+    let metadata = match &book.metadata_id {
+        Some(metadata_id) => Some(metadata::service::get_metadata(metadata_id).await?),
+        None => None,
+    };
+
+    let (spans, pages) = epubs::service::read_text(&book.epub_id).await?;
+    let page_order: HashMap<&str, usize> = pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| (page.path.as_str(), i))
+        .collect();
+    let chapters: HashMap<&str, &str> = pages
+        .iter()
+        .filter_map(|page| Some((page.path.as_str(), page.chapter.as_deref()?)))
+        .collect();
+
+    let mut annotations = get_book_annotations(book_id).await;
+    annotations.sort_by_key(|a| {
+        (
+            page_order.get(a.source.as_str()).copied().unwrap_or(usize::MAX),
+            spans.position(&a.source, &a.start_tag).unwrap_or(usize::MAX),
+            a.start_char,
+        )
+    });
+
+    let annotations = annotations
+        .into_iter()
+        .map(|a| ExportedAnnotation {
+            chapter: chapters.get(a.source.as_str()).map(ToString::to_string),
+            tags: a.tags,
+            annotation_id: a.annotation_id,
+            annotation_type: a.annotation_type,
+            text: a.text,
+            note: a.note,
+            color: a.color,
+            style: a.style,
+            source: a.source,
+            start_tag: a.start_tag,
+            end_tag: a.end_tag,
+            start_char: a.start_char,
+            end_char: a.end_char,
+            created_at: a.created_at,
+            updated_at: a.updated_at,
+            orphaned: a.orphaned,
+        })
+        .collect();
+
+    let metadata = metadata.unwrap_or_default();
+    let authors = metadata
+        .contributors
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| c.role.eq_ignore_ascii_case("author"))
+        .map(|c| c.name)
+        .collect();
+
+    Ok(BookAnnotationsExport {
+        book_id: book_id.to_string(),
+        title: metadata.title,
+        subtitle: metadata.subtitle,
+        authors,
+        publisher: metadata.publisher,
+        publication_date: metadata.publication_date,
+        isbn: metadata.isbn,
+        annotations,
+    })
+}
+
+/// Exports the annotations of every book of a user that has any.
+pub async fn export_user_annotations(owner_id: &str) -> Result<Vec<BookAnnotationsExport>, ProsaError> {
+    // Ensure user exists
+    users::service::get_user(owner_id).await?;
+
+    let mut exports = Vec::new();
+    for book_id in repository::get_annotated_books(owner_id).await {
+        let lock = LOCKS.get_book_lock(&book_id).await;
+        let _guard = lock.read().await;
+
+        // The book may have been deleted since it was listed
+        match export_book_annotations(&book_id).await {
+            Ok(export) => exports.push(export),
+            Err(_) if !books::service::book_exists(&book_id).await => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(exports)
+}
+
+/// Searches the annotations of every book of a user, newest first.
+pub async fn search_annotations(
+    owner_id: &str,
+    search: AnnotationSearch,
+    page: Option<i64>,
+    page_size: Option<i64>,
+) -> Result<PaginatedAnnotations, ProsaError> {
+    let page = page.unwrap_or(1);
+    let page_size = page_size.unwrap_or(10);
+
+    if page <= 0 || page_size <= 0 {
+        return Err(AnnotationError::InvalidPagination.into());
+    }
+
+    // Ensure user exists
+    users::service::get_user(owner_id).await?;
+
+    let (mut annotations, total_elements) =
+        repository::search_annotations(owner_id, &search, page, page_size).await;
+    for result in &mut annotations {
+        result.annotation.tags = repository::get_tags(&result.annotation.annotation_id).await;
+    }
+
+    let total_pages = (total_elements + page_size - 1) / page_size;
+
+    Ok(PaginatedAnnotations {
+        annotations,
+        page_size,
+        total_elements,
+        total_pages,
+        current_page: page,
+    })
+}
+
+/// Adds highlights made in another reader to the books of a user they were made in, or to the
+/// given book. Each is placed where its text is found in the book, and those whose book or text
+/// cannot be found are reported back.
+pub async fn import_annotations(
+    owner_id: &str,
+    book_id: Option<&str>,
+    clippings: Vec<Clipping>,
+    session_id: &str,
+) -> Result<ImportReport, ProsaError> {
+    // Ensure user exists
+    users::service::get_user(owner_id).await?;
+
+    if let Some(book_id) = book_id
+        && books::service::get_book(book_id).await?.owner_id != owner_id
+    {
+        return Err(BookError::BookNotFound.into());
+    }
+
+    let mut identities = Vec::new();
+    for clipping in &clippings {
+        if !identities.contains(&clipping.book) {
+            identities.push(clipping.book.clone());
+        }
+    }
+
+    let book_ids = match book_id {
+        Some(book_id) => vec![Some(book_id.to_string()); identities.len()],
+        None => duplicates::service::find_books(owner_id, &identities).await,
+    };
+    let matches: HashMap<_, _> = identities.into_iter().zip(book_ids).collect();
+
+    let mut report = ImportReport {
+        imported: 0,
+        existing: 0,
+        unmatched: Vec::new(),
+    };
+
+    let mut books: BTreeMap<String, Vec<Clipping>> = BTreeMap::new();
+    for clipping in clippings {
+        match &matches[&clipping.book] {
+            Some(book_id) => books.entry(book_id.clone()).or_default().push(clipping),
+            None => report
+                .unmatched
+                .push(unmatched(clipping, UnmatchedReason::BookNotFound)),
+        }
+    }
+
+    for (book_id, clippings) in books {
+        let lock = LOCKS.get_book_lock(&book_id).await;
+        let _guard = lock.write().await;
+
+        // The book may have been deleted since it was matched
+        let Ok(book) = books::service::get_book(&book_id).await else {
+            let clippings = clippings.into_iter();
+            report
+                .unmatched
+                .extend(clippings.map(|c| unmatched(c, UnmatchedReason::BookNotFound)));
+            continue;
+        };
+
+        let spans = epubs::service::read_spans(&book.epub_id).await?;
+        let mut changed = false;
+
+        for clipping in clippings {
+            let position = clipping.text.as_deref().and_then(|text| spans.locate(text));
+            let Some(TextPosition {
+                source,
+                start: (start_tag, start_char),
+                end: (end_tag, end_char),
+            }) = position
+            else {
+                report
+                    .unmatched
+                    .push(unmatched(clipping, UnmatchedReason::TextNotFound));
+                continue;
+            };
+
+            // Highlights imported along with a note are notes of their own
+            let annotation_type = if clipping.note.is_some() {
+                "Note"
+            } else {
+                "Highlight"
+            };
+            let annotation = NewAnnotation {
+                annotation_type: annotation_type.to_string(),
+                source,
+                start_tag,
+                end_tag,
+                start_char: u32::try_from(start_char).unwrap_or(u32::MAX),
+                end_char: u32::try_from(end_char).unwrap_or(u32::MAX),
+                note: clipping.note.clone(),
+                color: clipping.color.clone(),
+                style: clipping.style.clone(),
+                tags: Vec::new(),
+            };
+
+            match insert_annotation(&book_id, &book.epub_id, &annotation).await {
+                Ok(_) => {
+                    report.imported += 1;
+                    changed = true;
+                }
+                Err(AnnotationError::AnnotationConflict) => report.existing += 1,
+                // The text was found, so it is the rest of the clipping that cannot be stored
+                Err(AnnotationError::InvalidAnnotation) => {
+                    report
+                        .unmatched
+                        .push(unmatched(clipping, UnmatchedReason::InvalidAnnotation));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if changed {
+            sync::service::log_change(
+                &book_id,
+                ChangeLogEntityType::BookAnnotations,
+                ChangeLogAction::Create,
+                &book.owner_id,
+                session_id,
+            )
+            .await;
+        }
+    }
+
+    Ok(report)
+}
+
+fn unmatched(clipping: Clipping, reason: UnmatchedReason) -> UnmatchedClipping {
+    UnmatchedClipping {
+        title: clipping.book.title,
+        authors: clipping.book.authors,
+        text: clipping.text,
+        note: clipping.note,
+        reason,
+    }
+}
+
+/// Checks that an annotation points at existing text in the book, and returns that text.
+fn validate_annotation(annotation: &NewAnnotation, epub_id: &str) -> Option<String> {
+    let single_position =
+        annotation.start_tag == annotation.end_tag && annotation.start_char == annotation.end_char;
+
+    if !validate_tags(&annotation.start_tag, &annotation.end_tag)
+        || !validate_type(
+            &annotation.annotation_type,
+            annotation.note.as_deref(),
+            annotation.color.as_deref(),
+            annotation.style.as_deref(),
+        )
+        || (annotation.annotation_type == "Bookmark" && !single_position)
+    {
+        return None;
     }
 
     let source_cache_key = format!("sources:{epub_id}");
@@ -56,24 +502,26 @@ fn validate_annotation(annotation: &NewAnnotationRequest, epub_id: &str) -> bool
         "tag_lengths:{}:{}:{}",
         epub_id, &annotation.source, annotation.end_tag
     );
+    let span_cache_key = format!("spans:{}:{}", epub_id, &annotation.source);
 
-    if let (Some(sources), Some(tags), Some(start_length), Some(end_length)) = (
+    if let (Some(sources), Some(tags), Some(start_length), Some(end_length), Some(spans)) = (
         CACHE.source_cache.get(&source_cache_key),
         CACHE.tag_cache.get(&tag_cache_key),
         CACHE.tag_length_cache.get(&start_tag_length_cache_key),
         CACHE.tag_length_cache.get(&end_tag_length_cache_key),
+        CACHE.span_cache.get(&span_cache_key),
     ) {
-        return sources.contains(&annotation.source)
+        let valid = sources.contains(&annotation.source)
             && tags.contains(&annotation.start_tag)
             && tags.contains(&annotation.end_tag)
             && annotation.start_char < start_length
             && annotation.end_char < end_length;
+
+        return valid.then(|| quote_annotation(annotation, &spans));
     }
 
     let epub_file = format!("{}/{epub_id}.kepub.epub", CONFIG.book_storage.epub_path);
-    let Ok(mut doc) = EpubDoc::new(epub_file) else {
-        return false;
-    };
+    let mut doc = EpubDoc::new(epub_file).ok()?;
 
     let sources = CACHE.source_cache.get(&source_cache_key).unwrap_or_else(|| {
         let sources: HashSet<String> = doc
@@ -89,12 +537,10 @@ fn validate_annotation(annotation: &NewAnnotationRequest, epub_id: &str) -> bool
     });
 
     if !sources.contains(&annotation.source) {
-        return false;
+        return None;
     }
 
-    let Some(text) = doc.get_resource_str_by_path(&annotation.source) else {
-        return false;
-    };
+    let text = doc.get_resource_str_by_path(&annotation.source)?;
 
     let tags = CACHE.tag_cache.get(&tag_cache_key).unwrap_or_else(|| {
         let tags = extract_tags(&text);
@@ -104,7 +550,7 @@ fn validate_annotation(annotation: &NewAnnotationRequest, epub_id: &str) -> bool
     });
 
     if !tags.contains(&annotation.start_tag) || !tags.contains(&annotation.end_tag) {
-        return false;
+        return None;
     }
 
     let start_length = CACHE
@@ -131,7 +577,69 @@ fn validate_annotation(annotation: &NewAnnotationRequest, epub_id: &str) -> bool
             length
         });
 
-    annotation.start_char < start_length && annotation.end_char < end_length
+    let spans = CACHE.span_cache.get(&span_cache_key).unwrap_or_else(|| {
+        let spans = Arc::new(KoboSpans::read_page(&annotation.source, &text));
+        CACHE.span_cache.insert(span_cache_key.clone(), spans.clone());
+        spans
+    });
+
+    let valid = annotation.start_char < start_length && annotation.end_char < end_length;
+    valid.then(|| quote_annotation(annotation, &spans))
+}
+
+fn quote_annotation(annotation: &NewAnnotation, spans: &KoboSpans) -> String {
+    spans
+        .text_between(
+            &annotation.source,
+            (&annotation.start_tag, annotation.start_char as usize),
+            (&annotation.end_tag, annotation.end_char as usize),
+        )
+        .unwrap_or_default()
+}
+
+/// Notes need a note, and bookmarks are not drawn on the text, so they can't have a colour or style.
+fn validate_type(
+    annotation_type: &str,
+    note: Option<&str>,
+    color: Option<&str>,
+    style: Option<&str>,
+) -> bool {
+    let valid = match annotation_type {
+        "Highlight" => true,
+        "Note" => note.is_some_and(|n| !n.is_empty()),
+        "Bookmark" => color.is_none() && style.is_none(),
+        _ => false,
+    };
+
+    valid && validate_appearance(color, style)
+}
+
+/// Trims the tags and drops repeated ones. Tags are short labels of printable characters, like shelf names.
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, AnnotationError> {
+    let mut valid: Vec<String> = Vec::new();
+
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.chars().count() > 30 || tag.chars().any(char::is_control) {
+            return Err(AnnotationError::InvalidTags);
+        }
+
+        if !valid.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            valid.push(tag.to_string());
+        }
+    }
+
+    Ok(valid)
+}
+
+fn validate_appearance(color: Option<&str>, style: Option<&str>) -> bool {
+    let valid_color = color.is_none_or(|c| {
+        c.strip_prefix('#')
+            .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+    });
+    let valid_style = style.is_none_or(|s| VALID_HIGHLIGHT_STYLES.contains(&s));
+
+    valid_color && valid_style
 }
 
 fn get_tag_length(tag_id: &str, text: &str) -> Option<u32> {
@@ -62,6 +62,36 @@ pub async fn delete_refresh_token(token_hash: &str) -> Result<(), AuthTokenError
     Ok(())
 }
 
+pub async fn session_has_refresh_tokens(session_id: &str) -> bool {
+    let exists = sqlx::query_scalar::<_, i64>(
+        r"
+        SELECT 1
+        FROM refresh_tokens
+        WHERE session_id = $1
+        LIMIT 1
+        ",
+    )
+    .bind(session_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to verify session refresh tokens");
+
+    exists.is_some()
+}
+
+pub async fn delete_session_refresh_tokens(session_id: &str) {
+    sqlx::query(
+        r"
+        DELETE FROM refresh_tokens
+        WHERE session_id = $1
+        ",
+    )
+    .bind(session_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to delete session refresh tokens");
+}
+
 pub async fn add_api_key(
     key_id: &str,
     user_id: &str,
@@ -201,6 +201,24 @@ pub async fn revoke_api_key(user_id: &str, key_id: &str) -> Result<(), ProsaErro
     Ok(())
 }
 
+/// Signs a session out: its refresh tokens stop working, and so does the API key behind it if
+/// there is one. Access tokens already issued remain valid until they expire.
+pub async fn revoke_session(user_id: &str, session_id: &str) {
+    repository::delete_session_refresh_tokens(session_id).await;
+
+    // Sessions opened with a password have no API key behind them
+    let _ = repository::delete_api_key(user_id, session_id).await;
+}
+
+/// Tells whether the session behind the token is still signed in. Revoked API keys no longer
+/// authenticate at all, but access tokens outlive the refresh tokens of their session.
+pub async fn is_session_active(token: &AuthToken) -> bool {
+    match token.auth_type {
+        AuthType::ApiKey => true,
+        AuthType::Jwt => repository::session_has_refresh_tokens(&token.session_id).await,
+    }
+}
+
 pub fn hash_secret(secret: &str) -> String {
     let salt = SaltString::generate(&mut OsRng);
     Argon2::default()
@@ -5,10 +5,11 @@ use crate::app::{
 };
 use axum::{
     Extension,
-    extract::{Path, Request},
+    extract::{Path, Query, Request},
     middleware::Next,
     response::IntoResponse,
 };
+use std::collections::HashMap;
 
 fn user_id_matches(user_id: &str, token: &AuthToken) -> bool {
     let token_user_id = match &token.role {
@@ -56,3 +57,62 @@ pub async fn can_update_annotation(
 
     Ok(next.run(request).await)
 }
+
+pub async fn can_export_annotations(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    match params.get("user_id") {
+        Some(id) if !user_id_matches(id, &token) => Err(AuthError::Forbidden.into()),
+        _ => Ok(next.run(request).await),
+    }
+}
+
+pub async fn can_search_annotations(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    match params.get("user_id") {
+        Some(id) if !user_id_matches(id, &token) => Err(AuthError::Forbidden.into()),
+        _ => Ok(next.run(request).await),
+    }
+}
+
+pub async fn can_import_annotations(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&UPDATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if let Some(id) = params.get("user_id")
+        && !user_id_matches(id, &token)
+    {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if let Some(book_id) = params.get("book_id") {
+        let book = books::service::get_book(book_id).await?;
+
+        if !user_id_matches(&book.owner_id, &token) {
+            return Err(BookError::BookNotFound.into());
+        }
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,122 @@
+use crate::app::{
+    authentication::models::{AuthError, AuthRole, AuthToken, CREATE, DELETE, READ, UPDATE},
+    devices::{self, models::DeviceError},
+    error::ProsaError,
+};
+use axum::{
+    Extension,
+    extract::{Path, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+
+fn user_id_matches(user_id: &str, token: &AuthToken) -> bool {
+    let token_user_id = match &token.role {
+        AuthRole::Admin(_) => return true,
+        AuthRole::User(id) => id,
+    };
+
+    user_id == token_user_id
+}
+
+async fn device_belongs_to_user(device_id: &str, user_id: &str) -> Result<(), ProsaError> {
+    let device = devices::service::get_device(device_id).await?;
+
+    if device.owner_id != user_id {
+        return Err(DeviceError::DeviceNotFound.into());
+    }
+
+    Ok(())
+}
+
+pub async fn can_register_device(
+    Extension(token): Extension<AuthToken>,
+    Path(user_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&CREATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    // Only the session itself can be registered, so not even admins can do it for someone else
+    if user_id != token.role.get_user() {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_read_devices(
+    Extension(token): Extension<AuthToken>,
+    Path(user_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if !user_id_matches(&user_id, &token) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_read_device(
+    Extension(token): Extension<AuthToken>,
+    Path((user_id, device_id)): Path<(String, String)>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if !user_id_matches(&user_id, &token) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    device_belongs_to_user(&device_id, &user_id).await?;
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_update_device(
+    Extension(token): Extension<AuthToken>,
+    Path((user_id, device_id)): Path<(String, String)>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&UPDATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if !user_id_matches(&user_id, &token) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    device_belongs_to_user(&device_id, &user_id).await?;
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_delete_device(
+    Extension(token): Extension<AuthToken>,
+    Path((user_id, device_id)): Path<(String, String)>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&DELETE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if !user_id_matches(&user_id, &token) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    device_belongs_to_user(&device_id, &user_id).await?;
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,80 @@
+use crate::app::{
+    authentication::models::{AuthError, AuthRole, AuthToken, DELETE, READ, UPDATE},
+    books::{self, models::BookError},
+    duplicates::models::{DuplicateError, MergeBooksRequest},
+    error::ProsaError,
+};
+use axum::{
+    Extension, Json,
+    body::{Body, to_bytes},
+    extract::{FromRequest, Path, Query, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+use std::collections::HashMap;
+
+fn user_id_matches(user_id: &str, token: &AuthToken) -> bool {
+    let token_user_id = match &token.role {
+        AuthRole::Admin(_) => return true,
+        AuthRole::User(id) => id,
+    };
+
+    user_id == token_user_id
+}
+
+pub async fn can_list_duplicates(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    match params.get("user_id") {
+        Some(id) if !user_id_matches(id, &token) => Err(AuthError::Forbidden.into()),
+        _ => Ok(next.run(request).await),
+    }
+}
+
+pub async fn can_merge_books(
+    Extension(token): Extension<AuthToken>,
+    Path(book_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    // The duplicate is deleted once merged
+    if !token.capabilities.contains(&UPDATE.to_string()) || !token.capabilities.contains(&DELETE.to_string())
+    {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let book = books::service::get_book(&book_id).await?;
+
+    if !user_id_matches(&book.owner_id, &token) {
+        return Err(BookError::BookNotFound.into());
+    }
+
+    let (parts, body) = request.into_parts();
+    let body_bytes = to_bytes(body, 1000).await.expect("Failed to parse request");
+    let request1 = Request::from_parts(parts.clone(), Body::from(body_bytes.clone()));
+    let request2 = Request::from_parts(parts, Body::from(body_bytes));
+
+    let Json(payload): Json<MergeBooksRequest> = match Json::from_request(request1, &()).await {
+        Ok(p) => p,
+        Err(_) => return Err(DuplicateError::InvalidMergeRequest.into()),
+    };
+
+    let duplicate = books::service::get_book(&payload.duplicate_id).await?;
+
+    if !user_id_matches(&duplicate.owner_id, &token) {
+        return Err(BookError::BookNotFound.into());
+    }
+
+    if duplicate.owner_id != book.owner_id {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(next.run(request2).await)
+}
@@ -2,12 +2,15 @@ use crate::app::{
     authentication::models::{AuthError, AuthRole, AuthToken, READ, UPDATE},
     books::{self, models::BookError},
     error::ProsaError,
-    metadata::models::{MetadataError, MetadataFetchRequest},
+    metadata::{
+        self,
+        models::{MetadataError, MetadataFetchRequest},
+    },
 };
 use axum::{
     Extension, Json,
     body::{Body, to_bytes},
-    extract::{FromRequest, Query, Request},
+    extract::{FromRequest, Path, Query, Request},
     middleware::Next,
     response::IntoResponse,
 };
@@ -69,3 +72,79 @@ pub async fn can_add_metadata_request(
 
     Ok(next.run(request2).await)
 }
+
+pub async fn can_read_metadata_request(
+    Extension(token): Extension<AuthToken>,
+    Path(job_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let job = metadata::service::get_job(&job_id).await?;
+
+    if !user_id_matches(&job.user_id, &token) {
+        return Err(MetadataError::MetadataRequestNotFound.into());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_update_metadata_request(
+    Extension(token): Extension<AuthToken>,
+    Path(job_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&UPDATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let job = metadata::service::get_job(&job_id).await?;
+
+    if !user_id_matches(&job.user_id, &token) {
+        return Err(MetadataError::MetadataRequestNotFound.into());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_read_metadata_candidate(
+    Extension(token): Extension<AuthToken>,
+    Path((book_id, _)): Path<(String, String)>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let book = books::service::get_book(&book_id).await?;
+
+    if !user_id_matches(&book.owner_id, &token) {
+        return Err(BookError::BookNotFound.into());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_update_metadata_candidate(
+    Extension(token): Extension<AuthToken>,
+    Path((book_id, _)): Path<(String, String)>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&UPDATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let book = books::service::get_book(&book_id).await?;
+
+    if !user_id_matches(&book.owner_id, &token) {
+        return Err(BookError::BookNotFound.into());
+    }
+
+    Ok(next.run(request).await)
+}
@@ -1,6 +1,9 @@
 pub mod annotations;
 pub mod books;
+pub mod devices;
+pub mod duplicates;
 pub mod metadata;
 pub mod shelves;
 pub mod sync;
+pub mod trash;
 pub mod users;
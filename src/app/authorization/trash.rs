@@ -0,0 +1,76 @@
+use crate::app::{
+    authentication::models::{AuthError, AuthRole, AuthToken, CREATE, READ},
+    books::{self, models::BookError},
+    error::ProsaError,
+    shelves::{self, models::ShelfError},
+};
+use axum::{
+    Extension,
+    extract::{Path, Query, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+use std::collections::HashMap;
+
+fn user_id_matches(user_id: &str, token: &AuthToken) -> bool {
+    let token_user_id = match &token.role {
+        AuthRole::Admin(_) => return true,
+        AuthRole::User(id) => id,
+    };
+
+    user_id == token_user_id
+}
+
+pub async fn can_read_trash(
+    Extension(token): Extension<AuthToken>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&READ.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    match params.get("user_id") {
+        Some(id) if !user_id_matches(id, &token) => Err(AuthError::Forbidden.into()),
+        _ => Ok(next.run(request).await),
+    }
+}
+
+pub async fn can_restore_book(
+    Extension(token): Extension<AuthToken>,
+    Path(book_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&CREATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let book = books::service::get_trashed_book(&book_id).await?;
+
+    if !user_id_matches(&book.owner_id, &token) {
+        return Err(BookError::BookNotFound.into());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub async fn can_restore_shelf(
+    Extension(token): Extension<AuthToken>,
+    Path(shelf_id): Path<String>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ProsaError> {
+    if !token.capabilities.contains(&CREATE.to_string()) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let shelf = shelves::service::get_trashed_shelf(&shelf_id).await?;
+
+    if !user_id_matches(&shelf.owner_id, &token) {
+        return Err(ShelfError::ShelfNotFound.into());
+    }
+
+    Ok(next.run(request).await)
+}
@@ -1,4 +1,4 @@
-use super::models::{BookEntity, BookError, UploadBookRequest};
+use super::models::{BookEntity, BookError, ReplaceBookRequest, UploadBookRequest};
 use crate::app::{
     authentication::models::AuthToken,
     books::{
@@ -25,12 +25,43 @@ use axum::{
 use axum_typed_multipart::TypedMultipart;
 use std::collections::HashMap;
 
-pub async fn download_book_handler(Path(book_id): Path<String>) -> Result<Vec<u8>, ProsaError> {
+pub async fn download_book_handler(
+    Path(book_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Vec<u8>, ProsaError> {
+    let embed_metadata = match params.get("embed_metadata").map(|e| e.parse::<bool>()) {
+        Some(Ok(e)) => Some(e),
+        None => None,
+        _ => return Err(BookError::InvalidEmbedFlag.into()),
+    };
+
     let lock = LOCKS.get_book_lock(&book_id).await;
     let _guard = lock.read().await;
 
     let book = service::get_book(&book_id).await?;
-    let epub = epubs::service::read_epub(&book.epub_id).await?;
+
+    // Without an explicit choice, the owner's preference decides
+    let embed_metadata = match embed_metadata {
+        Some(e) => e,
+        None => users::service::get_preferences(&book.owner_id)
+            .await?
+            .embed_metadata
+            .unwrap_or(false),
+    };
+
+    if !embed_metadata {
+        let epub = epubs::service::read_epub(&book.epub_id).await?;
+        return Ok(epub);
+    }
+
+    let metadata = match &book.metadata_id {
+        Some(metadata_id) => Some(metadata::service::get_metadata(metadata_id).await?),
+        None => None,
+    };
+
+    let epub =
+        epubs::service::read_epub_with_metadata(&book_id, &book.epub_id, metadata, book.cover_id.as_deref())
+            .await?;
 
     Ok(epub)
 }
@@ -57,7 +88,7 @@ pub async fn upload_book_handler(
     TypedMultipart(data): TypedMultipart<UploadBookRequest>,
 ) -> Result<String, ProsaError> {
     if let Some(id) = &data.book_id
-        && service::book_exists(id).await
+        && service::book_id_is_in_use(id).await
     {
         return Err(BookError::BookIdConflict.into());
     }
@@ -70,9 +101,13 @@ pub async fn upload_book_handler(
     let preferences = users::service::get_preferences(owner_id).await?;
     let epub_id = epubs::service::write_epub(&data.epub.to_vec()).await?;
 
-    if service::epub_is_in_use_by_user(&epub_id, owner_id).await {
-        return Err(BookError::BookConflict.into());
-    }
+    service::check_epub_not_owned(&epub_id, owner_id).await?;
+
+    // The embedded cover is used regardless of the metadata preferences, an unreadable one is ignored
+    let cover_id = match epubs::service::extract_cover(&data.epub) {
+        Some(cover) => covers::service::write_cover(&cover, false).await.ok(),
+        None => None,
+    };
 
     let state_id = state::service::initialize_state().await;
 
@@ -80,7 +115,7 @@ pub async fn upload_book_handler(
         owner_id: owner_id.to_string(),
         epub_id,
         metadata_id: None,
-        cover_id: None,
+        cover_id,
         state_id,
     };
 
@@ -95,19 +130,36 @@ pub async fn upload_book_handler(
     )
     .await;
 
+    if book.cover_id.is_some() {
+        sync::service::log_change(
+            &book_id,
+            ChangeLogEntityType::BookCover,
+            ChangeLogAction::Create,
+            owner_id,
+            &token.session_id,
+        )
+        .await;
+    }
+
     let lock = LOCKS.get_book_lock(&book_id).await;
     let _guard = lock.write().await;
 
+    if book.cover_id.is_none() {
+        covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
+    }
+
     let automatic_metadata = preferences
         .automatic_metadata
         .expect("Metadata preference should be present");
 
+    // The refresh scheduler may have picked up the new book already
     if automatic_metadata {
         METADATA_FETCHER
-            .enqueue_request(
+            .try_enqueue_request(
                 owner_id,
                 &book_id,
                 preferences.metadata_providers.unwrap_or(vec![]),
+                0,
             )
             .await?;
     }
@@ -115,6 +167,19 @@ pub async fn upload_book_handler(
     Ok(book_id)
 }
 
+pub async fn replace_book_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(book_id): Path<String>,
+    TypedMultipart(data): TypedMultipart<ReplaceBookRequest>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.write().await;
+
+    service::replace_book_file(&book_id, &data.epub.to_vec(), &token.session_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn search_books_handler(
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<PaginatedBookResponse>, ProsaError> {
@@ -153,33 +218,7 @@ pub async fn delete_book_handler(
     let lock = LOCKS.get_book_lock(&book_id).await;
     let _guard = lock.write().await;
 
-    let book = service::get_book(&book_id).await?;
-    service::delete_book(&book_id).await?;
-
-    if let Some(metadata_id) = book.metadata_id {
-        metadata::service::delete_metadata(&metadata_id).await?;
-    }
-
-    if !service::epub_is_in_use(&book.epub_id).await {
-        epubs::service::delete_epub(&book.epub_id).await?;
-    }
-
-    sync::service::log_change(
-        &book_id,
-        ChangeLogEntityType::BookFile,
-        ChangeLogAction::Delete,
-        &book.owner_id,
-        &token.session_id,
-    )
-    .await;
-
-    let Some(cover_id) = book.cover_id else {
-        return Ok(StatusCode::NO_CONTENT);
-    };
-
-    if !service::cover_is_in_use(&cover_id).await {
-        covers::service::delete_cover(&cover_id).await?;
-    }
+    service::trash_book(&book_id, &token.session_id).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -18,6 +18,9 @@ pub enum BookError {
     #[strum(message = "This book is already in your library.")]
     #[strum(props(StatusCode = "409"))]
     BookConflict,
+    #[strum(message = "This book is in your trash, restore it instead.")]
+    #[strum(props(StatusCode = "409"))]
+    BookInTrash,
     #[strum(message = "The requested pagination is invalid.")]
     #[strum(props(StatusCode = "400"))]
     InvalidPagination,
@@ -27,6 +30,9 @@ pub enum BookError {
     #[strum(message = "The provided book id is already in use.")]
     #[strum(props(StatusCode = "409"))]
     BookIdConflict,
+    #[strum(message = "The provided metadata embedding flag is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidEmbedFlag,
     #[strum(message = "Internal error")]
     #[strum(props(StatusCode = "500"))]
     InternalError,
@@ -68,6 +74,12 @@ pub struct UploadBookRequest {
     pub epub: Bytes,
 }
 
+#[derive(TryFromMultipart)]
+pub struct ReplaceBookRequest {
+    #[form_data(limit = "50MiB")]
+    pub epub: Bytes,
+}
+
 #[derive(Serialize)]
 pub struct PaginatedBookResponse {
     pub book_ids: Vec<String>,
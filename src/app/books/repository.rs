@@ -1,12 +1,13 @@
 use super::models::{BookEntity, BookError, PaginatedBookResponse};
 use crate::DB_POOL;
+use chrono::{DateTime, Utc};
 
 pub async fn get_book(book_id: &str) -> Result<BookEntity, BookError> {
     let book = sqlx::query_as::<_, BookEntity>(
         r"
         SELECT owner_id, epub_id, metadata_id, cover_id, state_id
         FROM books
-        WHERE book_id = ?
+        WHERE book_id = ? AND deleted_at IS NULL
         ",
     )
     .bind(book_id)
@@ -16,6 +17,37 @@ pub async fn get_book(book_id: &str) -> Result<BookEntity, BookError> {
     Ok(book)
 }
 
+pub async fn get_trashed_book(book_id: &str) -> Result<BookEntity, BookError> {
+    let book = sqlx::query_as::<_, BookEntity>(
+        r"
+        SELECT owner_id, epub_id, metadata_id, cover_id, state_id
+        FROM books
+        WHERE book_id = ? AND deleted_at IS NOT NULL
+        ",
+    )
+    .bind(book_id)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(book)
+}
+
+pub async fn book_id_exists(book_id: &str) -> bool {
+    let exists = sqlx::query_scalar::<_, i64>(
+        r"
+        SELECT 1
+        FROM books
+        WHERE book_id = ?
+        ",
+    )
+    .bind(book_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to verify if book exists");
+
+    exists.is_some()
+}
+
 pub async fn add_book(book_id: &str, book: &BookEntity) -> Result<(), BookError> {
     sqlx::query(
         r"
@@ -53,6 +85,26 @@ pub async fn delete_book(book_id: &str) -> Result<(), BookError> {
     Ok(())
 }
 
+pub async fn set_deleted_at(book_id: &str, deleted_at: Option<DateTime<Utc>>) -> Result<(), BookError> {
+    let result = sqlx::query(
+        r"
+        UPDATE books
+        SET deleted_at = ?
+        WHERE book_id = ?
+        ",
+    )
+    .bind(deleted_at)
+    .bind(book_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(BookError::BookNotFound);
+    }
+
+    Ok(())
+}
+
 pub async fn update_book(book_id: &str, book: &BookEntity) -> Result<(), BookError> {
     let result = sqlx::query(
         r"
@@ -105,10 +157,11 @@ pub async fn get_books_by_epub(epub_id: &str) -> Vec<BookEntity> {
     .expect("Failed to retrieve books by epub")
 }
 
-pub async fn epub_belongs_to_user(epub_id: &str, user_id: &str) -> bool {
-    let exists = sqlx::query_scalar::<_, i64>(
+/// Tells whether the user has a book with this epub, and if so whether it is in the trash.
+pub async fn get_user_epub_trashed(epub_id: &str, user_id: &str) -> Option<bool> {
+    sqlx::query_scalar::<_, bool>(
         r"
-        SELECT 1
+        SELECT deleted_at IS NOT NULL
         FROM books
         WHERE epub_id = ? AND owner_id = ?
         LIMIT 1
@@ -118,9 +171,7 @@ pub async fn epub_belongs_to_user(epub_id: &str, user_id: &str) -> bool {
     .bind(user_id)
     .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
     .await
-    .expect("Failed to verify if epub belongs to user");
-
-    exists.is_some()
+    .expect("Failed to verify if epub belongs to user")
 }
 
 pub async fn get_paginated_books(
@@ -138,7 +189,7 @@ pub async fn get_paginated_books(
         INNER JOIN users u ON b.owner_id = u.user_id
         LEFT JOIN metadata m ON b.metadata_id = m.metadata_id
         LEFT JOIN contributors c ON b.metadata_id = c.metadata_id
-        WHERE 1=1
+        WHERE b.deleted_at IS NULL
     "
     .to_string();
 
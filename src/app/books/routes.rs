@@ -1,16 +1,18 @@
 use crate::app::{
     authentication::middleware::extract_token_middleware,
-    authorization::books::{can_create_book, can_delete_book, can_read_book, can_search_books},
+    authorization::books::{
+        can_create_book, can_delete_book, can_read_book, can_search_books, can_update_book,
+    },
     books::controller::{
-        delete_book_handler, download_book_handler, get_book_file_metadata_handler, search_books_handler,
-        upload_book_handler,
+        delete_book_handler, download_book_handler, get_book_file_metadata_handler, replace_book_handler,
+        search_books_handler, upload_book_handler,
     },
 };
 use axum::{
     Router,
     extract::DefaultBodyLimit,
     middleware::from_fn,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
 };
 
 #[rustfmt::skip]
@@ -25,6 +27,9 @@ pub fn get_routes() -> Router {
         .route("/books/{book_id}", get(download_book_handler) 
             .route_layer(from_fn(can_read_book))
         )
+        .route("/books/{book_id}", put(replace_book_handler)
+            .route_layer(from_fn(can_update_book))
+        )
         .route("/books/{book_id}", delete(delete_book_handler) 
             .route_layer(from_fn(can_delete_book))
         )
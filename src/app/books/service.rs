@@ -1,5 +1,16 @@
 use super::models::{BookEntity, BookError, PaginatedBookResponse};
-use crate::app::{books::repository, error::ProsaError};
+use crate::app::{
+    annotations,
+    books::repository,
+    covers, epubs,
+    error::ProsaError,
+    metadata, state,
+    sync::{
+        self,
+        models::{ChangeLogAction, ChangeLogEntityType},
+    },
+};
+use chrono::Utc;
 use std::str::FromStr;
 use uuid::Uuid;
 
@@ -30,6 +41,145 @@ pub async fn delete_book(book_id: &str) -> Result<(), ProsaError> {
     Ok(())
 }
 
+/// Moves a book to the trash. Devices are told it was deleted, but its state, annotations and
+/// shelves are kept until it is restored or purged.
+pub async fn trash_book(book_id: &str, session_id: &str) -> Result<(), ProsaError> {
+    let book = get_book(book_id).await?;
+    repository::set_deleted_at(book_id, Some(Utc::now())).await?;
+
+    sync::service::log_change(
+        book_id,
+        ChangeLogEntityType::BookFile,
+        ChangeLogAction::Delete,
+        &book.owner_id,
+        session_id,
+    )
+    .await;
+
+    Ok(())
+}
+
+pub async fn get_trashed_book(book_id: &str) -> Result<BookEntity, ProsaError> {
+    let book = repository::get_trashed_book(book_id).await?;
+    Ok(book)
+}
+
+pub async fn restore_book(book_id: &str) -> Result<(), ProsaError> {
+    repository::set_deleted_at(book_id, None).await?;
+    Ok(())
+}
+
+/// Deletes a book along with its metadata, and its file and covers once no other book uses them.
+pub async fn remove_book(book_id: &str, session_id: &str) -> Result<(), ProsaError> {
+    let book = get_book(book_id).await?;
+    let owner_id = book.owner_id.clone();
+    erase_book(book_id, book).await?;
+
+    sync::service::log_change(
+        book_id,
+        ChangeLogEntityType::BookFile,
+        ChangeLogAction::Delete,
+        &owner_id,
+        session_id,
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Permanently deletes a book from the trash. Devices were already told about it when it was trashed.
+pub async fn purge_book(book_id: &str) -> Result<(), ProsaError> {
+    let book = get_trashed_book(book_id).await?;
+    erase_book(book_id, book).await
+}
+
+async fn erase_book(book_id: &str, book: BookEntity) -> Result<(), ProsaError> {
+    let candidate_covers = metadata::service::get_candidate_covers(book_id).await;
+    delete_book(book_id).await?;
+    epubs::service::delete_cached_epubs(book_id).await;
+
+    if let Some(metadata_id) = book.metadata_id {
+        metadata::service::delete_metadata(&metadata_id).await?;
+    }
+
+    if !epub_is_in_use(&book.epub_id).await {
+        epubs::service::delete_epub(&book.epub_id).await?;
+    }
+
+    let mut cover_ids: Vec<String> = book.cover_id.into_iter().chain(candidate_covers).collect();
+    cover_ids.sort();
+    cover_ids.dedup();
+
+    for cover_id in cover_ids {
+        if !cover_is_in_use(&cover_id).await {
+            covers::service::delete_cover(&cover_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Swaps the file of a book for a new one, keeping its state, annotations and shelves.
+/// Annotations and the reading location follow their text into the new file.
+pub async fn replace_book_file(
+    book_id: &str,
+    epub_data: &Vec<u8>,
+    session_id: &str,
+) -> Result<(), ProsaError> {
+    let mut book = get_book(book_id).await?;
+    let old_spans = epubs::service::read_spans(&book.epub_id).await?;
+    let epub_id = epubs::service::write_epub(epub_data).await?;
+
+    if epub_id == book.epub_id {
+        return Ok(());
+    }
+
+    check_epub_not_owned(&epub_id, &book.owner_id).await?;
+
+    let new_spans = epubs::service::read_spans(&epub_id).await?;
+    let old_epub_id = std::mem::replace(&mut book.epub_id, epub_id);
+
+    update_book(book_id, &book).await?;
+    epubs::service::delete_cached_epubs(book_id).await;
+
+    if !epub_is_in_use(&old_epub_id).await {
+        epubs::service::delete_epub(&old_epub_id).await?;
+    }
+
+    sync::service::log_change(
+        book_id,
+        ChangeLogEntityType::BookFile,
+        ChangeLogAction::Update,
+        &book.owner_id,
+        session_id,
+    )
+    .await;
+
+    if annotations::service::remap_annotations(book_id, &old_spans, &new_spans).await {
+        sync::service::log_change(
+            book_id,
+            ChangeLogEntityType::BookAnnotations,
+            ChangeLogAction::Update,
+            &book.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    if state::service::remap_location(&book.state_id, &old_spans, &new_spans).await {
+        sync::service::log_change(
+            book_id,
+            ChangeLogEntityType::BookState,
+            ChangeLogAction::Update,
+            &book.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
 pub async fn search_books(
     username: Option<String>,
     title: Option<String>,
@@ -50,7 +200,7 @@ pub async fn search_books(
 
 pub async fn cover_is_in_use(cover_id: &str) -> bool {
     let books = repository::get_books_by_cover(cover_id).await;
-    !books.is_empty()
+    !books.is_empty() || metadata::service::cover_is_in_use_by_candidates(cover_id).await
 }
 
 pub async fn epub_is_in_use(epub_id: &str) -> bool {
@@ -58,11 +208,21 @@ pub async fn epub_is_in_use(epub_id: &str) -> bool {
     !books.is_empty()
 }
 
-pub async fn epub_is_in_use_by_user(epub_id: &str, user_id: &str) -> bool {
-    repository::epub_belongs_to_user(epub_id, user_id).await
+/// Fails when the user already has a book with this epub, trashed books included.
+pub async fn check_epub_not_owned(epub_id: &str, user_id: &str) -> Result<(), BookError> {
+    match repository::get_user_epub_trashed(epub_id, user_id).await {
+        Some(true) => Err(BookError::BookInTrash),
+        Some(false) => Err(BookError::BookConflict),
+        None => Ok(()),
+    }
 }
 
 pub async fn book_exists(book_id: &str) -> bool {
     let book = repository::get_book(book_id).await;
     book.is_ok()
 }
+
+/// Unlike `book_exists`, books in the trash are also taken into account.
+pub async fn book_id_is_in_use(book_id: &str) -> bool {
+    repository::book_id_exists(book_id).await
+}
@@ -1,22 +1,116 @@
-use super::providers::{epub_extractor::EpubExtractor, goodreads::GoodreadsMetadataScraper};
-use crate::app::metadata::models::Metadata;
+use super::providers::{
+    epub_extractor::EpubExtractor, goodreads::GoodreadsMetadataScraper,
+    google_books::GoogleBooksMetadataProvider, open_library::OpenLibraryMetadataProvider,
+    plugin::PluginMetadataProvider,
+};
+use crate::{
+    app::metadata::models::{METADATA_FIELDS, Metadata},
+    config::{GoogleBooks, MetadataCooldown, MetadataJobs, MetadataPlugin, OpenLibrary},
+};
 use async_trait::async_trait;
-use merge::Merge;
-use std::collections::HashMap;
+use image::ImageReader;
+use log::warn;
+use std::{collections::HashMap, fmt, io::Cursor, sync::Arc, time::Duration};
+use tokio::{
+    runtime::Handle,
+    sync::{Mutex, mpsc, oneshot},
+    task::spawn_blocking,
+    time::{sleep, timeout},
+};
+
+const COVER_ASPECT_RATIO: f64 = 2.0 / 3.0;
+
+/// The longest a provider waits before retrying, however many times it already failed.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_mins(5);
+
+pub type ProviderOutput = (Option<Metadata>, Option<Vec<u8>>);
 
 #[async_trait]
 pub trait MetadataProvider: Send + Sync {
-    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> (Option<Metadata>, Option<Vec<u8>>);
+    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> Result<ProviderOutput, ProviderError>;
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+    InvalidEpub,
+    Unavailable(String),
+    InvalidResponse(String),
+    Timeout,
+    Panicked,
+}
+
+impl ProviderError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ProviderError::Unavailable(_) | ProviderError::Timeout)
+    }
+}
+
+impl From<serde_json::Error> for ProviderError {
+    fn from(error: serde_json::Error) -> Self {
+        ProviderError::InvalidResponse(error.to_string())
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::InvalidEpub => write!(f, "the book file could not be read"),
+            ProviderError::Unavailable(e) => write!(f, "the provider is unavailable ({e})"),
+            ProviderError::InvalidResponse(e) => write!(f, "the provider sent an invalid response ({e})"),
+            ProviderError::Timeout => write!(f, "the provider timed out"),
+            ProviderError::Panicked => write!(f, "the provider crashed"),
+        }
+    }
+}
+
+pub struct ProviderResult {
+    pub provider: String,
+    pub metadata: Option<Metadata>,
+    pub cover: Option<Vec<u8>>,
+}
+
+pub struct FetchOutcome {
+    pub results: Vec<ProviderResult>,
+    pub errors: Vec<(String, ProviderError)>,
+}
+
+pub struct FetchedMetadata {
+    pub metadata: Option<Metadata>,
+    pub cover: Option<Vec<u8>>,
+    pub sources: HashMap<&'static str, String>,
+}
+
+type SharedProvider = Arc<Mutex<Box<dyn MetadataProvider>>>;
+
+struct ProviderTask {
+    epub_data: Arc<[u8]>,
+    reply: oneshot::Sender<Result<ProviderOutput, ProviderError>>,
 }
 
 pub struct MetadataFetcher {
-    providers: HashMap<String, Box<dyn MetadataProvider>>,
+    workers: HashMap<String, mpsc::UnboundedSender<ProviderTask>>,
 }
 
 impl MetadataFetcher {
-    pub fn new(epub_extractor_cooldown: u64, goodreads_cooldown: u64) -> Self {
-        let epub_extractor = EpubExtractor::new(epub_extractor_cooldown);
-        let goodreads_scraper = GoodreadsMetadataScraper::new(goodreads_cooldown);
+    pub fn new(
+        jobs: &MetadataJobs,
+        cooldown: &MetadataCooldown,
+        open_library: &OpenLibrary,
+        google_books: &GoogleBooks,
+        plugins: &[MetadataPlugin],
+    ) -> Self {
+        let epub_extractor = EpubExtractor::new(cooldown.epub_extractor);
+        let goodreads_scraper = GoodreadsMetadataScraper::new(cooldown.goodreads);
+        let open_library_provider = OpenLibraryMetadataProvider::new(
+            cooldown.open_library,
+            &open_library.base_url,
+            &open_library.covers_url,
+        );
+        let google_books_provider = GoogleBooksMetadataProvider::new(
+            cooldown.google_books,
+            &google_books.base_url,
+            google_books.api_key.clone(),
+        );
         let mut providers: HashMap<String, Box<dyn MetadataProvider>> = HashMap::new();
 
         providers.insert(
@@ -29,37 +123,234 @@ impl MetadataFetcher {
             Box::new(epub_extractor) as Box<dyn MetadataProvider>,
         );
 
-        Self { providers }
+        providers.insert(
+            "open_library_metadata_provider".to_string(),
+            Box::new(open_library_provider) as Box<dyn MetadataProvider>,
+        );
+
+        providers.insert(
+            "google_books_metadata_provider".to_string(),
+            Box::new(google_books_provider) as Box<dyn MetadataProvider>,
+        );
+
+        for plugin in plugins {
+            providers.insert(
+                plugin.name.clone(),
+                Box::new(PluginMetadataProvider::new(plugin)) as Box<dyn MetadataProvider>,
+            );
+        }
+
+        // Every provider gets its own worker, so a slow or rate limited provider only delays itself
+        let workers = providers
+            .into_iter()
+            .map(|(name, provider)| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                let worker = ProviderWorker {
+                    name: name.clone(),
+                    provider: Arc::new(Mutex::new(provider)),
+                    timeout: Duration::from_millis(jobs.timeout),
+                    retries: jobs.retries,
+                    retry_backoff: Duration::from_millis(jobs.retry_backoff),
+                };
+                tokio::spawn(worker.run(receiver));
+
+                (name, sender)
+            })
+            .collect();
+
+        Self { workers }
     }
 
-    pub async fn fetch_metadata(
-        &mut self,
-        epub_data: Vec<u8>,
-        providers: Vec<String>,
-    ) -> (Option<Metadata>, Option<Vec<u8>>) {
-        let mut metadata = Metadata::default();
-        let mut image: Vec<u8> = Vec::new();
+    pub async fn fetch_metadata(&self, epub_data: Vec<u8>, providers: Vec<String>) -> FetchOutcome {
+        let epub_data: Arc<[u8]> = epub_data.into();
+        let mut outcome = FetchOutcome {
+            results: Vec::new(),
+            errors: Vec::new(),
+        };
 
-        for provider in providers {
-            let Some(provider) = self.providers.get_mut(&provider) else {
+        // Hand the book to every provider first so they all work on it at the same time
+        let mut pending = Vec::new();
+        for name in providers {
+            let Some(worker) = self.workers.get(&name) else {
                 continue;
             };
-            let (m, i) = provider.fetch_metadata(&epub_data).await;
 
-            if let Some(m) = m {
-                metadata.merge(m);
+            let (reply, receiver) = oneshot::channel();
+            let task = ProviderTask {
+                epub_data: epub_data.clone(),
+                reply,
+            };
+
+            if worker.send(task).is_ok() {
+                pending.push((name, receiver));
             }
+        }
 
-            if let Some(i) = i
-                && i.len() > image.len()
-            {
-                image = i;
+        // Replies are collected in the requested order, which keeps merging deterministic
+        for (name, receiver) in pending {
+            match receiver.await {
+                Ok(Ok((None, None))) => (),
+                Ok(Ok((metadata, cover))) => outcome.results.push(ProviderResult {
+                    provider: name,
+                    metadata,
+                    cover,
+                }),
+                Ok(Err(e)) => outcome.errors.push((name, e)),
+                Err(_) => outcome.errors.push((name, ProviderError::Panicked)),
             }
         }
 
-        let metadata = if metadata.is_empty() { None } else { Some(metadata) };
-        let image = if image.is_empty() { None } else { Some(image) };
+        outcome
+    }
+}
 
-        (metadata, image)
+struct ProviderWorker {
+    name: String,
+    provider: SharedProvider,
+    timeout: Duration,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl ProviderWorker {
+    async fn run(self, mut tasks: mpsc::UnboundedReceiver<ProviderTask>) {
+        while let Some(task) = tasks.recv().await {
+            let result = self.fetch_with_retries(&task.epub_data).await;
+
+            // The job may have been dropped in the meantime, in which case nobody is waiting
+            let _ = task.reply.send(result);
+        }
+    }
+
+    async fn fetch_with_retries(&self, epub_data: &Arc<[u8]>) -> Result<ProviderOutput, ProviderError> {
+        let mut attempt = 0;
+
+        loop {
+            let result = self.fetch_once(epub_data).await;
+
+            match result {
+                Err(e) if e.is_transient() && attempt < self.retries => {
+                    warn!("Metadata provider {} failed, retrying: {e}", self.name);
+                    let backoff = 2u32
+                        .checked_pow(attempt)
+                        .and_then(|factor| self.retry_backoff.checked_mul(factor))
+                        .map_or(MAX_RETRY_BACKOFF, |backoff| backoff.min(MAX_RETRY_BACKOFF));
+                    sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn fetch_once(&self, epub_data: &Arc<[u8]>) -> Result<ProviderOutput, ProviderError> {
+        let provider = self.provider.clone();
+        let epub_data = epub_data.clone();
+
+        // Providers make blocking requests, so they run on the blocking pool where they cannot hold up
+        // the runtime, and where a panic does not take down the worker. A fetch that times out cannot be
+        // interrupted though: it keeps the provider busy until its own requests time out.
+        let handle = Handle::current();
+        let task = spawn_blocking(move || {
+            handle.block_on(async move { provider.lock().await.fetch_metadata(&epub_data).await })
+        });
+
+        match timeout(self.timeout, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(ProviderError::Panicked),
+            Err(_) => Err(ProviderError::Timeout),
+        }
+    }
+}
+
+pub fn merge_results(
+    results: Vec<ProviderResult>,
+    merge_policy: &HashMap<String, String>,
+) -> FetchedMetadata {
+    let mut cover: Option<(Vec<u8>, f64)> = None;
+    let mut candidates: Vec<(String, Metadata)> = Vec::new();
+
+    for result in results {
+        if let Some(m) = result.metadata {
+            candidates.push((result.provider, m));
+        }
+
+        let Some(i) = result.cover else {
+            continue;
+        };
+
+        let score = score_cover(&i);
+        if score > cover.as_ref().map_or(0.0, |(_, s)| *s) {
+            cover = Some((i, score));
+        }
+    }
+
+    let (metadata, sources) = merge_metadata(candidates, merge_policy);
+    let metadata = if metadata.is_empty() { None } else { Some(metadata) };
+
+    FetchedMetadata {
+        metadata,
+        cover: cover.map(|(i, _)| i),
+        sources,
+    }
+}
+
+fn merge_metadata(
+    mut results: Vec<(String, Metadata)>,
+    merge_policy: &HashMap<String, String>,
+) -> (Metadata, HashMap<&'static str, String>) {
+    let completeness: Vec<usize> = results.iter().map(|(_, m)| m.fields().len()).collect();
+    let mut metadata = Metadata::default();
+    let mut sources = HashMap::new();
+
+    for field in METADATA_FIELDS {
+        let candidates: Vec<usize> = (0..results.len())
+            .filter(|&i| results[i].1.has_field(field))
+            .collect();
+
+        // Candidates are in priority order, so ties are won by the higher priority provider
+        let chosen = match merge_policy.get(field).map(String::as_str) {
+            None | Some("priority") => candidates.first().copied(),
+            Some("longest") => candidates
+                .iter()
+                .rev()
+                .max_by_key(|&&i| results[i].1.field_length(field))
+                .copied(),
+            Some("most_complete") => candidates.iter().rev().max_by_key(|&&i| completeness[i]).copied(),
+            Some(provider) => candidates
+                .iter()
+                .find(|&&i| results[i].0 == provider)
+                .or(candidates.first())
+                .copied(),
+        };
+
+        let Some(chosen) = chosen else {
+            continue;
+        };
+
+        let (name, m) = &mut results[chosen];
+        metadata.take_field(m, field);
+        sources.insert(field, name.clone());
+    }
+
+    (metadata, sources)
+}
+
+fn score_cover(cover: &[u8]) -> f64 {
+    let Ok(reader) = ImageReader::new(Cursor::new(cover)).with_guessed_format() else {
+        return 0.0;
+    };
+    let Ok((width, height)) = reader.into_dimensions() else {
+        return 0.0;
+    };
+
+    if width == 0 || height == 0 {
+        return 0.0;
     }
+
+    // Penalize covers that stray from the usual 2:3 book cover aspect ratio
+    let ratio = f64::from(width) / f64::from(height);
+    let ratio_score = ratio.min(COVER_ASPECT_RATIO) / ratio.max(COVER_ASPECT_RATIO);
+
+    f64::from(width) * f64::from(height) * ratio_score
 }
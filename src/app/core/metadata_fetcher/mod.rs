@@ -1,6 +1,8 @@
 mod fetcher;
 mod providers;
+mod scheduler;
 mod service;
 
-pub use service::MetadataFetcherRequest;
+pub use providers::plugin::verify_plugins;
+pub use scheduler::spawn_refresh_scheduler;
 pub use service::MetadataFetcherService;
@@ -1,6 +1,6 @@
 use super::rate_limiter::RateLimiter;
 use crate::app::{
-    core::metadata_fetcher::fetcher::MetadataProvider,
+    core::metadata_fetcher::fetcher::{MetadataProvider, ProviderError, ProviderOutput},
     metadata::models::{Contributor, Metadata, Series},
 };
 use async_trait::async_trait;
@@ -22,11 +22,11 @@ impl EpubExtractor {
 
 #[async_trait]
 impl MetadataProvider for EpubExtractor {
-    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> (Option<Metadata>, Option<Vec<u8>>) {
+    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> Result<ProviderOutput, ProviderError> {
         self.rate_limiter.cooldown().await;
 
         let epub = Cursor::new(epub_data);
-        let mut epub = EpubDoc::from_reader(epub).expect("Failed to open epub");
+        let mut epub = EpubDoc::from_reader(epub).map_err(|_| ProviderError::InvalidEpub)?;
 
         let title = epub.mdata("title").map(|m| m.value.clone());
         let subtitle = epub.mdata("subtitle").map(|m| m.value.clone());
@@ -39,7 +39,7 @@ impl MetadataProvider for EpubExtractor {
 
         let series_number = epub
             .mdata("calibre:series_index")
-            .map(|num| num.value.parse().expect("Failed to parse series number"));
+            .and_then(|num| num.value.trim().parse().ok());
 
         let series = match (series, series_number) {
             (Some(series), Some(number)) => Some(Series {
@@ -49,13 +49,12 @@ impl MetadataProvider for EpubExtractor {
             _ => None,
         };
 
-        let contributors = match epub.mdata("creator") {
-            Some(c) => Some(vec![Contributor {
+        let contributors = epub.mdata("creator").map(|c| {
+            vec![Contributor {
                 name: c.value.clone(),
                 role: "Author".to_string(),
-            }]),
-            None => None,
-        };
+            }]
+        });
 
         let genres: Vec<String> = epub
             .metadata
@@ -68,7 +67,7 @@ impl MetadataProvider for EpubExtractor {
 
         let publication_date = epub
             .mdata("date")
-            .map(|date| parse_date(&date.value).expect("Failed to parse date"));
+            .and_then(|date| parse_date(date.value.trim()).ok());
 
         let metadata = Metadata {
             title,
@@ -85,12 +84,12 @@ impl MetadataProvider for EpubExtractor {
         };
 
         if metadata.is_empty() {
-            return (None, None);
+            return Ok((None, None));
         }
 
         let cover_image = epub.get_cover().map(|c| c.0);
 
-        (Some(metadata), cover_image)
+        Ok((Some(metadata), cover_image))
     }
 }
 
@@ -1,12 +1,15 @@
 use super::rate_limiter::RateLimiter;
 use crate::app::{
-    core::metadata_fetcher::fetcher::MetadataProvider,
+    core::metadata_fetcher::fetcher::{MetadataProvider, ProviderError, ProviderOutput},
     metadata::models::{Contributor, Metadata, Series},
 };
 use async_trait::async_trait;
 use epub::doc::EpubDoc;
-use grscraper::MetadataRequestBuilder;
-use std::io::Cursor;
+use grscraper::{MetadataRequestBuilder, ScraperError};
+use std::{io::Cursor, time::Duration};
+use ureq::Agent;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 type GoodreadsMetadata = grscraper::BookMetadata;
 type GoodreadsSeries = grscraper::BookSeries;
@@ -14,23 +17,30 @@ type GoodreadsContributor = grscraper::BookContributor;
 
 pub struct GoodreadsMetadataScraper {
     rate_limiter: RateLimiter,
+    agent: Agent,
 }
 
 impl GoodreadsMetadataScraper {
     pub fn new(cooldown: u64) -> Self {
+        let agent = Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+
         Self {
             rate_limiter: RateLimiter::new(cooldown),
+            agent,
         }
     }
 }
 
 #[async_trait]
 impl MetadataProvider for GoodreadsMetadataScraper {
-    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> (Option<Metadata>, Option<Vec<u8>>) {
+    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> Result<ProviderOutput, ProviderError> {
         self.rate_limiter.cooldown().await;
 
         let epub = Cursor::new(epub_data);
-        let epub = EpubDoc::from_reader(epub).unwrap();
+        let epub = EpubDoc::from_reader(epub).map_err(|_| ProviderError::InvalidEpub)?;
 
         let title = epub.mdata("title").map(|v| &v.value);
         let author = epub.mdata("creator").map(|v| &v.value);
@@ -45,31 +55,29 @@ impl MetadataProvider for GoodreadsMetadataScraper {
                     .await
             }
             (Some(t), None) => MetadataRequestBuilder::default().with_title(t).execute().await,
-            _ => return (None, None),
+            _ => return Ok((None, None)),
         };
 
         let metadata = match (metadata, isbn) {
             (Ok(Some(m)), _) => Ok(Some(m)),
             (_, Some(isbn)) => MetadataRequestBuilder::default().with_isbn(isbn).execute().await,
-            _ => return (None, None),
-        };
-
-        let Ok(Some(metadata)) = metadata else {
-            return (None, None);
+            (result, None) => result,
         };
 
-        let Some(image_url) = &metadata.image_url else {
-            return (Some(metadata.into()), None);
+        let metadata = match metadata {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => return Ok((None, None)),
+            Err(ScraperError::FetchError(e)) => return Err(ProviderError::Unavailable(e.to_string())),
+            Err(e) => return Err(ProviderError::InvalidResponse(format!("{e:?}"))),
         };
 
-        let image = ureq::get(image_url)
-            .call()
-            .expect("Failed to get image data")
-            .into_body()
-            .read_to_vec()
-            .expect("Failed to get image bytes");
+        // The cover is optional, so failing to download it does not discard the metadata
+        let image = metadata
+            .image_url
+            .as_ref()
+            .and_then(|url| self.agent.get(url).call().ok()?.into_body().read_to_vec().ok());
 
-        (Some(metadata.into()), Some(image))
+        Ok((Some(metadata.into()), image))
     }
 }
 
@@ -0,0 +1,268 @@
+use super::{
+    rate_limiter::RateLimiter,
+    utils::{normalize_isbn, request_error},
+};
+use crate::app::{
+    core::metadata_fetcher::fetcher::{MetadataProvider, ProviderError, ProviderOutput},
+    metadata::models::{Contributor, Metadata},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use epub::doc::EpubDoc;
+use serde::Deserialize;
+use std::{io::Cursor, time::Duration};
+use ureq::Agent;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESULTS: &str = "10";
+
+pub struct GoogleBooksMetadataProvider {
+    rate_limiter: RateLimiter,
+    agent: Agent,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VolumesResponse {
+    #[serde(default)]
+    items: Vec<Volume>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Volume {
+    volume_info: VolumeInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VolumeInfo {
+    title: Option<String>,
+    subtitle: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    publisher: Option<String>,
+    published_date: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    industry_identifiers: Vec<IndustryIdentifier>,
+    page_count: Option<i64>,
+    #[serde(default)]
+    categories: Vec<String>,
+    language: Option<String>,
+    image_links: Option<ImageLinks>,
+}
+
+#[derive(Deserialize)]
+struct IndustryIdentifier {
+    #[serde(rename = "type")]
+    kind: String,
+    identifier: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageLinks {
+    extra_large: Option<String>,
+    large: Option<String>,
+    medium: Option<String>,
+    small: Option<String>,
+    thumbnail: Option<String>,
+    small_thumbnail: Option<String>,
+}
+
+impl GoogleBooksMetadataProvider {
+    pub fn new(cooldown: u64, base_url: &str, api_key: Option<String>) -> Self {
+        let agent = Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+
+        Self {
+            rate_limiter: RateLimiter::new(cooldown),
+            agent,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+        }
+    }
+
+    fn search(&self, query: &str, language: Option<&str>) -> Result<Vec<Volume>, ProviderError> {
+        let mut request = self
+            .agent
+            .get(format!("{}/books/v1/volumes", self.base_url))
+            .query("q", query)
+            .query("maxResults", MAX_RESULTS)
+            .query("printType", "books");
+
+        if let Some(language) = language {
+            request = request.query("langRestrict", language);
+        }
+        if let Some(api_key) = &self.api_key {
+            request = request.query("key", api_key);
+        }
+
+        let response = request.call().map_err(request_error)?;
+        let body = response.into_body().read_to_string().map_err(request_error)?;
+
+        serde_json::from_str::<VolumesResponse>(&body)
+            .map(|r| r.items)
+            .map_err(ProviderError::from)
+    }
+
+    fn find_volume(
+        &self,
+        isbn: Option<&str>,
+        title: Option<&str>,
+        author: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<Option<VolumeInfo>, ProviderError> {
+        if let Some(isbn) = isbn
+            && let Some(volume) = select_volume(self.search(&format!("isbn:{isbn}"), None)?, language)
+        {
+            return Ok(Some(volume));
+        }
+
+        let Some(title) = title else {
+            return Ok(None);
+        };
+
+        let query = match author {
+            Some(author) => format!("intitle:{title} inauthor:{author}"),
+            None => format!("intitle:{title}"),
+        };
+
+        let volumes = match self.search(&query, language)? {
+            volumes if volumes.is_empty() && language.is_some() => self.search(&query, None)?,
+            volumes => volumes,
+        };
+
+        Ok(select_volume(volumes, language))
+    }
+
+    fn get_cover(&self, image_links: &ImageLinks) -> Option<Vec<u8>> {
+        let url = [
+            &image_links.extra_large,
+            &image_links.large,
+            &image_links.medium,
+            &image_links.small,
+            &image_links.thumbnail,
+            &image_links.small_thumbnail,
+        ]
+        .into_iter()
+        .find_map(Option::as_ref)?;
+
+        self.agent
+            .get(url.replace("&edge=curl", ""))
+            .call()
+            .ok()?
+            .into_body()
+            .read_to_vec()
+            .ok()
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for GoogleBooksMetadataProvider {
+    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> Result<ProviderOutput, ProviderError> {
+        self.rate_limiter.cooldown().await;
+
+        let epub = Cursor::new(epub_data);
+        let epub = EpubDoc::from_reader(epub).map_err(|_| ProviderError::InvalidEpub)?;
+
+        let title = epub.mdata("title").map(|m| m.value.clone());
+        let author = epub.mdata("creator").map(|m| m.value.clone());
+        let language = epub.mdata("language").and_then(|m| primary_language(&m.value));
+        let isbn = epub
+            .metadata
+            .iter()
+            .filter(|m| m.property == "identifier")
+            .find_map(|m| normalize_isbn(&m.value));
+
+        let Some(mut volume) = self.find_volume(
+            isbn.as_deref(),
+            title.as_deref(),
+            author.as_deref(),
+            language.as_deref(),
+        )?
+        else {
+            return Ok((None, None));
+        };
+
+        let cover = volume.image_links.take().and_then(|links| self.get_cover(&links));
+        let metadata = Metadata::from(volume);
+
+        if metadata.is_empty() {
+            return Ok((None, cover));
+        }
+
+        Ok((Some(metadata), cover))
+    }
+}
+
+impl From<VolumeInfo> for Metadata {
+    fn from(volume: VolumeInfo) -> Self {
+        let isbn = ["ISBN_13", "ISBN_10"].into_iter().find_map(|kind| {
+            volume
+                .industry_identifiers
+                .iter()
+                .find(|i| i.kind == kind)
+                .map(|i| i.identifier.clone())
+        });
+
+        let contributors: Vec<Contributor> = volume
+            .authors
+            .into_iter()
+            .map(|name| Contributor {
+                name,
+                role: "Author".to_string(),
+            })
+            .collect();
+
+        Metadata {
+            title: volume.title,
+            subtitle: volume.subtitle,
+            description: volume.description,
+            publisher: volume.publisher,
+            publication_date: volume.published_date.as_deref().and_then(parse_date),
+            isbn,
+            contributors: (!contributors.is_empty()).then_some(contributors),
+            genres: (!volume.categories.is_empty()).then_some(volume.categories),
+            series: None,
+            page_count: volume.page_count.filter(|p| *p > 0),
+            language: volume.language,
+        }
+    }
+}
+
+fn select_volume(volumes: Vec<Volume>, language: Option<&str>) -> Option<VolumeInfo> {
+    let mut volumes: Vec<VolumeInfo> = volumes.into_iter().map(|v| v.volume_info).collect();
+
+    let position = language
+        .and_then(|language| {
+            volumes.iter().position(|v| {
+                v.language
+                    .as_deref()
+                    .and_then(primary_language)
+                    .is_some_and(|l| l == language)
+            })
+        })
+        .unwrap_or(0);
+
+    (position < volumes.len()).then(|| volumes.swap_remove(position))
+}
+
+fn primary_language(language: &str) -> Option<String> {
+    let primary = language.split(['-', '_']).next()?.trim().to_lowercase();
+    (!primary.is_empty()).then_some(primary)
+}
+
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    let parsed = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{date}-01"), "%Y-%m-%d"))
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{date}-01-01"), "%Y-%m-%d"))
+        .ok()?;
+
+    parsed.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc())
+}
@@ -1,3 +1,7 @@
 pub mod epub_extractor;
 pub mod goodreads;
+pub mod google_books;
+pub mod open_library;
+pub mod plugin;
 mod rate_limiter;
+mod utils;
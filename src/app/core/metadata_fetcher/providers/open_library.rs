@@ -0,0 +1,352 @@
+use super::{
+    rate_limiter::RateLimiter,
+    utils::{normalize_isbn, not_found_as_none, request_error},
+};
+use crate::app::{
+    core::metadata_fetcher::fetcher::{MetadataProvider, ProviderError, ProviderOutput},
+    metadata::models::{Contributor, Metadata, Series},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use epub::doc::EpubDoc;
+use regex::Regex;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::{io::Cursor, sync::LazyLock, time::Duration};
+use ureq::Agent;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_GENRES: usize = 10;
+
+static SERIES_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?P<title>.+?)[\s,;:(]*(?:#|no\.?|book|vol\.?|volume)?\s*(?P<number>\d+(?:\.\d+)?)\)?\s*$",
+    )
+    .expect("Failed to compile series regex")
+});
+static YEAR_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?P<year>\d{4})\b").expect("Failed to compile year regex"));
+
+pub struct OpenLibraryMetadataProvider {
+    rate_limiter: RateLimiter,
+    agent: Agent,
+    base_url: String,
+    covers_url: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    docs: Vec<SearchDocument>,
+}
+
+#[derive(Deserialize)]
+struct SearchDocument {
+    cover_edition_key: Option<String>,
+    #[serde(default)]
+    edition_key: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Edition {
+    title: Option<String>,
+    subtitle: Option<String>,
+    description: Option<Text>,
+    #[serde(default)]
+    publishers: Vec<String>,
+    publish_date: Option<String>,
+    #[serde(default)]
+    isbn_13: Vec<String>,
+    #[serde(default)]
+    isbn_10: Vec<String>,
+    #[serde(default)]
+    authors: Vec<Reference>,
+    #[serde(default)]
+    works: Vec<Reference>,
+    #[serde(default)]
+    series: Vec<String>,
+    #[serde(default)]
+    subjects: Vec<String>,
+    #[serde(default)]
+    languages: Vec<Reference>,
+    number_of_pages: Option<i64>,
+    #[serde(default)]
+    covers: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct Work {
+    description: Option<Text>,
+    #[serde(default)]
+    subjects: Vec<String>,
+    #[serde(default)]
+    authors: Vec<WorkAuthor>,
+    #[serde(default)]
+    covers: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct WorkAuthor {
+    author: Reference,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Reference {
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Plain(String),
+    Typed { value: String },
+}
+
+impl OpenLibraryMetadataProvider {
+    pub fn new(cooldown: u64, base_url: &str, covers_url: &str) -> Self {
+        let agent = Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+
+        Self {
+            rate_limiter: RateLimiter::new(cooldown),
+            agent,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            covers_url: covers_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Option<T>, ProviderError> {
+        let mut request = self.agent.get(format!("{}{path}", self.base_url));
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+
+        let Some(response) = not_found_as_none(request.call())? else {
+            return Ok(None);
+        };
+        let body = response.into_body().read_to_string().map_err(request_error)?;
+
+        serde_json::from_str(&body).map(Some).map_err(ProviderError::from)
+    }
+
+    fn find_edition(
+        &self,
+        isbn: Option<&str>,
+        title: Option<&str>,
+        author: Option<&str>,
+    ) -> Result<Option<Edition>, ProviderError> {
+        if let Some(isbn) = isbn
+            && let Some(edition) = self.get_json(&format!("/isbn/{isbn}.json"), &[])?
+        {
+            return Ok(Some(edition));
+        }
+
+        let Some(title) = title else {
+            return Ok(None);
+        };
+
+        let mut query = vec![("title", title), ("limit", "1")];
+        if let Some(author) = author {
+            query.push(("author", author));
+        }
+
+        let Some(search) = self.get_json::<SearchResponse>("/search.json", &query)? else {
+            return Ok(None);
+        };
+        let Some(document) = search.docs.into_iter().next() else {
+            return Ok(None);
+        };
+        let Some(edition_key) = document
+            .cover_edition_key
+            .or_else(|| document.edition_key.into_iter().next())
+        else {
+            return Ok(None);
+        };
+
+        self.get_json(&format!("/books/{edition_key}.json"), &[])
+    }
+
+    fn get_author_name(&self, key: &str) -> Result<Option<String>, ProviderError> {
+        let author = self.get_json::<Author>(&format!("{key}.json"), &[])?;
+        Ok(author.map(|author| author.name))
+    }
+
+    fn get_cover(&self, cover_id: i64) -> Option<Vec<u8>> {
+        self.agent
+            .get(format!("{}/b/id/{cover_id}-L.jpg", self.covers_url))
+            .call()
+            .ok()?
+            .into_body()
+            .read_to_vec()
+            .ok()
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for OpenLibraryMetadataProvider {
+    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> Result<ProviderOutput, ProviderError> {
+        self.rate_limiter.cooldown().await;
+
+        let epub = Cursor::new(epub_data);
+        let epub = EpubDoc::from_reader(epub).map_err(|_| ProviderError::InvalidEpub)?;
+
+        let title = epub.mdata("title").map(|m| m.value.clone());
+        let author = epub.mdata("creator").map(|m| m.value.clone());
+        let isbn = epub
+            .metadata
+            .iter()
+            .filter(|m| m.property == "identifier")
+            .find_map(|m| normalize_isbn(&m.value));
+
+        let Some(edition) = self.find_edition(isbn.as_deref(), title.as_deref(), author.as_deref())? else {
+            return Ok((None, None));
+        };
+
+        let work = match edition.works.first() {
+            Some(work) => self.get_json::<Work>(&format!("{}.json", work.key), &[])?,
+            None => None,
+        };
+
+        let author_keys: Vec<&str> = match &work {
+            Some(work) if edition.authors.is_empty() => {
+                work.authors.iter().map(|a| a.author.key.as_str()).collect()
+            }
+            _ => edition.authors.iter().map(|a| a.key.as_str()).collect(),
+        };
+
+        let mut contributors = Vec::new();
+        for key in author_keys {
+            if let Some(name) = self.get_author_name(key)? {
+                contributors.push(Contributor {
+                    name,
+                    role: "Author".to_string(),
+                });
+            }
+        }
+
+        let cover_id = edition
+            .covers
+            .iter()
+            .chain(work.iter().flat_map(|w| &w.covers))
+            .copied()
+            .find(|id| *id > 0);
+
+        let metadata = build_metadata(edition, work, contributors);
+        let cover = cover_id.and_then(|id| self.get_cover(id));
+
+        if metadata.is_empty() {
+            return Ok((None, cover));
+        }
+
+        Ok((Some(metadata), cover))
+    }
+}
+
+fn build_metadata(edition: Edition, work: Option<Work>, contributors: Vec<Contributor>) -> Metadata {
+    let (work_description, work_subjects) = match work {
+        Some(work) => (work.description, work.subjects),
+        None => (None, Vec::new()),
+    };
+
+    let description = edition.description.or(work_description).map(Text::into_string);
+    let subjects = if work_subjects.is_empty() {
+        edition.subjects
+    } else {
+        work_subjects
+    };
+    let genres: Vec<String> = subjects.into_iter().take(MAX_GENRES).collect();
+
+    let isbn = edition.isbn_13.into_iter().chain(edition.isbn_10).next();
+    let series = edition.series.iter().find_map(|s| parse_series(s));
+    let language = edition
+        .languages
+        .first()
+        .and_then(|l| l.key.rsplit('/').next())
+        .map(language_code);
+
+    Metadata {
+        title: edition.title,
+        subtitle: edition.subtitle,
+        description,
+        publisher: edition.publishers.into_iter().next(),
+        publication_date: edition.publish_date.as_deref().and_then(parse_date),
+        isbn,
+        contributors: (!contributors.is_empty()).then_some(contributors),
+        genres: (!genres.is_empty()).then_some(genres),
+        series,
+        page_count: edition.number_of_pages,
+        language,
+    }
+}
+
+impl Text {
+    fn into_string(self) -> String {
+        match self {
+            Text::Plain(value) | Text::Typed { value } => value,
+        }
+    }
+}
+
+fn parse_series(series: &str) -> Option<Series> {
+    let captures = SERIES_REGEX.captures(series.trim())?;
+    let title = captures["title"].trim().to_string();
+    let number = captures["number"].parse().ok()?;
+
+    Some(Series { title, number })
+}
+
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date_formats = ["%B %d, %Y", "%b %d, %Y", "%Y-%m-%d", "%d %B %Y", "%d %b %Y"];
+
+    let date = date.trim();
+    let parsed = date_formats
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+        .or_else(|| {
+            let year = YEAR_REGEX.captures(date)?["year"].parse().ok()?;
+            NaiveDate::from_ymd_opt(year, 1, 1)
+        })?;
+
+    parsed.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc())
+}
+
+fn language_code(code: &str) -> String {
+    let language = match code {
+        "eng" => "en",
+        "fre" | "fra" => "fr",
+        "ger" | "deu" => "de",
+        "spa" => "es",
+        "ita" => "it",
+        "por" => "pt",
+        "dut" | "nld" => "nl",
+        "rus" => "ru",
+        "jpn" => "ja",
+        "chi" | "zho" => "zh",
+        "kor" => "ko",
+        "ara" => "ar",
+        "pol" => "pl",
+        "swe" => "sv",
+        "dan" => "da",
+        "nor" => "no",
+        "fin" => "fi",
+        "gre" | "ell" => "el",
+        "tur" => "tr",
+        "heb" => "he",
+        "cze" | "ces" => "cs",
+        "hun" => "hu",
+        "lat" => "la",
+        other => other,
+    };
+
+    language.to_string()
+}
@@ -0,0 +1,206 @@
+use super::{
+    rate_limiter::RateLimiter,
+    utils::{normalize_isbn, not_found_as_none, request_error},
+};
+use crate::{
+    app::{
+        core::metadata_fetcher::fetcher::{MetadataProvider, ProviderError, ProviderOutput},
+        metadata::models::{Metadata, USER_SOURCE},
+        users::models::{MERGE_POLICIES, VALID_PROVIDERS},
+    },
+    config::MetadataPlugin,
+};
+use async_trait::async_trait;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use epub::doc::EpubDoc;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io::Cursor, process::Stdio, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command, time::timeout};
+use ureq::Agent;
+
+pub struct PluginMetadataProvider {
+    rate_limiter: RateLimiter,
+    agent: Agent,
+    timeout: Duration,
+    target: PluginTarget,
+}
+
+enum PluginTarget {
+    Http {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+    Command {
+        command: String,
+        args: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct PluginRequest {
+    title: Option<String>,
+    author: Option<String>,
+    isbn: Option<String>,
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PluginResponse {
+    metadata: Option<Metadata>,
+    cover: Option<String>,
+    cover_url: Option<String>,
+}
+
+impl PluginMetadataProvider {
+    pub fn new(plugin: &MetadataPlugin) -> Self {
+        let timeout = Duration::from_millis(plugin.timeout);
+        let agent = Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .build()
+            .into();
+
+        let target = match (&plugin.url, &plugin.command) {
+            (Some(url), _) => PluginTarget::Http {
+                url: url.clone(),
+                headers: plugin.headers.clone(),
+            },
+            (None, Some(command)) => PluginTarget::Command {
+                command: command.clone(),
+                args: plugin.args.clone(),
+            },
+            (None, None) => unreachable!("Metadata plugins are verified on startup"),
+        };
+
+        Self {
+            rate_limiter: RateLimiter::new(plugin.cooldown),
+            agent,
+            timeout,
+            target,
+        }
+    }
+
+    async fn call(&self, request: &PluginRequest) -> Result<Option<Vec<u8>>, ProviderError> {
+        let body = serde_json::to_vec(request).expect("Failed to serialize plugin request");
+
+        match &self.target {
+            PluginTarget::Http { url, headers } => {
+                let mut http_request = self.agent.post(url).header("Content-Type", "application/json");
+                for (name, value) in headers {
+                    http_request = http_request.header(name, value);
+                }
+
+                let Some(response) = not_found_as_none(http_request.send(&body))? else {
+                    return Ok(None);
+                };
+
+                response
+                    .into_body()
+                    .read_to_vec()
+                    .map(Some)
+                    .map_err(request_error)
+            }
+            PluginTarget::Command { command, args } => {
+                let mut child = Command::new(command)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| ProviderError::Unavailable(e.to_string()))?;
+
+                // A plugin may exit without reading its input, which is not an error on its own
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(&body).await;
+                }
+
+                let output = timeout(self.timeout, child.wait_with_output())
+                    .await
+                    .map_err(|_| ProviderError::Timeout)?
+                    .map_err(|e| ProviderError::Unavailable(e.to_string()))?;
+
+                Ok(output.status.success().then_some(output.stdout))
+            }
+        }
+    }
+
+    fn get_cover(&self, response: &PluginResponse) -> Option<Vec<u8>> {
+        if let Some(cover) = &response.cover {
+            return BASE64_STANDARD.decode(cover).ok();
+        }
+
+        self.agent
+            .get(response.cover_url.as_ref()?)
+            .call()
+            .ok()?
+            .into_body()
+            .read_to_vec()
+            .ok()
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for PluginMetadataProvider {
+    async fn fetch_metadata(&mut self, epub_data: &[u8]) -> Result<ProviderOutput, ProviderError> {
+        self.rate_limiter.cooldown().await;
+
+        let request = {
+            let epub = Cursor::new(epub_data);
+            let epub = EpubDoc::from_reader(epub).map_err(|_| ProviderError::InvalidEpub)?;
+
+            PluginRequest {
+                title: epub.mdata("title").map(|m| m.value.clone()),
+                author: epub.mdata("creator").map(|m| m.value.clone()),
+                isbn: epub
+                    .metadata
+                    .iter()
+                    .filter(|m| m.property == "identifier")
+                    .find_map(|m| normalize_isbn(&m.value)),
+                language: epub.mdata("language").map(|m| m.value.clone()),
+            }
+        };
+
+        if request.title.is_none() && request.isbn.is_none() {
+            return Ok((None, None));
+        }
+
+        let Some(output) = self.call(&request).await? else {
+            return Ok((None, None));
+        };
+        let response: PluginResponse = serde_json::from_slice(&output).map_err(ProviderError::from)?;
+
+        let cover = self.get_cover(&response);
+        let metadata = response.metadata.filter(|m| !m.is_empty());
+
+        Ok((metadata, cover))
+    }
+}
+
+pub fn verify_plugins(plugins: &[MetadataPlugin]) -> Result<(), String> {
+    let reserved = VALID_PROVIDERS
+        .iter()
+        .chain(&MERGE_POLICIES)
+        .chain(&[USER_SOURCE]);
+
+    for (index, plugin) in plugins.iter().enumerate() {
+        if plugin.name.is_empty() || reserved.clone().any(|r| *r == plugin.name) {
+            return Err(format!(
+                "Metadata plugin name '{}' is invalid or reserved",
+                plugin.name
+            ));
+        }
+
+        if plugins[..index].iter().any(|p| p.name == plugin.name) {
+            return Err(format!("Metadata plugin name '{}' is duplicated", plugin.name));
+        }
+
+        if plugin.url.is_some() == plugin.command.is_some() {
+            return Err(format!(
+                "Metadata plugin '{}' must define exactly one of url or command",
+                plugin.name
+            ));
+        }
+    }
+
+    Ok(())
+}
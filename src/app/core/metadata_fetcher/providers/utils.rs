@@ -0,0 +1,45 @@
+use crate::app::core::metadata_fetcher::fetcher::ProviderError;
+
+pub fn normalize_isbn(identifier: &str) -> Option<String> {
+    let identifier = identifier.trim();
+    let identifier = identifier
+        .strip_prefix("urn:isbn:")
+        .or_else(|| identifier.strip_prefix("isbn:"))
+        .unwrap_or(identifier);
+
+    let isbn: String = identifier.chars().filter(|c| *c != '-' && *c != ' ').collect();
+    if !isbn.is_ascii() {
+        return None;
+    }
+
+    let (body, check) = isbn.split_at(isbn.len().saturating_sub(1));
+    let valid = match isbn.len() {
+        10 => {
+            body.chars().all(|c| c.is_ascii_digit())
+                && (check.eq_ignore_ascii_case("x") || check.chars().all(|c| c.is_ascii_digit()))
+        }
+        13 => isbn.chars().all(|c| c.is_ascii_digit()),
+        _ => false,
+    };
+
+    valid.then(|| isbn.to_uppercase())
+}
+
+pub fn request_error(error: ureq::Error) -> ProviderError {
+    match error {
+        ureq::Error::StatusCode(code) if code == 429 || code >= 500 => {
+            ProviderError::Unavailable(format!("HTTP status {code}"))
+        }
+        ureq::Error::StatusCode(code) => ProviderError::InvalidResponse(format!("HTTP status {code}")),
+        ureq::Error::Timeout(_) => ProviderError::Timeout,
+        error => ProviderError::Unavailable(error.to_string()),
+    }
+}
+
+pub fn not_found_as_none<T>(result: Result<T, ureq::Error>) -> Result<Option<T>, ProviderError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ureq::Error::StatusCode(404)) => Ok(None),
+        Err(error) => Err(request_error(error)),
+    }
+}
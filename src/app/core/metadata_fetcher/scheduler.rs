@@ -0,0 +1,71 @@
+use super::MetadataFetcherService;
+use crate::{
+    app::{metadata, users},
+    config::MetadataRefresh,
+};
+use chrono::TimeDelta;
+use log::info;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::time::{MissedTickBehavior, interval};
+
+// Scheduled refreshes yield to the requests made by users
+const REFRESH_PRIORITY: i64 = -1;
+
+pub fn spawn_refresh_scheduler(fetcher: Arc<MetadataFetcherService>, settings: &MetadataRefresh) {
+    if settings.interval == 0 {
+        return;
+    }
+
+    let settings = settings.clone();
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(settings.interval));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            refresh_books(&fetcher, &settings).await;
+        }
+    });
+}
+
+async fn refresh_books(fetcher: &MetadataFetcherService, settings: &MetadataRefresh) {
+    let max_age = TimeDelta::from_std(Duration::from_secs(settings.max_age)).unwrap_or(TimeDelta::MAX);
+    let retry_after =
+        TimeDelta::from_std(Duration::from_secs(settings.retry_after)).unwrap_or(TimeDelta::MAX);
+
+    let due = metadata::service::get_due_refreshes(max_age, retry_after, settings.batch_size).await;
+    let mut providers: HashMap<String, Vec<String>> = HashMap::new();
+    let mut queued = 0;
+
+    for book in due {
+        if !providers.contains_key(&book.owner_id) {
+            let Ok(preferences) = users::service::get_preferences(&book.owner_id).await else {
+                continue;
+            };
+            let owner_providers = preferences.metadata_providers.unwrap_or_default();
+            providers.insert(book.owner_id.clone(), owner_providers);
+        }
+
+        let owner_providers = &providers[&book.owner_id];
+        if owner_providers.is_empty() {
+            continue;
+        }
+
+        let result = fetcher
+            .try_enqueue_request(
+                &book.owner_id,
+                &book.book_id,
+                owner_providers.clone(),
+                REFRESH_PRIORITY,
+            )
+            .await;
+
+        if let Ok(true) = result {
+            queued += 1;
+        }
+    }
+
+    if queued > 0 {
+        info!("Queued {queued} books for a scheduled metadata refresh");
+    }
+}
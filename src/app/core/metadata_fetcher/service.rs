@@ -1,41 +1,45 @@
-use super::fetcher::MetadataFetcher;
-use crate::app::{
-    books, covers, epubs,
-    error::ProsaError,
-    metadata::{
-        self,
-        models::{Metadata, MetadataError},
-    },
-    server::LOCKS,
-    sync::{
-        self,
-        models::{ChangeLogAction, ChangeLogEntityType},
+use super::fetcher::{FetchOutcome, FetchedMetadata, MetadataFetcher, ProviderResult, merge_results};
+use crate::{
+    app::{
+        books, covers, epubs,
+        error::ProsaError,
+        metadata::{
+            self,
+            models::{JobStatus, Metadata, MetadataError, MetadataJob},
+        },
+        server::LOCKS,
+        sync::{
+            self,
+            models::{ChangeLogAction, ChangeLogEntityType},
+        },
+        users,
     },
+    config::{GoogleBooks, MetadataCooldown, MetadataJobs, MetadataPlugin, OpenLibrary},
 };
 use log::warn;
-use serde::Serialize;
-use std::{collections::VecDeque, sync::Arc};
-use tokio::sync::{Mutex, Notify, RwLock};
-
-#[derive(Clone, Serialize, PartialEq)]
-pub struct MetadataFetcherRequest {
-    user_id: String,
-    book_id: String,
-    providers: Vec<String>,
-}
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{Mutex, Notify, OwnedSemaphorePermit, Semaphore};
 
 pub struct MetadataFetcherService {
-    queue: RwLock<VecDeque<MetadataFetcherRequest>>,
+    enqueue_lock: Mutex<()>,
     notify: Notify,
-    fetcher: Mutex<MetadataFetcher>,
+    job_slots: Arc<Semaphore>,
+    fetcher: MetadataFetcher,
 }
 
 impl MetadataFetcherService {
-    pub fn new(epub_extractor_cooldown: u64, goodreads_cooldown: u64) -> Arc<Self> {
+    pub fn new(
+        jobs: &MetadataJobs,
+        cooldown: &MetadataCooldown,
+        open_library: &OpenLibrary,
+        google_books: &GoogleBooks,
+        plugins: &[MetadataPlugin],
+    ) -> Arc<Self> {
         let manager = Self {
-            queue: RwLock::new(VecDeque::new()),
+            enqueue_lock: Mutex::new(()),
             notify: Notify::new(),
-            fetcher: Mutex::new(MetadataFetcher::new(epub_extractor_cooldown, goodreads_cooldown)),
+            job_slots: Arc::new(Semaphore::new(jobs.concurrency.max(1))),
+            fetcher: MetadataFetcher::new(jobs, cooldown, open_library, google_books, plugins),
         };
 
         let manager = Arc::new(manager);
@@ -50,107 +54,246 @@ impl MetadataFetcherService {
         user_id: &str,
         book_id: &str,
         providers: Vec<String>,
-    ) -> Result<(), MetadataError> {
-        let req = MetadataFetcherRequest {
-            user_id: user_id.to_string(),
-            book_id: book_id.to_string(),
-            providers,
-        };
-
-        let q = self.queue.read().await;
-        if q.contains(&req) {
-            return Err(MetadataError::MetadataRequestConflict);
+        priority: i64,
+    ) -> Result<(), ProsaError> {
+        if !self
+            .try_enqueue_request(user_id, book_id, providers, priority)
+            .await?
+        {
+            return Err(MetadataError::MetadataRequestConflict.into());
         }
-        drop(q);
-
-        let mut q = self.queue.write().await;
-        q.push_back(req);
-        self.notify.notify_one();
 
         Ok(())
     }
 
-    pub async fn get_enqueued(&self, user_id: Option<String>) -> Vec<MetadataFetcherRequest> {
-        let q = self.queue.read().await;
-        q.iter()
-            .filter(|req| user_id.as_ref().is_none_or(|uid| &req.user_id == uid))
-            .cloned()
-            .collect()
+    /// Returns false instead of failing when an identical request is already queued.
+    pub async fn try_enqueue_request(
+        &self,
+        user_id: &str,
+        book_id: &str,
+        providers: Vec<String>,
+        priority: i64,
+    ) -> Result<bool, ProsaError> {
+        let _guard = self.enqueue_lock.lock().await;
+
+        if metadata::service::job_is_queued(user_id, book_id, &providers).await {
+            return Ok(false);
+        }
+
+        metadata::service::add_job(user_id, book_id, &providers, priority).await?;
+        self.notify.notify_one();
+
+        Ok(true)
     }
 
     async fn worker_loop(self: Arc<Self>) {
         loop {
-            let mut q = self.queue.write().await;
-            let req = q.pop_front();
-            drop(q);
-
-            let Some(req) = req else {
-                self.notify.notified().await;
-                continue;
+            let permit = self
+                .job_slots
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Failed to acquire metadata job slot");
+
+            let job = match metadata::service::claim_next_job().await {
+                Ok(Some(job)) => job,
+                Ok(None) => {
+                    drop(permit);
+                    self.notify.notified().await;
+                    continue;
+                }
+                Err(_) => {
+                    warn!("Failed to retrieve the next metadata job");
+                    drop(permit);
+                    self.notify.notified().await;
+                    continue;
+                }
             };
 
-            let (metadata, image) = self.fetch_metadata(&req.book_id, req.providers).await;
-            if metadata.is_none() && image.is_none() {
-                continue;
-            }
-            self.store_metadata(&req.book_id, metadata, image).await;
+            tokio::spawn(self.clone().run_job(job, permit));
         }
     }
 
-    async fn fetch_metadata(
-        &self,
-        book_id: &str,
-        providers: Vec<String>,
-    ) -> (Option<Metadata>, Option<Vec<u8>>) {
-        let lock = LOCKS.get_book_lock(book_id).await;
-        let _guard = lock.read().await;
-
-        let Ok(book) = books::service::get_book(book_id).await else {
-            warn!("Background metadata fetching failed for book {book_id}");
-            return (None, None);
-        };
-        let Ok(epub_data) = epubs::service::read_epub(&book.epub_id).await else {
-            warn!("Background metadata fetching failed for book {book_id}");
-            return (None, None);
+    async fn run_job(self: Arc<Self>, job: MetadataJob, permit: OwnedSemaphorePermit) {
+        // Each job runs in its own task, so a panic only fails the job that caused it
+        let task = tokio::spawn(self.clone().process_request(
+            job.user_id.clone(),
+            job.book_id.clone(),
+            job.providers,
+        ));
+        let (status, reason) = match task.await {
+            Ok(Ok(())) => (JobStatus::Succeeded, None),
+            Ok(Err(reason)) => (JobStatus::Failed, Some(reason)),
+            Err(_) => (JobStatus::Failed, Some("Metadata fetching crashed".to_string())),
         };
 
-        self.fetcher
-            .lock()
+        if let Some(reason) = &reason {
+            warn!(
+                "Background metadata fetching failed for book {}: {reason}",
+                job.book_id
+            );
+        }
+
+        if metadata::service::finish_job(&job.job_id, status, reason.as_deref())
             .await
-            .fetch_metadata(epub_data, providers)
+            .is_err()
+        {
+            warn!("Failed to update metadata job {}", job.job_id);
+        }
+
+        // Jobs for this book were held back while it was being processed, so look at the queue again
+        drop(permit);
+        self.notify.notify_one();
+    }
+
+    async fn process_request(
+        self: Arc<Self>,
+        user_id: String,
+        book_id: String,
+        providers: Vec<String>,
+    ) -> Result<(), String> {
+        let outcome = self.fetch_metadata(&book_id, providers).await?;
+
+        for (provider, error) in &outcome.errors {
+            warn!("Metadata provider {provider} failed for book {book_id}: {error}");
+        }
+
+        // Without any result, the failures may be what hid the book's metadata
+        if outcome.results.is_empty() {
+            if outcome.errors.is_empty() {
+                return Ok(());
+            }
+
+            let reasons: Vec<String> = outcome
+                .errors
+                .iter()
+                .map(|(provider, error)| format!("{provider}: {error}"))
+                .collect();
+            return Err(reasons.join("; "));
+        }
+
+        let preferences = users::service::get_preferences(&user_id)
             .await
+            .map_err(|_| "Failed to retrieve user preferences".to_string())?;
+
+        if preferences.metadata_review == Some(true) {
+            return self.store_candidates(&book_id, outcome.results).await;
+        }
+
+        let merge_policy = preferences.metadata_merge_policy.unwrap_or_default();
+        let fetched = merge_results(outcome.results, &merge_policy);
+        if fetched.metadata.is_none() && fetched.cover.is_none() {
+            return Ok(());
+        }
+
+        self.store_metadata(&book_id, fetched).await
+    }
+
+    async fn fetch_metadata(&self, book_id: &str, providers: Vec<String>) -> Result<FetchOutcome, String> {
+        let epub_data = {
+            let lock = LOCKS.get_book_lock(book_id).await;
+            let _guard = lock.read().await;
+
+            let book = books::service::get_book(book_id)
+                .await
+                .map_err(|_| "The book no longer exists".to_string())?;
+            epubs::service::read_epub(&book.epub_id)
+                .await
+                .map_err(|_| "Failed to read the book file".to_string())?
+        };
+
+        // Providers can take minutes with retries, so the book is not locked meanwhile. Storing the
+        // results locks it again and reads it anew.
+        Ok(self.fetcher.fetch_metadata(epub_data, providers).await)
     }
 
-    async fn store_metadata(&self, book_id: &str, metadata: Option<Metadata>, image: Option<Vec<u8>>) -> () {
+    async fn store_metadata(&self, book_id: &str, fetched: FetchedMetadata) -> Result<(), String> {
         let lock = LOCKS.get_book_lock(book_id).await;
         let _guard = lock.write().await;
 
-        let Ok(book) = books::service::get_book(book_id).await else {
-            warn!("Background metadata fetching failed for book {book_id}");
-            return;
-        };
+        let book = books::service::get_book(book_id)
+            .await
+            .map_err(|_| "The book no longer exists".to_string())?;
 
-        let metadata_result = match (book.metadata_id, metadata) {
+        let metadata_result = match (book.metadata_id, fetched.metadata) {
             (_, None) => Ok(()),
-            (Some(_), Some(metadata)) => self.handle_metadata_update(book_id, metadata).await,
-            (None, Some(metadata)) => self.handle_metadata_create(book_id, metadata).await,
+            (Some(_), Some(metadata)) => {
+                self.handle_metadata_update(book_id, metadata, fetched.sources)
+                    .await
+            }
+            (None, Some(metadata)) => {
+                self.handle_metadata_create(book_id, metadata, fetched.sources)
+                    .await
+            }
         };
 
-        let cover_result = match (book.cover_id, image) {
+        // A generated cover is replaced as if the book had none
+        let cover_result = match (
+            covers::service::without_placeholder(book.cover_id).await,
+            fetched.cover,
+        ) {
             (_, None) => Ok(()),
             (Some(_), Some(image)) => self.handle_cover_update(book_id, image).await,
             (None, Some(image)) => self.handle_cover_create(book_id, image).await,
         };
 
-        if cover_result.is_err() || metadata_result.is_err() {
-            warn!("Background metadata fetching failed for book {book_id}");
+        let placeholder_result = covers::service::refresh_placeholder(book_id, "prosa").await;
+
+        if cover_result.is_err() || metadata_result.is_err() || placeholder_result.is_err() {
+            return Err("Failed to store the fetched metadata".to_string());
         }
+
+        Ok(())
     }
 
-    async fn handle_metadata_update(&self, book_id: &str, metadata: Metadata) -> Result<(), ProsaError> {
+    async fn store_candidates(&self, book_id: &str, results: Vec<ProviderResult>) -> Result<(), String> {
+        let lock = LOCKS.get_book_lock(book_id).await;
+        let _guard = lock.write().await;
+
+        if !books::service::book_exists(book_id).await {
+            return Err("The book no longer exists".to_string());
+        }
+
+        let mut failed = false;
+        for result in results {
+            let candidate_result =
+                metadata::service::add_candidate(book_id, &result.provider, result.metadata, result.cover)
+                    .await;
+
+            failed |= candidate_result.is_err();
+        }
+
+        if failed {
+            return Err("Failed to store the metadata candidates".to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn handle_metadata_update(
+        &self,
+        book_id: &str,
+        mut metadata: Metadata,
+        mut sources: HashMap<&'static str, String>,
+    ) -> Result<(), ProsaError> {
         let book = books::service::get_book(book_id).await?;
         let metadata_id = book.metadata_id.as_ref().expect("Failed to retrieve metadata id");
+
+        let locked_fields = metadata::service::get_locked_fields(book_id).await?;
+        let mut current = metadata::service::get_metadata(metadata_id).await?;
+        for field in &locked_fields {
+            metadata.take_field(&mut current, field);
+            sources.remove(field.as_str());
+        }
+
+        if metadata.is_empty() {
+            return Ok(());
+        }
+
+        let missing_fields = metadata.missing_fields();
         metadata::service::update_metadata(metadata_id, metadata).await?;
+        self.record_sources(book_id, sources).await?;
+        metadata::service::clear_provenance(book_id, &missing_fields).await?;
 
         sync::service::log_change(
             book_id,
@@ -164,11 +307,28 @@ impl MetadataFetcherService {
         Ok(())
     }
 
-    async fn handle_metadata_create(&self, book_id: &str, metadata: Metadata) -> Result<(), ProsaError> {
+    async fn handle_metadata_create(
+        &self,
+        book_id: &str,
+        mut metadata: Metadata,
+        mut sources: HashMap<&'static str, String>,
+    ) -> Result<(), ProsaError> {
         let mut book = books::service::get_book(book_id).await?;
+
+        let locked_fields = metadata::service::get_locked_fields(book_id).await?;
+        for field in &locked_fields {
+            metadata.clear_field(field);
+            sources.remove(field.as_str());
+        }
+
+        if metadata.is_empty() {
+            return Ok(());
+        }
+
         let metadata_id = metadata::service::add_metadata(metadata).await?;
         book.metadata_id = Some(metadata_id);
         books::service::update_book(book_id, &book).await?;
+        self.record_sources(book_id, sources).await?;
 
         sync::service::log_change(
             book_id,
@@ -182,11 +342,31 @@ impl MetadataFetcherService {
         Ok(())
     }
 
+    async fn record_sources(
+        &self,
+        book_id: &str,
+        sources: HashMap<&'static str, String>,
+    ) -> Result<(), ProsaError> {
+        let mut fields_by_source: HashMap<String, Vec<&str>> = HashMap::new();
+        for (field, source) in sources {
+            fields_by_source.entry(source).or_default().push(field);
+        }
+
+        for (source, fields) in fields_by_source {
+            metadata::service::set_provenance(book_id, &fields, &source).await?;
+        }
+
+        Ok(())
+    }
+
     async fn handle_cover_update(&self, book_id: &str, cover: Vec<u8>) -> Result<(), ProsaError> {
         let mut book = books::service::get_book(book_id).await?;
 
         let old_cover_id = book.cover_id.expect("Failed to retrieve old cover id");
-        let new_cover_id = covers::service::write_cover(&cover).await?;
+        let new_cover_id = covers::service::write_cover(&cover, false).await?;
+        if new_cover_id == old_cover_id {
+            return Ok(());
+        }
 
         book.cover_id = Some(new_cover_id);
         books::service::update_book(book_id, &book).await?;
@@ -209,10 +389,16 @@ impl MetadataFetcherService {
 
     async fn handle_cover_create(&self, book_id: &str, cover: Vec<u8>) -> Result<(), ProsaError> {
         let mut book = books::service::get_book(book_id).await?;
-        let cover_id = covers::service::write_cover(&cover).await?;
-        book.cover_id = Some(cover_id);
+        let cover_id = covers::service::write_cover(&cover, false).await?;
+        let placeholder_id = book.cover_id.replace(cover_id);
         books::service::update_book(book_id, &book).await?;
 
+        if let Some(placeholder_id) = placeholder_id
+            && !books::service::cover_is_in_use(&placeholder_id).await
+        {
+            covers::service::delete_cover(&placeholder_id).await?;
+        }
+
         sync::service::log_change(
             book_id,
             ChangeLogEntityType::BookCover,
@@ -1,29 +1,64 @@
-use super::models::CoverError;
-use crate::app::{
-    authentication::models::AuthToken,
-    books, covers,
-    error::ProsaError,
-    server::LOCKS,
-    sync::{
-        self,
-        models::{ChangeLogAction, ChangeLogEntityType},
+use super::models::{CoverError, CoverFormat};
+use crate::{
+    CONFIG,
+    app::{
+        authentication::models::AuthToken,
+        books, covers, epubs,
+        error::ProsaError,
+        server::LOCKS,
+        sync::{
+            self,
+            models::{ChangeLogAction, ChangeLogEntityType},
+        },
     },
 };
-use axum::{Extension, body::Bytes, extract::Path, http::StatusCode};
+use axum::{
+    Extension,
+    body::Bytes,
+    extract::{Path, Query},
+    http::{StatusCode, header},
+};
+use std::collections::HashMap;
+
+pub async fn get_cover_handler(
+    Path(book_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<([(header::HeaderName, &'static str); 1], Vec<u8>), ProsaError> {
+    let parse_size = |name: &str, max: u32| match params.get(name).map(|s| s.parse::<u32>()) {
+        None => Ok(None),
+        Some(Ok(size)) if size > 0 => Ok(Some(size.min(max))),
+        _ => Err(CoverError::InvalidCoverSize),
+    };
+
+    let width = parse_size("width", CONFIG.covers.max_width)?;
+    let height = parse_size("height", CONFIG.covers.max_height)?;
+
+    let format = match params.get("format").map(String::as_str) {
+        None => None,
+        Some("jpeg" | "jpg") => Some(CoverFormat::Jpeg),
+        Some("png") => Some(CoverFormat::Png),
+        Some("webp") => Some(CoverFormat::Webp),
+        _ => return Err(CoverError::InvalidCoverFormat.into()),
+    };
 
-pub async fn get_cover_handler(Path(book_id): Path<String>) -> Result<Vec<u8>, ProsaError> {
     let lock = LOCKS.get_book_lock(&book_id).await;
     let _guard = lock.read().await;
 
     let book = books::service::get_book(&book_id).await?;
-
     let Some(cover_id) = book.cover_id else {
         return Err(CoverError::CoverNotFound.into());
     };
 
-    let cover = covers::service::read_cover(&cover_id).await?;
+    if width.is_none() && height.is_none() && format.is_none() {
+        let cover = covers::service::read_cover(&cover_id).await?;
+        let format = covers::service::cover_format(&cover);
+        return Ok(([(header::CONTENT_TYPE, format.media_type())], cover));
+    }
+
+    let format = format.unwrap_or(CoverFormat::Jpeg);
+    let cover = covers::service::read_cover_variant(&cover_id, width, height, format).await?;
 
-    Ok(cover)
+    Ok(([(header::CONTENT_TYPE, format.media_type())], cover))
 }
 
 pub async fn add_cover_handler(
@@ -36,14 +71,23 @@ pub async fn add_cover_handler(
 
     let mut book = books::service::get_book(&book_id).await?;
 
-    let cover_id = match book.cover_id {
-        None => covers::service::write_cover(&cover_data.to_vec()).await?,
-        Some(_) => return Err(CoverError::CoverConflict.into()),
-    };
+    if covers::service::without_placeholder(book.cover_id.clone())
+        .await
+        .is_some()
+    {
+        return Err(CoverError::CoverConflict.into());
+    }
 
-    book.cover_id = Some(cover_id);
+    let cover_id = covers::service::write_cover(&cover_data, false).await?;
+    let placeholder_id = book.cover_id.replace(cover_id);
     books::service::update_book(&book_id, &book).await?;
 
+    if let Some(placeholder_id) = placeholder_id
+        && !books::service::cover_is_in_use(&placeholder_id).await
+    {
+        covers::service::delete_cover(&placeholder_id).await?;
+    }
+
     sync::service::log_change(
         &book_id,
         ChangeLogEntityType::BookCover,
@@ -65,7 +109,7 @@ pub async fn delete_cover_handler(
 
     let mut book = books::service::get_book(&book_id).await?;
 
-    let Some(cover_id) = book.cover_id else {
+    let Some(cover_id) = covers::service::without_placeholder(book.cover_id).await else {
         return Err(CoverError::CoverNotFound.into());
     };
 
@@ -85,6 +129,9 @@ pub async fn delete_cover_handler(
     )
     .await;
 
+    // A placeholder takes the place of the deleted cover
+    covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -98,11 +145,11 @@ pub async fn update_cover_handler(
 
     let mut book = books::service::get_book(&book_id).await?;
 
-    let Some(old_cover_id) = book.cover_id else {
+    let Some(old_cover_id) = covers::service::without_placeholder(book.cover_id).await else {
         return Err(CoverError::CoverNotFound.into());
     };
 
-    let new_cover_id = covers::service::write_cover(&cover_data.to_vec()).await?;
+    let new_cover_id = covers::service::write_cover(&cover_data, false).await?;
     book.cover_id = Some(new_cover_id);
     books::service::update_book(&book_id, &book).await?;
 
@@ -121,3 +168,49 @@ pub async fn update_cover_handler(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+pub async fn extract_cover_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(book_id): Path<String>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.write().await;
+
+    let mut book = books::service::get_book(&book_id).await?;
+
+    let epub = epubs::service::read_epub(&book.epub_id).await?;
+    let Some(cover_data) = epubs::service::extract_cover(&epub) else {
+        return Err(CoverError::EmbeddedCoverNotFound.into());
+    };
+
+    let cover_id = covers::service::write_cover(&cover_data, false).await?;
+    let old_cover_id = book.cover_id.replace(cover_id.clone());
+    if old_cover_id.as_ref() == Some(&cover_id) {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    // Replacing a placeholder gives the book its first actual cover
+    let action = match covers::service::without_placeholder(old_cover_id.clone()).await {
+        Some(_) => ChangeLogAction::Update,
+        None => ChangeLogAction::Create,
+    };
+
+    books::service::update_book(&book_id, &book).await?;
+
+    if let Some(old_cover_id) = old_cover_id
+        && !books::service::cover_is_in_use(&old_cover_id).await
+    {
+        covers::service::delete_cover(&old_cover_id).await?;
+    }
+
+    sync::service::log_change(
+        &book_id,
+        ChangeLogEntityType::BookCover,
+        action,
+        &book.owner_id,
+        &token.session_id,
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,5 +1,7 @@
 pub mod controller;
-mod models;
+pub mod models;
+mod placeholder;
+mod processing;
 pub mod repository;
 pub mod routes;
 pub mod service;
@@ -14,6 +14,15 @@ pub enum CoverError {
     #[strum(message = "The provided cover image is invalid.")]
     #[strum(props(StatusCode = "400"))]
     InvalidCover,
+    #[strum(message = "The requested cover size is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidCoverSize,
+    #[strum(message = "The requested cover format is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidCoverFormat,
+    #[strum(message = "The book does not contain a cover.")]
+    #[strum(props(StatusCode = "404"))]
+    EmbeddedCoverNotFound,
     #[strum(message = "Internal error")]
     #[strum(props(StatusCode = "500"))]
     InternalError,
@@ -27,3 +36,28 @@ impl From<FileError> for CoverError {
         }
     }
 }
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CoverFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl CoverFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CoverFormat::Jpeg => "jpeg",
+            CoverFormat::Png => "png",
+            CoverFormat::Webp => "webp",
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            CoverFormat::Jpeg => "image/jpeg",
+            CoverFormat::Png => "image/png",
+            CoverFormat::Webp => "image/webp",
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use super::{models::CoverFormat, processing};
+use image::{DynamicImage, ImageResult, Rgb, RgbImage};
+use sha2::{Digest, Sha256};
+
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = 900;
+const MARGIN: u32 = 60;
+const TITLE_TOP: u32 = 170;
+const TITLE_BOTTOM: u32 = 690;
+const TITLE_SCALES: [u32; 5] = [8, 7, 6, 5, 4];
+const DETAIL_SCALE: u32 = 4;
+const AUTHOR_LINES: usize = 2;
+
+const FOREGROUND: Rgb<u8> = Rgb([245, 241, 230]);
+const PALETTE: [[u8; 3]; 12] = [
+    [142, 59, 70],
+    [46, 94, 78],
+    [43, 76, 126],
+    [94, 70, 140],
+    [168, 94, 42],
+    [52, 110, 120],
+    [120, 60, 110],
+    [80, 96, 50],
+    [60, 64, 84],
+    [150, 70, 50],
+    [40, 100, 140],
+    [110, 84, 60],
+];
+
+/// Draws a cover for a book that has none. The same title, author and series number
+/// always give the same image, with a background colour picked from the title.
+pub fn generate_placeholder(
+    title: &str,
+    author: Option<&str>,
+    series_number: Option<f32>,
+    quality: u8,
+) -> ImageResult<Vec<u8>> {
+    let hash = Sha256::digest(title.as_bytes());
+    let background = PALETTE[usize::from(hash[0]) % PALETTE.len()];
+    let accent = background.map(|c| (u16::from(c) + (255 - u16::from(c)) * 35 / 100) as u8);
+
+    let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb(background));
+    draw_frame(&mut image, 24, 4, Rgb(accent));
+
+    if let Some(number) = series_number {
+        draw_lines(&mut image, &[format!("#{number}")], 90, DETAIL_SCALE, Rgb(accent));
+    }
+
+    let (scale, lines) = fit_title(&normalize(title));
+    let block_height = text_height(lines.len(), scale);
+    let top = TITLE_TOP + (TITLE_BOTTOM - TITLE_TOP).saturating_sub(block_height) / 2;
+    draw_lines(&mut image, &lines, top, scale, FOREGROUND);
+
+    if let Some(author) = author {
+        let mut lines = wrap(&normalize(author), max_chars(DETAIL_SCALE));
+        truncate(&mut lines, AUTHOR_LINES, max_chars(DETAIL_SCALE));
+        let top = HEIGHT - 90 - text_height(lines.len(), DETAIL_SCALE);
+        draw_lines(&mut image, &lines, top, DETAIL_SCALE, Rgb(accent));
+    }
+
+    processing::encode(&DynamicImage::ImageRgb8(image), CoverFormat::Jpeg, quality)
+}
+
+/// Picks the largest scale at which the whole title fits, cutting it short if none does.
+fn fit_title(title: &str) -> (u32, Vec<String>) {
+    for scale in TITLE_SCALES {
+        let lines = wrap(title, max_chars(scale));
+        if text_height(lines.len(), scale) <= TITLE_BOTTOM - TITLE_TOP {
+            return (scale, lines);
+        }
+    }
+
+    let scale = TITLE_SCALES[TITLE_SCALES.len() - 1];
+    let mut lines = wrap(title, max_chars(scale));
+    let max_lines = ((TITLE_BOTTOM - TITLE_TOP + 3 * scale) / (10 * scale)) as usize;
+    truncate(&mut lines, max_lines, max_chars(scale));
+
+    (scale, lines)
+}
+
+fn max_chars(scale: u32) -> usize {
+    ((WIDTH - 2 * MARGIN + scale) / (6 * scale)) as usize
+}
+
+fn text_height(lines: usize, scale: u32) -> u32 {
+    let lines = u32::try_from(lines).unwrap_or(u32::MAX);
+    (lines * 10 * scale).saturating_sub(3 * scale)
+}
+
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line: Vec<char> = Vec::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+
+        // Words that are too long for a line of their own are split
+        while word.len() > max_chars {
+            if !line.is_empty() {
+                lines.push(line.drain(..).collect());
+            }
+            lines.push(word.drain(..max_chars).collect());
+        }
+
+        if line.is_empty() {
+            line = word;
+        } else if line.len() + 1 + word.len() <= max_chars {
+            line.push(' ');
+            line.extend(word);
+        } else {
+            lines.push(line.drain(..).collect());
+            line = word;
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line.into_iter().collect());
+    }
+
+    lines
+}
+
+fn truncate(lines: &mut Vec<String>, max_lines: usize, max_chars: usize) {
+    if lines.len() <= max_lines {
+        return;
+    }
+
+    lines.truncate(max_lines);
+    if let Some(last) = lines.last_mut() {
+        let kept: String = last.chars().take(max_chars.saturating_sub(3)).collect();
+        *last = format!("{}...", kept.trim_end());
+    }
+}
+
+/// Uppercases the text and keeps only what the built-in font can draw.
+fn normalize(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .flat_map(char::to_uppercase)
+        .filter_map(|c| match c {
+            _ if glyph(c).is_some() => Some(c),
+            'À'..='Å' => Some('A'),
+            'Ç' => Some('C'),
+            'È'..='Ë' => Some('E'),
+            'Ì'..='Ï' => Some('I'),
+            'Ñ' => Some('N'),
+            'Ò'..='Ö' | 'Ø' => Some('O'),
+            'Ù'..='Ü' => Some('U'),
+            'Ý' => Some('Y'),
+            '‘' | '’' => Some('\''),
+            '“' | '”' => Some('"'),
+            '–' | '—' => Some('-'),
+            _ if c.is_whitespace() => Some(' '),
+            _ => None,
+        })
+        .collect();
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn draw_frame(image: &mut RgbImage, inset: u32, thickness: u32, color: Rgb<u8>) {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let inside = x >= inset && y >= inset && x < WIDTH - inset && y < HEIGHT - inset;
+        let border = x < inset + thickness
+            || y < inset + thickness
+            || x >= WIDTH - inset - thickness
+            || y >= HEIGHT - inset - thickness;
+
+        if inside && border {
+            *pixel = color;
+        }
+    }
+}
+
+fn draw_lines(image: &mut RgbImage, lines: &[String], top: u32, scale: u32, color: Rgb<u8>) {
+    for (index, line) in (0..).zip(lines) {
+        let chars = u32::try_from(line.chars().count()).unwrap_or(u32::MAX);
+        let width = (chars * 6 * scale).saturating_sub(scale);
+        let left = WIDTH.saturating_sub(width) / 2;
+        let top = top + index * 10 * scale;
+
+        for (position, c) in (0..).zip(line.chars()) {
+            let Some(rows) = glyph(c) else {
+                continue;
+            };
+
+            for (row, bits) in (0..).zip(rows) {
+                for column in 0..5 {
+                    if bits & (0b10000 >> column) == 0 {
+                        continue;
+                    }
+
+                    let x = left + (position * 6 + column) * scale;
+                    let y = top + row * scale;
+                    fill(image, x, y, scale, color);
+                }
+            }
+        }
+    }
+}
+
+fn fill(image: &mut RgbImage, x: u32, y: u32, size: u32, color: Rgb<u8>) {
+    for dy in 0..size {
+        for dx in 0..size {
+            if x + dx < WIDTH && y + dy < HEIGHT {
+                image.put_pixel(x + dx, y + dy, color);
+            }
+        }
+    }
+}
+
+/// A 5x7 bitmap font, one row per byte with the leftmost pixel in the fifth bit.
+fn glyph(c: char) -> Option<[u8; 7]> {
+    let rows = match c {
+        'A' => [0b01110, 0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        ';' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000],
+        '\'' => [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '"' => [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+        '?' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+        '&' => [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '#' => [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        ' ' => [0; 7],
+        _ => return None,
+    };
+
+    Some(rows)
+}
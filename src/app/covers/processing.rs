@@ -0,0 +1,134 @@
+use super::models::CoverFormat;
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult, Rgb, RgbImage,
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    metadata::Orientation,
+};
+use std::io::Cursor;
+
+/// Prepares a cover for storage as an upright JPEG that fits within the given dimensions.
+///
+/// JPEGs that already meet those requirements only lose their EXIF, IPTC and comment
+/// segments, so they are not degraded by being encoded a second time.
+pub fn normalize_cover(data: &[u8], max_width: u32, max_height: u32, quality: u8) -> ImageResult<Vec<u8>> {
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let (width, height) = decoder.dimensions();
+
+    if image::guess_format(data)? == ImageFormat::Jpeg
+        && orientation == Orientation::NoTransforms
+        && width <= max_width
+        && height <= max_height
+        && let Some(stripped) = strip_jpeg_metadata(data)
+    {
+        return Ok(stripped);
+    }
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    encode(&shrink(image, max_width, max_height), CoverFormat::Jpeg, quality)
+}
+
+/// Scales a cover down to fit the requested dimensions, keeping its aspect ratio.
+/// Covers are never scaled up.
+pub fn resize_cover(
+    data: &[u8],
+    width: Option<u32>,
+    height: Option<u32>,
+    format: CoverFormat,
+    quality: u8,
+) -> ImageResult<Vec<u8>> {
+    let image = image::load_from_memory(data)?;
+    let (max_width, max_height) = (width.unwrap_or(u32::MAX), height.unwrap_or(u32::MAX));
+
+    // Nothing to do, and encoding again would only lose quality
+    if image.width() <= max_width && image.height() <= max_height && detect_format(data) == Some(format) {
+        return Ok(data.to_vec());
+    }
+
+    encode(&shrink(image, max_width, max_height), format, quality)
+}
+
+pub fn detect_format(data: &[u8]) -> Option<CoverFormat> {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Jpeg) => Some(CoverFormat::Jpeg),
+        Ok(ImageFormat::Png) => Some(CoverFormat::Png),
+        Ok(ImageFormat::WebP) => Some(CoverFormat::Webp),
+        _ => None,
+    }
+}
+
+fn shrink(image: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+    if image.width() <= max_width && image.height() <= max_height {
+        return image;
+    }
+
+    image.resize(max_width, max_height, FilterType::Lanczos3)
+}
+
+pub fn encode(image: &DynamicImage, format: CoverFormat, quality: u8) -> ImageResult<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    match format {
+        CoverFormat::Jpeg => {
+            flatten(image).write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality))?;
+        }
+        CoverFormat::Png => image.write_with_encoder(PngEncoder::new(&mut buffer))?,
+        CoverFormat::Webp => {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut buffer))?;
+        }
+    }
+
+    Ok(buffer)
+}
+
+/// JPEG has no transparency, so transparent areas are laid over a white background.
+fn flatten(image: &DynamicImage) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return DynamicImage::ImageRgb8(image.to_rgb8());
+    }
+
+    let rgba = image.to_rgba8();
+    let rgb = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((u16::from(c) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    });
+
+    DynamicImage::ImageRgb8(rgb)
+}
+
+fn strip_jpeg_metadata(data: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(data.get(..2).filter(|soi| *soi == [0xFF, 0xD8])?);
+
+    let mut position = 2;
+    loop {
+        if *data.get(position)? != 0xFF {
+            return None;
+        }
+
+        // Everything from the start of scan onwards is image data
+        let marker = *data.get(position + 1)?;
+        if marker == 0xDA {
+            stripped.extend_from_slice(&data[position..]);
+            return Some(stripped);
+        }
+
+        let length = u16::from_be_bytes([*data.get(position + 2)?, *data.get(position + 3)?]);
+        let end = position + 2 + usize::from(length);
+        let segment = data.get(position..end)?;
+
+        // APP1 holds EXIF and XMP, APP13 holds IPTC and 0xFE is a comment
+        if !matches!(marker, 0xE1 | 0xED | 0xFE) {
+            stripped.extend_from_slice(segment);
+        }
+
+        position = end;
+    }
+}
@@ -1,15 +1,16 @@
 use super::models::CoverError;
 use crate::DB_POOL;
 
-pub async fn add_cover(cover_id: &str, hash: &str) {
+pub async fn add_cover(cover_id: &str, hash: &str, generated: bool) {
     sqlx::query(
         r"
-        INSERT INTO covers (cover_id, hash)
-        VALUES ($1, $2)
+        INSERT INTO covers (cover_id, hash, generated)
+        VALUES ($1, $2, $3)
         ",
     )
     .bind(cover_id)
     .bind(hash)
+    .bind(generated)
     .execute(DB_POOL.get().expect("Failed to get database pool"))
     .await
     .expect("Failed to add cover");
@@ -47,3 +48,32 @@ pub async fn get_cover_by_hash(hash: &str) -> Option<String> {
     .await
     .expect("Failed to get cover by hash")
 }
+
+pub async fn get_hash(cover_id: &str) -> Option<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT hash
+        FROM covers
+        WHERE cover_id = $1
+        ",
+    )
+    .bind(cover_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to get cover hash")
+}
+
+pub async fn is_generated(cover_id: &str) -> bool {
+    sqlx::query_scalar(
+        r"
+        SELECT generated
+        FROM covers
+        WHERE cover_id = $1
+        ",
+    )
+    .bind(cover_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to check if cover is generated")
+    .unwrap_or(false)
+}
@@ -1,7 +1,10 @@
 use crate::app::{
     authentication::middleware::extract_token_middleware,
     authorization::books::{can_delete_book, can_read_book, can_update_book},
-    covers::controller::{add_cover_handler, delete_cover_handler, get_cover_handler, update_cover_handler},
+    covers::controller::{
+        add_cover_handler, delete_cover_handler, extract_cover_handler, get_cover_handler,
+        update_cover_handler,
+    },
 };
 use axum::{
     Router,
@@ -25,6 +28,9 @@ pub fn get_routes() -> Router {
         .route("/books/{book_id}/cover", put(update_cover_handler) 
             .route_layer(from_fn(can_update_book))
         )
+        .route("/books/{book_id}/cover/extract", post(extract_cover_handler)
+            .route_layer(from_fn(can_update_book))
+        )
         .layer(from_fn(extract_token_middleware))
         .layer(DefaultBodyLimit::max(15728640))
 }
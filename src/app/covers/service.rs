@@ -1,27 +1,138 @@
-use super::models::CoverError;
+use super::{
+    models::{CoverError, CoverFormat},
+    placeholder, processing,
+};
 use crate::{
     CONFIG,
     app::{
+        books::{self, models::BookEntity},
         covers::repository,
+        error::ProsaError,
+        metadata::{self, models::Metadata},
         server::{CACHE, LOCKS},
+        sync::{
+            self,
+            models::{ChangeLogAction, ChangeLogEntityType},
+        },
     },
 };
 use base64::{Engine, prelude::BASE64_STANDARD};
 use image::ImageFormat;
+use log::warn;
 use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use tokio::{
-    fs::{File, remove_file},
+    fs::{self, File, remove_file},
     io::{AsyncReadExt, AsyncWriteExt},
+    task::spawn_blocking,
 };
 use uuid::Uuid;
 
-pub async fn write_cover(cover_data: &Vec<u8>) -> Result<String, CoverError> {
+/// Stores a cover, unless it is already stored. Generated covers are marked as such, so that any
+/// other cover replaces them.
+pub async fn write_cover(cover_data: &[u8], generated: bool) -> Result<String, CoverError> {
     if !is_valid_image(cover_data) {
         return Err(CoverError::InvalidCover);
     }
 
-    let hash = BASE64_STANDARD.encode(Sha256::digest(cover_data));
+    let cover_data = cover_data.to_vec();
+    let cover_data = spawn_blocking(move || {
+        let config = &CONFIG.covers;
+        processing::normalize_cover(&cover_data, config.max_width, config.max_height, config.quality)
+    })
+    .await
+    .expect("Failed to normalize cover")
+    .map_err(|_| CoverError::InvalidCover)?;
+
+    store_cover(cover_data, generated).await
+}
+
+pub async fn get_cover_hash(cover_id: &str) -> Option<String> {
+    repository::get_hash(cover_id).await
+}
+
+pub async fn is_generated(cover_id: &str) -> bool {
+    repository::is_generated(cover_id).await
+}
+
+/// Generated covers only stand in for a missing one, so they do not count as the book's cover.
+pub async fn without_placeholder(cover_id: Option<String>) -> Option<String> {
+    match cover_id {
+        Some(cover_id) if !is_generated(&cover_id).await => Some(cover_id),
+        _ => None,
+    }
+}
+
+/// Gives a book without a cover a generated one, or draws its generated cover again so it follows
+/// changes to the book's metadata.
+pub async fn refresh_placeholder(book_id: &str, session_id: &str) -> Result<(), ProsaError> {
+    let book = books::service::get_book(book_id).await?;
+    let action = match &book.cover_id {
+        None => ChangeLogAction::Create,
+        Some(cover_id) if is_generated(cover_id).await => ChangeLogAction::Update,
+        Some(_) => return Ok(()),
+    };
+
+    let owner_id = book.owner_id.clone();
+    if set_placeholder(book_id, book).await? {
+        sync::service::log_change(
+            book_id,
+            ChangeLogEntityType::BookCover,
+            action,
+            &owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+async fn set_placeholder(book_id: &str, mut book: BookEntity) -> Result<bool, ProsaError> {
+    let metadata = match &book.metadata_id {
+        Some(metadata_id) => Some(metadata::service::get_metadata(metadata_id).await?),
+        None => None,
+    };
+
+    let cover_id = write_placeholder(metadata.as_ref()).await?;
+    let old_cover_id = book.cover_id.replace(cover_id.clone());
+    if old_cover_id.as_ref() == Some(&cover_id) {
+        return Ok(false);
+    }
+
+    books::service::update_book(book_id, &book).await?;
+
+    if let Some(old_cover_id) = old_cover_id
+        && !books::service::cover_is_in_use(&old_cover_id).await
+    {
+        delete_cover(&old_cover_id).await?;
+    }
+
+    Ok(true)
+}
+
+async fn write_placeholder(metadata: Option<&Metadata>) -> Result<String, CoverError> {
+    let title = metadata
+        .and_then(|m| m.title.clone())
+        .unwrap_or_else(|| "Untitled".to_string());
+    let author = metadata
+        .and_then(|m| m.contributors.as_ref())
+        .and_then(|c| c.iter().find(|c| c.role.eq_ignore_ascii_case("author")))
+        .map(|c| c.name.clone());
+    let series_number = metadata.and_then(|m| m.series.as_ref()).map(|s| s.number);
+
+    let placeholder = spawn_blocking(move || {
+        placeholder::generate_placeholder(&title, author.as_deref(), series_number, CONFIG.covers.quality)
+    })
+    .await
+    .expect("Failed to generate placeholder cover")
+    .map_err(|_| CoverError::InternalError)?;
+
+    write_cover(&placeholder, true).await
+}
+
+async fn store_cover(cover_data: Vec<u8>, generated: bool) -> Result<String, CoverError> {
+    let hash = BASE64_STANDARD.encode(Sha256::digest(&cover_data));
     let lock = LOCKS.get_hash_lock(&hash).await;
     let _guard = lock.write().await;
 
@@ -35,16 +146,18 @@ pub async fn write_cover(cover_data: &Vec<u8>) -> Result<String, CoverError> {
         .await
         .expect("Failed to create cover file");
 
-    file.write_all(cover_data)
+    file.write_all(&cover_data)
         .await
         .expect("Failed to write cover file to disk");
 
     file.sync_all().await.expect("Failed to sync cover file");
 
-    repository::add_cover(&cover_id, &hash).await;
+    repository::add_cover(&cover_id, &hash, generated).await;
 
     let cache_key = format!("images:{cover_id}");
-    CACHE.image_cache.insert(cache_key, Arc::new(cover_data.clone()));
+    CACHE.image_cache.insert(cache_key, Arc::new(cover_data));
+
+    tokio::spawn(generate_thumbnails(cover_id.clone()));
 
     Ok(cover_id)
 }
@@ -66,10 +179,86 @@ pub async fn read_cover(cover_id: &str) -> Result<Vec<u8>, CoverError> {
     Ok(buffer)
 }
 
+/// Resizes and converts a cover. Only the configured thumbnail widths are kept on disk, so that the
+/// cached variants of a cover stay bounded, any other size is resized on every request.
+pub async fn read_cover_variant(
+    cover_id: &str,
+    width: Option<u32>,
+    height: Option<u32>,
+    format: CoverFormat,
+) -> Result<Vec<u8>, CoverError> {
+    let cached = is_cached_variant(width, height);
+    let variant_file = format!(
+        "{}/{cover_id}.{}x{}.{}",
+        variants_path(),
+        width.unwrap_or(0),
+        height.unwrap_or(0),
+        format.extension()
+    );
+
+    if cached && let Ok(variant) = fs::read(&variant_file).await {
+        return Ok(variant);
+    }
+
+    let cover = read_cover(cover_id).await?;
+    let variant = spawn_blocking(move || {
+        processing::resize_cover(&cover, width, height, format, CONFIG.covers.quality)
+    })
+    .await
+    .expect("Failed to resize cover")
+    .map_err(|_| CoverError::InternalError)?;
+
+    if cached {
+        let temp_file = format!("{variant_file}.{}", Uuid::new_v4());
+        if fs::write(&temp_file, &variant).await.is_ok()
+            && fs::rename(&temp_file, &variant_file).await.is_err()
+        {
+            let _ = remove_file(&temp_file).await;
+        }
+    }
+
+    Ok(variant)
+}
+
+/// Removes the cached variants that are not kept anymore, either because their cover was deleted
+/// or because they were cached by an older version that kept every requested size.
+pub async fn prune_cover_variants() {
+    let Ok(mut entries) = fs::read_dir(variants_path()).await else {
+        return;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let size = |size: &str| size.parse::<u32>().ok().map(|s| (s > 0).then_some(s));
+        let keep = match file_name.split('.').collect::<Vec<_>>()[..] {
+            [cover_id, variant_size, _] => match variant_size.split_once('x') {
+                Some((width, height)) => match (size(width), size(height)) {
+                    (Some(width), Some(height)) => {
+                        is_cached_variant(width, height) && repository::get_hash(cover_id).await.is_some()
+                    }
+                    _ => false,
+                },
+                None => false,
+            },
+            _ => false,
+        };
+
+        if !keep {
+            let _ = remove_file(entry.path()).await;
+        }
+    }
+}
+
+pub fn cover_format(cover_data: &[u8]) -> CoverFormat {
+    processing::detect_format(cover_data).unwrap_or(CoverFormat::Jpeg)
+}
+
 pub async fn delete_cover(cover_id: &str) -> Result<(), CoverError> {
     let cover_file = format!("{}/{}.jpeg", CONFIG.book_storage.cover_path, cover_id);
     remove_file(&cover_file).await?;
 
+    delete_cover_variants(cover_id).await;
+
     repository::delete_cover(cover_id).await?;
 
     let cache_key = format!("images:{cover_id}");
@@ -78,6 +267,35 @@ pub async fn delete_cover(cover_id: &str) -> Result<(), CoverError> {
     Ok(())
 }
 
+fn is_cached_variant(width: Option<u32>, height: Option<u32>) -> bool {
+    height.is_none() && width.is_none_or(|width| CONFIG.covers.thumbnail_widths.contains(&width))
+}
+
+fn variants_path() -> String {
+    format!("{}/covers", CONFIG.book_storage.cache_path)
+}
+
+async fn generate_thumbnails(cover_id: String) {
+    for width in &CONFIG.covers.thumbnail_widths {
+        if let Err(e) = read_cover_variant(&cover_id, Some(*width), None, CoverFormat::Jpeg).await {
+            warn!("Failed to generate thumbnail for cover {cover_id}: {e:?}");
+        }
+    }
+}
+
+async fn delete_cover_variants(cover_id: &str) {
+    let Ok(mut entries) = fs::read_dir(variants_path()).await else {
+        return;
+    };
+
+    let prefix = format!("{cover_id}.");
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            let _ = remove_file(entry.path()).await;
+        }
+    }
+}
+
 fn is_valid_image(cover_data: &[u8]) -> bool {
     if cover_data.len() > 10485760 {
         return false;
@@ -0,0 +1,45 @@
+use crate::app::{
+    authentication::models::AuthToken,
+    devices::{
+        models::{Device, PatchDeviceRequest, RegisterDeviceRequest},
+        service,
+    },
+    error::ProsaError,
+};
+use axum::{Extension, Json, extract::Path, http::StatusCode};
+
+pub async fn register_device_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(user_id): Path<String>,
+    Json(request): Json<RegisterDeviceRequest>,
+) -> Result<String, ProsaError> {
+    let device_id = service::register_device(&user_id, &token.session_id, request).await?;
+    Ok(device_id)
+}
+
+pub async fn list_devices_handler(Path(user_id): Path<String>) -> Result<Json<Vec<Device>>, ProsaError> {
+    let devices = service::get_devices(&user_id).await?;
+    Ok(Json(devices))
+}
+
+pub async fn get_device_handler(
+    Path((_, device_id)): Path<(String, String)>,
+) -> Result<Json<Device>, ProsaError> {
+    let device = service::get_device(&device_id).await?;
+    Ok(Json(device))
+}
+
+pub async fn patch_device_handler(
+    Path((_, device_id)): Path<(String, String)>,
+    Json(request): Json<PatchDeviceRequest>,
+) -> Result<StatusCode, ProsaError> {
+    service::patch_device(&device_id, request).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_device_handler(
+    Path((_, device_id)): Path<(String, String)>,
+) -> Result<StatusCode, ProsaError> {
+    service::remove_device(&device_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
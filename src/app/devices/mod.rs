@@ -0,0 +1,5 @@
+pub mod controller;
+pub mod models;
+pub mod repository;
+pub mod routes;
+pub mod service;
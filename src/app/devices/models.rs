@@ -0,0 +1,88 @@
+use chrono::{
+    DateTime, Utc,
+    serde::{ts_milliseconds, ts_milliseconds_option},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    FromRow,
+    error::{DatabaseError, ErrorKind},
+    sqlite::SqliteError,
+};
+use strum_macros::{EnumMessage, EnumProperty};
+
+type SqlxError = sqlx::Error;
+
+#[derive(EnumMessage, EnumProperty, Debug)]
+pub enum DeviceError {
+    #[strum(message = "The requested device does not exist or is not accessible.")]
+    #[strum(props(StatusCode = "404"))]
+    DeviceNotFound,
+    #[strum(message = "This session is already registered as a device.")]
+    #[strum(props(StatusCode = "409"))]
+    DeviceConflict,
+    #[strum(message = "The provided device name is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidName,
+    #[strum(message = "The provided device type is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidDeviceType,
+    #[strum(message = "The provided sync token is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidSyncToken,
+    #[strum(message = "Internal error")]
+    #[strum(props(StatusCode = "500"))]
+    InternalError,
+}
+
+impl From<SqlxError> for DeviceError {
+    fn from(error: SqlxError) -> Self {
+        match error {
+            SqlxError::RowNotFound => DeviceError::DeviceNotFound,
+            SqlxError::Database(error) => error.downcast_ref::<SqliteError>().into(),
+            _ => DeviceError::InternalError,
+        }
+    }
+}
+
+impl From<&SqliteError> for DeviceError {
+    fn from(error: &SqliteError) -> Self {
+        match error.kind() {
+            ErrorKind::UniqueViolation => DeviceError::DeviceConflict,
+            _ => DeviceError::InternalError,
+        }
+    }
+}
+
+pub const VALID_DEVICE_TYPES: [&str; 5] = ["Ereader", "Phone", "Tablet", "Computer", "Other"];
+
+#[derive(Serialize, FromRow)]
+pub struct Device {
+    pub device_id: String,
+    pub owner_id: String,
+    #[serde(skip)]
+    pub session_id: String,
+    pub name: String,
+    pub device_type: String,
+    /// The sync token the device was last sent, from which it resumes by default.
+    pub sync_token: i64,
+    /// How many changes the device has yet to sync past its sync token.
+    #[sqlx(skip)]
+    pub pending_changes: i64,
+    #[serde(with = "ts_milliseconds_option")]
+    pub last_seen: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterDeviceRequest {
+    pub name: String,
+    pub device_type: String,
+}
+
+#[derive(Deserialize)]
+pub struct PatchDeviceRequest {
+    pub name: Option<String>,
+    pub device_type: Option<String>,
+    pub sync_token: Option<i64>,
+}
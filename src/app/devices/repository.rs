@@ -0,0 +1,134 @@
+use super::models::{Device, DeviceError};
+use crate::DB_POOL;
+use chrono::Utc;
+
+pub async fn add_device(
+    device_id: &str,
+    owner_id: &str,
+    session_id: &str,
+    name: &str,
+    device_type: &str,
+) -> Result<(), DeviceError> {
+    sqlx::query(
+        r"
+        INSERT INTO devices (device_id, owner_id, session_id, name, device_type, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ",
+    )
+    .bind(device_id)
+    .bind(owner_id)
+    .bind(session_id)
+    .bind(name)
+    .bind(device_type)
+    .bind(Utc::now())
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_device(device_id: &str) -> Result<Device, DeviceError> {
+    let device: Device = sqlx::query_as(
+        r"
+        SELECT device_id, owner_id, session_id, name, device_type, sync_token, last_seen, created_at
+        FROM devices
+        WHERE device_id = $1
+        ",
+    )
+    .bind(device_id)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(device)
+}
+
+pub async fn get_session_device(session_id: &str, owner_id: &str) -> Option<Device> {
+    sqlx::query_as(
+        r"
+        SELECT device_id, owner_id, session_id, name, device_type, sync_token, last_seen, created_at
+        FROM devices
+        WHERE session_id = $1 AND owner_id = $2
+        ",
+    )
+    .bind(session_id)
+    .bind(owner_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to get session device")
+}
+
+pub async fn get_devices(owner_id: &str) -> Vec<Device> {
+    sqlx::query_as(
+        r"
+        SELECT device_id, owner_id, session_id, name, device_type, sync_token, last_seen, created_at
+        FROM devices
+        WHERE owner_id = $1
+        ORDER BY created_at ASC
+        ",
+    )
+    .bind(owner_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list devices")
+}
+
+pub async fn update_device(
+    device_id: &str,
+    name: &str,
+    device_type: &str,
+    sync_token: i64,
+) -> Result<(), DeviceError> {
+    let result = sqlx::query(
+        r"
+        UPDATE devices
+        SET name = $1, device_type = $2, sync_token = $3
+        WHERE device_id = $4
+        ",
+    )
+    .bind(name)
+    .bind(device_type)
+    .bind(sync_token)
+    .bind(device_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(DeviceError::DeviceNotFound);
+    }
+
+    Ok(())
+}
+
+pub async fn update_sync(device_id: &str, sync_token: i64) {
+    sqlx::query(
+        r"
+        UPDATE devices
+        SET sync_token = $1, last_seen = $2
+        WHERE device_id = $3
+        ",
+    )
+    .bind(sync_token)
+    .bind(Utc::now())
+    .bind(device_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to update device sync");
+}
+
+pub async fn delete_device(device_id: &str) -> Result<(), DeviceError> {
+    let result = sqlx::query(
+        r"
+        DELETE FROM devices
+        WHERE device_id = $1
+        ",
+    )
+    .bind(device_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(DeviceError::DeviceNotFound);
+    }
+
+    Ok(())
+}
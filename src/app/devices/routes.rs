@@ -0,0 +1,36 @@
+use crate::app::{
+    authentication::middleware::extract_token_middleware,
+    authorization::devices::{
+        can_delete_device, can_read_device, can_read_devices, can_register_device, can_update_device,
+    },
+    devices::controller::{
+        get_device_handler, list_devices_handler, patch_device_handler, register_device_handler,
+        remove_device_handler,
+    },
+};
+use axum::{
+    Router,
+    middleware::from_fn,
+    routing::{delete, get, patch, post},
+};
+
+#[rustfmt::skip]
+pub fn get_routes() -> Router {
+    Router::new()
+        .route("/users/{user_id}/devices", post(register_device_handler)
+            .route_layer(from_fn(can_register_device))
+        )
+        .route("/users/{user_id}/devices", get(list_devices_handler)
+            .route_layer(from_fn(can_read_devices))
+        )
+        .route("/users/{user_id}/devices/{device_id}", get(get_device_handler)
+            .route_layer(from_fn(can_read_device))
+        )
+        .route("/users/{user_id}/devices/{device_id}", patch(patch_device_handler)
+            .route_layer(from_fn(can_update_device))
+        )
+        .route("/users/{user_id}/devices/{device_id}", delete(remove_device_handler)
+            .route_layer(from_fn(can_delete_device))
+        )
+        .layer(from_fn(extract_token_middleware))
+}
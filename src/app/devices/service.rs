@@ -0,0 +1,107 @@
+use crate::app::{
+    authentication,
+    devices::{
+        models::{Device, DeviceError, PatchDeviceRequest, RegisterDeviceRequest, VALID_DEVICE_TYPES},
+        repository,
+    },
+    error::ProsaError,
+    sync, users,
+};
+use uuid::Uuid;
+
+/// Registers the session as one of the user's devices.
+pub async fn register_device(
+    owner_id: &str,
+    session_id: &str,
+    request: RegisterDeviceRequest,
+) -> Result<String, ProsaError> {
+    users::service::get_user(owner_id).await?;
+
+    let name = verify_name(&request.name)?;
+    verify_device_type(&request.device_type)?;
+
+    let device_id = Uuid::new_v4().to_string();
+    repository::add_device(&device_id, owner_id, session_id, &name, &request.device_type).await?;
+
+    Ok(device_id)
+}
+
+pub async fn get_device(device_id: &str) -> Result<Device, ProsaError> {
+    let mut device = repository::get_device(device_id).await?;
+    device.pending_changes = count_pending_changes(&device).await;
+    Ok(device)
+}
+
+pub async fn get_devices(owner_id: &str) -> Result<Vec<Device>, ProsaError> {
+    users::service::get_user(owner_id).await?;
+
+    let mut devices = repository::get_devices(owner_id).await;
+    for device in &mut devices {
+        device.pending_changes = count_pending_changes(device).await;
+    }
+
+    Ok(devices)
+}
+
+pub async fn get_session_device(session_id: &str, owner_id: &str) -> Option<Device> {
+    repository::get_session_device(session_id, owner_id).await
+}
+
+pub async fn patch_device(device_id: &str, request: PatchDeviceRequest) -> Result<(), ProsaError> {
+    let device = repository::get_device(device_id).await?;
+
+    let name = match request.name {
+        Some(name) => verify_name(&name)?,
+        None => device.name,
+    };
+
+    let device_type = request.device_type.unwrap_or(device.device_type);
+    verify_device_type(&device_type)?;
+
+    // Devices can only acknowledge changes that have been logged
+    let sync_token = request.sync_token.unwrap_or(device.sync_token);
+    if sync_token < -1 || sync_token > sync::repository::get_latest_token(&device.owner_id).await {
+        return Err(DeviceError::InvalidSyncToken.into());
+    }
+
+    repository::update_device(device_id, &name, &device_type, sync_token).await?;
+    Ok(())
+}
+
+/// Records that the device synced, moving its cursor to the sync token it was sent.
+pub async fn record_sync(device_id: &str, sync_token: i64) {
+    repository::update_sync(device_id, sync_token).await;
+}
+
+/// Removes the device and signs its session out.
+pub async fn remove_device(device_id: &str) -> Result<(), ProsaError> {
+    let device = repository::get_device(device_id).await?;
+    repository::delete_device(device_id).await?;
+
+    authentication::service::revoke_session(&device.owner_id, &device.session_id).await;
+    sync::repository::delete_session(&device.session_id).await;
+
+    Ok(())
+}
+
+async fn count_pending_changes(device: &Device) -> i64 {
+    sync::repository::count_changes(&device.owner_id, device.sync_token, &device.session_id).await
+}
+
+fn verify_name(name: &str) -> Result<String, DeviceError> {
+    let name = name.trim();
+
+    if name.is_empty() || name.chars().count() > 50 || name.chars().any(char::is_control) {
+        return Err(DeviceError::InvalidName);
+    }
+
+    Ok(name.to_string())
+}
+
+fn verify_device_type(device_type: &str) -> Result<(), DeviceError> {
+    if !VALID_DEVICE_TYPES.contains(&device_type) {
+        return Err(DeviceError::InvalidDeviceType);
+    }
+
+    Ok(())
+}
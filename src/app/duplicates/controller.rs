@@ -0,0 +1,51 @@
+use super::{
+    models::{Duplicate, DuplicateError, MergeBooksRequest},
+    service,
+};
+use crate::app::{authentication::models::AuthToken, error::ProsaError, server::LOCKS};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    http::StatusCode,
+};
+use std::collections::HashMap;
+
+pub async fn list_duplicates_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(token): Extension<AuthToken>,
+) -> Result<Json<Vec<Duplicate>>, ProsaError> {
+    let user_id = match params.get("user_id") {
+        Some(id) => id,
+        None => token.role.get_user(),
+    };
+
+    let duplicates = service::find_duplicates(user_id).await?;
+
+    Ok(Json(duplicates))
+}
+
+pub async fn merge_books_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(book_id): Path<String>,
+    Json(request): Json<MergeBooksRequest>,
+) -> Result<StatusCode, ProsaError> {
+    if request.duplicate_id == book_id {
+        return Err(DuplicateError::InvalidMergeRequest.into());
+    }
+
+    // Always lock in the same order, so two merges of the same pair cannot deadlock
+    let (first_id, second_id) = if book_id < request.duplicate_id {
+        (&book_id, &request.duplicate_id)
+    } else {
+        (&request.duplicate_id, &book_id)
+    };
+
+    let first_lock = LOCKS.get_book_lock(first_id).await;
+    let _first_guard = first_lock.write().await;
+    let second_lock = LOCKS.get_book_lock(second_id).await;
+    let _second_guard = second_lock.write().await;
+
+    service::merge_books(&book_id, &request.duplicate_id, &token.session_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use strum_macros::{EnumMessage, EnumProperty};
+
+#[derive(EnumMessage, EnumProperty, Debug)]
+pub enum DuplicateError {
+    #[strum(message = "The provided merge request is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidMergeRequest,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    Isbn,
+    TitleAuthor,
+    Content,
+}
+
+#[derive(Serialize)]
+pub struct Duplicate {
+    pub book_id: String,
+    pub duplicate_id: String,
+    pub reasons: Vec<DuplicateReason>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeBooksRequest {
+    pub duplicate_id: String,
+}
+
+/// How a book kept outside the library is known, to find it among the books of a user.
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct BookIdentity {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub isbn: Option<String>,
+}
+
+#[derive(FromRow)]
+pub struct LibraryBook {
+    pub book_id: String,
+    pub epub_id: String,
+    pub title: Option<String>,
+    pub isbn: Option<String>,
+    pub authors: Option<String>,
+    pub fingerprint: Option<i64>,
+}
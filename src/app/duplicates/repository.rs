@@ -0,0 +1,32 @@
+use super::models::LibraryBook;
+use crate::DB_POOL;
+
+/// Separates the author names gathered for each book.
+pub const AUTHOR_SEPARATOR: char = '\u{1f}';
+
+pub async fn get_library(owner_id: &str) -> Vec<LibraryBook> {
+    sqlx::query_as(
+        r"
+        SELECT
+            b.book_id,
+            b.epub_id,
+            m.title,
+            m.isbn,
+            (
+                SELECT GROUP_CONCAT(c.name, char(31))
+                FROM contributors c
+                WHERE c.metadata_id = b.metadata_id AND LOWER(c.role) = 'author'
+            ) AS authors,
+            e.fingerprint
+        FROM books b
+        JOIN epubs e ON e.epub_id = b.epub_id
+        LEFT JOIN metadata m ON m.metadata_id = b.metadata_id
+        WHERE b.owner_id = $1 AND b.deleted_at IS NULL
+        ORDER BY b.book_id
+        ",
+    )
+    .bind(owner_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve library")
+}
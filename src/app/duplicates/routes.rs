@@ -0,0 +1,22 @@
+use crate::app::{
+    authentication::middleware::extract_token_middleware,
+    authorization::duplicates::{can_list_duplicates, can_merge_books},
+    duplicates::controller::{list_duplicates_handler, merge_books_handler},
+};
+use axum::{
+    Router,
+    middleware::from_fn,
+    routing::{get, post},
+};
+
+#[rustfmt::skip]
+pub fn get_routes() -> Router {
+    Router::new()
+        .route("/duplicates", get(list_duplicates_handler)
+            .route_layer(from_fn(can_list_duplicates))
+        )
+        .route("/books/{book_id}/merge", post(merge_books_handler)
+            .route_layer(from_fn(can_merge_books))
+        )
+        .layer(from_fn(extract_token_middleware))
+}
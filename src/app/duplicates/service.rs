@@ -0,0 +1,250 @@
+use super::{
+    models::{BookIdentity, Duplicate, DuplicateReason, LibraryBook},
+    repository::{self, AUTHOR_SEPARATOR},
+};
+use crate::app::{
+    annotations, books, epubs,
+    error::ProsaError,
+    shelves, state,
+    sync::{
+        self,
+        models::{ChangeLogAction, ChangeLogEntityType},
+    },
+    users,
+};
+use std::collections::HashSet;
+
+/// Fingerprints that differ in at most this many bits are taken to be of the same text.
+const MAX_FINGERPRINT_DISTANCE: u32 = 3;
+
+struct DuplicateKeys {
+    book_id: String,
+    isbn: Option<String>,
+    title: Option<String>,
+    authors: HashSet<String>,
+    fingerprint: Option<i64>,
+}
+
+pub async fn find_duplicates(owner_id: &str) -> Result<Vec<Duplicate>, ProsaError> {
+    // Ensure user exists
+    users::service::get_user(owner_id).await?;
+
+    let mut library = repository::get_library(owner_id).await;
+    for book in library.iter_mut().filter(|b| b.fingerprint.is_none()) {
+        book.fingerprint = epubs::service::compute_fingerprint(&book.epub_id)
+            .await
+            .ok()
+            .flatten();
+    }
+
+    let keys: Vec<DuplicateKeys> = library.into_iter().map(duplicate_keys).collect();
+    let mut duplicates = Vec::new();
+
+    for (i, book) in keys.iter().enumerate() {
+        for other in &keys[i + 1..] {
+            let reasons = compare(book, other);
+            if !reasons.is_empty() {
+                duplicates.push(Duplicate {
+                    book_id: book.book_id.clone(),
+                    duplicate_id: other.book_id.clone(),
+                    reasons,
+                });
+            }
+        }
+    }
+
+    Ok(duplicates)
+}
+
+/// Keeps a book and folds the reading state, annotations and shelves of its duplicate into it,
+/// before deleting the duplicate.
+pub async fn merge_books(book_id: &str, duplicate_id: &str, session_id: &str) -> Result<(), ProsaError> {
+    let book = books::service::get_book(book_id).await?;
+    let duplicate = books::service::get_book(duplicate_id).await?;
+
+    if state::service::merge_state(&book.state_id, &book.epub_id, &duplicate.state_id).await {
+        sync::service::log_change(
+            book_id,
+            ChangeLogEntityType::BookState,
+            ChangeLogAction::Update,
+            &book.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    let spans = epubs::service::read_spans(&book.epub_id).await?;
+    let duplicate_spans = epubs::service::read_spans(&duplicate.epub_id).await?;
+    if annotations::service::move_annotations(duplicate_id, book_id, &duplicate_spans, &spans).await {
+        sync::service::log_change(
+            book_id,
+            ChangeLogEntityType::BookAnnotations,
+            ChangeLogAction::Update,
+            &book.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    for shelf_id in shelves::service::replace_book_in_shelves(duplicate_id, book_id).await {
+        sync::service::log_change(
+            &shelf_id,
+            ChangeLogEntityType::ShelfContent,
+            ChangeLogAction::Update,
+            &book.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    books::service::remove_book(duplicate_id, session_id).await
+}
+
+/// Finds the books of a user's library that books kept elsewhere are copies of, going by their
+/// ISBN or else by their title and authors.
+pub async fn find_books(owner_id: &str, identities: &[BookIdentity]) -> Vec<Option<String>> {
+    let library: Vec<DuplicateKeys> = repository::get_library(owner_id)
+        .await
+        .into_iter()
+        .map(duplicate_keys)
+        .collect();
+
+    identities
+        .iter()
+        .map(|identity| find_book(&library, identity))
+        .collect()
+}
+
+fn find_book(library: &[DuplicateKeys], identity: &BookIdentity) -> Option<String> {
+    if let Some(isbn) = identity.isbn.as_deref().and_then(normalize_isbn)
+        && let Some(book) = library.iter().find(|b| b.isbn.as_ref() == Some(&isbn))
+    {
+        return Some(book.book_id.clone());
+    }
+
+    let title = identity
+        .title
+        .as_deref()
+        .map(normalize_text)
+        .filter(|t| !t.is_empty())?;
+    let authors: Vec<String> = identity
+        .authors
+        .iter()
+        .map(|a| normalize_text(a))
+        .filter(|a| !a.is_empty())
+        .collect();
+
+    let exact = |b: &&DuplicateKeys| b.title.as_ref() == Some(&title);
+
+    // A title alone is only trusted when it is an exact match for a single book
+    if authors.is_empty() {
+        let mut matches = library.iter().filter(exact);
+        return match (matches.next(), matches.next()) {
+            (Some(book), None) => Some(book.book_id.clone()),
+            _ => None,
+        };
+    }
+
+    // Titles from elsewhere often carry a subtitle or series the library does not, or the other way around
+    let partial = |b: &&DuplicateKeys| {
+        b.title.as_ref().is_some_and(|t| {
+            t.strip_prefix(&title)
+                .or_else(|| title.strip_prefix(t.as_str()))
+                .is_some_and(|rest| rest.starts_with(' '))
+        })
+    };
+    let shares_author = |b: &&DuplicateKeys| {
+        b.authors
+            .iter()
+            .any(|a| authors.iter().any(|other| same_author(a, other)))
+    };
+
+    library
+        .iter()
+        .filter(exact)
+        .find(shares_author)
+        .or_else(|| library.iter().filter(partial).find(shares_author))
+        .map(|b| b.book_id.clone())
+}
+
+fn duplicate_keys(book: LibraryBook) -> DuplicateKeys {
+    let authors = book
+        .authors
+        .unwrap_or_default()
+        .split(AUTHOR_SEPARATOR)
+        .map(normalize_text)
+        .filter(|a| !a.is_empty())
+        .collect();
+
+    DuplicateKeys {
+        book_id: book.book_id,
+        isbn: book.isbn.as_deref().and_then(normalize_isbn),
+        title: book
+            .title
+            .as_deref()
+            .map(normalize_text)
+            .filter(|t| !t.is_empty()),
+        authors,
+        fingerprint: book.fingerprint,
+    }
+}
+
+fn compare(book: &DuplicateKeys, other: &DuplicateKeys) -> Vec<DuplicateReason> {
+    let mut reasons = Vec::new();
+
+    if book.isbn.is_some() && book.isbn == other.isbn {
+        reasons.push(DuplicateReason::Isbn);
+    }
+
+    // A shared title alone is too common to go by
+    if book.title.is_some() && book.title == other.title && !book.authors.is_disjoint(&other.authors) {
+        reasons.push(DuplicateReason::TitleAuthor);
+    }
+
+    if let (Some(a), Some(b)) = (book.fingerprint, other.fingerprint)
+        && epubs::service::fingerprint_distance(a, b) <= MAX_FINGERPRINT_DISTANCE
+    {
+        reasons.push(DuplicateReason::Content);
+    }
+
+    reasons
+}
+
+/// Names may be written first name first or last name first.
+fn same_author(a: &str, b: &str) -> bool {
+    let words = |name: &str| {
+        let mut words: Vec<String> = name.split(' ').map(ToString::to_string).collect();
+        words.sort();
+        words
+    };
+
+    words(a) == words(b)
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// ISBN-10s are converted so they match the ISBN-13 of the same edition.
+fn normalize_isbn(isbn: &str) -> Option<String> {
+    let isbn: String = isbn
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    match isbn.len() {
+        13 if isbn.bytes().all(|b| b.is_ascii_digit()) => Some(isbn),
+        10 => {
+            let prefix = format!("978{}", &isbn[..9]);
+            let digits: Vec<u32> = prefix.chars().map(|c| c.to_digit(10)).collect::<Option<_>>()?;
+            let sum: u32 = digits.iter().zip([1, 3].iter().cycle()).map(|(d, w)| d * w).sum();
+            Some(format!("{prefix}{}", (10 - sum % 10) % 10))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,55 @@
+use epub::doc::{EpubDoc, NavPoint};
+use std::{collections::HashMap, io::Cursor};
+
+/// A page of an EPUB, along with the title of the chapter it belongs to.
+pub struct Page {
+    pub path: String,
+    pub chapter: Option<String>,
+}
+
+/// Lists the pages of an EPUB in reading order.
+///
+/// A page takes the title of the last table of contents entry pointing at it or at an earlier
+/// page, so chapters split over several files keep their title.
+pub fn read_pages(epub_data: &[u8]) -> Option<Vec<Page>> {
+    let doc = EpubDoc::from_reader(Cursor::new(epub_data)).ok()?;
+
+    let mut titles = HashMap::new();
+    collect_titles(&doc.toc, &mut titles);
+
+    let mut chapter = None;
+    let pages = doc
+        .spine
+        .iter()
+        .filter_map(|item| doc.resources.get(&item.idref)?.path.to_str())
+        .map(|path| {
+            if let Some(title) = titles.get(path) {
+                chapter = Some(title.clone());
+            }
+
+            Page {
+                path: path.to_string(),
+                chapter: chapter.clone(),
+            }
+        })
+        .collect();
+
+    Some(pages)
+}
+
+fn collect_titles(points: &[NavPoint], titles: &mut HashMap<String, String>) {
+    for point in points {
+        let Some(content) = point.content.to_str() else {
+            continue;
+        };
+
+        // A page holding several chapters is given the first of them
+        let path = content.split('#').next().unwrap_or_default();
+        let label = point.label.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !label.is_empty() {
+            titles.entry(path.to_string()).or_insert(label);
+        }
+
+        collect_titles(&point.children, titles);
+    }
+}
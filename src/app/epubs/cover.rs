@@ -0,0 +1,105 @@
+use epub::doc::EpubDoc;
+use std::{
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+};
+use xml::{ParserConfig, reader::XmlEvent};
+
+type Epub<'a> = EpubDoc<Cursor<&'a [u8]>>;
+
+/// Finds the cover image embedded in an EPUB.
+///
+/// The EPUB 3 `cover-image` property is preferred, then the `<meta name="cover">` convention of
+/// EPUB 2 and finally the first image shown on the first page of the spine.
+pub fn extract_cover(epub_data: &[u8]) -> Option<Vec<u8>> {
+    let mut epub = EpubDoc::from_reader(Cursor::new(epub_data)).ok()?;
+    let lookups: [fn(&mut Epub) -> Option<PathBuf>; 3] =
+        [cover_image_path, cover_meta_path, spine_image_path];
+
+    lookups.iter().find_map(|lookup| {
+        let path = lookup(&mut epub)?;
+        epub.get_resource_by_path(path)
+            .filter(|data| image::guess_format(data).is_ok())
+    })
+}
+
+fn cover_image_path(epub: &mut Epub) -> Option<PathBuf> {
+    epub.resources.values().find_map(|resource| {
+        let properties = resource.properties.as_deref()?;
+        properties
+            .split_ascii_whitespace()
+            .any(|p| p == "cover-image")
+            .then(|| resource.path.clone())
+    })
+}
+
+fn cover_meta_path(epub: &mut Epub) -> Option<PathBuf> {
+    let cover = epub.mdata("cover")?.value.clone();
+    if let Some(resource) = epub.resources.get(&cover) {
+        return Some(resource.path.clone());
+    }
+
+    // Some books point at the image itself rather than at its manifest entry
+    let path = resolve(&epub.root_base, &cover);
+    epub.resources
+        .values()
+        .any(|resource| resource.path == path)
+        .then_some(path)
+}
+
+fn spine_image_path(epub: &mut Epub) -> Option<PathBuf> {
+    let idref = epub.spine.first()?.idref.clone();
+    let resource = epub.resources.get(&idref)?;
+    let (page_path, mime) = (resource.path.clone(), resource.mime.clone());
+
+    if mime.starts_with("image/") {
+        return Some(page_path);
+    }
+
+    let page = epub.get_resource_by_path(&page_path)?;
+    let href = first_image_href(&page)?;
+
+    Some(resolve(page_path.parent()?, &href))
+}
+
+fn first_image_href(page: &[u8]) -> Option<String> {
+    let parser = ParserConfig::new()
+        .add_entity("nbsp", "\u{a0}")
+        .create_reader(page);
+
+    for event in parser {
+        let XmlEvent::StartElement { name, attributes, .. } = event.ok()? else {
+            continue;
+        };
+
+        // <img src="..."> in XHTML pages, <image xlink:href="..."> in SVG ones
+        let attribute = match name.local_name.as_str() {
+            "img" => "src",
+            "image" => "href",
+            _ => continue,
+        };
+
+        if let Some(href) = attributes.into_iter().find(|a| a.name.local_name == attribute) {
+            return Some(href.value);
+        }
+    }
+
+    None
+}
+
+fn resolve(base: &Path, href: &str) -> PathBuf {
+    let href = href.split(['#', '?']).next().unwrap_or_default();
+    let mut path = PathBuf::new();
+
+    for component in base.join(href).components() {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::Normal(part) => path.push(part),
+            _ => {}
+        }
+    }
+
+    path
+}
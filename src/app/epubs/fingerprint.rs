@@ -0,0 +1,85 @@
+use epub::doc::EpubDoc;
+use std::io::Cursor;
+
+const SHINGLE_SIZE: usize = 3;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Computes a simhash of the text in the spine of an EPUB.
+///
+/// Markup, punctuation and case are ignored, so re-exports and lightly edited copies of the same text
+/// end up with fingerprints that only differ in a few bits.
+pub fn fingerprint(epub_data: &[u8]) -> Option<i64> {
+    let mut epub = EpubDoc::from_reader(Cursor::new(epub_data)).ok()?;
+    let idrefs: Vec<String> = epub.spine.iter().map(|item| item.idref.clone()).collect();
+
+    let mut words = Vec::new();
+    for idref in idrefs {
+        if let Some((page, _)) = epub.get_resource_str(&idref) {
+            words.extend(page_words(&page));
+        }
+    }
+
+    if words.len() < SHINGLE_SIZE {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(SHINGLE_SIZE) {
+        let hash = fnv1a(shingle);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+
+    let simhash = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |hash, (bit, _)| hash | 1 << bit);
+
+    Some(simhash.cast_signed())
+}
+
+/// Number of bits that differ between two fingerprints.
+pub fn distance(a: i64, b: i64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn page_words(page: &str) -> Vec<String> {
+    let body = page.find("<body").map_or(page, |start| &page[start..]);
+    let mut text = String::with_capacity(body.len());
+    let (mut in_tag, mut in_entity) = (false, false);
+
+    // Tags and entities are replaced with spaces so words on either side of them stay apart
+    for c in body.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            '&' if !in_tag => in_entity = true,
+            ';' | ' ' | '\n' if in_entity => {
+                in_entity = false;
+                text.push(' ');
+            }
+            _ if !in_tag && !in_entity => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn fnv1a(words: &[String]) -> u64 {
+    words
+        .iter()
+        .flat_map(|word| word.bytes().chain([b' ']))
+        .fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
@@ -1,3 +1,8 @@
+pub mod chapters;
+mod cover;
+mod fingerprint;
 mod models;
+mod opf;
 pub mod repository;
 pub mod service;
+pub mod spans;
@@ -0,0 +1,471 @@
+use crate::app::metadata::models::Metadata;
+use std::{
+    collections::HashSet,
+    error::Error,
+    io::{Cursor, Read, Write},
+};
+use xml::{
+    EmitterConfig, EventWriter, ParserConfig, attribute::OwnedAttribute, name::OwnedName,
+    reader::XmlEvent as ReaderEvent, writer::XmlEvent as WriterEvent,
+};
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+type OpfResult<T> = Result<T, Box<dyn Error>>;
+
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const COVER_ID: &str = "prosa-cover";
+
+struct Cover<'a> {
+    data: &'a [u8],
+    media_type: &'static str,
+    path: String,
+    href: String,
+}
+
+#[derive(Default)]
+struct PackageInfo {
+    version: String,
+    unique_identifier: Option<String>,
+    cover_id: Option<String>,
+    cover_href: Option<String>,
+    isbn_identifiers: HashSet<usize>,
+}
+
+/// Rewrites the OPF of an EPUB so it carries the given metadata and cover.
+pub fn embed_metadata(
+    epub_data: &[u8],
+    metadata: Option<&Metadata>,
+    cover: Option<&[u8]>,
+) -> OpfResult<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(epub_data))?;
+    let opf_path = find_opf_path(&mut archive)?;
+    let opf = read_entry(&mut archive, &opf_path)?;
+    let package = inspect_package(&opf)?;
+
+    // The book's own cover entry is replaced when it has one, otherwise a new one is added next to the OPF
+    let cover = cover.and_then(|data| {
+        let format = image::guess_format(data).ok()?;
+        let href = package
+            .cover_href
+            .clone()
+            .unwrap_or_else(|| format!("{COVER_ID}.{}", format.extensions_str()[0]));
+        let path = resolve_href(&opf_path, &href);
+
+        Some(Cover {
+            data,
+            media_type: format.to_mime_type(),
+            path,
+            href,
+        })
+    });
+
+    let opf = rewrite_opf(
+        &opf,
+        &package,
+        metadata.unwrap_or(&Metadata::default()),
+        cover.as_ref(),
+    )?;
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut cover_written = false;
+
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+
+        if file.name() == opf_path {
+            writer.start_file(&opf_path, options)?;
+            writer.write_all(&opf)?;
+        } else if let Some(cover) = &cover
+            && file.name() == cover.path
+        {
+            writer.start_file(&cover.path, options)?;
+            writer.write_all(cover.data)?;
+            cover_written = true;
+        } else {
+            writer.raw_copy_file(file)?;
+        }
+    }
+
+    if let Some(cover) = &cover
+        && !cover_written
+    {
+        writer.start_file(&cover.path, options)?;
+        writer.write_all(cover.data)?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Turns an href of the OPF into the name of the entry it points to in the archive, since hrefs are
+/// relative to the OPF and percent-encoded.
+fn resolve_href(opf_path: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or_default());
+    let mut parts: Vec<&str> = match opf_path.rsplit_once('/') {
+        Some((dir, _)) if !href.starts_with('/') => dir.split('/').collect(),
+        _ => Vec::new(),
+    };
+
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).unwrap_or_else(|_| text.to_string())
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> OpfResult<Vec<u8>> {
+    let mut entry = archive.by_name(name)?;
+    let mut buffer = Vec::new();
+    entry.read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+fn find_opf_path(archive: &mut ZipArchive<Cursor<&[u8]>>) -> OpfResult<String> {
+    let container = read_entry(archive, CONTAINER_PATH)?;
+
+    for event in ParserConfig::new().create_reader(container.as_slice()) {
+        if let ReaderEvent::StartElement { name, attributes, .. } = event?
+            && name.local_name == "rootfile"
+            && let Some(path) = attribute(&attributes, "full-path")
+        {
+            return Ok(path.to_string());
+        }
+    }
+
+    Err("The container does not reference a package document".into())
+}
+
+fn inspect_package(opf: &[u8]) -> OpfResult<PackageInfo> {
+    let mut package = PackageInfo::default();
+    let mut cover_meta = None;
+    let mut identifier: Option<(usize, bool, bool)> = None;
+    let mut identifiers = 0;
+    let mut text = String::new();
+
+    for event in ParserConfig::new().create_reader(opf) {
+        match event? {
+            ReaderEvent::StartElement { name, attributes, .. } => match name.local_name.as_str() {
+                "package" => {
+                    package.version = attribute(&attributes, "version").unwrap_or_default().to_string();
+                    package.unique_identifier =
+                        attribute(&attributes, "unique-identifier").map(ToString::to_string);
+                }
+                "identifier" if is_dc(&name) => {
+                    // The package's unique identifier must stay, even when it is an ISBN
+                    let id = attribute(&attributes, "id");
+                    let is_unique = id.is_some() && id == package.unique_identifier.as_deref();
+                    let scheme = attribute(&attributes, "scheme").unwrap_or_default();
+
+                    identifier = Some((identifiers, is_unique, scheme.eq_ignore_ascii_case("isbn")));
+                    identifiers += 1;
+                    text.clear();
+                }
+                "meta" if attribute(&attributes, "name") == Some("cover") => {
+                    cover_meta = attribute(&attributes, "content").map(ToString::to_string);
+                }
+                "item" => {
+                    let id = attribute(&attributes, "id");
+                    let properties = attribute(&attributes, "properties").unwrap_or_default();
+                    let is_cover = properties.split_whitespace().any(|p| p == "cover-image");
+
+                    if is_cover || (id.is_some() && id == cover_meta.as_deref() && package.cover_id.is_none())
+                    {
+                        package.cover_id = id.map(ToString::to_string);
+                        package.cover_href = attribute(&attributes, "href").map(ToString::to_string);
+                    }
+                }
+                _ => (),
+            },
+            ReaderEvent::Characters(data) if identifier.is_some() => text.push_str(&data),
+            ReaderEvent::EndElement { name } if is_dc(&name) && name.local_name == "identifier" => {
+                let Some((index, is_unique, is_isbn_scheme)) = identifier.take() else {
+                    continue;
+                };
+
+                if !is_unique && (is_isbn_scheme || text.trim().to_ascii_lowercase().starts_with("urn:isbn:"))
+                {
+                    package.isbn_identifiers.insert(index);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(package)
+}
+
+fn rewrite_opf(
+    opf: &[u8],
+    package: &PackageInfo,
+    metadata: &Metadata,
+    cover: Option<&Cover>,
+) -> OpfResult<Vec<u8>> {
+    let reader = ParserConfig::new().ignore_comments(false).create_reader(opf);
+    let mut writer = EmitterConfig::new().create_writer(Vec::new());
+
+    let authors: Vec<&str> = metadata
+        .contributors
+        .iter()
+        .flatten()
+        .filter(|c| c.role.eq_ignore_ascii_case("author"))
+        .map(|c| c.name.as_str())
+        .collect();
+
+    // Extracted identifiers are not always ISBNs, and those are left out
+    let isbn = metadata.isbn.as_deref().filter(|i| is_isbn(i));
+
+    let mut depth = 0;
+    let mut metadata_depth = None;
+    let mut skip_depth = None;
+    let mut skipped_last = false;
+    let mut removed_ids: HashSet<String> = HashSet::new();
+    let mut identifiers = 0;
+
+    for event in reader {
+        let event = event?;
+
+        // Indentation of removed elements would otherwise leave blank lines behind
+        if skipped_last && matches!(event, ReaderEvent::Whitespace(_)) {
+            continue;
+        }
+        skipped_last = false;
+
+        match &event {
+            ReaderEvent::StartElement { name, attributes, .. } => {
+                depth += 1;
+                if skip_depth.is_some() {
+                    continue;
+                }
+
+                if name.local_name == "metadata" && metadata_depth.is_none() {
+                    metadata_depth = Some(depth);
+                }
+
+                if metadata_depth.is_some_and(|d| d + 1 == depth) {
+                    let identifier = (is_dc(name) && name.local_name == "identifier").then(|| {
+                        identifiers += 1;
+                        identifiers - 1
+                    });
+
+                    let replaced = is_replaced(name, attributes, metadata, &authors, cover.is_some())
+                        || identifier
+                            .is_some_and(|i| isbn.is_some() && package.isbn_identifiers.contains(&i))
+                        || attribute(attributes, "refines")
+                            .and_then(|r| r.strip_prefix('#'))
+                            .is_some_and(|r| removed_ids.contains(r));
+
+                    if replaced {
+                        if let Some(id) = attribute(attributes, "id") {
+                            removed_ids.insert(id.to_string());
+                        }
+                        skip_depth = Some(depth);
+                        continue;
+                    }
+                }
+
+                if let Some(cover) = cover
+                    && name.local_name == "item"
+                    && attribute(attributes, "id").is_some_and(|id| Some(id) == package.cover_id.as_deref())
+                {
+                    let attributes: Vec<OwnedAttribute> = attributes
+                        .iter()
+                        .map(|a| match a.name.local_name.as_str() {
+                            "media-type" => OwnedAttribute::new(a.name.clone(), cover.media_type),
+                            _ => a.clone(),
+                        })
+                        .collect();
+
+                    let mut element = WriterEvent::start_element(name.borrow());
+                    for a in &attributes {
+                        element = element.attr(a.name.borrow(), &a.value);
+                    }
+                    writer.write(element)?;
+                    continue;
+                }
+            }
+            ReaderEvent::EndElement { name } => {
+                depth -= 1;
+                if let Some(skipped) = skip_depth {
+                    if skipped == depth + 1 {
+                        skip_depth = None;
+                        skipped_last = true;
+                    }
+                    continue;
+                }
+
+                if metadata_depth == Some(depth + 1) && name.local_name == "metadata" {
+                    write_metadata(&mut writer, metadata, &authors, isbn, package, cover)?;
+                    metadata_depth = None;
+                }
+
+                if name.local_name == "manifest"
+                    && let Some(cover) = cover
+                    && package.cover_id.is_none()
+                {
+                    let mut item = WriterEvent::start_element("item")
+                        .attr("id", COVER_ID)
+                        .attr("href", &cover.href)
+                        .attr("media-type", cover.media_type);
+                    if package.version.starts_with('3') {
+                        item = item.attr("properties", "cover-image");
+                    }
+                    writer.write(item)?;
+                    writer.write(WriterEvent::end_element())?;
+                }
+            }
+            ReaderEvent::EndDocument => break,
+            _ if skip_depth.is_some() => continue,
+            _ => (),
+        }
+
+        if let Some(event) = event.as_writer_event() {
+            writer.write(event)?;
+        }
+    }
+
+    Ok(writer.into_inner())
+}
+
+fn is_replaced(
+    name: &OwnedName,
+    attributes: &[OwnedAttribute],
+    metadata: &Metadata,
+    authors: &[&str],
+    has_cover: bool,
+) -> bool {
+    if is_dc(name) {
+        return match name.local_name.as_str() {
+            "title" => metadata.title.is_some(),
+            "creator" => !authors.is_empty(),
+            "subject" => metadata.genres.as_ref().is_some_and(|g| !g.is_empty()),
+            "description" => metadata.description.is_some(),
+            _ => false,
+        };
+    }
+
+    if name.local_name != "meta" {
+        return false;
+    }
+
+    match attribute(attributes, "name").or(attribute(attributes, "property")) {
+        Some("calibre:series" | "calibre:series_index" | "belongs-to-collection") => {
+            metadata.series.is_some()
+        }
+        Some("cover") => has_cover,
+        _ => false,
+    }
+}
+
+fn write_metadata<W: Write>(
+    writer: &mut EventWriter<W>,
+    metadata: &Metadata,
+    authors: &[&str],
+    isbn: Option<&str>,
+    package: &PackageInfo,
+    cover: Option<&Cover>,
+) -> OpfResult<()> {
+    let dc_element = |writer: &mut EventWriter<W>, name: &str, value: &str| -> OpfResult<()> {
+        writer.write(WriterEvent::start_element(("dc", name)).ns("dc", DC_NAMESPACE))?;
+        writer.write(WriterEvent::characters(value))?;
+        writer.write(WriterEvent::end_element())?;
+        Ok(())
+    };
+
+    if let Some(title) = &metadata.title {
+        dc_element(writer, "title", title)?;
+    }
+
+    for author in authors {
+        dc_element(writer, "creator", author)?;
+    }
+
+    for genre in metadata.genres.iter().flatten() {
+        dc_element(writer, "subject", genre)?;
+    }
+
+    if let Some(description) = &metadata.description {
+        dc_element(writer, "description", description)?;
+    }
+
+    if let Some(isbn) = isbn {
+        dc_element(writer, "identifier", &format!("urn:isbn:{isbn}"))?;
+    }
+
+    if let Some(series) = &metadata.series {
+        let number = series.number.to_string();
+        writer.write(
+            WriterEvent::start_element("meta")
+                .attr("name", "calibre:series")
+                .attr("content", &series.title),
+        )?;
+        writer.write(WriterEvent::end_element())?;
+        writer.write(
+            WriterEvent::start_element("meta")
+                .attr("name", "calibre:series_index")
+                .attr("content", &number),
+        )?;
+        writer.write(WriterEvent::end_element())?;
+    }
+
+    if cover.is_some() {
+        let cover_id = package.cover_id.as_deref().unwrap_or(COVER_ID);
+        writer.write(
+            WriterEvent::start_element("meta")
+                .attr("name", "cover")
+                .attr("content", cover_id),
+        )?;
+        writer.write(WriterEvent::end_element())?;
+    }
+
+    Ok(())
+}
+
+fn is_isbn(isbn: &str) -> bool {
+    let digits: Vec<char> = isbn.chars().filter(|c| *c != '-' && *c != ' ').collect();
+    let (last, rest) = digits.split_last().unzip();
+
+    matches!(digits.len(), 10 | 13)
+        && rest.is_some_and(|r| r.iter().all(char::is_ascii_digit))
+        && last.is_some_and(|l| l.is_ascii_digit() || (digits.len() == 10 && l.eq_ignore_ascii_case(&'X')))
+}
+
+fn is_dc(name: &OwnedName) -> bool {
+    name.namespace.as_deref() == Some(DC_NAMESPACE)
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
@@ -1,15 +1,16 @@
 use super::models::EpubError;
 use crate::DB_POOL;
 
-pub async fn add_epub(epub_id: &str, hash: &str) {
+pub async fn add_epub(epub_id: &str, hash: &str, fingerprint: Option<i64>) {
     sqlx::query(
         r"
-        INSERT INTO epubs (epub_id, hash)
-        VALUES ($1, $2)
+        INSERT INTO epubs (epub_id, hash, fingerprint)
+        VALUES ($1, $2, $3)
         ",
     )
     .bind(epub_id)
     .bind(hash)
+    .bind(fingerprint)
     .execute(DB_POOL.get().expect("Failed to get database pool"))
     .await
     .expect("Failed to add epub");
@@ -47,3 +48,18 @@ pub async fn get_epub_by_hash(hash: &str) -> Option<String> {
     .await
     .expect("Failed to get epub by hash")
 }
+
+pub async fn set_fingerprint(epub_id: &str, fingerprint: Option<i64>) {
+    sqlx::query(
+        r"
+        UPDATE epubs
+        SET fingerprint = $1
+        WHERE epub_id = $2
+        ",
+    )
+    .bind(fingerprint)
+    .bind(epub_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to set epub fingerprint");
+}
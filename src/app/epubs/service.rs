@@ -1,16 +1,27 @@
-use super::models::EpubError;
+use super::{
+    chapters::{self, Page},
+    cover, fingerprint,
+    models::EpubError,
+    opf,
+    spans::KoboSpans,
+};
 use crate::{
     CONFIG,
-    app::{epubs::repository, server::LOCKS},
+    app::{covers, epubs::repository, metadata::models::Metadata, server::LOCKS},
+};
+use base64::{
+    Engine,
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
 };
-use base64::{Engine, prelude::BASE64_STANDARD};
 use epub::doc::EpubDoc;
+use log::warn;
 use sha2::{Digest, Sha256};
 use std::io::Cursor;
 use tokio::{
     fs::{self, File, remove_file},
     io::{AsyncReadExt, AsyncWriteExt},
     process::Command,
+    task::spawn_blocking,
 };
 use uuid::Uuid;
 
@@ -40,8 +51,14 @@ pub async fn write_epub(epub_data: &Vec<u8>) -> Result<String, EpubError> {
 
     file.sync_all().await.expect("Failed to sync epub file");
 
+    // Computed before the conversion, which adds markup but leaves the text alone
+    let epub_data = epub_data.clone();
+    let fingerprint = spawn_blocking(move || fingerprint::fingerprint(&epub_data))
+        .await
+        .expect("Failed to fingerprint epub");
+
     convert_to_kepub(&epub_file).await;
-    repository::add_epub(&epub_id, &hash).await;
+    repository::add_epub(&epub_id, &hash, fingerprint).await;
 
     Ok(epub_id)
 }
@@ -66,6 +83,117 @@ pub async fn read_epub(epub_id: &str) -> Result<Vec<u8>, EpubError> {
     Ok(buffer)
 }
 
+/// Fingerprints a stored EPUB, for those added before fingerprints were recorded.
+pub async fn compute_fingerprint(epub_id: &str) -> Result<Option<i64>, EpubError> {
+    let epub = read_epub(epub_id).await?;
+    let fingerprint = spawn_blocking(move || fingerprint::fingerprint(&epub))
+        .await
+        .expect("Failed to fingerprint epub");
+
+    repository::set_fingerprint(epub_id, fingerprint).await;
+
+    Ok(fingerprint)
+}
+
+pub fn fingerprint_distance(a: i64, b: i64) -> u32 {
+    fingerprint::distance(a, b)
+}
+
+pub async fn read_spans(epub_id: &str) -> Result<KoboSpans, EpubError> {
+    let epub = read_epub(epub_id).await?;
+    spawn_blocking(move || KoboSpans::read(&epub))
+        .await
+        .expect("Failed to read kobo spans")
+        .ok_or(EpubError::InternalError)
+}
+
+/// Reads the kobo spans of a stored EPUB along with its pages in reading order.
+pub async fn read_text(epub_id: &str) -> Result<(KoboSpans, Vec<Page>), EpubError> {
+    let epub = read_epub(epub_id).await?;
+    spawn_blocking(move || Some((KoboSpans::read(&epub)?, chapters::read_pages(&epub)?)))
+        .await
+        .expect("Failed to read book text")
+        .ok_or(EpubError::InternalError)
+}
+
+pub fn extract_cover(epub_data: &[u8]) -> Option<Vec<u8>> {
+    cover::extract_cover(epub_data)
+}
+
+pub async fn read_epub_with_metadata(
+    book_id: &str,
+    epub_id: &str,
+    metadata: Option<Metadata>,
+    cover_id: Option<&str>,
+) -> Result<Vec<u8>, EpubError> {
+    let version = metadata_version(epub_id, metadata.as_ref(), cover_id);
+    let cached_file = format!(
+        "{}/{book_id}.{version}.kepub.epub",
+        CONFIG.book_storage.cache_path
+    );
+
+    if let Ok(epub) = fs::read(&cached_file).await {
+        return Ok(epub);
+    }
+
+    let epub = read_epub(epub_id).await?;
+    let cover = match cover_id {
+        Some(cover_id) => covers::service::read_cover(cover_id).await.ok(),
+        None => None,
+    };
+
+    let (epub, rewritten) = spawn_blocking(move || {
+        let rewritten =
+            opf::embed_metadata(&epub, metadata.as_ref(), cover.as_deref()).map_err(|e| e.to_string());
+        (epub, rewritten)
+    })
+    .await
+    .expect("Failed to embed metadata");
+
+    // A book that cannot be rewritten is still served as it was uploaded
+    let epub = match rewritten {
+        Ok(rewritten) => rewritten,
+        Err(e) => {
+            warn!("Failed to embed metadata into book {book_id}: {e}");
+            return Ok(epub);
+        }
+    };
+
+    // Older versions of the book are stale by now
+    delete_cached_epubs(book_id).await;
+
+    let temp_file = format!("{cached_file}.{}", Uuid::new_v4());
+    if fs::write(&temp_file, &epub).await.is_ok() && fs::rename(&temp_file, &cached_file).await.is_err() {
+        let _ = remove_file(&temp_file).await;
+    }
+
+    Ok(epub)
+}
+
+pub async fn delete_cached_epubs(book_id: &str) {
+    let Ok(mut entries) = fs::read_dir(&CONFIG.book_storage.cache_path).await else {
+        return;
+    };
+
+    let prefix = format!("{book_id}.");
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            let _ = remove_file(entry.path()).await;
+        }
+    }
+}
+
+fn metadata_version(epub_id: &str, metadata: Option<&Metadata>, cover_id: Option<&str>) -> String {
+    let metadata = metadata.map(|m| serde_json::to_string(m).expect("Failed to serialize metadata"));
+
+    let mut hasher = Sha256::new();
+    hasher.update(epub_id);
+    hasher.update(metadata.unwrap_or_default());
+    hasher.update(cover_id.unwrap_or_default());
+
+    BASE64_URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
 pub async fn delete_epub(epub_id: &str) -> Result<(), EpubError> {
     let epub_file = format!("{}/{}.kepub.epub", CONFIG.book_storage.epub_path, epub_id);
     remove_file(epub_file).await?;
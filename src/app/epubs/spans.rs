@@ -0,0 +1,245 @@
+use epub::doc::EpubDoc;
+use regex::Regex;
+use std::{collections::HashMap, io::Cursor};
+
+/// Where a text is in a kepub: its page, along with the span and character offset it starts at
+/// and the span and character offset it ends before.
+pub struct TextPosition {
+    pub source: String,
+    pub start: (String, usize),
+    pub end: (String, usize),
+}
+
+/// The text of every kobo span in a kepub, in reading order within each page.
+///
+/// Annotations and reading locations point at these spans, so comparing the spans of two files
+/// of the same book tells where that text ended up.
+pub struct KoboSpans {
+    pages: HashMap<String, Vec<(String, String)>>,
+}
+
+impl KoboSpans {
+    pub fn read(kepub_data: &[u8]) -> Option<Self> {
+        let mut doc = EpubDoc::from_reader(Cursor::new(kepub_data)).ok()?;
+
+        let paths: Vec<String> = doc
+            .resources
+            .values()
+            .filter(|r| r.mime == "application/xhtml+xml")
+            .filter_map(|r| r.path.to_str().map(ToString::to_string))
+            .collect();
+
+        let mut pages = HashMap::new();
+        for path in paths {
+            let Some(text) = doc.get_resource_str_by_path(&path) else {
+                continue;
+            };
+
+            pages.insert(path, read_spans(&text));
+        }
+
+        Some(Self { pages })
+    }
+
+    /// Reads the spans of a single page of a kepub.
+    pub fn read_page(source: &str, text: &str) -> Self {
+        let pages = HashMap::from([(source.to_string(), read_spans(text))]);
+        Self { pages }
+    }
+
+    pub fn text(&self, source: &str, tag: &str) -> Option<&str> {
+        self.pages
+            .get(source)?
+            .iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, text)| text.as_str())
+    }
+
+    /// Where a span falls in its page, for sorting positions in reading order.
+    pub fn position(&self, source: &str, tag: &str) -> Option<usize> {
+        self.pages.get(source)?.iter().position(|(t, _)| t == tag)
+    }
+
+    /// The text between two positions of a page, each given as a span and a character offset in it.
+    pub fn text_between(
+        &self,
+        source: &str,
+        (start_tag, start_char): (&str, usize),
+        (end_tag, end_char): (&str, usize),
+    ) -> Option<String> {
+        let spans = self.pages.get(source)?;
+        let start = spans.iter().position(|(t, _)| t == start_tag)?;
+        let end = start + spans[start..].iter().position(|(t, _)| t == end_tag)?;
+
+        let mut text = String::new();
+        for (i, (_, span_text)) in spans[start..=end].iter().enumerate() {
+            let chars: Vec<char> = decode_entities(span_text).chars().collect();
+            let from = if i == 0 { start_char.min(chars.len()) } else { 0 };
+            let to = if start + i == end {
+                end_char.min(chars.len())
+            } else {
+                chars.len()
+            };
+            text.extend(chars.get(from..to).unwrap_or_default());
+        }
+
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Finds where a quoted text is. Whitespace and the style of quotes are ignored, since readers
+    /// rarely copy them as they are in the book.
+    pub fn locate(&self, text: &str) -> Option<TextPosition> {
+        let quote: Vec<char> = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(fold_quote)
+            .collect();
+        if quote.is_empty() {
+            return None;
+        }
+
+        let mut sources: Vec<&String> = self.pages.keys().collect();
+        sources.sort();
+
+        sources.into_iter().find_map(|source| {
+            let spans = &self.pages[source];
+            let lengths: Vec<usize> = spans
+                .iter()
+                .map(|(_, t)| decode_entities(t).chars().count())
+                .collect();
+
+            // The text of the page, along with the span and offset each character comes from
+            let (page, origins): (Vec<char>, Vec<(usize, usize)>) = spans
+                .iter()
+                .enumerate()
+                .flat_map(|(i, (_, span_text))| {
+                    decode_entities(span_text)
+                        .chars()
+                        .enumerate()
+                        .filter(|(_, c)| !c.is_whitespace())
+                        .map(|(j, c)| (fold_quote(c), (i, j)))
+                        .collect::<Vec<_>>()
+                })
+                .unzip();
+
+            let start = page.windows(quote.len()).position(|w| w == quote)?;
+            let (start_span, start_char) = origins[start];
+            let (mut end_span, mut end_char) = origins[start + quote.len() - 1];
+            end_char += 1;
+
+            // A position past the end of a span is the start of the next one. The last span of a
+            // page has none, so the quote loses its last character.
+            if end_char == lengths[end_span] {
+                if end_span + 1 < spans.len() {
+                    end_span += 1;
+                    end_char = 0;
+                } else {
+                    end_char -= 1;
+                }
+            }
+
+            Some(TextPosition {
+                source: source.clone(),
+                start: (spans[start_span].0.clone(), start_char),
+                end: (spans[end_span].0.clone(), end_char),
+            })
+        })
+    }
+
+    /// Finds the first span holding the given text, looking in the given page before the others.
+    pub fn find(&self, source: &str, text: &str) -> Option<(String, String)> {
+        self.find_range(source, text, text)
+            .map(|(source, tag, _)| (source, tag))
+    }
+
+    /// Finds the first pair of spans, in reading order, holding the given start and end text.
+    /// Both spans must be in the same page, which is looked for in the given page before the others.
+    pub fn find_range(
+        &self,
+        source: &str,
+        start_text: &str,
+        end_text: &str,
+    ) -> Option<(String, String, String)> {
+        // Blank spans are everywhere, so they say nothing about where the text went
+        if start_text.trim().is_empty() || end_text.trim().is_empty() {
+            return None;
+        }
+
+        let mut others: Vec<&String> = self.pages.keys().filter(|s| *s != source).collect();
+        others.sort();
+
+        let mut sources = self
+            .pages
+            .get_key_value(source)
+            .map(|(s, _)| s)
+            .into_iter()
+            .chain(others);
+
+        sources.find_map(|page_source| {
+            let spans = &self.pages[page_source];
+            spans.iter().enumerate().find_map(|(i, (start_tag, text))| {
+                if text != start_text {
+                    return None;
+                }
+
+                let (end_tag, _) = spans[i..].iter().find(|(_, text)| text == end_text)?;
+                Some((page_source.clone(), start_tag.clone(), end_tag.clone()))
+            })
+        })
+    }
+}
+
+fn read_spans(text: &str) -> Vec<(String, String)> {
+    let span_pattern = Regex::new(r#"<span class="koboSpan" id="(kobo\.[^"]+)">([^<]*)</span>"#).unwrap();
+
+    span_pattern
+        .captures_iter(text)
+        .map(|cap| (cap[1].to_string(), cap[2].to_string()))
+        .collect()
+}
+
+fn fold_quote(c: char) -> char {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' => '\'',
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' => '"',
+        _ => c,
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let character = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => name
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| name.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        });
+
+        if let (Some(c), Some((_, end))) = (character, entity) {
+            decoded.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
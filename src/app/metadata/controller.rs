@@ -1,18 +1,31 @@
 use crate::app::authentication::models::AuthToken;
-use crate::app::core::metadata_fetcher::MetadataFetcherRequest;
+use crate::app::covers::models::CoverError;
 use crate::app::error::ProsaError;
-use crate::app::metadata::models::{Metadata, MetadataError, MetadataFetchRequest};
+use crate::app::metadata::models::{
+    ApplyCandidateRequest, CandidateStatus, JobStatus, METADATA_FIELDS, Metadata, MetadataCandidate,
+    MetadataError, MetadataFetchRequest, MetadataJob, MetadataJobUpdateRequest, MetadataLocksRequest,
+    MetadataResponse, USER_SOURCE,
+};
 use crate::app::metadata::service;
 use crate::app::server::{LOCKS, METADATA_FETCHER};
 use crate::app::sync::models::{ChangeLogAction, ChangeLogEntityType};
-use crate::app::users::models::{PreferencesError, VALID_PROVIDERS};
-use crate::app::{books, sync, users};
+use crate::app::users::models::PreferencesError;
+use crate::app::{books, covers, sync, users};
 use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::{Extension, Json};
 use std::collections::HashMap;
 
-pub async fn get_metadata_handler(Path(book_id): Path<String>) -> Result<Json<Metadata>, ProsaError> {
+pub async fn get_metadata_handler(
+    Path(book_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<MetadataResponse>, ProsaError> {
+    let include_provenance = match params.get("provenance").map(|p| p.parse::<bool>()) {
+        Some(Ok(p)) => p,
+        None => false,
+        _ => return Err(MetadataError::InvalidProvenanceFlag.into()),
+    };
+
     let lock = LOCKS.get_book_lock(&book_id).await;
     let _guard = lock.read().await;
 
@@ -23,7 +36,13 @@ pub async fn get_metadata_handler(Path(book_id): Path<String>) -> Result<Json<Me
     };
 
     let metadata = service::get_metadata(&metadata_id).await?;
-    Ok(Json(metadata))
+    let provenance = if include_provenance {
+        Some(service::get_provenance(&book_id).await?)
+    } else {
+        None
+    };
+
+    Ok(Json(MetadataResponse { metadata, provenance }))
 }
 
 pub async fn add_metadata_handler(
@@ -35,6 +54,7 @@ pub async fn add_metadata_handler(
     let _guard = lock.write().await;
 
     let mut book = books::service::get_book(&book_id).await?;
+    let fields = metadata.fields();
 
     let metadata_id = match book.metadata_id {
         None => service::add_metadata(metadata).await?,
@@ -43,6 +63,8 @@ pub async fn add_metadata_handler(
 
     book.metadata_id = Some(metadata_id);
     books::service::update_book(&book_id, &book).await?;
+    service::set_provenance(&book_id, &fields, USER_SOURCE).await?;
+    covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
 
     sync::service::log_change(
         &book_id,
@@ -70,6 +92,8 @@ pub async fn delete_metadata_handler(
     };
 
     service::delete_metadata(&metadata_id).await?;
+    service::clear_provenance(&book_id, &METADATA_FIELDS).await?;
+    covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
 
     sync::service::log_change(
         &book_id,
@@ -97,7 +121,10 @@ pub async fn patch_metadata_handler(
         return Err(MetadataError::MetadataNotFound.into());
     };
 
+    let fields = metadata.fields();
     service::patch_metadata(&metadata_id, metadata).await?;
+    service::set_provenance(&book_id, &fields, USER_SOURCE).await?;
+    covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
 
     sync::service::log_change(
         &book_id,
@@ -125,7 +152,11 @@ pub async fn update_metadata_handler(
         return Err(MetadataError::MetadataNotFound.into());
     };
 
+    let (fields, missing_fields) = (metadata.fields(), metadata.missing_fields());
     service::update_metadata(&metadata_id, metadata).await?;
+    service::set_provenance(&book_id, &fields, USER_SOURCE).await?;
+    service::clear_provenance(&book_id, &missing_fields).await?;
+    covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
 
     sync::service::log_change(
         &book_id,
@@ -139,7 +170,190 @@ pub async fn update_metadata_handler(
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub async fn update_metadata_locks_handler(
+    Path(book_id): Path<String>,
+    Json(request): Json<MetadataLocksRequest>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.write().await;
+
+    books::service::get_book(&book_id).await?;
+    service::set_locked_fields(&book_id, request.locked_fields).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_metadata_candidates_handler(
+    Path(book_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Vec<MetadataCandidate>>, ProsaError> {
+    let status = match params.get("status").map(String::as_str) {
+        None | Some("pending") => CandidateStatus::Pending,
+        Some("applied") => CandidateStatus::Applied,
+        Some("rejected") => CandidateStatus::Rejected,
+        _ => return Err(MetadataError::InvalidCandidateStatus.into()),
+    };
+
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.read().await;
+
+    let book = books::service::get_book(&book_id).await?;
+
+    let current = match book.metadata_id {
+        Some(metadata_id) => Some(service::get_metadata(&metadata_id).await?),
+        None => None,
+    };
+
+    let candidates = service::list_candidates(&book_id, status, current.as_ref()).await?;
+    Ok(Json(candidates))
+}
+
+pub async fn get_metadata_candidate_cover_handler(
+    Path((book_id, candidate_id)): Path<(String, String)>,
+) -> Result<Vec<u8>, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.read().await;
+
+    let candidate = service::get_candidate(&book_id, &candidate_id).await?;
+
+    let Some(cover_id) = candidate.cover_id else {
+        return Err(CoverError::CoverNotFound.into());
+    };
+
+    let cover = covers::service::read_cover(&cover_id).await?;
+    Ok(cover)
+}
+
+pub async fn apply_metadata_candidate_handler(
+    Extension(token): Extension<AuthToken>,
+    Path((book_id, candidate_id)): Path<(String, String)>,
+    Json(request): Json<ApplyCandidateRequest>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.write().await;
+
+    let mut book = books::service::get_book(&book_id).await?;
+    let candidate = service::get_candidate(&book_id, &candidate_id).await?;
+    if candidate.status != CandidateStatus::Pending {
+        return Err(MetadataError::InvalidCandidateStatus.into());
+    }
+
+    let mut candidate_metadata = service::parse_candidate_metadata(&candidate).unwrap_or_default();
+
+    let (fields, apply_cover) = match request.fields {
+        None => (candidate_metadata.fields(), request.cover.unwrap_or(true)),
+        Some(fields) => {
+            if !fields.iter().all(|f| METADATA_FIELDS.contains(&f.as_str())) {
+                return Err(MetadataError::InvalidMetadataField.into());
+            }
+            let fields = METADATA_FIELDS
+                .into_iter()
+                .filter(|f| fields.iter().any(|s| s == f))
+                .collect();
+            (fields, request.cover.unwrap_or(false))
+        }
+    };
+
+    let mut metadata = Metadata::default();
+    for field in &fields {
+        metadata.take_field(&mut candidate_metadata, field);
+    }
+
+    let cover_id = candidate.cover_id.clone().filter(|_| apply_cover);
+    if metadata.is_empty() && cover_id.is_none() {
+        return Err(MetadataError::InvalidCandidateSelection.into());
+    }
+
+    let applied_fields = metadata.fields();
+    let metadata_action = match &book.metadata_id {
+        _ if metadata.is_empty() => None,
+        Some(metadata_id) => {
+            service::patch_metadata(metadata_id, metadata).await?;
+            Some(ChangeLogAction::Update)
+        }
+        None => {
+            book.metadata_id = Some(service::add_metadata(metadata).await?);
+            Some(ChangeLogAction::Create)
+        }
+    };
+
+    let old_cover_id = book.cover_id.clone();
+    let cover_action = match (
+        covers::service::without_placeholder(old_cover_id.clone()).await,
+        &cover_id,
+    ) {
+        (_, None) => None,
+        (Some(old), Some(new)) if &old == new => None,
+        (Some(_), Some(_)) => Some(ChangeLogAction::Update),
+        (None, Some(_)) => Some(ChangeLogAction::Create),
+    };
+
+    if cover_action.is_some() {
+        book.cover_id.clone_from(&cover_id);
+    }
+
+    books::service::update_book(&book_id, &book).await?;
+    service::set_provenance(&book_id, &applied_fields, &candidate.provider).await?;
+    service::update_candidate_status(&candidate_id, CandidateStatus::Applied).await?;
+
+    let replaced_cover_id = old_cover_id.filter(|_| cover_action.is_some());
+    for cover_id in replaced_cover_id.into_iter().chain(candidate.cover_id) {
+        if !books::service::cover_is_in_use(&cover_id).await {
+            covers::service::delete_cover(&cover_id).await?;
+        }
+    }
+
+    covers::service::refresh_placeholder(&book_id, &token.session_id).await?;
+
+    if let Some(action) = metadata_action {
+        sync::service::log_change(
+            &book_id,
+            ChangeLogEntityType::BookMetadata,
+            action,
+            &book.owner_id,
+            &token.session_id,
+        )
+        .await;
+    }
+
+    if let Some(action) = cover_action {
+        sync::service::log_change(
+            &book_id,
+            ChangeLogEntityType::BookCover,
+            action,
+            &book.owner_id,
+            &token.session_id,
+        )
+        .await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn reject_metadata_candidate_handler(
+    Path((book_id, candidate_id)): Path<(String, String)>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.write().await;
+
+    let candidate = service::get_candidate(&book_id, &candidate_id).await?;
+    if candidate.status != CandidateStatus::Pending {
+        return Err(MetadataError::InvalidCandidateStatus.into());
+    }
+
+    service::update_candidate_status(&candidate_id, CandidateStatus::Rejected).await?;
+
+    if let Some(cover_id) = candidate.cover_id
+        && !books::service::cover_is_in_use(&cover_id).await
+    {
+        covers::service::delete_cover(&cover_id).await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn add_metadata_request_handler(
+    Extension(token): Extension<AuthToken>,
     Json(request): Json<MetadataFetchRequest>,
 ) -> Result<StatusCode, ProsaError> {
     let book = books::service::get_book(&request.book_id).await?;
@@ -152,12 +366,17 @@ pub async fn add_metadata_request_handler(
             .expect("Providers should be present"),
     };
 
-    if !providers.iter().all(|p| VALID_PROVIDERS.contains(&p.as_str())) {
+    if !providers.iter().all(|p| users::service::is_valid_provider(p)) {
         return Err(PreferencesError::InvalidMetadataProvider.into());
     }
 
     METADATA_FETCHER
-        .enqueue_request(&book.owner_id, &request.book_id, providers)
+        .enqueue_request(
+            &book.owner_id,
+            &request.book_id,
+            providers,
+            service::bound_priority(request.priority.unwrap_or_default(), &token.role),
+        )
         .await?;
 
     Ok(StatusCode::NO_CONTENT)
@@ -165,9 +384,42 @@ pub async fn add_metadata_request_handler(
 
 pub async fn list_metadata_requests_handler(
     Query(params): Query<HashMap<String, String>>,
-) -> Result<Json<Vec<MetadataFetcherRequest>>, ProsaError> {
-    let user_id = params.get("user_id").map(ToString::to_string);
-    let enqueued = METADATA_FETCHER.get_enqueued(user_id).await;
+) -> Result<Json<Vec<MetadataJob>>, ProsaError> {
+    let statuses = match params.get("status").map(String::as_str) {
+        None => vec![JobStatus::Queued, JobStatus::Running],
+        Some("queued") => vec![JobStatus::Queued],
+        Some("running") => vec![JobStatus::Running],
+        Some("succeeded") => vec![JobStatus::Succeeded],
+        Some("failed") => vec![JobStatus::Failed],
+        Some("cancelled") => vec![JobStatus::Cancelled],
+        _ => return Err(MetadataError::InvalidJobStatus.into()),
+    };
 
-    Ok(Json(enqueued))
+    let user_id = params.get("user_id").map(String::as_str);
+    let book_id = params.get("book_id").map(String::as_str);
+    let jobs = service::list_jobs(user_id, book_id, &statuses).await?;
+
+    Ok(Json(jobs))
+}
+
+pub async fn get_metadata_request_handler(
+    Path(job_id): Path<String>,
+) -> Result<Json<MetadataJob>, ProsaError> {
+    let job = service::get_job(&job_id).await?;
+    Ok(Json(job))
+}
+
+pub async fn update_metadata_request_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(job_id): Path<String>,
+    Json(request): Json<MetadataJobUpdateRequest>,
+) -> Result<StatusCode, ProsaError> {
+    let priority = service::bound_priority(request.priority, &token.role);
+    service::update_job_priority(&job_id, priority).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn cancel_metadata_request_handler(Path(job_id): Path<String>) -> Result<StatusCode, ProsaError> {
+    service::cancel_job(&job_id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
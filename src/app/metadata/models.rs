@@ -1,16 +1,38 @@
-use chrono::{DateTime, Utc, serde::ts_milliseconds_option};
+use chrono::{
+    DateTime, Utc,
+    serde::{ts_milliseconds, ts_milliseconds_option},
+};
 use merge::Merge;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use serde_with::skip_serializing_none;
 use sqlx::{
+    Type,
     error::{DatabaseError, ErrorKind},
     prelude::FromRow,
     sqlite::SqliteError,
 };
+use std::collections::HashMap;
 use strum_macros::{EnumMessage, EnumProperty};
 
 type SqlxError = sqlx::Error;
 
+pub const METADATA_FIELDS: [&str; 11] = [
+    "title",
+    "subtitle",
+    "description",
+    "publisher",
+    "publication_date",
+    "isbn",
+    "contributors",
+    "genres",
+    "series",
+    "page_count",
+    "language",
+];
+
+pub const USER_SOURCE: &str = "user";
+
 #[derive(EnumMessage, EnumProperty, Debug)]
 pub enum MetadataError {
     #[strum(message = "The provided metadata is invalid.")]
@@ -22,12 +44,36 @@ pub enum MetadataError {
     #[strum(message = "This metadata request is already enqueued.")]
     #[strum(props(StatusCode = "409"))]
     MetadataRequestConflict,
+    #[strum(message = "The requested metadata request does not exist or is not accessible.")]
+    #[strum(props(StatusCode = "404"))]
+    MetadataRequestNotFound,
+    #[strum(message = "This metadata request is no longer queued.")]
+    #[strum(props(StatusCode = "409"))]
+    MetadataRequestNotQueued,
     #[strum(message = "The requested metadata does not exist or is not accessible.")]
     #[strum(props(StatusCode = "404"))]
     MetadataNotFound,
     #[strum(message = "This book already has metadata.")]
     #[strum(props(StatusCode = "409"))]
     MetadataConflict,
+    #[strum(message = "The provided metadata field is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidMetadataField,
+    #[strum(message = "The provided provenance flag is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidProvenanceFlag,
+    #[strum(message = "The requested metadata candidate does not exist or is not accessible.")]
+    #[strum(props(StatusCode = "404"))]
+    CandidateNotFound,
+    #[strum(message = "The provided candidate status is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidCandidateStatus,
+    #[strum(message = "The provided candidate selection is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidCandidateSelection,
+    #[strum(message = "The provided metadata request status is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidJobStatus,
     #[strum(message = "Internal error")]
     #[strum(props(StatusCode = "500"))]
     InternalError,
@@ -100,10 +146,232 @@ impl Metadata {
             && self.page_count.is_none()
             && self.language.is_none()
     }
+
+    pub fn has_field(&self, field: &str) -> bool {
+        match field {
+            "title" => self.title.is_some(),
+            "subtitle" => self.subtitle.is_some(),
+            "description" => self.description.is_some(),
+            "publisher" => self.publisher.is_some(),
+            "publication_date" => self.publication_date.is_some(),
+            "isbn" => self.isbn.is_some(),
+            "contributors" => self.contributors.is_some(),
+            "genres" => self.genres.is_some(),
+            "series" => self.series.is_some(),
+            "page_count" => self.page_count.is_some(),
+            "language" => self.language.is_some(),
+            _ => false,
+        }
+    }
+
+    pub fn fields(&self) -> Vec<&'static str> {
+        METADATA_FIELDS
+            .into_iter()
+            .filter(|f| self.has_field(f))
+            .collect()
+    }
+
+    pub fn field_length(&self, field: &str) -> usize {
+        let text_length = |t: &Option<String>| t.as_ref().map_or(0, |t| t.chars().count());
+        match field {
+            "title" => text_length(&self.title),
+            "subtitle" => text_length(&self.subtitle),
+            "description" => text_length(&self.description),
+            "publisher" => text_length(&self.publisher),
+            "isbn" => text_length(&self.isbn),
+            "language" => text_length(&self.language),
+            "contributors" => self
+                .contributors
+                .iter()
+                .flatten()
+                .map(|c| c.name.chars().count())
+                .sum(),
+            "genres" => self.genres.iter().flatten().map(|g| g.chars().count()).sum(),
+            "series" => self.series.as_ref().map_or(0, |s| s.title.chars().count()),
+            _ => 0,
+        }
+    }
+
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        METADATA_FIELDS
+            .into_iter()
+            .filter(|f| !self.has_field(f))
+            .collect()
+    }
+
+    pub fn take_field(&mut self, from: &mut Metadata, field: &str) {
+        match field {
+            "title" => self.title = from.title.take(),
+            "subtitle" => self.subtitle = from.subtitle.take(),
+            "description" => self.description = from.description.take(),
+            "publisher" => self.publisher = from.publisher.take(),
+            "publication_date" => self.publication_date = from.publication_date.take(),
+            "isbn" => self.isbn = from.isbn.take(),
+            "contributors" => self.contributors = from.contributors.take(),
+            "genres" => self.genres = from.genres.take(),
+            "series" => self.series = from.series.take(),
+            "page_count" => self.page_count = from.page_count.take(),
+            "language" => self.language = from.language.take(),
+            _ => (),
+        }
+    }
+
+    pub fn clear_field(&mut self, field: &str) {
+        self.take_field(&mut Metadata::default(), field);
+    }
+}
+
+#[skip_serializing_none]
+#[derive(FromRow, Serialize)]
+pub struct FieldProvenance {
+    #[serde(skip)]
+    pub field: String,
+    pub source: Option<String>,
+    #[serde(with = "ts_milliseconds_option")]
+    pub updated_at: Option<DateTime<Utc>>,
+    pub locked: bool,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct MetadataResponse {
+    #[serde(flatten)]
+    pub metadata: Metadata,
+    pub provenance: Option<HashMap<String, FieldProvenance>>,
+}
+
+#[derive(Deserialize)]
+pub struct MetadataLocksRequest {
+    pub locked_fields: Vec<String>,
 }
 
 #[derive(Deserialize)]
 pub struct MetadataFetchRequest {
     pub book_id: String,
     pub metadata_providers: Option<Vec<String>>,
+    pub priority: Option<i64>,
+}
+
+#[derive(Type, Serialize, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateStatus {
+    Pending,
+    Applied,
+    Rejected,
+}
+
+#[derive(FromRow)]
+pub struct MetadataCandidateEntity {
+    pub candidate_id: String,
+    pub provider: String,
+    pub metadata: Option<String>,
+    pub cover_id: Option<String>,
+    pub status: CandidateStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct FieldDiff {
+    pub current: Option<Value>,
+    pub candidate: Value,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct MetadataCandidate {
+    pub candidate_id: String,
+    pub provider: String,
+    pub status: CandidateStatus,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+    pub metadata: Option<Metadata>,
+    pub has_cover: bool,
+    pub diff: HashMap<String, FieldDiff>,
+}
+
+#[derive(Deserialize)]
+pub struct ApplyCandidateRequest {
+    pub fields: Option<Vec<String>>,
+    pub cover: Option<bool>,
+}
+
+#[derive(Type, Serialize, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(FromRow)]
+pub struct MetadataJobEntity {
+    pub job_id: String,
+    pub user_id: String,
+    pub book_id: String,
+    pub providers: String,
+    pub priority: i64,
+    pub status: JobStatus,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+pub struct RefreshCandidate {
+    pub book_id: String,
+    pub owner_id: String,
+    pub refresh_age: Option<i64>,
+    pub incomplete: bool,
+    pub last_attempt: Option<DateTime<Utc>>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct MetadataJob {
+    pub job_id: String,
+    pub user_id: String,
+    pub book_id: String,
+    pub providers: Vec<String>,
+    pub priority: i64,
+    pub status: JobStatus,
+    pub reason: Option<String>,
+    pub position: Option<i64>,
+    #[serde(with = "ts_milliseconds_option")]
+    pub estimated_start_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds_option")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds_option")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct MetadataJobUpdateRequest {
+    pub priority: i64,
+}
+
+impl From<MetadataJobEntity> for MetadataJob {
+    fn from(job: MetadataJobEntity) -> Self {
+        MetadataJob {
+            job_id: job.job_id,
+            user_id: job.user_id,
+            book_id: job.book_id,
+            providers: serde_json::from_str(&job.providers).expect("Failed to parse job providers"),
+            priority: job.priority,
+            status: job.status,
+            reason: job.reason,
+            position: None,
+            estimated_start_at: None,
+            created_at: job.created_at,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+        }
+    }
 }
@@ -1,5 +1,9 @@
-use super::models::{Contributor, Metadata, MetadataError, Series};
+use super::models::{
+    CandidateStatus, Contributor, FieldProvenance, JobStatus, Metadata, MetadataCandidateEntity,
+    MetadataError, MetadataJobEntity, RefreshCandidate, Series,
+};
 use crate::DB_POOL;
+use chrono::{DateTime, Utc};
 use sqlx::QueryBuilder;
 
 pub async fn get_metadata(metadata_id: &str) -> Result<Metadata, MetadataError> {
@@ -224,3 +228,557 @@ pub async fn update_metadata(metadata_id: &str, metadata: &Metadata) -> Result<(
     tx.commit().await?;
     Ok(())
 }
+
+pub async fn get_provenance(book_id: &str) -> Result<Vec<FieldProvenance>, MetadataError> {
+    let provenance: Vec<FieldProvenance> = sqlx::query_as(
+        r"
+        SELECT field, source, updated_at, locked
+        FROM metadata_fields
+        WHERE book_id = $1
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(provenance)
+}
+
+pub async fn get_locked_fields(book_id: &str) -> Result<Vec<String>, MetadataError> {
+    let fields: Vec<String> = sqlx::query_scalar(
+        r"
+        SELECT field
+        FROM metadata_fields
+        WHERE book_id = $1 AND locked = TRUE
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(fields)
+}
+
+pub async fn set_provenance(book_id: &str, fields: &[&str], source: &str) -> Result<(), MetadataError> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let mut query = QueryBuilder::new("INSERT INTO metadata_fields (book_id, field, source, updated_at)");
+    query.push_values(fields, |mut b, field| {
+        b.push_bind(book_id)
+            .push_bind(*field)
+            .push_bind(source)
+            .push_bind(now);
+    });
+    query.push(
+        " ON CONFLICT(book_id, field) DO UPDATE SET source = excluded.source, updated_at = excluded.updated_at",
+    );
+    query
+        .build()
+        .execute(DB_POOL.get().expect("Failed to get database pool"))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn clear_provenance(book_id: &str, fields: &[&str]) -> Result<(), MetadataError> {
+    let mut tx = DB_POOL
+        .get()
+        .expect("Failed to get database pool")
+        .begin()
+        .await?;
+
+    for field in fields {
+        sqlx::query(
+            r"
+            UPDATE metadata_fields SET
+                source = NULL,
+                updated_at = NULL
+            WHERE book_id = $1 AND field = $2
+            ",
+        )
+        .bind(book_id)
+        .bind(field)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query("DELETE FROM metadata_fields WHERE book_id = ? AND source IS NULL AND locked = FALSE")
+        .bind(book_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn set_locked_fields(book_id: &str, fields: &[String]) -> Result<(), MetadataError> {
+    let mut tx = DB_POOL
+        .get()
+        .expect("Failed to get database pool")
+        .begin()
+        .await?;
+
+    sqlx::query("UPDATE metadata_fields SET locked = FALSE WHERE book_id = ?")
+        .bind(book_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if !fields.is_empty() {
+        let mut query = QueryBuilder::new("INSERT INTO metadata_fields (book_id, field, locked)");
+        query.push_values(fields, |mut b, field| {
+            b.push_bind(book_id).push_bind(field).push_bind(true);
+        });
+        query.push(" ON CONFLICT(book_id, field) DO UPDATE SET locked = excluded.locked");
+        query.build().execute(&mut *tx).await?;
+    }
+
+    sqlx::query("DELETE FROM metadata_fields WHERE book_id = ? AND source IS NULL AND locked = FALSE")
+        .bind(book_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn candidate_exists(book_id: &str, hash: &str) -> bool {
+    let exists: bool = sqlx::query_scalar(
+        r"
+        SELECT EXISTS (
+            SELECT 1
+            FROM metadata_candidates
+            WHERE book_id = $1 AND hash = $2
+        )
+        ",
+    )
+    .bind(book_id)
+    .bind(hash)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to check metadata candidate");
+
+    exists
+}
+
+pub async fn add_candidate(
+    candidate_id: &str,
+    book_id: &str,
+    provider: &str,
+    metadata: Option<String>,
+    cover_id: Option<String>,
+    hash: &str,
+) -> Result<(), MetadataError> {
+    sqlx::query(
+        r"
+        INSERT INTO metadata_candidates (candidate_id, book_id, provider, metadata, cover_id, hash, status, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ",
+    )
+    .bind(candidate_id)
+    .bind(book_id)
+    .bind(provider)
+    .bind(metadata)
+    .bind(cover_id)
+    .bind(hash)
+    .bind(CandidateStatus::Pending)
+    .bind(Utc::now())
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_candidate(
+    book_id: &str,
+    candidate_id: &str,
+) -> Result<MetadataCandidateEntity, MetadataError> {
+    let candidate: Option<MetadataCandidateEntity> = sqlx::query_as(
+        r"
+        SELECT candidate_id, provider, metadata, cover_id, status, created_at
+        FROM metadata_candidates
+        WHERE book_id = $1 AND candidate_id = $2
+        ",
+    )
+    .bind(book_id)
+    .bind(candidate_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    candidate.ok_or(MetadataError::CandidateNotFound)
+}
+
+pub async fn list_candidates(
+    book_id: &str,
+    status: CandidateStatus,
+) -> Result<Vec<MetadataCandidateEntity>, MetadataError> {
+    let candidates: Vec<MetadataCandidateEntity> = sqlx::query_as(
+        r"
+        SELECT candidate_id, provider, metadata, cover_id, status, created_at
+        FROM metadata_candidates
+        WHERE book_id = $1 AND status = $2
+        ORDER BY created_at, rowid
+        ",
+    )
+    .bind(book_id)
+    .bind(status)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(candidates)
+}
+
+pub async fn update_candidate_status(
+    candidate_id: &str,
+    status: CandidateStatus,
+) -> Result<(), MetadataError> {
+    sqlx::query(
+        r"
+        UPDATE metadata_candidates SET
+            status = $2,
+            cover_id = NULL
+        WHERE candidate_id = $1
+        ",
+    )
+    .bind(candidate_id)
+    .bind(status)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_candidate_covers(book_id: &str) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT cover_id
+        FROM metadata_candidates
+        WHERE book_id = $1 AND cover_id IS NOT NULL
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve candidate covers")
+}
+
+pub async fn cover_is_in_use_by_candidates(cover_id: &str) -> bool {
+    let exists: bool = sqlx::query_scalar(
+        r"
+        SELECT EXISTS (
+            SELECT 1
+            FROM metadata_candidates
+            WHERE cover_id = $1
+        )
+        ",
+    )
+    .bind(cover_id)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to check candidate covers");
+
+    exists
+}
+
+pub async fn add_job(
+    job_id: &str,
+    user_id: &str,
+    book_id: &str,
+    providers: &str,
+    priority: i64,
+) -> Result<(), MetadataError> {
+    sqlx::query(
+        r"
+        INSERT INTO metadata_jobs (job_id, user_id, book_id, providers, priority, status, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ",
+    )
+    .bind(job_id)
+    .bind(user_id)
+    .bind(book_id)
+    .bind(providers)
+    .bind(priority)
+    .bind(JobStatus::Queued)
+    .bind(Utc::now())
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn job_is_queued(user_id: &str, book_id: &str, providers: &str) -> bool {
+    let exists: bool = sqlx::query_scalar(
+        r"
+        SELECT EXISTS (
+            SELECT 1
+            FROM metadata_jobs
+            WHERE user_id = $1 AND book_id = $2 AND providers = $3 AND status = $4
+        )
+        ",
+    )
+    .bind(user_id)
+    .bind(book_id)
+    .bind(providers)
+    .bind(JobStatus::Queued)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to check metadata jobs");
+
+    exists
+}
+
+pub async fn get_job(job_id: &str) -> Result<MetadataJobEntity, MetadataError> {
+    let job: Option<MetadataJobEntity> = sqlx::query_as(
+        r"
+        SELECT job_id, user_id, book_id, providers, priority, status, reason, created_at, started_at, finished_at
+        FROM metadata_jobs
+        WHERE job_id = $1
+        ",
+    )
+    .bind(job_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    job.ok_or(MetadataError::MetadataRequestNotFound)
+}
+
+pub async fn claim_next_job() -> Result<Option<MetadataJobEntity>, MetadataError> {
+    let job = sqlx::query_as(
+        r"
+        UPDATE metadata_jobs SET
+            status = $1,
+            started_at = $2
+        WHERE job_id = (
+            SELECT job_id
+            FROM metadata_jobs
+            WHERE status = $3 AND book_id NOT IN (
+                SELECT book_id
+                FROM metadata_jobs
+                WHERE status = $1
+            )
+            ORDER BY priority DESC, created_at, rowid
+            LIMIT 1
+        )
+        RETURNING job_id, user_id, book_id, providers, priority, status, reason, created_at, started_at, finished_at
+        ",
+    )
+    .bind(JobStatus::Running)
+    .bind(Utc::now())
+    .bind(JobStatus::Queued)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(job)
+}
+
+pub async fn finish_job(job_id: &str, status: JobStatus, reason: Option<&str>) -> Result<(), MetadataError> {
+    sqlx::query(
+        r"
+        UPDATE metadata_jobs SET
+            status = $2,
+            reason = $3,
+            finished_at = $4
+        WHERE job_id = $1
+        ",
+    )
+    .bind(job_id)
+    .bind(status)
+    .bind(reason)
+    .bind(Utc::now())
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn cancel_job(job_id: &str) -> Result<bool, MetadataError> {
+    let result = sqlx::query(
+        r"
+        UPDATE metadata_jobs SET
+            status = $2,
+            finished_at = $3
+        WHERE job_id = $1 AND status = $4
+        ",
+    )
+    .bind(job_id)
+    .bind(JobStatus::Cancelled)
+    .bind(Utc::now())
+    .bind(JobStatus::Queued)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn update_job_priority(job_id: &str, priority: i64) -> Result<bool, MetadataError> {
+    let result = sqlx::query(
+        r"
+        UPDATE metadata_jobs SET
+            priority = $2
+        WHERE job_id = $1 AND status = $3
+        ",
+    )
+    .bind(job_id)
+    .bind(priority)
+    .bind(JobStatus::Queued)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn requeue_running_jobs() -> Result<(), MetadataError> {
+    sqlx::query(
+        r"
+        UPDATE metadata_jobs SET
+            status = $1,
+            started_at = NULL
+        WHERE status = $2
+        ",
+    )
+    .bind(JobStatus::Queued)
+    .bind(JobStatus::Running)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_queue() -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT job_id
+        FROM metadata_jobs
+        WHERE status = $1
+        ORDER BY priority DESC, created_at, rowid
+        ",
+    )
+    .bind(JobStatus::Queued)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve metadata queue")
+}
+
+pub async fn get_running_jobs_start() -> Vec<DateTime<Utc>> {
+    sqlx::query_scalar(
+        r"
+        SELECT started_at
+        FROM metadata_jobs
+        WHERE status = $1 AND started_at IS NOT NULL
+        ",
+    )
+    .bind(JobStatus::Running)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve running metadata jobs")
+}
+
+/// Deletes the jobs that finished before the given time, except the latest one of each book, which
+/// the refresh scheduler needs to know when the book was last attempted.
+pub async fn prune_jobs(finished_before: DateTime<Utc>) -> u64 {
+    sqlx::query(
+        r"
+        DELETE FROM metadata_jobs
+        WHERE status IN ($1, $2, $3) AND finished_at < $4
+        AND finished_at < (SELECT MAX(j.finished_at) FROM metadata_jobs j WHERE j.book_id = metadata_jobs.book_id)
+        ",
+    )
+    .bind(JobStatus::Succeeded)
+    .bind(JobStatus::Failed)
+    .bind(JobStatus::Cancelled)
+    .bind(finished_before)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to prune metadata jobs")
+    .rows_affected()
+}
+
+pub async fn get_recent_job_durations(limit: i64) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    sqlx::query_as(
+        r"
+        SELECT started_at, finished_at
+        FROM metadata_jobs
+        WHERE status IN ($1, $2) AND started_at IS NOT NULL AND finished_at IS NOT NULL
+        ORDER BY finished_at DESC
+        LIMIT $3
+        ",
+    )
+    .bind(JobStatus::Succeeded)
+    .bind(JobStatus::Failed)
+    .bind(limit)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve metadata job durations")
+}
+
+pub async fn get_refresh_candidates() -> Vec<RefreshCandidate> {
+    sqlx::query_as(
+        r"
+        SELECT
+            b.book_id,
+            b.owner_id,
+            u.metadata_refresh_age AS refresh_age,
+            (
+                b.metadata_id IS NULL
+                OR b.cover_id IS NULL
+                OR EXISTS (SELECT 1 FROM covers c WHERE c.cover_id = b.cover_id AND c.generated)
+                OR (
+                    m.description IS NULL
+                    AND NOT EXISTS (SELECT 1 FROM metadata_fields f WHERE f.book_id = b.book_id AND f.field = 'description' AND f.locked)
+                )
+                OR (
+                    s.metadata_id IS NULL
+                    AND NOT EXISTS (SELECT 1 FROM metadata_fields f WHERE f.book_id = b.book_id AND f.field = 'series' AND f.locked)
+                )
+            ) AS incomplete,
+            (SELECT MAX(j.finished_at) FROM metadata_jobs j WHERE j.book_id = b.book_id) AS last_attempt
+        FROM books b
+        JOIN users u ON u.user_id = b.owner_id
+        LEFT JOIN metadata m ON m.metadata_id = b.metadata_id
+        LEFT JOIN series s ON s.metadata_id = b.metadata_id
+        WHERE u.automatic_metadata = TRUE AND b.deleted_at IS NULL
+        AND NOT EXISTS (SELECT 1 FROM metadata_jobs j WHERE j.book_id = b.book_id AND j.status IN ($1, $2))
+        ",
+    )
+    .bind(JobStatus::Queued)
+    .bind(JobStatus::Running)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to retrieve metadata refresh candidates")
+}
+
+pub async fn list_jobs(
+    user_id: Option<&str>,
+    book_id: Option<&str>,
+    statuses: &[JobStatus],
+) -> Result<Vec<MetadataJobEntity>, MetadataError> {
+    let mut query = QueryBuilder::new(
+        r"
+        SELECT job_id, user_id, book_id, providers, priority, status, reason, created_at, started_at, finished_at
+        FROM metadata_jobs
+        WHERE status IN (",
+    );
+
+    let mut separated = query.separated(", ");
+    for status in statuses {
+        separated.push_bind(*status);
+    }
+    query.push(")");
+
+    if let Some(user_id) = user_id {
+        query.push(" AND user_id = ").push_bind(user_id);
+    }
+    if let Some(book_id) = book_id {
+        query.push(" AND book_id = ").push_bind(book_id);
+    }
+    query.push(" ORDER BY created_at, rowid");
+
+    let jobs = query
+        .build_query_as()
+        .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+        .await?;
+
+    Ok(jobs)
+}
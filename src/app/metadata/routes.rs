@@ -2,11 +2,17 @@ use crate::app::{
     authentication::middleware::extract_token_middleware,
     authorization::{
         books::{can_delete_book, can_read_book, can_update_book},
-        metadata::{can_add_metadata_request, can_list_metadata_requests},
+        metadata::{
+            can_add_metadata_request, can_list_metadata_requests, can_read_metadata_candidate,
+            can_read_metadata_request, can_update_metadata_candidate, can_update_metadata_request,
+        },
     },
     metadata::controller::{
-        add_metadata_handler, add_metadata_request_handler, delete_metadata_handler, get_metadata_handler,
-        list_metadata_requests_handler, patch_metadata_handler, update_metadata_handler,
+        add_metadata_handler, add_metadata_request_handler, apply_metadata_candidate_handler,
+        cancel_metadata_request_handler, delete_metadata_handler, get_metadata_candidate_cover_handler,
+        get_metadata_handler, get_metadata_request_handler, list_metadata_candidates_handler,
+        list_metadata_requests_handler, patch_metadata_handler, reject_metadata_candidate_handler,
+        update_metadata_handler, update_metadata_locks_handler, update_metadata_request_handler,
     },
 };
 use axum::{
@@ -33,11 +39,35 @@ pub fn get_routes() -> Router {
         .route("/books/{book_id}/metadata", patch(patch_metadata_handler) 
             .route_layer(from_fn(can_update_book))
         )
+        .route("/books/{book_id}/metadata/locks", put(update_metadata_locks_handler) 
+            .route_layer(from_fn(can_update_book))
+        )
+        .route("/books/{book_id}/metadata/candidates", get(list_metadata_candidates_handler) 
+            .route_layer(from_fn(can_read_book))
+        )
+        .route("/books/{book_id}/metadata/candidates/{candidate_id}/cover", get(get_metadata_candidate_cover_handler) 
+            .route_layer(from_fn(can_read_metadata_candidate))
+        )
+        .route("/books/{book_id}/metadata/candidates/{candidate_id}/apply", post(apply_metadata_candidate_handler) 
+            .route_layer(from_fn(can_update_metadata_candidate))
+        )
+        .route("/books/{book_id}/metadata/candidates/{candidate_id}/reject", post(reject_metadata_candidate_handler) 
+            .route_layer(from_fn(can_update_metadata_candidate))
+        )
         .route("/metadata-requests", post(add_metadata_request_handler) 
             .route_layer(from_fn(can_add_metadata_request))
         )
         .route("/metadata-requests", get(list_metadata_requests_handler) 
             .route_layer(from_fn(can_list_metadata_requests))
         )
+        .route("/metadata-requests/{job_id}", get(get_metadata_request_handler) 
+            .route_layer(from_fn(can_read_metadata_request))
+        )
+        .route("/metadata-requests/{job_id}", patch(update_metadata_request_handler) 
+            .route_layer(from_fn(can_update_metadata_request))
+        )
+        .route("/metadata-requests/{job_id}", delete(cancel_metadata_request_handler) 
+            .route_layer(from_fn(can_update_metadata_request))
+        )
         .layer(from_fn(extract_token_middleware))
 }
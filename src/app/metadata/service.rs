@@ -1,8 +1,26 @@
-use super::models::{Metadata, MetadataError};
-use crate::app::{error::ProsaError, metadata::repository};
+use super::models::{
+    CandidateStatus, FieldDiff, FieldProvenance, JobStatus, METADATA_FIELDS, Metadata, MetadataCandidate,
+    MetadataCandidateEntity, MetadataError, MetadataJob, RefreshCandidate,
+};
+use crate::{
+    CONFIG,
+    app::{authentication::models::AuthRole, covers, error::ProsaError, metadata::repository},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{DateTime, TimeDelta, Utc};
+use log::info;
 use merge::Merge;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, time::Duration};
 use uuid::Uuid;
 
+const JOB_DURATION_SAMPLES: i64 = 20;
+
+/// The priorities users can give their requests, so that they cannot get ahead of every other user's.
+const MIN_USER_PRIORITY: i64 = 0;
+const MAX_USER_PRIORITY: i64 = 10;
+
 pub async fn get_metadata(metadata_id: &str) -> Result<Metadata, ProsaError> {
     let metadata = repository::get_metadata(metadata_id).await?;
     Ok(metadata)
@@ -42,3 +60,333 @@ pub async fn update_metadata(metadata_id: &str, metadata: Metadata) -> Result<()
     repository::update_metadata(metadata_id, &metadata).await?;
     Ok(())
 }
+
+pub async fn get_provenance(book_id: &str) -> Result<HashMap<String, FieldProvenance>, ProsaError> {
+    let provenance = repository::get_provenance(book_id)
+        .await?
+        .into_iter()
+        .map(|p| (p.field.clone(), p))
+        .collect();
+
+    Ok(provenance)
+}
+
+pub async fn get_locked_fields(book_id: &str) -> Result<Vec<String>, ProsaError> {
+    let fields = repository::get_locked_fields(book_id).await?;
+    Ok(fields)
+}
+
+pub async fn set_provenance(book_id: &str, fields: &[&str], source: &str) -> Result<(), ProsaError> {
+    repository::set_provenance(book_id, fields, source).await?;
+    Ok(())
+}
+
+pub async fn clear_provenance(book_id: &str, fields: &[&str]) -> Result<(), ProsaError> {
+    repository::clear_provenance(book_id, fields).await?;
+    Ok(())
+}
+
+pub async fn set_locked_fields(book_id: &str, mut fields: Vec<String>) -> Result<(), ProsaError> {
+    if !fields.iter().all(|f| METADATA_FIELDS.contains(&f.as_str())) {
+        return Err(MetadataError::InvalidMetadataField.into());
+    }
+
+    fields.sort();
+    fields.dedup();
+
+    repository::set_locked_fields(book_id, &fields).await?;
+    Ok(())
+}
+
+pub async fn add_candidate(
+    book_id: &str,
+    provider: &str,
+    metadata: Option<Metadata>,
+    cover: Option<Vec<u8>>,
+) -> Result<(), ProsaError> {
+    // Stored metadata lists come back sorted, so candidates are sorted the same way for diffing and hashing
+    let metadata = metadata.map(|mut m| {
+        if let Some(genres) = m.genres.as_mut() {
+            genres.sort();
+        }
+        if let Some(contributors) = m.contributors.as_mut() {
+            contributors.sort_by(|a, b| (&a.role, &a.name).cmp(&(&b.role, &b.name)));
+        }
+        serde_json::to_string(&m).expect("Failed to serialize metadata")
+    });
+
+    let mut hasher = Sha256::new();
+    hasher.update(provider);
+    hasher.update(metadata.as_deref().unwrap_or_default());
+    hasher.update(cover.as_deref().unwrap_or_default());
+    let hash = BASE64_STANDARD.encode(hasher.finalize());
+
+    // Identical candidates are only stored once, so rejected results are not proposed again
+    if repository::candidate_exists(book_id, &hash).await {
+        return Ok(());
+    }
+
+    let cover_id = match cover {
+        Some(cover) => covers::service::write_cover(&cover, false).await.ok(),
+        None => None,
+    };
+
+    if metadata.is_none() && cover_id.is_none() {
+        return Ok(());
+    }
+
+    let candidate_id = Uuid::new_v4().to_string();
+    repository::add_candidate(&candidate_id, book_id, provider, metadata, cover_id, &hash).await?;
+    Ok(())
+}
+
+pub async fn get_candidate(book_id: &str, candidate_id: &str) -> Result<MetadataCandidateEntity, ProsaError> {
+    let candidate = repository::get_candidate(book_id, candidate_id).await?;
+    Ok(candidate)
+}
+
+pub async fn list_candidates(
+    book_id: &str,
+    status: CandidateStatus,
+    current: Option<&Metadata>,
+) -> Result<Vec<MetadataCandidate>, ProsaError> {
+    let current = current.map(|m| serde_json::to_value(m).expect("Failed to serialize metadata"));
+
+    let candidates = repository::list_candidates(book_id, status)
+        .await?
+        .into_iter()
+        .map(|c| {
+            let metadata = parse_candidate_metadata(&c);
+            let diff = match &metadata {
+                Some(m) => diff_metadata(current.as_ref(), m),
+                None => HashMap::new(),
+            };
+
+            MetadataCandidate {
+                candidate_id: c.candidate_id,
+                provider: c.provider,
+                status: c.status,
+                created_at: c.created_at,
+                metadata,
+                has_cover: c.cover_id.is_some(),
+                diff,
+            }
+        })
+        .collect();
+
+    Ok(candidates)
+}
+
+pub async fn update_candidate_status(candidate_id: &str, status: CandidateStatus) -> Result<(), ProsaError> {
+    repository::update_candidate_status(candidate_id, status).await?;
+    Ok(())
+}
+
+pub async fn get_candidate_covers(book_id: &str) -> Vec<String> {
+    repository::get_candidate_covers(book_id).await
+}
+
+pub async fn cover_is_in_use_by_candidates(cover_id: &str) -> bool {
+    repository::cover_is_in_use_by_candidates(cover_id).await
+}
+
+pub async fn add_job(
+    user_id: &str,
+    book_id: &str,
+    providers: &[String],
+    priority: i64,
+) -> Result<String, ProsaError> {
+    let job_id = Uuid::new_v4().to_string();
+    let providers = serde_json::to_string(providers).expect("Failed to serialize job providers");
+    repository::add_job(&job_id, user_id, book_id, &providers, priority).await?;
+
+    Ok(job_id)
+}
+
+pub async fn job_is_queued(user_id: &str, book_id: &str, providers: &[String]) -> bool {
+    let providers = serde_json::to_string(providers).expect("Failed to serialize job providers");
+    repository::job_is_queued(user_id, book_id, &providers).await
+}
+
+pub async fn get_job(job_id: &str) -> Result<MetadataJob, ProsaError> {
+    let mut job = [repository::get_job(job_id).await?.into()];
+    estimate_queue(&mut job).await;
+
+    let [job] = job;
+    Ok(job)
+}
+
+pub async fn claim_next_job() -> Result<Option<MetadataJob>, ProsaError> {
+    let job = repository::claim_next_job().await?;
+    Ok(job.map(MetadataJob::from))
+}
+
+pub async fn finish_job(job_id: &str, status: JobStatus, reason: Option<&str>) -> Result<(), ProsaError> {
+    repository::finish_job(job_id, status, reason).await?;
+    Ok(())
+}
+
+pub async fn cancel_job(job_id: &str) -> Result<(), ProsaError> {
+    if !repository::cancel_job(job_id).await? {
+        return Err(MetadataError::MetadataRequestNotQueued.into());
+    }
+
+    Ok(())
+}
+
+/// Keeps the priority of a request made by a user within the band users are allowed, while admins
+/// can set any priority.
+pub fn bound_priority(priority: i64, role: &AuthRole) -> i64 {
+    match role {
+        AuthRole::Admin(_) => priority,
+        AuthRole::User(_) => priority.clamp(MIN_USER_PRIORITY, MAX_USER_PRIORITY),
+    }
+}
+
+pub async fn update_job_priority(job_id: &str, priority: i64) -> Result<(), ProsaError> {
+    if !repository::update_job_priority(job_id, priority).await? {
+        return Err(MetadataError::MetadataRequestNotQueued.into());
+    }
+
+    Ok(())
+}
+
+pub async fn requeue_running_jobs() -> Result<(), ProsaError> {
+    repository::requeue_running_jobs().await?;
+    Ok(())
+}
+
+/// Deletes the jobs that finished longer ago than the retention period, so that the job history
+/// does not grow forever.
+pub async fn prune_jobs() {
+    let retention =
+        TimeDelta::from_std(Duration::from_secs(CONFIG.metadata_jobs.retention)).unwrap_or(TimeDelta::MAX);
+    let finished_before = Utc::now()
+        .checked_sub_signed(retention)
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+    let pruned = repository::prune_jobs(finished_before).await;
+    if pruned > 0 {
+        info!("Pruned {pruned} finished metadata jobs");
+    }
+}
+
+pub async fn list_jobs(
+    user_id: Option<&str>,
+    book_id: Option<&str>,
+    statuses: &[JobStatus],
+) -> Result<Vec<MetadataJob>, ProsaError> {
+    let mut jobs: Vec<MetadataJob> = repository::list_jobs(user_id, book_id, statuses)
+        .await?
+        .into_iter()
+        .map(MetadataJob::from)
+        .collect();
+
+    estimate_queue(&mut jobs).await;
+    Ok(jobs)
+}
+
+pub async fn get_due_refreshes(
+    max_age: TimeDelta,
+    retry_after: TimeDelta,
+    limit: usize,
+) -> Vec<RefreshCandidate> {
+    let now = Utc::now();
+
+    let mut due: Vec<RefreshCandidate> = repository::get_refresh_candidates()
+        .await
+        .into_iter()
+        .filter(|c| {
+            // A refresh age of zero means the owner opted out of scheduled refreshes
+            let max_age = c.refresh_age.map_or(max_age, |age| {
+                TimeDelta::try_seconds(age).unwrap_or(TimeDelta::MAX)
+            });
+            if max_age <= TimeDelta::zero() {
+                return false;
+            }
+
+            let wait = if c.incomplete {
+                retry_after.min(max_age)
+            } else {
+                max_age
+            };
+            c.last_attempt.is_none_or(|t| now - t >= wait)
+        })
+        .collect();
+
+    // Incomplete books go first, then the ones that have gone the longest without a fetch
+    due.sort_by_key(|c| (!c.incomplete, c.last_attempt));
+    due.truncate(limit);
+    due
+}
+
+async fn estimate_queue(jobs: &mut [MetadataJob]) {
+    if jobs.iter().all(|j| j.status != JobStatus::Queued) {
+        return;
+    }
+
+    let positions: HashMap<String, i64> = repository::get_queue().await.into_iter().zip(1..).collect();
+    let average = average_job_duration().await;
+    let now = Utc::now();
+    let slots = i32::try_from(CONFIG.metadata_jobs.concurrency.max(1)).unwrap_or(i32::MAX);
+
+    // Jobs that are already running delay the whole queue until they are expected to finish
+    let running: TimeDelta = match average {
+        Some(average) => repository::get_running_jobs_start()
+            .await
+            .into_iter()
+            .map(|started_at| (average - (now - started_at)).max(TimeDelta::zero()))
+            .sum(),
+        None => TimeDelta::zero(),
+    };
+
+    for job in jobs.iter_mut().filter(|j| j.status == JobStatus::Queued) {
+        job.position = positions.get(&job.job_id).copied();
+
+        let jobs_ahead = job.position.and_then(|p| i32::try_from(p - 1).ok());
+        job.estimated_start_at = match (average, jobs_ahead) {
+            (Some(average), Some(jobs_ahead)) => Some(now + (running + average * jobs_ahead) / slots),
+            _ => None,
+        };
+    }
+}
+
+async fn average_job_duration() -> Option<TimeDelta> {
+    let durations = repository::get_recent_job_durations(JOB_DURATION_SAMPLES).await;
+    let count = i32::try_from(durations.len()).ok().filter(|c| *c > 0)?;
+    let total: TimeDelta = durations.into_iter().map(|(start, end)| end - start).sum();
+
+    Some(total / count)
+}
+
+pub fn parse_candidate_metadata(candidate: &MetadataCandidateEntity) -> Option<Metadata> {
+    candidate
+        .metadata
+        .as_ref()
+        .map(|m| serde_json::from_str(m).expect("Failed to deserialize candidate metadata"))
+}
+
+fn diff_metadata(current: Option<&Value>, candidate: &Metadata) -> HashMap<String, FieldDiff> {
+    let Value::Object(candidate) = serde_json::to_value(candidate).expect("Failed to serialize metadata")
+    else {
+        return HashMap::new();
+    };
+
+    candidate
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let current = current.and_then(|c| c.get(&field)).cloned();
+            if current.as_ref() == Some(&value) {
+                return None;
+            }
+
+            Some((
+                field,
+                FieldDiff {
+                    current,
+                    candidate: value,
+                },
+            ))
+        })
+        .collect()
+}
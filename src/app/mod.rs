@@ -4,6 +4,8 @@ mod authorization;
 mod books;
 mod core;
 mod covers;
+mod devices;
+mod duplicates;
 mod epubs;
 mod error;
 mod metadata;
@@ -12,5 +14,7 @@ mod shelves;
 mod state;
 mod sync;
 mod tracing;
+mod trash;
 mod users;
+pub use core::metadata_fetcher::verify_plugins;
 pub use server::run;
@@ -1,8 +1,9 @@
-use super::{annotations, books, covers, metadata, state, sync, users};
+use super::{annotations, books, covers, devices, duplicates, metadata, state, sync, trash, users};
 use crate::CONFIG;
 use crate::app::core::locking::service::LockService;
-use crate::app::core::metadata_fetcher::MetadataFetcherService;
+use crate::app::core::metadata_fetcher::{MetadataFetcherService, spawn_refresh_scheduler};
 use crate::app::core::utils;
+use crate::app::epubs::spans::KoboSpans;
 use crate::app::{authentication, shelves, tracing};
 use axum::Router;
 use axum::middleware::from_fn;
@@ -18,6 +19,7 @@ pub struct Cache {
     pub source_cache: QuickCache<String, Arc<HashSet<String>>>,
     pub tag_cache: QuickCache<String, Arc<HashSet<String>>>,
     pub tag_length_cache: QuickCache<String, u32>,
+    pub span_cache: QuickCache<String, Arc<KoboSpans>>,
 }
 
 pub static CACHE: LazyLock<Cache> = LazyLock::new(|| Cache {
@@ -25,12 +27,16 @@ pub static CACHE: LazyLock<Cache> = LazyLock::new(|| Cache {
     source_cache: QuickCache::new(100000),
     tag_cache: QuickCache::new(100000),
     tag_length_cache: QuickCache::new(100000),
+    span_cache: QuickCache::new(1000),
 });
 
 pub static METADATA_FETCHER: LazyLock<Arc<MetadataFetcherService>> = LazyLock::new(|| {
     MetadataFetcherService::new(
-        CONFIG.metadata_cooldown.epub_extractor,
-        CONFIG.metadata_cooldown.goodreads,
+        &CONFIG.metadata_jobs,
+        &CONFIG.metadata_cooldown,
+        &CONFIG.open_library,
+        &CONFIG.google_books,
+        &CONFIG.metadata_plugins,
     )
 });
 
@@ -42,10 +48,21 @@ pub async fn run() {
     tracing::init_logging();
     info!("Server started on http://{host}");
 
+    // Jobs interrupted by a previous shutdown are run again, along with the rest of the queue
+    metadata::service::requeue_running_jobs()
+        .await
+        .expect("Failed to requeue metadata jobs");
+    LazyLock::force(&METADATA_FETCHER);
+    spawn_refresh_scheduler(METADATA_FETCHER.clone(), &CONFIG.metadata_refresh);
+    trash::scheduler::spawn_purge_scheduler(&CONFIG.trash);
+    sync::scheduler::spawn_prune_scheduler(&CONFIG.change_log);
+    tokio::spawn(covers::service::prune_cover_variants());
+
     let app = Router::new()
         .route("/health", get(utils::health_check))
         .route("/config", get(utils::get_public_config))
         .merge(users::routes::get_routes())
+        .merge(devices::routes::get_routes())
         .merge(metadata::routes::get_routes())
         .merge(covers::routes::get_routes())
         .merge(state::routes::get_routes())
@@ -53,6 +70,8 @@ pub async fn run() {
         .merge(books::routes::get_routes())
         .merge(annotations::routes::get_routes())
         .merge(shelves::routes::get_routes())
+        .merge(duplicates::routes::get_routes())
+        .merge(trash::routes::get_routes())
         .merge(authentication::routes::get_routes())
         .layer(from_fn(tracing::log_layer));
 
@@ -87,7 +87,7 @@ pub async fn delete_shelf_handler(
     let _guard = lock.write().await;
 
     let shelf = service::get_shelf(&shelf_id).await?;
-    service::delete_shelf(&shelf_id).await?;
+    service::trash_shelf(&shelf_id).await?;
 
     sync::service::log_change(
         &shelf_id,
@@ -16,6 +16,9 @@ pub enum ShelfError {
     #[strum(message = "There is already a shelf with this name in your library.")]
     #[strum(props(StatusCode = "409"))]
     ShelfConflict,
+    #[strum(message = "There is a shelf with this name in your trash, restore it instead.")]
+    #[strum(props(StatusCode = "409"))]
+    ShelfInTrash,
     #[strum(message = "The provided shelf name is invalid.")]
     #[strum(props(StatusCode = "400"))]
     InvalidName,
@@ -1,13 +1,14 @@
 use super::models::{Shelf, ShelfError};
 use crate::DB_POOL;
 use crate::app::shelves::models::{PaginatedShelves, ShelfBookError};
+use chrono::{DateTime, Utc};
 
 pub async fn get_shelf(shelf_id: &str) -> Result<Shelf, ShelfError> {
     let shelf: Shelf = sqlx::query_as(
         r"
         SELECT name, owner_id
         FROM shelf
-        WHERE shelf_id = $1
+        WHERE shelf_id = $1 AND deleted_at IS NULL
         ",
     )
     .bind(shelf_id)
@@ -17,11 +18,27 @@ pub async fn get_shelf(shelf_id: &str) -> Result<Shelf, ShelfError> {
     Ok(shelf)
 }
 
-pub async fn get_shelf_by_name_and_owner(name: &str, owner_id: &str) -> Option<Shelf> {
-    sqlx::query_as(
+pub async fn get_trashed_shelf(shelf_id: &str) -> Result<Shelf, ShelfError> {
+    let shelf: Shelf = sqlx::query_as(
         r"
         SELECT name, owner_id
         FROM shelf
+        WHERE shelf_id = $1 AND deleted_at IS NOT NULL
+        ",
+    )
+    .bind(shelf_id)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    Ok(shelf)
+}
+
+/// Tells whether the user has a shelf with this name, and if so whether it is in the trash.
+pub async fn get_shelf_name_trashed(name: &str, owner_id: &str) -> Option<bool> {
+    sqlx::query_scalar(
+        r"
+        SELECT deleted_at IS NOT NULL
+        FROM shelf
         WHERE name = $1 AND owner_id = $2
         ",
     )
@@ -66,6 +83,26 @@ pub async fn delete_shelf(shelf_id: &str) -> Result<(), ShelfError> {
     Ok(())
 }
 
+pub async fn set_deleted_at(shelf_id: &str, deleted_at: Option<DateTime<Utc>>) -> Result<(), ShelfError> {
+    let result = sqlx::query(
+        r"
+        UPDATE shelf
+        SET deleted_at = $1
+        WHERE shelf_id = $2;
+        ",
+    )
+    .bind(deleted_at)
+    .bind(shelf_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ShelfError::ShelfNotFound);
+    }
+
+    Ok(())
+}
+
 pub async fn update_shelf(shelf_id: &str, name: &str) -> Result<(), ShelfError> {
     let result = sqlx::query(
         r"
@@ -100,7 +137,7 @@ pub async fn get_paginated_shelves(
         SELECT DISTINCT shelf_id
         FROM shelf s
         INNER JOIN users u ON s.owner_id = u.user_id
-        WHERE 1=1
+        WHERE s.deleted_at IS NULL
         ",
     );
 
@@ -109,7 +146,7 @@ pub async fn get_paginated_shelves(
         SELECT COUNT(DISTINCT shelf_id)
         FROM shelf s
         INNER JOIN users u ON s.owner_id = u.user_id
-        WHERE 1=1
+        WHERE s.deleted_at IS NULL
         ",
     );
 
@@ -171,9 +208,10 @@ pub async fn get_paginated_shelves(
 pub async fn get_shelf_book_count(shelf_id: &str) -> i64 {
     let count: (i64,) = sqlx::query_as(
         r"
-        SELECT COUNT(book_id)
-        FROM is_in_shelf
-        WHERE shelf_id = $1
+        SELECT COUNT(i.book_id)
+        FROM is_in_shelf i
+        JOIN books b ON b.book_id = i.book_id
+        WHERE i.shelf_id = $1 AND b.deleted_at IS NULL
         ",
     )
     .bind(shelf_id)
@@ -202,9 +240,10 @@ pub async fn add_book_to_shelf(shelf_id: &str, book_id: &str) -> Result<(), Shel
 pub async fn get_shelf_books(shelf_id: &str) -> Vec<String> {
     sqlx::query_scalar(
         r"
-        SELECT book_id
-        FROM is_in_shelf
-        WHERE shelf_id = $1
+        SELECT i.book_id
+        FROM is_in_shelf i
+        JOIN books b ON b.book_id = i.book_id
+        WHERE i.shelf_id = $1 AND b.deleted_at IS NULL
         ",
     )
     .bind(shelf_id)
@@ -231,3 +270,34 @@ pub async fn delete_book_from_shelf(shelf_id: &str, book_id: &str) -> Result<(),
 
     Ok(())
 }
+
+pub async fn get_book_shelves(book_id: &str) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT i.shelf_id
+        FROM is_in_shelf i
+        JOIN shelf s ON s.shelf_id = i.shelf_id
+        WHERE i.book_id = $1 AND s.deleted_at IS NULL
+        ",
+    )
+    .bind(book_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list book shelves")
+}
+
+/// Puts a book in every shelf another book is in, in place of that book.
+pub async fn replace_book_in_shelves(old_book_id: &str, new_book_id: &str) {
+    sqlx::query(
+        r"
+        UPDATE OR IGNORE is_in_shelf
+        SET book_id = $1
+        WHERE book_id = $2
+        ",
+    )
+    .bind(new_book_id)
+    .bind(old_book_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to replace book in shelves");
+}
@@ -6,6 +6,7 @@ use crate::app::{
         repository,
     },
 };
+use chrono::Utc;
 use uuid::Uuid;
 
 pub async fn get_shelf(shelf_id: &str) -> Result<Shelf, ProsaError> {
@@ -29,10 +30,10 @@ pub async fn get_shelf_metadata(shelf_id: &str) -> Result<ShelfMetadata, ProsaEr
 pub async fn add_shelf(shelf: Shelf) -> Result<String, ProsaError> {
     verify_shelf_name(&shelf.name)?;
 
-    let old_shelf = repository::get_shelf_by_name_and_owner(&shelf.name, &shelf.owner_id).await;
-
-    if old_shelf.is_some() {
-        return Err(ShelfError::ShelfConflict.into());
+    match repository::get_shelf_name_trashed(&shelf.name, &shelf.owner_id).await {
+        Some(true) => return Err(ShelfError::ShelfInTrash.into()),
+        Some(false) => return Err(ShelfError::ShelfConflict.into()),
+        None => {}
     }
 
     let shelf_id = Uuid::new_v4().to_string();
@@ -43,11 +44,36 @@ pub async fn add_shelf(shelf: Shelf) -> Result<String, ProsaError> {
 
 pub async fn update_shelf(shelf_id: &str, name: &str) -> Result<(), ProsaError> {
     verify_shelf_name(name)?;
+
+    // Only a trashed shelf needs telling apart, any other name clash fails on the update
+    let shelf = repository::get_shelf(shelf_id).await?;
+    if repository::get_shelf_name_trashed(name, &shelf.owner_id).await == Some(true) {
+        return Err(ShelfError::ShelfInTrash.into());
+    }
+
     repository::update_shelf(shelf_id, name).await?;
     Ok(())
 }
 
-pub async fn delete_shelf(shelf_id: &str) -> Result<(), ProsaError> {
+/// Moves a shelf to the trash, keeping the books it holds until it is restored or purged.
+pub async fn trash_shelf(shelf_id: &str) -> Result<(), ProsaError> {
+    repository::set_deleted_at(shelf_id, Some(Utc::now())).await?;
+    Ok(())
+}
+
+pub async fn get_trashed_shelf(shelf_id: &str) -> Result<Shelf, ProsaError> {
+    let shelf = repository::get_trashed_shelf(shelf_id).await?;
+    Ok(shelf)
+}
+
+pub async fn restore_shelf(shelf_id: &str) -> Result<(), ProsaError> {
+    repository::set_deleted_at(shelf_id, None).await?;
+    Ok(())
+}
+
+/// Permanently deletes a shelf from the trash.
+pub async fn purge_shelf(shelf_id: &str) -> Result<(), ProsaError> {
+    get_trashed_shelf(shelf_id).await?;
     repository::delete_shelf(shelf_id).await?;
     Ok(())
 }
@@ -93,6 +119,17 @@ pub async fn delete_book_from_shelf(shelf_id: &str, book_id: &str) -> Result<(),
     Ok(())
 }
 
+pub async fn get_book_shelves(book_id: &str) -> Vec<String> {
+    repository::get_book_shelves(book_id).await
+}
+
+/// Returns the shelves that held the old book.
+pub async fn replace_book_in_shelves(old_book_id: &str, new_book_id: &str) -> Vec<String> {
+    let shelf_ids = repository::get_book_shelves(old_book_id).await;
+    repository::replace_book_in_shelves(old_book_id, new_book_id).await;
+    shelf_ids
+}
+
 fn verify_shelf_name(name: &str) -> Result<(), ShelfError> {
     if !name.chars().all(|c| (' '..='~').contains(&c)) {
         return Err(ShelfError::InvalidName);
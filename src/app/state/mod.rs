@@ -1,5 +1,5 @@
 pub mod controller;
-mod models;
+pub mod models;
 pub mod repository;
 pub mod routes;
 pub mod service;
@@ -1,7 +1,7 @@
 use super::models::{Location, State, StateError, Statistics, VALID_READING_STATUS};
 use crate::{
     CONFIG,
-    app::{error::ProsaError, server::CACHE, state::repository},
+    app::{epubs::spans::KoboSpans, error::ProsaError, server::CACHE, state::repository},
 };
 use epub::doc::EpubDoc;
 use merge::Merge;
@@ -48,6 +48,79 @@ pub async fn update_state(state_id: &str, epub_id: &str, state: State) -> Result
     Ok(())
 }
 
+/// Folds the state of another book into this one, keeping the furthest reading status.
+/// The other book's location is only kept when this book has none and it exists in this book's file.
+pub async fn merge_state(state_id: &str, epub_id: &str, other_state_id: &str) -> bool {
+    let mut state = repository::get_state(state_id).await;
+    let other = repository::get_state(other_state_id).await;
+
+    let (Some(statistics), Some(other_statistics)) = (&mut state.statistics, other.statistics) else {
+        return false;
+    };
+
+    let progress = |status: &Option<String>| {
+        status
+            .as_deref()
+            .and_then(|s| VALID_READING_STATUS.iter().position(|v| *v == s))
+    };
+
+    let mut changed = false;
+
+    if progress(&other_statistics.reading_status) > progress(&statistics.reading_status) {
+        statistics.reading_status = other_statistics.reading_status;
+        changed = true;
+    }
+
+    if statistics.rating.is_none() && other_statistics.rating.is_some() {
+        statistics.rating = other_statistics.rating;
+        changed = true;
+    }
+
+    if state.location.is_none()
+        && let Some(location) = other.location
+        && validate_location(&location, epub_id).is_ok()
+    {
+        state.location = Some(location);
+        changed = true;
+    }
+
+    if changed {
+        repository::update_state(state_id, state).await;
+    }
+
+    changed
+}
+
+/// Moves the reading location to where its text is in a new file of the book.
+/// The location is dropped when the text is gone.
+pub async fn remap_location(state_id: &str, old_spans: &KoboSpans, new_spans: &KoboSpans) -> bool {
+    let mut state = repository::get_state(state_id).await;
+
+    let Some(Location {
+        tag: Some(tag),
+        source: Some(source),
+    }) = &state.location
+    else {
+        return false;
+    };
+
+    let text = old_spans.text(source, tag);
+    if text.is_some() && text == new_spans.text(source, tag) {
+        return false;
+    }
+
+    state.location = text
+        .and_then(|text| new_spans.find(source, text))
+        .map(|(source, tag)| Location {
+            tag: Some(tag),
+            source: Some(source),
+        });
+
+    repository::update_state(state_id, state).await;
+
+    true
+}
+
 fn validate_state(state: &State, epub_id: &str) -> Result<(), ProsaError> {
     match &state.statistics {
         Some(s) => validate_statistics(s)?,
@@ -1,6 +1,9 @@
 use super::models::SyncError;
 use crate::app::{
-    authentication::models::AuthToken,
+    authentication::{
+        self,
+        models::{AuthToken, AuthTokenError},
+    },
     error::ProsaError,
     sync::{models::UnsyncedResponse, service},
 };
@@ -19,12 +22,30 @@ pub async fn get_unsynced_handler(
     };
 
     let sync_token = match sync_token {
-        Some(Ok(t)) => t,
-        None => -1,
+        Some(Ok(t)) => Some(t),
+        None => None,
         _ => return Err(SyncError::InvalidSyncToken.into()),
     };
 
-    let unsynced = service::get_unsynced_changes(user_id, &token.session_id, sync_token).await?;
+    let expand = match params.get("expand").map(|e| e.parse::<bool>()) {
+        Some(Ok(e)) => e,
+        None => false,
+        _ => return Err(SyncError::InvalidExpandFlag.into()),
+    };
+
+    let limit = match params.get("limit").map(|l| l.parse::<i64>()) {
+        Some(Ok(l)) if l > 0 => Some(l),
+        None => None,
+        _ => return Err(SyncError::InvalidLimit.into()),
+    };
+
+    // A removed device must not pick its sync session back up with an access token it still holds
+    if !authentication::service::is_session_active(&token).await {
+        return Err(AuthTokenError::InvalidToken.into());
+    }
+
+    let unsynced =
+        service::get_unsynced_changes(user_id, &token.session_id, sync_token, expand, limit).await?;
 
     Ok(Json(unsynced))
 }
@@ -1,4 +1,9 @@
+use crate::app::{
+    annotations::models::Annotation, metadata::models::Metadata, shelves::models::ShelfMetadata,
+    state::models::State,
+};
 use serde::Serialize;
+use serde_with::skip_serializing_none;
 use sqlx::{Type, prelude::FromRow};
 use strum_macros::{EnumMessage, EnumProperty};
 
@@ -7,6 +12,12 @@ pub enum SyncError {
     #[strum(message = "The provided sync token is invalid.")]
     #[strum(props(StatusCode = "400"))]
     InvalidSyncToken,
+    #[strum(message = "The provided expand flag is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidExpandFlag,
+    #[strum(message = "The requested limit is invalid.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidLimit,
 }
 
 #[derive(Type, PartialEq)]
@@ -59,9 +70,32 @@ pub struct UnsyncedShelves {
 
 //TODO change sync structs in kobont, adjust logic accordingly
 
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct BookChanges {
+    pub book_id: String,
+    pub metadata: Option<Metadata>,
+    pub state: Option<State>,
+    pub annotations: Option<Vec<Annotation>>,
+    pub cover_hash: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct ShelfChanges {
+    pub shelf_id: String,
+    pub metadata: Option<ShelfMetadata>,
+    pub contents: Option<Vec<String>>,
+}
+
+#[skip_serializing_none]
 #[derive(Serialize)]
 pub struct UnsyncedResponse {
     pub new_sync_token: i64,
+    pub continuation_token: Option<i64>,
+    pub full_resync_required: Option<bool>,
     pub unsynced_books: UnsyncedBooks,
     pub unsynced_shelves: UnsyncedShelves,
+    pub books: Option<Vec<BookChanges>>,
+    pub shelves: Option<Vec<ShelfChanges>>,
 }
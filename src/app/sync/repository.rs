@@ -1,5 +1,7 @@
 use crate::DB_POOL;
 use crate::app::sync::models::{ChangeLogAction, ChangeLogEntityType, ChangeLogEntry};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 
 pub async fn delete_log_entries(entity_id: &str) {
     sqlx::query(
@@ -14,6 +16,21 @@ pub async fn delete_log_entries(entity_id: &str) {
     .expect("Failed to delete logs");
 }
 
+pub async fn delete_superseded_entries(entity_id: &str, entity_type: &ChangeLogEntityType) {
+    sqlx::query(
+        r"
+        DELETE FROM change_log
+        WHERE entity_id = $1
+        AND entity_type = $2
+        ",
+    )
+    .bind(entity_id)
+    .bind(entity_type)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to compact logs");
+}
+
 pub async fn log_change(
     entity_id: &str,
     entity_type: ChangeLogEntityType,
@@ -23,8 +40,8 @@ pub async fn log_change(
 ) {
     sqlx::query(
         r"
-        INSERT INTO change_log (entity_id, entity_type, owner_id, session_id, action)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO change_log (entity_id, entity_type, owner_id, session_id, action, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
         ",
     )
     .bind(entity_id)
@@ -32,12 +49,19 @@ pub async fn log_change(
     .bind(owner_id)
     .bind(session_id)
     .bind(action)
+    .bind(Utc::now())
     .execute(DB_POOL.get().expect("Failed to get database pool"))
     .await
     .expect("Failed to log change");
 }
 
-pub async fn get_changes(user_id: &str, last_sync_token: i64, session_id: &str) -> Vec<ChangeLogEntry> {
+/// Gets the changes after the sync token, up to the limit if there is one.
+pub async fn get_changes(
+    user_id: &str,
+    last_sync_token: i64,
+    session_id: &str,
+    limit: Option<i64>,
+) -> Vec<ChangeLogEntry> {
     let changes: Vec<ChangeLogEntry> = sqlx::query_as(
         r"
         SELECT log_id, entity_id, entity_type, owner_id, session_id, action
@@ -46,14 +70,227 @@ pub async fn get_changes(user_id: &str, last_sync_token: i64, session_id: &str)
         AND log_id > $2
         AND session_id != $3
         ORDER BY log_id ASC
+        LIMIT $4
         ",
     )
     .bind(user_id)
     .bind(last_sync_token)
     .bind(session_id)
+    .bind(limit.unwrap_or(-1))
     .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
     .await
     .expect("Failed to fetch change log");
 
     changes
 }
+
+pub async fn count_changes(user_id: &str, last_sync_token: i64, session_id: &str) -> i64 {
+    sqlx::query_scalar(
+        r"
+        SELECT COUNT(*)
+        FROM change_log
+        WHERE owner_id = $1
+        AND log_id > $2
+        AND session_id != $3
+        ",
+    )
+    .bind(user_id)
+    .bind(last_sync_token)
+    .bind(session_id)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to count changes")
+}
+
+pub async fn get_latest_token(user_id: &str) -> i64 {
+    sqlx::query_scalar(
+        r"
+        SELECT MAX(
+            COALESCE((SELECT MAX(log_id) FROM change_log WHERE owner_id = $1), -1),
+            COALESCE((SELECT pruned_token FROM change_log_retention WHERE owner_id = $1), -1)
+        )
+        ",
+    )
+    .bind(user_id)
+    .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to get latest sync token")
+}
+
+/// Gets the newest change log entry of the user that was pruned, if there is one.
+pub async fn get_pruned_token(user_id: &str) -> Option<i64> {
+    sqlx::query_scalar(
+        r"
+        SELECT pruned_token
+        FROM change_log_retention
+        WHERE owner_id = $1
+        ",
+    )
+    .bind(user_id)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to get pruned sync token")
+}
+
+pub async fn update_session(session_id: &str, user_id: &str, sync_token: i64) {
+    sqlx::query(
+        r"
+        INSERT INTO sync_sessions (session_id, owner_id, sync_token, synced_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT(session_id, owner_id) DO UPDATE SET sync_token = excluded.sync_token, synced_at = excluded.synced_at
+        ",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(sync_token)
+    .bind(Utc::now())
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to update sync session");
+}
+
+pub async fn delete_session(session_id: &str) {
+    sqlx::query(
+        r"
+        DELETE FROM sync_sessions
+        WHERE session_id = $1
+        ",
+    )
+    .bind(session_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to delete sync session");
+}
+
+pub async fn delete_inactive_sessions(synced_before: DateTime<Utc>) {
+    sqlx::query(
+        r"
+        DELETE FROM sync_sessions
+        WHERE synced_at <= $1
+        ",
+    )
+    .bind(synced_before)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to delete inactive sync sessions");
+}
+
+/// Deletes the entries logged before the given date that every active session of their owner has
+/// already synced past, and returns how many were deleted.
+pub async fn prune_changes(created_before: DateTime<Utc>) -> u64 {
+    let mut tx = DB_POOL
+        .get()
+        .expect("Failed to get database pool")
+        .begin()
+        .await
+        .expect("Failed to start transaction");
+
+    let pruned: Vec<(String, i64)> = sqlx::query_as(
+        r"
+        DELETE FROM change_log
+        WHERE created_at <= $1
+        AND log_id <= COALESCE(
+            (SELECT MIN(sync_token) FROM sync_sessions s WHERE s.owner_id = change_log.owner_id),
+            9223372036854775807
+        )
+        RETURNING owner_id, log_id
+        ",
+    )
+    .bind(created_before)
+    .fetch_all(&mut *tx)
+    .await
+    .expect("Failed to prune change log");
+
+    let mut pruned_tokens: HashMap<String, i64> = HashMap::new();
+    for (owner_id, log_id) in &pruned {
+        let token = pruned_tokens.entry(owner_id.clone()).or_insert(*log_id);
+        *token = (*token).max(*log_id);
+    }
+
+    for (owner_id, pruned_token) in pruned_tokens {
+        sqlx::query(
+            r"
+            INSERT INTO change_log_retention (owner_id, pruned_token)
+            VALUES ($1, $2)
+            ON CONFLICT(owner_id) DO UPDATE SET pruned_token = MAX(pruned_token, excluded.pruned_token)
+            ",
+        )
+        .bind(owner_id)
+        .bind(pruned_token)
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to update change log retention");
+    }
+
+    tx.commit().await.expect("Failed to commit transaction");
+
+    pruned.len() as u64
+}
+
+/// Gets where the full resync of the session stopped, if it was served up to the sync token.
+pub async fn get_resync_cursor(session_id: &str, user_id: &str, sync_token: i64) -> Option<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT resync_cursor
+        FROM sync_sessions
+        WHERE session_id = $1
+        AND owner_id = $2
+        AND resync_token = $3
+        AND resync_cursor IS NOT NULL
+        ",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(sync_token)
+    .fetch_optional(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to get resync cursor")
+}
+
+pub async fn update_resync(session_id: &str, user_id: &str, resync: Option<(i64, &str)>) {
+    sqlx::query(
+        r"
+        UPDATE sync_sessions
+        SET resync_token = $1, resync_cursor = $2
+        WHERE session_id = $3
+        AND owner_id = $4
+        ",
+    )
+    .bind(resync.map(|(token, _)| token))
+    .bind(resync.map(|(_, cursor)| cursor))
+    .bind(session_id)
+    .bind(user_id)
+    .execute(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to update resync cursor");
+}
+
+/// Lists the books of the user, then their shelves, after the cursor. Each entry is the ID
+/// prefixed with `b` or `s`, which doubles as the cursor of the next page.
+pub async fn get_resync_entries(user_id: &str, cursor: &str, limit: Option<i64>) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT entry
+        FROM (
+            SELECT 'b' || book_id AS entry
+            FROM books
+            WHERE owner_id = $1
+            AND deleted_at IS NULL
+            UNION ALL
+            SELECT 's' || shelf_id AS entry
+            FROM shelf
+            WHERE owner_id = $1
+            AND deleted_at IS NULL
+        )
+        WHERE entry > $2
+        ORDER BY entry ASC
+        LIMIT $3
+        ",
+    )
+    .bind(user_id)
+    .bind(cursor)
+    .bind(limit.unwrap_or(-1))
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list books and shelves")
+}
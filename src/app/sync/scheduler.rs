@@ -0,0 +1,22 @@
+use super::service;
+use crate::{app::metadata, config::ChangeLog};
+use std::time::Duration;
+use tokio::time::{MissedTickBehavior, interval};
+
+pub fn spawn_prune_scheduler(settings: &ChangeLog) {
+    if settings.interval == 0 {
+        return;
+    }
+
+    let period = Duration::from_secs(settings.interval);
+    tokio::spawn(async move {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            service::prune_change_log().await;
+            metadata::service::prune_jobs().await;
+        }
+    });
+}
@@ -1,12 +1,25 @@
-use super::models::UnsyncedBooks;
-use crate::app::{
-    error::ProsaError,
-    sync::{
-        models::{ChangeLogAction, ChangeLogEntityType, UnsyncedResponse, UnsyncedShelves},
-        repository,
+use super::models::{BookChanges, ShelfChanges, SyncError, UnsyncedBooks};
+use crate::{
+    CONFIG,
+    app::{
+        annotations, books, covers, devices,
+        error::ProsaError,
+        metadata,
+        server::LOCKS,
+        shelves, state,
+        sync::{
+            models::{ChangeLogAction, ChangeLogEntityType, UnsyncedResponse, UnsyncedShelves},
+            repository,
+        },
+        users,
     },
-    users,
 };
+use chrono::{DateTime, TimeDelta, Utc};
+use log::info;
+use std::time::Duration;
+
+/// Maximum number of change log entries returned at once in expanded mode.
+const MAX_EXPANDED_CHANGES: i64 = 100;
 
 pub async fn log_change(
     entity_id: &str,
@@ -15,13 +28,17 @@ pub async fn log_change(
     owner_id: &str,
     session_id: &str,
 ) {
-    if action == ChangeLogAction::Delete
+    // Deleting or restoring a book or shelf supersedes everything logged about it before
+    if action != ChangeLogAction::Update
         && matches!(
             entity_type,
             ChangeLogEntityType::BookFile | ChangeLogEntityType::ShelfMetadata
         )
     {
         repository::delete_log_entries(entity_id).await;
+    } else {
+        // Devices only need the latest change of each kind
+        repository::delete_superseded_entries(entity_id, &entity_type).await;
     }
 
     repository::log_change(entity_id, entity_type, action, owner_id, session_id).await;
@@ -30,12 +47,72 @@ pub async fn log_change(
 pub async fn get_unsynced_changes(
     owner_id: &str,
     session_id: &str,
-    sync_token: i64,
+    sync_token: Option<i64>,
+    expand: bool,
+    limit: Option<i64>,
 ) -> Result<UnsyncedResponse, ProsaError> {
     // Ensure user exists
     users::repository::get_user(owner_id).await?;
 
-    let changes = repository::get_changes(owner_id, sync_token, session_id).await;
+    // A token past the latest change would skip whatever gets logged up to it
+    if let Some(sync_token) = sync_token
+        && (sync_token < -1 || sync_token > repository::get_latest_token(owner_id).await)
+    {
+        return Err(SyncError::InvalidSyncToken.into());
+    }
+
+    // Registered devices resume where their previous sync left off, unless they provide a token
+    let device = devices::service::get_session_device(session_id, owner_id).await;
+    let sync_token = match (sync_token, &device) {
+        (Some(t), _) => t,
+        (None, Some(device)) => device.sync_token,
+        (None, None) => -1,
+    };
+
+    repository::update_session(session_id, owner_id, sync_token).await;
+
+    let limit = page_size(expand, limit);
+
+    let response = if let Some(cursor) = repository::get_resync_cursor(session_id, owner_id, sync_token).await
+    {
+        // The continuation of a full resync picks up where its previous page stopped
+        get_full_resync(owner_id, session_id, sync_token, &cursor, expand, limit).await
+    } else if repository::get_pruned_token(owner_id)
+        .await
+        .is_some_and(|pruned_token| sync_token < pruned_token)
+    {
+        // Changes the device has not seen may have been pruned, so it has to start over. Read the
+        // token first, so that anything changed meanwhile comes up again once it is done.
+        let resync_token = repository::get_latest_token(owner_id).await;
+        get_full_resync(owner_id, session_id, resync_token, "", expand, limit).await
+    } else {
+        get_changes(owner_id, session_id, sync_token, expand, limit).await
+    };
+
+    // The device's cursor moves past what it was sent, so that it is not sent again
+    if let Some(device) = device {
+        devices::service::record_sync(&device.device_id, response.new_sync_token).await;
+    }
+
+    Ok(response)
+}
+
+async fn get_changes(
+    owner_id: &str,
+    session_id: &str,
+    sync_token: i64,
+    expand: bool,
+    limit: Option<i64>,
+) -> UnsyncedResponse {
+    // Fetch one extra entry to know whether there is more to come
+    let mut changes = repository::get_changes(
+        owner_id,
+        sync_token,
+        session_id,
+        limit.map(|l| l.saturating_add(1)),
+    )
+    .await;
+    let has_more = truncate_page(&mut changes, limit);
 
     let mut unsynced_books = UnsyncedBooks {
         file: Vec::new(),
@@ -78,9 +155,230 @@ pub async fn get_unsynced_changes(
         }
     }
 
-    Ok(UnsyncedResponse {
+    let (books, shelves) = if expand {
+        (
+            Some(expand_books(&unsynced_books).await),
+            Some(expand_shelves(&unsynced_shelves).await),
+        )
+    } else {
+        (None, None)
+    };
+
+    UnsyncedResponse {
         new_sync_token,
+        continuation_token: has_more.then_some(new_sync_token),
+        full_resync_required: None,
+        unsynced_books,
+        unsynced_shelves,
+        books,
+        shelves,
+    }
+}
+
+/// Lists every book and shelf of the user, for devices whose sync token predates the retained
+/// part of the change log. Pages carry on after the cursor and all share the resync token.
+async fn get_full_resync(
+    owner_id: &str,
+    session_id: &str,
+    resync_token: i64,
+    cursor: &str,
+    expand: bool,
+    limit: Option<i64>,
+) -> UnsyncedResponse {
+    let mut entries =
+        repository::get_resync_entries(owner_id, cursor, limit.map(|l| l.saturating_add(1))).await;
+    let has_more = truncate_page(&mut entries, limit);
+
+    // Remember where this page stopped, for when the device passes the resync token back
+    let next_cursor = entries.last().filter(|_| has_more);
+    repository::update_resync(
+        session_id,
+        owner_id,
+        next_cursor.map(|cursor| (resync_token, cursor.as_str())),
+    )
+    .await;
+
+    let mut unsynced_books = UnsyncedBooks {
+        file: Vec::new(),
+        metadata: Vec::new(),
+        cover: Vec::new(),
+        state: Vec::new(),
+        annotations: Vec::new(),
+        deleted: Vec::new(),
+    };
+
+    let mut unsynced_shelves = UnsyncedShelves {
+        contents: Vec::new(),
+        metadata: Vec::new(),
+        deleted: Vec::new(),
+    };
+
+    for entry in entries {
+        match entry.split_at(1) {
+            ("b", book_id) => unsynced_books.file.push(book_id.to_string()),
+            (_, shelf_id) => {
+                unsynced_shelves.contents.push(shelf_id.to_string());
+                unsynced_shelves.metadata.push(shelf_id.to_string());
+            }
+        }
+    }
+
+    let (books, shelves) = if expand {
+        (
+            Some(expand_books(&unsynced_books).await),
+            Some(expand_shelves(&unsynced_shelves).await),
+        )
+    } else {
+        (None, None)
+    };
+
+    UnsyncedResponse {
+        new_sync_token: resync_token,
+        continuation_token: has_more.then_some(resync_token),
+        full_resync_required: Some(true),
         unsynced_books,
         unsynced_shelves,
-    })
+        books,
+        shelves,
+    }
+}
+
+/// Caps the number of entries of a response, which expanded mode always does.
+fn page_size(expand: bool, limit: Option<i64>) -> Option<i64> {
+    match (expand, limit) {
+        (true, Some(l)) => Some(l.min(MAX_EXPANDED_CHANGES)),
+        (true, None) => Some(MAX_EXPANDED_CHANGES),
+        (false, l) => l,
+    }
+}
+
+/// Cuts the entries down to the limit, returning whether there were more.
+fn truncate_page<T>(entries: &mut Vec<T>, limit: Option<i64>) -> bool {
+    let limit = limit.map(|l| usize::try_from(l).unwrap_or(usize::MAX));
+    let has_more = limit.is_some_and(|l| entries.len() > l);
+    if let Some(l) = limit {
+        entries.truncate(l);
+    }
+
+    has_more
+}
+
+/// Forgets the sessions that have not synced within the retention period, then prunes the change
+/// log entries older than it that every remaining session has already synced past.
+pub async fn prune_change_log() {
+    let expired_before = Utc::now()
+        .checked_sub_signed(retention())
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+    repository::delete_inactive_sessions(expired_before).await;
+    let pruned = repository::prune_changes(expired_before).await;
+
+    if pruned > 0 {
+        info!("Pruned {pruned} entries from the change log");
+    }
+}
+
+fn retention() -> TimeDelta {
+    TimeDelta::from_std(Duration::from_secs(CONFIG.change_log.retention)).unwrap_or(TimeDelta::MAX)
+}
+
+async fn expand_books(unsynced: &UnsyncedBooks) -> Vec<BookChanges> {
+    let mut book_ids: Vec<&String> = Vec::new();
+    for book_id in unsynced
+        .file
+        .iter()
+        .chain(&unsynced.metadata)
+        .chain(&unsynced.cover)
+        .chain(&unsynced.state)
+        .chain(&unsynced.annotations)
+    {
+        if !book_ids.contains(&book_id) && !unsynced.deleted.contains(book_id) {
+            book_ids.push(book_id);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for book_id in book_ids {
+        let lock = LOCKS.get_book_lock(book_id).await;
+        let _guard = lock.read().await;
+
+        // The book may have been trashed since the change was logged
+        let Ok(book) = books::service::get_book(book_id).await else {
+            continue;
+        };
+
+        // A new or replaced file makes everything about the book relevant
+        let file = unsynced.file.contains(book_id);
+
+        let metadata = match &book.metadata_id {
+            Some(metadata_id) if file || unsynced.metadata.contains(book_id) => {
+                metadata::service::get_metadata(metadata_id).await.ok()
+            }
+            _ => None,
+        };
+
+        let state = if file || unsynced.state.contains(book_id) {
+            Some(state::service::get_state(&book.state_id).await)
+        } else {
+            None
+        };
+
+        let annotations = if file || unsynced.annotations.contains(book_id) {
+            Some(annotations::service::get_book_annotations(book_id).await)
+        } else {
+            None
+        };
+
+        let cover_hash = match &book.cover_id {
+            Some(cover_id) if file || unsynced.cover.contains(book_id) => {
+                covers::service::get_cover_hash(cover_id).await
+            }
+            _ => None,
+        };
+
+        changes.push(BookChanges {
+            book_id: book_id.clone(),
+            metadata,
+            state,
+            annotations,
+            cover_hash,
+        });
+    }
+
+    changes
+}
+
+async fn expand_shelves(unsynced: &UnsyncedShelves) -> Vec<ShelfChanges> {
+    let mut shelf_ids: Vec<&String> = Vec::new();
+    for shelf_id in unsynced.metadata.iter().chain(&unsynced.contents) {
+        if !shelf_ids.contains(&shelf_id) && !unsynced.deleted.contains(shelf_id) {
+            shelf_ids.push(shelf_id);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for shelf_id in shelf_ids {
+        let lock = LOCKS.get_shelf_lock(shelf_id).await;
+        let _guard = lock.read().await;
+
+        // The shelf may have been trashed since the change was logged
+        let Ok(metadata) = shelves::service::get_shelf_metadata(shelf_id).await else {
+            continue;
+        };
+
+        let metadata = unsynced.metadata.contains(shelf_id).then_some(metadata);
+        let contents = if unsynced.contents.contains(shelf_id) {
+            shelves::service::list_shelf_books(shelf_id).await.ok()
+        } else {
+            None
+        };
+
+        changes.push(ShelfChanges {
+            shelf_id: shelf_id.clone(),
+            metadata,
+            contents,
+        });
+    }
+
+    changes
 }
@@ -0,0 +1,46 @@
+use super::{models::Trash, service};
+use crate::app::{authentication::models::AuthToken, error::ProsaError, server::LOCKS};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    http::StatusCode,
+};
+use std::collections::HashMap;
+
+pub async fn get_trash_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(token): Extension<AuthToken>,
+) -> Result<Json<Trash>, ProsaError> {
+    let user_id = match params.get("user_id") {
+        Some(id) => id,
+        None => token.role.get_user(),
+    };
+
+    let trash = service::get_trash(user_id).await?;
+
+    Ok(Json(trash))
+}
+
+pub async fn restore_book_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(book_id): Path<String>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_book_lock(&book_id).await;
+    let _guard = lock.write().await;
+
+    service::restore_book(&book_id, &token.session_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn restore_shelf_handler(
+    Extension(token): Extension<AuthToken>,
+    Path(shelf_id): Path<String>,
+) -> Result<StatusCode, ProsaError> {
+    let lock = LOCKS.get_shelf_lock(&shelf_id).await;
+    let _guard = lock.write().await;
+
+    service::restore_shelf(&shelf_id, &token.session_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,6 @@
+pub mod controller;
+pub mod models;
+pub mod repository;
+pub mod routes;
+pub mod scheduler;
+pub mod service;
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc, serde::ts_milliseconds};
+use serde::Serialize;
+use serde_with::skip_serializing_none;
+use sqlx::FromRow;
+
+#[derive(Serialize)]
+pub struct Trash {
+    pub books: Vec<TrashedBook>,
+    pub shelves: Vec<TrashedShelf>,
+}
+
+#[skip_serializing_none]
+#[derive(FromRow, Serialize)]
+pub struct TrashedBook {
+    pub book_id: String,
+    pub title: Option<String>,
+    #[serde(with = "ts_milliseconds")]
+    pub deleted_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(with = "ts_milliseconds")]
+    pub purge_at: DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct TrashedShelf {
+    pub shelf_id: String,
+    pub name: String,
+    #[serde(with = "ts_milliseconds")]
+    pub deleted_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(with = "ts_milliseconds")]
+    pub purge_at: DateTime<Utc>,
+}
@@ -0,0 +1,62 @@
+use super::models::{TrashedBook, TrashedShelf};
+use crate::DB_POOL;
+use chrono::{DateTime, Utc};
+
+pub async fn get_trashed_books(owner_id: &str) -> Vec<TrashedBook> {
+    sqlx::query_as(
+        r"
+        SELECT b.book_id, m.title, b.deleted_at
+        FROM books b
+        LEFT JOIN metadata m ON m.metadata_id = b.metadata_id
+        WHERE b.owner_id = $1 AND b.deleted_at IS NOT NULL
+        ORDER BY b.deleted_at DESC
+        ",
+    )
+    .bind(owner_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list trashed books")
+}
+
+pub async fn get_trashed_shelves(owner_id: &str) -> Vec<TrashedShelf> {
+    sqlx::query_as(
+        r"
+        SELECT shelf_id, name, deleted_at
+        FROM shelf
+        WHERE owner_id = $1 AND deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        ",
+    )
+    .bind(owner_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list trashed shelves")
+}
+
+pub async fn get_expired_books(deleted_before: DateTime<Utc>) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT book_id
+        FROM books
+        WHERE deleted_at <= $1
+        ",
+    )
+    .bind(deleted_before)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list expired books")
+}
+
+pub async fn get_expired_shelves(deleted_before: DateTime<Utc>) -> Vec<String> {
+    sqlx::query_scalar(
+        r"
+        SELECT shelf_id
+        FROM shelf
+        WHERE deleted_at <= $1
+        ",
+    )
+    .bind(deleted_before)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await
+    .expect("Failed to list expired shelves")
+}
@@ -0,0 +1,25 @@
+use crate::app::{
+    authentication::middleware::extract_token_middleware,
+    authorization::trash::{can_read_trash, can_restore_book, can_restore_shelf},
+    trash::controller::{get_trash_handler, restore_book_handler, restore_shelf_handler},
+};
+use axum::{
+    Router,
+    middleware::from_fn,
+    routing::{get, post},
+};
+
+#[rustfmt::skip]
+pub fn get_routes() -> Router {
+    Router::new()
+        .route("/trash", get(get_trash_handler)
+            .route_layer(from_fn(can_read_trash))
+        )
+        .route("/books/{book_id}/restore", post(restore_book_handler)
+            .route_layer(from_fn(can_restore_book))
+        )
+        .route("/shelves/{shelf_id}/restore", post(restore_shelf_handler)
+            .route_layer(from_fn(can_restore_shelf))
+        )
+        .layer(from_fn(extract_token_middleware))
+}
@@ -0,0 +1,21 @@
+use super::service;
+use crate::config::Trash;
+use std::time::Duration;
+use tokio::time::{MissedTickBehavior, interval};
+
+pub fn spawn_purge_scheduler(settings: &Trash) {
+    if settings.interval == 0 {
+        return;
+    }
+
+    let period = Duration::from_secs(settings.interval);
+    tokio::spawn(async move {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            service::purge_expired().await;
+        }
+    });
+}
@@ -0,0 +1,149 @@
+use super::{models::Trash, repository};
+use crate::{
+    CONFIG,
+    app::{
+        annotations, books,
+        error::ProsaError,
+        server::LOCKS,
+        shelves,
+        sync::{
+            self,
+            models::{ChangeLogAction, ChangeLogEntityType},
+        },
+        users,
+    },
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{info, warn};
+use std::time::Duration;
+
+pub async fn get_trash(owner_id: &str) -> Result<Trash, ProsaError> {
+    // Ensure user exists
+    users::service::get_user(owner_id).await?;
+
+    let mut books = repository::get_trashed_books(owner_id).await;
+    for book in &mut books {
+        book.purge_at = purge_date(book.deleted_at);
+    }
+
+    let mut shelves = repository::get_trashed_shelves(owner_id).await;
+    for shelf in &mut shelves {
+        shelf.purge_at = purge_date(shelf.deleted_at);
+    }
+
+    Ok(Trash { books, shelves })
+}
+
+/// Takes a book out of the trash. Devices receive it again along with its state, annotations and
+/// the shelves it was in.
+pub async fn restore_book(book_id: &str, session_id: &str) -> Result<(), ProsaError> {
+    let book = books::service::get_trashed_book(book_id).await?;
+    books::service::restore_book(book_id).await?;
+
+    let mut changes = vec![
+        (ChangeLogEntityType::BookFile, ChangeLogAction::Create),
+        (ChangeLogEntityType::BookState, ChangeLogAction::Update),
+    ];
+
+    if book.metadata_id.is_some() {
+        changes.push((ChangeLogEntityType::BookMetadata, ChangeLogAction::Create));
+    }
+
+    if book.cover_id.is_some() {
+        changes.push((ChangeLogEntityType::BookCover, ChangeLogAction::Create));
+    }
+
+    if !annotations::service::get_annotations(book_id, None)
+        .await
+        .is_empty()
+    {
+        changes.push((ChangeLogEntityType::BookAnnotations, ChangeLogAction::Create));
+    }
+
+    for (entity_type, action) in changes {
+        sync::service::log_change(book_id, entity_type, action, &book.owner_id, session_id).await;
+    }
+
+    for shelf_id in shelves::service::get_book_shelves(book_id).await {
+        sync::service::log_change(
+            &shelf_id,
+            ChangeLogEntityType::ShelfContent,
+            ChangeLogAction::Create,
+            &book.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Takes a shelf out of the trash, with the books it held.
+pub async fn restore_shelf(shelf_id: &str, session_id: &str) -> Result<(), ProsaError> {
+    let shelf = shelves::service::get_trashed_shelf(shelf_id).await?;
+    shelves::service::restore_shelf(shelf_id).await?;
+
+    sync::service::log_change(
+        shelf_id,
+        ChangeLogEntityType::ShelfMetadata,
+        ChangeLogAction::Create,
+        &shelf.owner_id,
+        session_id,
+    )
+    .await;
+
+    if shelves::service::get_shelf_metadata(shelf_id).await?.book_count > 0 {
+        sync::service::log_change(
+            shelf_id,
+            ChangeLogEntityType::ShelfContent,
+            ChangeLogAction::Create,
+            &shelf.owner_id,
+            session_id,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Permanently deletes the books and shelves that have been in the trash for longer than the retention period.
+pub async fn purge_expired() {
+    let deleted_before = Utc::now()
+        .checked_sub_signed(retention())
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let (mut purged_books, mut purged_shelves) = (0, 0);
+
+    for book_id in repository::get_expired_books(deleted_before).await {
+        let lock = LOCKS.get_book_lock(&book_id).await;
+        let _guard = lock.write().await;
+
+        match books::service::purge_book(&book_id).await {
+            Ok(()) => purged_books += 1,
+            Err(e) => warn!("Failed to purge book {book_id} from the trash: {e:?}"),
+        }
+    }
+
+    for shelf_id in repository::get_expired_shelves(deleted_before).await {
+        let lock = LOCKS.get_shelf_lock(&shelf_id).await;
+        let _guard = lock.write().await;
+
+        match shelves::service::purge_shelf(&shelf_id).await {
+            Ok(()) => purged_shelves += 1,
+            Err(e) => warn!("Failed to purge shelf {shelf_id} from the trash: {e:?}"),
+        }
+    }
+
+    if purged_books + purged_shelves > 0 {
+        info!("Purged {purged_books} books and {purged_shelves} shelves from the trash");
+    }
+}
+
+fn purge_date(deleted_at: DateTime<Utc>) -> DateTime<Utc> {
+    deleted_at
+        .checked_add_signed(retention())
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn retention() -> TimeDelta {
+    TimeDelta::from_std(Duration::from_secs(CONFIG.trash.retention)).unwrap_or(TimeDelta::MAX)
+}
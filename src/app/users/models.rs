@@ -3,6 +3,7 @@ use merge::Merge;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use sqlx::{error::DatabaseError, prelude::FromRow, sqlite::SqliteError};
+use std::collections::HashMap;
 use strum_macros::{EnumMessage, EnumProperty};
 
 type SqlxError = sqlx::error::Error;
@@ -68,6 +69,14 @@ pub enum PreferencesError {
     #[strum(props(StatusCode = "400"))]
     MissingAutomaticMetadata,
 
+    #[strum(message = "Invalid or unsupported metadata merge policy.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidMergePolicy,
+
+    #[strum(message = "Invalid metadata refresh age.")]
+    #[strum(props(StatusCode = "400"))]
+    InvalidRefreshAge,
+
     #[strum(message = "Invalid or unsupported preferences provided.")]
     #[strum(props(StatusCode = "400"))]
     InvalidPreferences,
@@ -130,13 +139,24 @@ pub struct RefreshTokenRequest {
     pub refresh_token: String,
 }
 
-pub const VALID_PROVIDERS: [&str; 2] = ["epub_metadata_extractor", "goodreads_metadata_scraper"];
+pub const VALID_PROVIDERS: [&str; 4] = [
+    "epub_metadata_extractor",
+    "goodreads_metadata_scraper",
+    "open_library_metadata_provider",
+    "google_books_metadata_provider",
+];
+pub const MERGE_POLICIES: [&str; 3] = ["priority", "longest", "most_complete"];
 
 #[derive(FromRow, Serialize, Deserialize, Merge)]
 #[merge(strategy = merge::option::overwrite_none)]
 pub struct Preferences {
     pub metadata_providers: Option<Vec<String>>,
     pub automatic_metadata: Option<bool>,
+    pub metadata_review: Option<bool>,
+    pub metadata_refresh_age: Option<i64>,
+    pub embed_metadata: Option<bool>,
+    #[sqlx(skip)]
+    pub metadata_merge_policy: Option<HashMap<String, String>>,
 }
 
 #[derive(FromRow)]
@@ -153,9 +153,14 @@ pub async fn get_preferences(user_id: &str) -> Result<Preferences, PreferencesEr
     .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
     .await?;
 
-    let automatic_metadata: bool = sqlx::query_scalar(
+    let (automatic_metadata, metadata_review, metadata_refresh_age, embed_metadata): (
+        bool,
+        bool,
+        Option<i64>,
+        bool,
+    ) = sqlx::query_as(
         r"
-        SELECT automatic_metadata
+        SELECT automatic_metadata, metadata_review, metadata_refresh_age, embed_metadata
         FROM users
         WHERE user_id = $1
         ",
@@ -164,9 +169,24 @@ pub async fn get_preferences(user_id: &str) -> Result<Preferences, PreferencesEr
     .fetch_one(DB_POOL.get().expect("Failed to get database pool"))
     .await?;
 
+    let merge_policy: Vec<(String, String)> = sqlx::query_as(
+        r"
+        SELECT field, policy
+        FROM merge_policies
+        WHERE user_id = $1
+        ",
+    )
+    .bind(user_id)
+    .fetch_all(DB_POOL.get().expect("Failed to get database pool"))
+    .await?;
+
     Ok(Preferences {
         metadata_providers: Some(providers),
         automatic_metadata: Some(automatic_metadata),
+        metadata_review: Some(metadata_review),
+        metadata_refresh_age,
+        embed_metadata: Some(embed_metadata),
+        metadata_merge_policy: Some(merge_policy.into_iter().collect()),
     })
 }
 
@@ -177,6 +197,10 @@ pub async fn update_preferences(user_id: &str, preferences: Preferences) -> Resu
     let providers = preferences
         .metadata_providers
         .expect("Providers should be present");
+    let metadata_review = preferences.metadata_review.unwrap_or(false);
+    let metadata_refresh_age = preferences.metadata_refresh_age;
+    let embed_metadata = preferences.embed_metadata.unwrap_or(false);
+    let merge_policy = preferences.metadata_merge_policy.unwrap_or_default();
 
     let mut tx = DB_POOL
         .get()
@@ -187,11 +211,14 @@ pub async fn update_preferences(user_id: &str, preferences: Preferences) -> Resu
     sqlx::query(
         r"
         UPDATE users
-        SET automatic_metadata = $1
-        WHERE user_id = $2
+        SET automatic_metadata = $1, metadata_review = $2, metadata_refresh_age = $3, embed_metadata = $4
+        WHERE user_id = $5
         ",
     )
     .bind(automatic_metadata)
+    .bind(metadata_review)
+    .bind(metadata_refresh_age)
+    .bind(embed_metadata)
     .bind(user_id)
     .execute(&mut *tx)
     .await?;
@@ -207,20 +234,39 @@ pub async fn update_preferences(user_id: &str, preferences: Preferences) -> Resu
     .execute(&mut *tx)
     .await?;
 
-    if providers.is_empty() {
-        tx.commit().await?;
-        return Ok(());
+    sqlx::query(
+        r"
+        DELETE
+        FROM merge_policies
+        WHERE user_id = $1
+        ",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    if !providers.is_empty() {
+        let mut index = 1;
+        let mut query = QueryBuilder::new("INSERT INTO providers (provider_type, priority, user_id)");
+
+        query.push_values(providers, |mut b, provider| {
+            b.push_bind(provider).push_bind(index).push_bind(user_id);
+            index += 1;
+        });
+
+        query.build().execute(&mut *tx).await?;
     }
 
-    let mut index = 1;
-    let mut query = QueryBuilder::new("INSERT INTO providers (provider_type, priority, user_id)");
+    if !merge_policy.is_empty() {
+        let mut query = QueryBuilder::new("INSERT INTO merge_policies (user_id, field, policy)");
 
-    query.push_values(providers, |mut b, provider| {
-        b.push_bind(provider).push_bind(index).push_bind(user_id);
-        index += 1;
-    });
+        query.push_values(merge_policy, |mut b, (field, policy)| {
+            b.push_bind(user_id).push_bind(field).push_bind(policy);
+        });
+
+        query.build().execute(&mut *tx).await?;
+    }
 
-    query.build().execute(&mut *tx).await?;
     tx.commit().await?;
 
     Ok(())
@@ -1,14 +1,19 @@
 use super::models::{ApiKey, Preferences, User, UserError};
-use crate::app::{
-    authentication,
-    error::ProsaError,
-    users::{
-        models::{PreferencesError, UserProfile, VALID_PROVIDERS},
-        repository,
+use crate::{
+    CONFIG,
+    app::{
+        authentication,
+        error::ProsaError,
+        metadata::models::METADATA_FIELDS,
+        users::{
+            models::{MERGE_POLICIES, PreferencesError, UserProfile, VALID_PROVIDERS},
+            repository,
+        },
     },
 };
 use merge::Merge;
 use regex::Regex;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 pub async fn register_user(username: &str, password: &str, is_admin: bool) -> Result<String, ProsaError> {
@@ -80,8 +85,18 @@ pub async fn update_preferences(user_id: &str, preferences: Preferences) -> Resu
         return Err(PreferencesError::MissingAutomaticMetadata.into());
     }
 
-    if preferences.metadata_providers.is_none() {
+    let Some(providers) = &preferences.metadata_providers else {
         return Err(PreferencesError::InvalidMetadataProvider.into());
+    };
+
+    verify_providers(providers)?;
+
+    if let Some(merge_policy) = &preferences.metadata_merge_policy {
+        verify_merge_policy(merge_policy)?;
+    }
+
+    if preferences.metadata_refresh_age.is_some_and(|age| age < 0) {
+        return Err(PreferencesError::InvalidRefreshAge.into());
     }
 
     repository::update_preferences(user_id, preferences).await?;
@@ -91,10 +106,28 @@ pub async fn update_preferences(user_id: &str, preferences: Preferences) -> Resu
 pub async fn patch_preferences(user_id: &str, mut preferences: Preferences) -> Result<(), ProsaError> {
     repository::get_user(user_id).await?;
 
-    if preferences.automatic_metadata.is_none() && preferences.metadata_providers.is_none() {
+    if preferences.automatic_metadata.is_none()
+        && preferences.metadata_providers.is_none()
+        && preferences.metadata_review.is_none()
+        && preferences.metadata_merge_policy.is_none()
+        && preferences.metadata_refresh_age.is_none()
+        && preferences.embed_metadata.is_none()
+    {
         return Err(PreferencesError::InvalidPreferences.into());
     }
 
+    if let Some(providers) = &preferences.metadata_providers {
+        verify_providers(providers)?;
+    }
+
+    if let Some(merge_policy) = &preferences.metadata_merge_policy {
+        verify_merge_policy(merge_policy)?;
+    }
+
+    if preferences.metadata_refresh_age.is_some_and(|age| age < 0) {
+        return Err(PreferencesError::InvalidRefreshAge.into());
+    }
+
     let original = repository::get_preferences(user_id).await?;
     preferences.merge(original);
 
@@ -106,6 +139,32 @@ pub async fn patch_preferences(user_id: &str, mut preferences: Preferences) -> R
     Ok(())
 }
 
+pub fn is_valid_provider(provider: &str) -> bool {
+    VALID_PROVIDERS.contains(&provider) || CONFIG.metadata_plugins.iter().any(|p| p.name == provider)
+}
+
+fn verify_providers(providers: &[String]) -> Result<(), PreferencesError> {
+    if !providers.iter().all(|p| is_valid_provider(p)) {
+        return Err(PreferencesError::InvalidMetadataProvider);
+    }
+
+    Ok(())
+}
+
+fn verify_merge_policy(merge_policy: &HashMap<String, String>) -> Result<(), PreferencesError> {
+    for (field, policy) in merge_policy {
+        if !METADATA_FIELDS.contains(&field.as_str()) {
+            return Err(PreferencesError::InvalidMergePolicy);
+        }
+
+        if !MERGE_POLICIES.contains(&policy.as_str()) && !is_valid_provider(policy) {
+            return Err(PreferencesError::InvalidMergePolicy);
+        }
+    }
+
+    Ok(())
+}
+
 fn verify_username(username: &str) -> Result<(), UserError> {
     let filter = Regex::new(r"^[\w.!@-]+$").unwrap();
     if !filter.is_match(username) {
@@ -1,5 +1,6 @@
 use config::{Config, ConfigError, File};
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Default, Deserialize, Clone)]
 #[serde(default)]
@@ -7,7 +8,15 @@ pub struct Configuration {
     pub server: Server,
     pub auth: Auth,
     pub book_storage: BookStorage,
+    pub covers: Covers,
     pub metadata_cooldown: MetadataCooldown,
+    pub metadata_jobs: MetadataJobs,
+    pub metadata_refresh: MetadataRefresh,
+    pub trash: Trash,
+    pub change_log: ChangeLog,
+    pub open_library: OpenLibrary,
+    pub google_books: GoogleBooks,
+    pub metadata_plugins: Vec<MetadataPlugin>,
     pub database: Database,
     pub kepubify: Kepubify,
 }
@@ -35,6 +44,16 @@ pub struct Auth {
 pub struct BookStorage {
     pub epub_path: String,
     pub cover_path: String,
+    pub cache_path: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Covers {
+    pub max_width: u32,
+    pub max_height: u32,
+    pub quality: u8,
+    pub thumbnail_widths: Vec<u32>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -54,6 +73,70 @@ pub struct Database {
 pub struct MetadataCooldown {
     pub goodreads: u64,
     pub epub_extractor: u64,
+    pub open_library: u64,
+    pub google_books: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MetadataJobs {
+    pub timeout: u64,
+    pub retries: u32,
+    pub retry_backoff: u64,
+    pub concurrency: usize,
+    pub retention: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MetadataRefresh {
+    pub interval: u64,
+    pub max_age: u64,
+    pub retry_after: u64,
+    pub batch_size: usize,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Trash {
+    pub retention: u64,
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ChangeLog {
+    pub retention: u64,
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct OpenLibrary {
+    pub base_url: String,
+    pub covers_url: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct GoogleBooks {
+    pub base_url: String,
+    pub api_key: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MetadataPlugin {
+    pub name: String,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub cooldown: u64,
+    #[serde(default = "default_plugin_timeout")]
+    pub timeout: u64,
 }
 
 impl Default for Server {
@@ -83,6 +166,18 @@ impl Default for BookStorage {
         Self {
             epub_path: "library/epubs".to_string(),
             cover_path: "library/covers".to_string(),
+            cache_path: "library/cache".to_string(),
+        }
+    }
+}
+
+impl Default for Covers {
+    fn default() -> Self {
+        Self {
+            max_width: 1600,
+            max_height: 2400,
+            quality: 90,
+            thumbnail_widths: vec![160, 320, 640],
         }
     }
 }
@@ -92,6 +187,67 @@ impl Default for MetadataCooldown {
         Self {
             goodreads: 1000,
             epub_extractor: 0,
+            open_library: 1000,
+            google_books: 1000,
+        }
+    }
+}
+
+impl Default for MetadataJobs {
+    fn default() -> Self {
+        Self {
+            timeout: 60000,
+            retries: 2,
+            retry_backoff: 1000,
+            concurrency: 4,
+            retention: 2592000,
+        }
+    }
+}
+
+impl Default for MetadataRefresh {
+    fn default() -> Self {
+        Self {
+            interval: 3600,
+            max_age: 2592000,
+            retry_after: 604800,
+            batch_size: 100,
+        }
+    }
+}
+
+impl Default for Trash {
+    fn default() -> Self {
+        Self {
+            retention: 2592000,
+            interval: 3600,
+        }
+    }
+}
+
+impl Default for ChangeLog {
+    fn default() -> Self {
+        Self {
+            retention: 2592000,
+            interval: 3600,
+        }
+    }
+}
+
+impl Default for OpenLibrary {
+    fn default() -> Self {
+        Self {
+            base_url: "https://openlibrary.org".to_string(),
+            covers_url: "https://covers.openlibrary.org".to_string(),
+        }
+    }
+}
+
+impl Default for GoogleBooks {
+    fn default() -> Self {
+        Self {
+            base_url: "https://www.googleapis.com".to_string(),
+            api_key: None,
         }
     }
 }
@@ -112,6 +268,10 @@ impl Default for Kepubify {
     }
 }
 
+fn default_plugin_timeout() -> u64 {
+    10000
+}
+
 impl Configuration {
     pub fn new() -> Result<Self, ConfigError> {
         let config_path =
@@ -1,4 +1,4 @@
-use super::tables::{clear_tables, create_tables};
+use super::tables::{clear_tables, create_tables, migrate_tables};
 use sqlx::{Pool, Sqlite, SqlitePool, sqlite::SqliteConnectOptions};
 
 #[allow(dead_code)]
@@ -10,6 +10,7 @@ pub async fn init(filename: &str) -> Pool<Sqlite> {
     let pool = SqlitePool::connect_with(db_options).await.unwrap();
 
     create_tables(&pool).await;
+    migrate_tables(&pool).await;
 
     pool
 }
@@ -1,3 +1,4 @@
+use chrono::Utc;
 use sqlx::SqlitePool;
 
 pub async fn create_tables(pool: &SqlitePool) {
@@ -9,7 +10,10 @@ pub async fn create_tables(pool: &SqlitePool) {
             username TEXT NOT NULL UNIQUE,
             password_hash TEXT NOT NULL,
             is_admin BOOLEAN DEFAULT FALSE,
-            automatic_metadata BOOL NOT NULL DEFAULT TRUE
+            automatic_metadata BOOL NOT NULL DEFAULT TRUE,
+            metadata_review BOOL NOT NULL DEFAULT FALSE,
+            metadata_refresh_age INTEGER,
+            embed_metadata BOOL NOT NULL DEFAULT FALSE
         );
 
         CREATE TABLE IF NOT EXISTS refresh_tokens (
@@ -21,13 +25,21 @@ pub async fn create_tables(pool: &SqlitePool) {
         );
 
         CREATE TABLE IF NOT EXISTS providers (
-            provider_type TEXT NOT NULL CHECK(provider_type IN ('goodreads_metadata_scraper','epub_metadata_extractor')),
+            provider_type TEXT NOT NULL,
             priority INTEGER NOT NULL,
             user_id TEXT NOT NULL,
             FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE,
             PRIMARY KEY (provider_type, user_id)
         );
 
+        CREATE TABLE IF NOT EXISTS merge_policies (
+            user_id TEXT NOT NULL,
+            field TEXT NOT NULL CHECK(field IN ('title','subtitle','description','publisher','publication_date','isbn','contributors','genres','series','page_count','language')),
+            policy TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+            PRIMARY KEY (user_id, field)
+        );
+
         CREATE TABLE IF NOT EXISTS api_keys (
             key_id TEXT PRIMARY KEY NOT NULL,
             user_id TEXT NOT NULL,
@@ -59,6 +71,7 @@ pub async fn create_tables(pool: &SqlitePool) {
             metadata_id TEXT,
             cover_id TEXT,
             state_id TEXT NOT NULL,
+            deleted_at DATETIME,
             FOREIGN KEY(epub_id) REFERENCES epubs(epub_id) ON DELETE CASCADE,
             FOREIGN KEY(metadata_id) REFERENCES metadata(metadata_id) ON DELETE SET NULL,
             FOREIGN KEY(cover_id) REFERENCES covers(cover_id) ON DELETE SET NULL,
@@ -69,12 +82,14 @@ pub async fn create_tables(pool: &SqlitePool) {
 
         CREATE TABLE IF NOT EXISTS epubs (
             epub_id TEXT PRIMARY KEY NOT NULL,
-            hash TEXT NOT NULL UNIQUE
+            hash TEXT NOT NULL UNIQUE,
+            fingerprint INTEGER
         );
 
         CREATE TABLE IF NOT EXISTS covers (
             cover_id TEXT PRIMARY KEY NOT NULL,
-            hash TEXT NOT NULL UNIQUE
+            hash TEXT NOT NULL UNIQUE,
+            generated BOOL NOT NULL DEFAULT FALSE
         );
 
         CREATE TABLE IF NOT EXISTS metadata (
@@ -111,6 +126,45 @@ pub async fn create_tables(pool: &SqlitePool) {
             PRIMARY KEY(metadata_id, genre)
         );
 
+        CREATE TABLE IF NOT EXISTS metadata_fields (
+            book_id TEXT NOT NULL,
+            field TEXT NOT NULL CHECK(field IN ('title','subtitle','description','publisher','publication_date','isbn','contributors','genres','series','page_count','language')),
+            source TEXT,
+            updated_at DATETIME,
+            locked BOOL NOT NULL DEFAULT FALSE,
+            FOREIGN KEY(book_id) REFERENCES books(book_id) ON DELETE CASCADE,
+            PRIMARY KEY(book_id, field)
+        );
+
+        CREATE TABLE IF NOT EXISTS metadata_candidates (
+            candidate_id TEXT PRIMARY KEY NOT NULL,
+            book_id TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            metadata TEXT,
+            cover_id TEXT,
+            hash TEXT NOT NULL,
+            status TEXT NOT NULL CHECK(status IN ('pending','applied','rejected')),
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY(book_id) REFERENCES books(book_id) ON DELETE CASCADE,
+            FOREIGN KEY(cover_id) REFERENCES covers(cover_id) ON DELETE SET NULL,
+            UNIQUE(book_id, hash)
+        );
+
+        CREATE TABLE IF NOT EXISTS metadata_jobs (
+            job_id TEXT PRIMARY KEY NOT NULL,
+            user_id TEXT NOT NULL,
+            book_id TEXT NOT NULL,
+            providers TEXT NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL CHECK(status IN ('queued','running','succeeded','failed','cancelled')),
+            reason TEXT,
+            created_at DATETIME NOT NULL,
+            started_at DATETIME,
+            finished_at DATETIME,
+            FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+            FOREIGN KEY(book_id) REFERENCES books(book_id) ON DELETE CASCADE
+        );
+
         CREATE TABLE IF NOT EXISTS state (
             state_id TEXT PRIMARY KEY NOT NULL,
             tag TEXT,
@@ -122,15 +176,29 @@ pub async fn create_tables(pool: &SqlitePool) {
         CREATE TABLE IF NOT EXISTS annotations (
             annotation_id TEXT PRIMARY KEY NOT NULL,
             book_id TEXT NOT NULL,
+            annotation_type TEXT NOT NULL CHECK(annotation_type IN ('Highlight','Note','Bookmark')),
             source TEXT NOT NULL,
             start_tag TEXT NOT NULL,
             end_tag TEXT NOT NULL,
             start_char INTEGER NOT NULL,
             end_char INTEGER NOT NULL,
+            text TEXT,
             note TEXT,
+            color TEXT,
+            style TEXT,
+            orphaned BOOL NOT NULL DEFAULT FALSE,
+            created_at DATETIME NOT NULL,
+            updated_at DATETIME NOT NULL,
             FOREIGN KEY(book_id) REFERENCES books(book_id) ON DELETE CASCADE,
             UNIQUE (book_id, source, start_tag, end_tag, start_char, end_char)
         );
+
+        CREATE TABLE IF NOT EXISTS annotation_tags (
+            annotation_id TEXT NOT NULL,
+            tag TEXT NOT NULL COLLATE NOCASE,
+            FOREIGN KEY(annotation_id) REFERENCES annotations(annotation_id) ON DELETE CASCADE,
+            PRIMARY KEY(annotation_id, tag)
+        );
         ",
     )
     .execute(pool)
@@ -144,6 +212,7 @@ pub async fn create_tables(pool: &SqlitePool) {
             shelf_id TEXT PRIMARY KEY NOT NULL,
             name TEXT NOT NULL,
             owner_id TEXT NOT NULL,
+            deleted_at DATETIME,
             FOREIGN KEY(owner_id) REFERENCES users(user_id) ON DELETE CASCADE,
             UNIQUE (owner_id, name)
         );
@@ -165,12 +234,42 @@ pub async fn create_tables(pool: &SqlitePool) {
     sqlx::query(
         r"
         CREATE TABLE IF NOT EXISTS change_log (
-            log_id INTEGER PRIMARY KEY,
+            log_id INTEGER PRIMARY KEY AUTOINCREMENT,
             entity_id TEXT NOT NULL,
             entity_type TEXT NOT NULL CHECK(entity_type IN ('book_file','book_metadata','book_cover','book_state','book_annotations','shelf_metadata','shelf_content')),
             owner_id TEXT NOT NULL,
             session_id TEXT NOT NULL,
             action TEXT NOT NULL CHECK(action IN ('update','delete','create')),
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY(owner_id) REFERENCES users(user_id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS sync_sessions (
+            session_id TEXT NOT NULL,
+            owner_id TEXT NOT NULL,
+            sync_token INTEGER NOT NULL,
+            synced_at DATETIME NOT NULL,
+            resync_token INTEGER,
+            resync_cursor TEXT,
+            FOREIGN KEY(owner_id) REFERENCES users(user_id) ON DELETE CASCADE,
+            PRIMARY KEY(session_id, owner_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS devices (
+            device_id TEXT PRIMARY KEY NOT NULL,
+            owner_id TEXT NOT NULL,
+            session_id TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            device_type TEXT NOT NULL CHECK(device_type IN ('Ereader','Phone','Tablet','Computer','Other')),
+            sync_token INTEGER NOT NULL DEFAULT -1,
+            last_seen DATETIME,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY(owner_id) REFERENCES users(user_id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS change_log_retention (
+            owner_id TEXT PRIMARY KEY NOT NULL,
+            pruned_token INTEGER NOT NULL,
             FOREIGN KEY(owner_id) REFERENCES users(user_id) ON DELETE CASCADE
         );
         ",
@@ -180,24 +279,222 @@ pub async fn create_tables(pool: &SqlitePool) {
     .expect("Failed to create sync tables");
 }
 
+/// Brings tables created by an older version up to date, since `CREATE TABLE IF NOT EXISTS` leaves
+/// them as they were.
+pub async fn migrate_tables(pool: &SqlitePool) {
+    let now = Utc::now();
+
+    // Users
+    add_column(pool, "users", "metadata_review", "BOOL NOT NULL DEFAULT FALSE").await;
+    add_column(pool, "users", "metadata_refresh_age", "INTEGER").await;
+    add_column(pool, "users", "embed_metadata", "BOOL NOT NULL DEFAULT FALSE").await;
+
+    // Providers used to be limited to the built-in ones, which rejects every other provider and
+    // plugin, and a CHECK constraint cannot be dropped from an existing table
+    let providers = sqlx::query_scalar::<_, String>(
+        r"
+        SELECT sql
+        FROM sqlite_master
+        WHERE type = 'table' AND name = 'providers'
+        ",
+    )
+    .fetch_one(pool)
+    .await
+    .expect("Failed to read providers table");
+
+    if providers.contains("CHECK") {
+        let mut tx = pool.begin().await.expect("Failed to start transaction");
+
+        sqlx::query(
+            r"
+            CREATE TABLE providers_migrated (
+                provider_type TEXT NOT NULL,
+                priority INTEGER NOT NULL,
+                user_id TEXT NOT NULL,
+                FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+                PRIMARY KEY (provider_type, user_id)
+            );
+
+            INSERT INTO providers_migrated (provider_type, priority, user_id)
+            SELECT provider_type, priority, user_id
+            FROM providers;
+
+            DROP TABLE providers;
+            ALTER TABLE providers_migrated RENAME TO providers;
+            ",
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to migrate providers");
+
+        tx.commit().await.expect("Failed to commit transaction");
+    }
+
+    // Books
+    add_column(pool, "books", "deleted_at", "DATETIME").await;
+    add_column(pool, "epubs", "fingerprint", "INTEGER").await;
+    add_column(pool, "covers", "generated", "BOOL NOT NULL DEFAULT FALSE").await;
+
+    // Annotations
+    add_column(pool, "annotations", "text", "TEXT").await;
+    add_column(pool, "annotations", "color", "TEXT").await;
+    add_column(pool, "annotations", "style", "TEXT").await;
+    add_column(pool, "annotations", "orphaned", "BOOL NOT NULL DEFAULT FALSE").await;
+
+    if add_column(
+        pool,
+        "annotations",
+        "annotation_type",
+        "TEXT NOT NULL DEFAULT 'Highlight' CHECK(annotation_type IN ('Highlight','Note','Bookmark'))",
+    )
+    .await
+    {
+        sqlx::query("UPDATE annotations SET annotation_type = 'Note' WHERE note IS NOT NULL")
+            .execute(pool)
+            .await
+            .expect("Failed to migrate annotation types");
+    }
+
+    // Existing annotations are dated from the migration, their creation time is unknown
+    if add_column(pool, "annotations", "created_at", "DATETIME NOT NULL DEFAULT 0").await {
+        sqlx::query("UPDATE annotations SET created_at = ?")
+            .bind(now)
+            .execute(pool)
+            .await
+            .expect("Failed to migrate annotation timestamps");
+    }
+
+    if add_column(pool, "annotations", "updated_at", "DATETIME NOT NULL DEFAULT 0").await {
+        sqlx::query("UPDATE annotations SET updated_at = created_at")
+            .execute(pool)
+            .await
+            .expect("Failed to migrate annotation timestamps");
+    }
+
+    // Shelves
+    add_column(pool, "shelf", "deleted_at", "DATETIME").await;
+
+    // Sync
+    add_column(pool, "sync_sessions", "resync_token", "INTEGER").await;
+    add_column(pool, "sync_sessions", "resync_cursor", "TEXT").await;
+
+    // Change log ids have to keep growing even when the latest entry is deleted, which only
+    // AUTOINCREMENT guarantees and cannot be added to an existing table
+    let change_log = sqlx::query_scalar::<_, String>(
+        r"
+        SELECT sql
+        FROM sqlite_master
+        WHERE type = 'table' AND name = 'change_log'
+        ",
+    )
+    .fetch_one(pool)
+    .await
+    .expect("Failed to read change log table");
+
+    if !change_log.contains("AUTOINCREMENT") {
+        let mut tx = pool.begin().await.expect("Failed to start transaction");
+
+        sqlx::query(
+            r"
+            CREATE TABLE change_log_migrated (
+                log_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity_id TEXT NOT NULL,
+                entity_type TEXT NOT NULL CHECK(entity_type IN ('book_file','book_metadata','book_cover','book_state','book_annotations','shelf_metadata','shelf_content')),
+                owner_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                action TEXT NOT NULL CHECK(action IN ('update','delete','create')),
+                created_at DATETIME NOT NULL,
+                FOREIGN KEY(owner_id) REFERENCES users(user_id) ON DELETE CASCADE
+            )
+            ",
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to create change log table");
+
+        // Existing entries are dated from the migration, so that pruning keeps them for a while
+        sqlx::query(
+            r"
+            INSERT INTO change_log_migrated (log_id, entity_id, entity_type, owner_id, session_id, action, created_at)
+            SELECT log_id, entity_id, entity_type, owner_id, session_id, action, ?
+            FROM change_log
+            ",
+        )
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to migrate change log");
+
+        sqlx::query(
+            r"
+            DROP TABLE change_log;
+            ALTER TABLE change_log_migrated RENAME TO change_log;
+            ",
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to replace change log table");
+
+        tx.commit().await.expect("Failed to commit transaction");
+    }
+}
+
+/// Adds a column unless the table already has it, returning whether it was added.
+async fn add_column(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> bool {
+    let exists = sqlx::query_scalar::<_, bool>(
+        r"
+        SELECT EXISTS(
+            SELECT 1
+            FROM pragma_table_info(?)
+            WHERE name = ?
+        )
+        ",
+    )
+    .bind(table)
+    .bind(column)
+    .fetch_one(pool)
+    .await
+    .expect("Failed to read table columns");
+
+    if exists {
+        return false;
+    }
+
+    sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"))
+        .execute(pool)
+        .await
+        .expect("Failed to add column");
+
+    true
+}
+
 pub async fn clear_tables(pool: &SqlitePool) {
     sqlx::query(
         r"
         DROP TABLE IF EXISTS key_capabilities;
         DROP TABLE IF EXISTS providers;
+        DROP TABLE IF EXISTS merge_policies;
         DROP TABLE IF EXISTS refresh_tokens;
         DROP TABLE IF EXISTS shelf;
         DROP TABLE IF EXISTS is_in_shelf;
+        DROP TABLE IF EXISTS annotation_tags;
+        DROP TABLE IF EXISTS annotations;
         DROP TABLE IF EXISTS books;
         DROP TABLE IF EXISTS series;
         DROP TABLE IF EXISTS contributors;
         DROP TABLE IF EXISTS genres;
+        DROP TABLE IF EXISTS metadata_fields;
+        DROP TABLE IF EXISTS metadata_candidates;
+        DROP TABLE IF EXISTS metadata_jobs;
         DROP TABLE IF EXISTS api_keys;
         DROP TABLE IF EXISTS epubs;
         DROP TABLE IF EXISTS covers;
         DROP TABLE IF EXISTS metadata;
         DROP TABLE IF EXISTS state;
         DROP TABLE IF EXISTS change_log;
+        DROP TABLE IF EXISTS sync_sessions;
+        DROP TABLE IF EXISTS devices;
+        DROP TABLE IF EXISTS change_log_retention;
         DROP TABLE IF EXISTS users;
         ",
     )
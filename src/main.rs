@@ -47,11 +47,15 @@ async fn run_startup_checks() -> Result<(), Box<dyn std::error::Error>> {
         return Err("admin_key must be configured and at least 8 characters long".into());
     }
 
+    app::verify_plugins(&CONFIG.metadata_plugins)?;
+
     create_parent_dir(&CONFIG.database.file_path).await?;
     create_parent_dir(&CONFIG.auth.public_key_path).await?;
     create_parent_dir(&CONFIG.auth.private_key_path).await?;
     create_dir_all(&CONFIG.book_storage.epub_path).await?;
     create_dir_all(&CONFIG.book_storage.cover_path).await?;
+    create_dir_all(&CONFIG.book_storage.cache_path).await?;
+    create_dir_all(format!("{}/covers", CONFIG.book_storage.cache_path)).await?;
 
     Ok(())
 }
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Metadata plugin used by the integration tests.
+
+request=$(cat)
+
+if [ "$1" = "--slow" ]; then
+  sleep 3
+fi
+
+case "$request" in
+  *"Alice's Adventures in Wonderland"*)
+    echo '{"metadata": {"title": "Alice in Wonderland", "publisher": "Catalogue Press", "page_count": 150}}'
+    ;;
+  *)
+    exit 1
+    ;;
+esac
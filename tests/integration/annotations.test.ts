@@ -1,7 +1,7 @@
-import { addAnnotation, ALICE_NOTE, ANNOTATION_CONFLICT, ANNOTATION_NOT_FOUND, deleteAnnotation, getAnnotation, INVALID_ANNOTATION, listAnnotations, patchAnnotation } from '../utils/annotations.js';
-import { BOOK_NOT_FOUND, uploadBook } from '../utils/books.js';
+import { addAnnotation, ALICE_BOOKMARK, ALICE_CLIPPINGS, ALICE_NOTE, ANNOTATION_CONFLICT, ANNOTATION_NOT_FOUND, deleteAnnotation, exportAnnotations, exportBookAnnotations, getAnnotation, importAnnotations, INVALID_ANNOTATION, INVALID_ANNOTATION_TYPE, INVALID_EXPORT_FORMAT, INVALID_IMPORT_FILE, INVALID_IMPORT_FORMAT, INVALID_TAGS, INVALID_TIMESTAMP, listAnnotations, patchAnnotation, searchAnnotations } from '../utils/annotations.js';
+import { BOOK_NOT_FOUND, INVALID_PAGINATION, uploadBook } from '../utils/books.js';
 import { FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
-import { createApiKey, registerUser } from '../utils/users.js';
+import { createApiKey, registerUser, USER_NOT_FOUND } from '../utils/users.js';
 
 describe('Add annotation JWT', () => {
   test('Simple', async () => {
@@ -16,6 +16,46 @@ describe('Add annotation JWT', () => {
     expect(addAnnotationResponse.status).toBe(200);
   });
 
+  test('Colour and style', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, color: '#FFD700', style: 'Underline' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.color).toBe('#FFD700');
+    expect(getAnnotationResponse.body.style).toBe('Underline');
+    expect(getAnnotationResponse.body.text.length).toBeGreaterThan(0);
+    expect(getAnnotationResponse.body.created_at).toBe(getAnnotationResponse.body.updated_at);
+  });
+
+  test('Invalid colour and style', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    let addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, color: 'yellow' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(400);
+    expect(addAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+
+    addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, color: '#FFD70' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(400);
+    expect(addAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+
+    addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, style: 'Bold' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(400);
+    expect(addAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+  });
+
   test('Non-existent book', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -349,6 +389,10 @@ describe('Get annotation JWT', () => {
 
     let expectedResponse: any = structuredClone(ALICE_NOTE);
     expectedResponse['annotation_id'] = addAnnotationResponse.text;
+    expectedResponse['orphaned'] = false;
+    expectedResponse['text'] = expect.any(String);
+    expectedResponse['created_at'] = expect.any(Number);
+    expectedResponse['updated_at'] = expect.any(Number);
 
     const getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
     expect(getAnnotationResponse.status).toBe(200);
@@ -445,6 +489,10 @@ describe('Get annotation api key', () => {
 
     let expectedResponse: any = structuredClone(ALICE_NOTE);
     expectedResponse['annotation_id'] = addAnnotationResponse.text;
+    expectedResponse['orphaned'] = false;
+    expectedResponse['text'] = expect.any(String);
+    expectedResponse['created_at'] = expect.any(Number);
+    expectedResponse['updated_at'] = expect.any(Number);
 
     const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read'], undefined, { jwt: registerResponse.body.jwt_token });
     expect(createApiKeyResponse.status).toBe(200);
@@ -872,6 +920,10 @@ describe('Delete annotation api key', () => {
 
     let expectedResponse: any = structuredClone(ALICE_NOTE);
     expectedResponse['annotation_id'] = addAnnotationResponse.text;
+    expectedResponse['orphaned'] = false;
+    expectedResponse['text'] = expect.any(String);
+    expectedResponse['created_at'] = expect.any(Number);
+    expectedResponse['updated_at'] = expect.any(Number);
 
     const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update'], undefined, { jwt: registerResponse.body.jwt_token });
     expect(createApiKeyResponse.status).toBe(200);
@@ -1019,6 +1071,10 @@ describe('Patch annotation JWT', () => {
 
     let expectedResponse: any = structuredClone(ALICE_NOTE);
     expectedResponse['annotation_id'] = addAnnotationResponse.text;
+    expectedResponse['orphaned'] = false;
+    expectedResponse['text'] = expect.any(String);
+    expectedResponse['created_at'] = expect.any(Number);
+    expectedResponse['updated_at'] = expect.any(Number);
 
     let getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
     expect(getAnnotationResponse.status).toBe(200);
@@ -1043,6 +1099,47 @@ describe('Patch annotation JWT', () => {
     expect(getAnnotationResponse.body).toEqual(expectedResponse);
   });
 
+  test('Colour and style', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, color: '#FFD700', style: 'Underline' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    let getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    const createdAt = getAnnotationResponse.body.created_at;
+
+    await wait(0.1);
+
+    let patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { color: '#00FF00' });
+    expect(patchAnnotationResponse.status).toBe(204);
+
+    getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.color).toBe('#00FF00');
+    expect(getAnnotationResponse.body.style).toBe('Underline');
+    expect(getAnnotationResponse.body.note).toBe(ALICE_NOTE.note);
+    expect(getAnnotationResponse.body.created_at).toBe(createdAt);
+    expect(getAnnotationResponse.body.updated_at).toBeGreaterThan(createdAt);
+
+    patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { style: '' });
+    expect(patchAnnotationResponse.status).toBe(204);
+
+    getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.color).toBe('#00FF00');
+    expect(getAnnotationResponse.body.style).toBeUndefined();
+
+    patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { color: 'green' });
+    expect(patchAnnotationResponse.status).toBe(400);
+    expect(patchAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+  });
+
   test('Non-existent annotation', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -1133,6 +1230,10 @@ describe('Delete annotation api key', () => {
 
     let expectedResponse: any = structuredClone(ALICE_NOTE);
     expectedResponse['annotation_id'] = addAnnotationResponse.text;
+    expectedResponse['orphaned'] = false;
+    expectedResponse['text'] = expect.any(String);
+    expectedResponse['created_at'] = expect.any(Number);
+    expectedResponse['updated_at'] = expect.any(Number);
 
     let getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
     expect(getAnnotationResponse.status).toBe(200);
@@ -1276,3 +1377,826 @@ describe('Delete annotation api key', () => {
     expect(patchAnnotationResponse.text).toBe(INVALID_API_KEY);
   });
 });
+
+describe('Export book annotations', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text, 'json', { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(200);
+    expect(exportResponse.body.book_id).toBe(uploadResponse.text);
+    expect(exportResponse.body.title).toBe("Alice's Adventures in Wonderland");
+    expect(exportResponse.body.authors).toEqual(['Lewis Carroll']);
+    expect(exportResponse.body.annotations).toHaveLength(1);
+
+    const annotation = exportResponse.body.annotations[0];
+    expect(annotation.annotation_id).toBe(addAnnotationResponse.text);
+    expect(annotation.chapter).toBe('CHAPTER X. The Lobster Quadrille');
+    expect(annotation.text.length).toBeGreaterThan(0);
+    expect(annotation.note).toBe(ALICE_NOTE.note);
+  });
+
+  test('Markdown', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(200);
+    expect(exportResponse.headers['content-type']).toContain('text/markdown');
+    expect(exportResponse.text).toContain("# Alice's Adventures in Wonderland");
+    expect(exportResponse.text).toContain('## CHAPTER X. The Lobster Quadrille');
+    expect(exportResponse.text).toContain('\n> ');
+    expect(exportResponse.text).toContain(ALICE_NOTE.note);
+  });
+
+  test('Markdown multi-line note', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, note: 'First line\n\n# Not a heading' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(200);
+    expect(exportResponse.text).toContain('> **Note:** First line\n>\n> # Not a heading\n');
+    expect(exportResponse.text).not.toContain('\n# Not a heading');
+  });
+
+  test('CSV', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text, 'csv', { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(200);
+    expect(exportResponse.headers['content-type']).toContain('text/csv');
+
+    const lines = exportResponse.text.trim().split('\r\n');
+    expect(lines).toHaveLength(2);
+    expect(lines[0]).toBe('Highlight,Title,Author,Note,Chapter,Book ID,Annotation ID');
+    expect(lines[1]).toContain(ALICE_NOTE.note);
+    expect(lines[1]).toContain(addAnnotationResponse.text);
+  });
+
+  test('Invalid format', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text, 'pdf', { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(400);
+    expect(exportResponse.text).toBe(INVALID_EXPORT_FORMAT);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text, 'json', { jwt: registerResponse2.body.jwt_token });
+    expect(exportResponse.status).toBe(404);
+    expect(exportResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const exportResponse = await exportBookAnnotations(uploadResponse.text);
+    expect(exportResponse.status).toBe(401);
+    expect(exportResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Export all annotations', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const uploadResponse2 = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse2.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const exportResponse = await exportAnnotations(undefined, 'json', { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(200);
+    expect(exportResponse.body).toHaveLength(1);
+    expect(exportResponse.body[0].book_id).toBe(uploadResponse.text);
+    expect(exportResponse.body[0].annotations[0].annotation_id).toBe(addAnnotationResponse.text);
+  });
+
+  test('Invalid format', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const exportResponse = await exportAnnotations(undefined, 'pdf', { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(400);
+    expect(exportResponse.text).toBe(INVALID_EXPORT_FORMAT);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const exportResponse = await exportAnnotations(userId, 'json', { jwt: registerResponse2.body.jwt_token });
+    expect(exportResponse.status).toBe(403);
+    expect(exportResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const exportResponse = await exportAnnotations(userId, 'json', { jwt: registerResponse2.body.jwt_token });
+    expect(exportResponse.status).toBe(200);
+    expect(exportResponse.body).toHaveLength(1);
+  });
+
+  test('Non-existent user', async () => {
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const exportResponse = await exportAnnotations('non-existent', 'json', { jwt: registerResponse.body.jwt_token });
+    expect(exportResponse.status).toBe(404);
+    expect(exportResponse.text).toBe(USER_NOT_FOUND);
+  });
+});
+
+describe('Import annotations', () => {
+  test('Kindle', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+    expect(importResponse.body.imported).toBe(1);
+    expect(importResponse.body.existing).toBe(0);
+    expect(importResponse.body.unmatched).toHaveLength(2);
+    expect(importResponse.body.unmatched[0].title).toBe('Some Other Book');
+    expect(importResponse.body.unmatched[0].reason).toBe('book_not_found');
+    expect(importResponse.body.unmatched[1].text).toBe('This sentence is not in the book at all.');
+    expect(importResponse.body.unmatched[1].reason).toBe('text_not_found');
+
+    const listResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toHaveLength(1);
+
+    const getResponse = await getAnnotation(uploadResponse.text, listResponse.body[0], { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.text).toContain('nor did Alice think it so very much out of the way');
+    expect(getResponse.body.note).toBe('Poor rabbit');
+  });
+
+  test('Import twice', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+    expect(importResponse.body.imported).toBe(1);
+
+    const importResponse2 = await importAnnotations(ALICE_CLIPPINGS, 'kindle', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse2.status).toBe(200);
+    expect(importResponse2.body.imported).toBe(0);
+    expect(importResponse2.body.existing).toBe(1);
+
+    const listResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toHaveLength(1);
+  });
+
+  test('KOReader', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const metadata = `-- we can read Lua syntax here!
+return {
+    ["annotations"] = {
+        [1] = {
+            ["color"] = "yellow",
+            ["drawer"] = "underscore",
+            ["note"] = "Daisies",
+            ["text"] = "whether the pleasure of making a daisy-chain would be worth the trouble",
+        },
+        [2] = {
+            ["page"] = "/body/DocFragment[3]/body/div/p[1]/text().0",
+            ["text"] = "in bookmark",
+        },
+    },
+    ["doc_props"] = {
+        ["authors"] = "Lewis Carroll",
+        ["title"] = "Alice's Adventures in Wonderland",
+    },
+}`;
+
+    const importResponse = await importAnnotations(metadata, 'koreader', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+    expect(importResponse.body).toEqual({ imported: 1, existing: 0, unmatched: [] });
+
+    const listResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toHaveLength(1);
+
+    const getResponse = await getAnnotation(uploadResponse.text, listResponse.body[0], { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.note).toBe('Daisies');
+    expect(getResponse.body.color).toBe('#FFFF33');
+    expect(getResponse.body.style).toBe('Underline');
+  });
+
+  test('Empty note', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const metadata = `return {
+    ["annotations"] = {
+        [1] = {
+            ["drawer"] = "lighten",
+            ["note"] = "  ",
+            ["text"] = "whether the pleasure of making a daisy-chain would be worth the trouble",
+        },
+    },
+    ["doc_props"] = {
+        ["authors"] = "Lewis Carroll",
+        ["title"] = "Alice's Adventures in Wonderland",
+    },
+}`;
+
+    // A cleared note leaves a plain highlight
+    const importResponse = await importAnnotations(metadata, 'koreader', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+    expect(importResponse.body).toEqual({ imported: 1, existing: 0, unmatched: [] });
+
+    const listResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+
+    const getResponse = await getAnnotation(uploadResponse.text, listResponse.body[0], { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.annotation_type).toBe('Highlight');
+    expect(getResponse.body.note).toBeUndefined();
+  });
+
+  test('Calibre', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const annotations = JSON.stringify({
+      annotations: [
+        { type: 'highlight', highlighted_text: 'when suddenly a White Rabbit with pink eyes ran close by her.', notes: 'rabbit!', style: { kind: 'color', type: 'builtin', which: 'green' } },
+        { type: 'bookmark', title: 'Bookmark 1' },
+        { type: 'highlight', highlighted_text: 'Down the Rabbit-Hole', removed: true }
+      ]
+    });
+
+    const withoutBookResponse = await importAnnotations(annotations, 'calibre', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(withoutBookResponse.status).toBe(200);
+    expect(withoutBookResponse.body.imported).toBe(0);
+    expect(withoutBookResponse.body.unmatched).toHaveLength(1);
+    expect(withoutBookResponse.body.unmatched[0].reason).toBe('book_not_found');
+
+    const importResponse = await importAnnotations(annotations, 'calibre', uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+    expect(importResponse.body).toEqual({ imported: 1, existing: 0, unmatched: [] });
+
+    const listResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+
+    const getResponse = await getAnnotation(uploadResponse.text, listResponse.body[0], { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.text).toBe('when suddenly a White Rabbit with pink eyes ran close by her.');
+    expect(getResponse.body.note).toBe('rabbit!');
+    expect(getResponse.body.color).toBe('#C0ED72');
+    expect(getResponse.body.style).toBe('Highlight');
+  });
+
+  test('Invalid format', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'pdf', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(400);
+    expect(importResponse.text).toBe(INVALID_IMPORT_FORMAT);
+
+    const missingFormatResponse = await importAnnotations(ALICE_CLIPPINGS, undefined, undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(missingFormatResponse.status).toBe(400);
+    expect(missingFormatResponse.text).toBe(INVALID_IMPORT_FORMAT);
+  });
+
+  test('Invalid file', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'calibre', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(400);
+    expect(importResponse.text).toBe(INVALID_IMPORT_FILE);
+
+    const importResponse2 = await importAnnotations('{ "annotations": [] }', 'koreader', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse2.status).toBe(400);
+    expect(importResponse2.text).toBe(INVALID_IMPORT_FILE);
+  });
+
+  test('Deeply nested file', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const nested = `return ${'{'.repeat(100000)}${'}'.repeat(100000)}`;
+    const importResponse = await importAnnotations(nested, 'koreader', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(400);
+    expect(importResponse.text).toBe(INVALID_IMPORT_FILE);
+
+    const nestedKeys = `return { ${'a = {'.repeat(100000)}${'}'.repeat(100000)} }`;
+    const importResponse2 = await importAnnotations(nestedKeys, 'koreader', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse2.status).toBe(400);
+    expect(importResponse2.text).toBe(INVALID_IMPORT_FILE);
+  });
+
+  test('Non-existent book', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle', 'non-existent', undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(404);
+    expect(importResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle', undefined, userId, { jwt: registerResponse2.body.jwt_token });
+    expect(importResponse.status).toBe(403);
+    expect(importResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle', undefined, userId, { jwt: registerResponse2.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+    expect(importResponse.body.imported).toBe(1);
+  });
+
+  test('No auth', async () => {
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle');
+    expect(importResponse.status).toBe(401);
+    expect(importResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Annotation types', () => {
+  test('Bookmark', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_BOOKMARK, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body).toEqual({
+      ...ALICE_BOOKMARK,
+      annotation_id: addAnnotationResponse.text,
+      end_tag: ALICE_BOOKMARK.start_tag,
+      end_char: ALICE_BOOKMARK.start_char,
+      tags: [],
+      orphaned: false,
+      created_at: expect.any(Number),
+      updated_at: expect.any(Number)
+    });
+  });
+
+  test('Invalid bookmark', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const rangeResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, annotation_type: 'Bookmark' }, { jwt: registerResponse.body.jwt_token });
+    expect(rangeResponse.status).toBe(400);
+    expect(rangeResponse.text).toBe(INVALID_ANNOTATION);
+
+    const styleResponse = await addAnnotation(uploadResponse.text, { ...ALICE_BOOKMARK, style: 'Underline' }, { jwt: registerResponse.body.jwt_token });
+    expect(styleResponse.status).toBe(400);
+    expect(styleResponse.text).toBe(INVALID_ANNOTATION);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_BOOKMARK, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { color: '#FFD700' });
+    expect(patchAnnotationResponse.status).toBe(400);
+    expect(patchAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+  });
+
+  test('Note', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const withoutNoteResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, annotation_type: 'Note', note: undefined }, { jwt: registerResponse.body.jwt_token });
+    expect(withoutNoteResponse.status).toBe(400);
+    expect(withoutNoteResponse.text).toBe(INVALID_ANNOTATION);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, annotation_type: 'Note' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.annotation_type).toBe('Note');
+
+    const patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, '', { jwt: registerResponse.body.jwt_token });
+    expect(patchAnnotationResponse.status).toBe(400);
+    expect(patchAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+  });
+
+  test('Invalid type', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, annotation_type: 'Sticker' }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(400);
+    expect(addAnnotationResponse.text).toBe(INVALID_ANNOTATION);
+
+    const listAnnotationsResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, 'Sticker');
+    expect(listAnnotationsResponse.status).toBe(400);
+    expect(listAnnotationsResponse.text).toBe(INVALID_ANNOTATION_TYPE);
+  });
+
+  test('List by type', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const highlightResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(highlightResponse.status).toBe(200);
+
+    const bookmarkResponse = await addAnnotation(uploadResponse.text, { ...ALICE_BOOKMARK, source: 'OEBPS/229714655232534212_11-h-1.htm.xhtml', start_tag: 'kobo.1.1' }, { jwt: registerResponse.body.jwt_token });
+    expect(bookmarkResponse.status).toBe(200);
+
+    let listAnnotationsResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, 'Bookmark');
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body).toEqual([bookmarkResponse.text]);
+
+    listAnnotationsResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, 'Highlight');
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body).toEqual([highlightResponse.text]);
+
+    listAnnotationsResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, 'Note');
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body).toEqual([]);
+
+    listAnnotationsResponse = await listAnnotations(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body).toHaveLength(2);
+  });
+});
+
+describe('Annotation tags', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, tags: [' quotes ', 'Quotes', 'favourites'] }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    let getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.tags).toEqual(['favourites', 'quotes']);
+
+    let patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, 'Changed note', { jwt: registerResponse.body.jwt_token });
+    expect(patchAnnotationResponse.status).toBe(204);
+
+    getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.body.tags).toEqual(['favourites', 'quotes']);
+
+    patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { tags: ['chapter 10'] });
+    expect(patchAnnotationResponse.status).toBe(204);
+
+    getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.body.tags).toEqual(['chapter 10']);
+
+    patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { tags: [] });
+    expect(patchAnnotationResponse.status).toBe(204);
+
+    getAnnotationResponse = await getAnnotation(uploadResponse.text, addAnnotationResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getAnnotationResponse.body.tags).toEqual([]);
+  });
+
+  test('Invalid tags', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const emptyResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, tags: ['  '] }, { jwt: registerResponse.body.jwt_token });
+    expect(emptyResponse.status).toBe(400);
+    expect(emptyResponse.text).toBe(INVALID_TAGS);
+
+    const longResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, tags: ['a'.repeat(31)] }, { jwt: registerResponse.body.jwt_token });
+    expect(longResponse.status).toBe(400);
+    expect(longResponse.text).toBe(INVALID_TAGS);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const patchAnnotationResponse = await patchAnnotation(uploadResponse.text, addAnnotationResponse.text, undefined, { jwt: registerResponse.body.jwt_token }, { tags: [''] });
+    expect(patchAnnotationResponse.status).toBe(400);
+    expect(patchAnnotationResponse.text).toBe(INVALID_TAGS);
+  });
+});
+
+describe('Search annotations', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    let searchResponse = await searchAnnotations({}, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body).toEqual({ annotations: [], page_size: 10, total_elements: 0, total_pages: 0, current_page: 1 });
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, tags: ['favourites'] }, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    searchResponse = await searchAnnotations({}, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.total_elements).toBe(1);
+    expect(searchResponse.body.annotations[0].annotation_id).toBe(addAnnotationResponse.text);
+    expect(searchResponse.body.annotations[0].book_id).toBe(uploadResponse.text);
+    expect(searchResponse.body.annotations[0].note).toBe(ALICE_NOTE.note);
+    expect(searchResponse.body.annotations[0].tags).toEqual(['favourites']);
+  });
+
+  test('Text', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const importResponse = await importAnnotations(ALICE_CLIPPINGS, 'kindle', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(importResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    let searchResponse = await searchAnnotations({ q: 'RABBIT dear' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.total_elements).toBe(1);
+    expect(searchResponse.body.annotations[0].note).toBe('Poor rabbit');
+
+    searchResponse = await searchAnnotations({ q: 'loved' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.total_elements).toBe(1);
+    expect(searchResponse.body.annotations[0].annotation_id).toBe(addAnnotationResponse.text);
+
+    searchResponse = await searchAnnotations({ q: 'rabbit loved' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.total_elements).toBe(0);
+  });
+
+  test('Filters', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const uploadResponse2 = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse2.status).toBe(200);
+
+    const before = Date.now();
+    const highlightResponse = await addAnnotation(uploadResponse.text, { ...ALICE_NOTE, tags: ['Favourites'] }, { jwt: registerResponse.body.jwt_token });
+    expect(highlightResponse.status).toBe(200);
+
+    const bookmarkResponse = await addAnnotation(uploadResponse.text, { ...ALICE_BOOKMARK, source: 'OEBPS/229714655232534212_11-h-1.htm.xhtml', start_tag: 'kobo.1.1' }, { jwt: registerResponse.body.jwt_token });
+    expect(bookmarkResponse.status).toBe(200);
+
+    let searchResponse = await searchAnnotations({ tag: 'favourites' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.body.annotations.map((a: any) => a.annotation_id)).toEqual([highlightResponse.text]);
+
+    searchResponse = await searchAnnotations({ type: 'Bookmark' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.body.annotations.map((a: any) => a.annotation_id)).toEqual([bookmarkResponse.text]);
+
+    searchResponse = await searchAnnotations({ book_id: uploadResponse2.text }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.body.total_elements).toBe(0);
+
+    searchResponse = await searchAnnotations({ from: before }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.body.total_elements).toBe(2);
+
+    searchResponse = await searchAnnotations({ to: before - 1 }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.body.total_elements).toBe(0);
+  });
+
+  test('Pagination', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const highlightResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(highlightResponse.status).toBe(200);
+
+    await wait(0.01);
+
+    const bookmarkResponse = await addAnnotation(uploadResponse.text, ALICE_BOOKMARK, { jwt: registerResponse.body.jwt_token });
+    expect(bookmarkResponse.status).toBe(200);
+
+    let searchResponse = await searchAnnotations({ page: 1, size: 1 }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.page_size).toBe(1);
+    expect(searchResponse.body.total_elements).toBe(2);
+    expect(searchResponse.body.total_pages).toBe(2);
+    expect(searchResponse.body.current_page).toBe(1);
+    expect(searchResponse.body.annotations[0].annotation_id).toBe(bookmarkResponse.text);
+
+    searchResponse = await searchAnnotations({ page: 2, size: 1 }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.annotations[0].annotation_id).toBe(highlightResponse.text);
+  });
+
+  test('Invalid parameters', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    let searchResponse = await searchAnnotations({ page: 0 }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(400);
+    expect(searchResponse.text).toBe(INVALID_PAGINATION);
+
+    searchResponse = await searchAnnotations({ size: 'abc' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(400);
+    expect(searchResponse.text).toBe(INVALID_PAGINATION);
+
+    searchResponse = await searchAnnotations({ type: 'Sticker' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(400);
+    expect(searchResponse.text).toBe(INVALID_ANNOTATION_TYPE);
+
+    searchResponse = await searchAnnotations({ from: 'yesterday' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(400);
+    expect(searchResponse.text).toBe(INVALID_TIMESTAMP);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const searchResponse = await searchAnnotations({ user_id: userId }, { jwt: registerResponse2.body.jwt_token });
+    expect(searchResponse.status).toBe(403);
+    expect(searchResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addAnnotationResponse = await addAnnotation(uploadResponse.text, ALICE_NOTE, { jwt: registerResponse.body.jwt_token });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const searchResponse = await searchAnnotations({ user_id: userId }, { jwt: registerResponse2.body.jwt_token });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.total_elements).toBe(1);
+  });
+
+  test('Non-existent user', async () => {
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const searchResponse = await searchAnnotations({ user_id: 'non-existent' }, { jwt: registerResponse.body.jwt_token });
+    expect(searchResponse.status).toBe(404);
+    expect(searchResponse.text).toBe(USER_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const searchResponse = await searchAnnotations({});
+    expect(searchResponse.status).toBe(401);
+    expect(searchResponse.text).toBe(UNAUTHORIZED);
+  });
+});
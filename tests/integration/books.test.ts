@@ -1,11 +1,13 @@
 import fs from 'fs';
 import path from 'path';
 import { addAnnotation, ALICE_NOTE, getAnnotation } from '../utils/annotations.js';
-import { BOOK_CONFLICT, BOOK_ID_CONFLICT, BOOK_NOT_FOUND, deleteBook, downloadBook, getBookFileMetadata, INVALID_BOOK, INVALID_BOOK_ID, INVALID_PAGINATION, searchBooks, uploadBook } from '../utils/books.js';
-import { BOOK_DIR, FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
+import { BOOK_CONFLICT, BOOK_ID_CONFLICT, BOOK_NOT_FOUND, deleteBook, downloadBook, getBookFileMetadata, INVALID_BOOK, INVALID_BOOK_ID, INVALID_EMBED_FLAG, INVALID_PAGINATION, replaceBook, searchBooks, uploadBook } from '../utils/books.js';
+import { BOOK_DIR, FORBIDDEN, INVALID_API_KEY, randomString, UNAUTHORIZED, wait } from '../utils/common.js';
 import { getCover } from '../utils/covers.js';
-import { getMetadata } from '../utils/metadata.js';
-import { createApiKey, registerUser, USER_NOT_FOUND } from '../utils/users.js';
+import { addMetadata, getMetadata } from '../utils/metadata.js';
+import { addBookToShelf, createShelf, listBooksFromShelf } from '../utils/shelves.js';
+import { ALICE_STATE, getState, updateState } from '../utils/state.js';
+import { createApiKey, patchPreferences, registerUser, USER_NOT_FOUND } from '../utils/users.js';
 import { randomUUID } from 'crypto';
 
 describe('Upload book JWT', () => {
@@ -445,6 +447,72 @@ describe('Upload book api key', () => {
 });
 
 describe('Download book JWT', () => {
+  test('Embed metadata', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addMetadataResponse = await addMetadata(uploadResponse.text, { title: 'Embedded title', description: 'Embedded description' }, { jwt: registerResponse.body.jwt_token });
+    expect(addMetadataResponse.status).toBe(204);
+
+    const downloadResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    const embeddedResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(embeddedResponse.status).toBe(200);
+    expect(Buffer.compare(embeddedResponse.body, downloadResponse.body)).not.toBe(0);
+
+    // The rewritten book is cached, so downloading it again gives the same file
+    const cachedResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(cachedResponse.status).toBe(200);
+    expect(Buffer.compare(cachedResponse.body, embeddedResponse.body)).toBe(0);
+  });
+
+  test('Embed metadata preference', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addMetadataResponse = await addMetadata(uploadResponse.text, { title: 'Embedded title' }, { jwt: registerResponse.body.jwt_token });
+    expect(addMetadataResponse.status).toBe(204);
+
+    const plainResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(plainResponse.status).toBe(200);
+
+    const embeddedResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(embeddedResponse.status).toBe(200);
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, undefined, { jwt: registerResponse.body.jwt_token }, undefined, undefined, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    let downloadResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+    expect(Buffer.compare(downloadResponse.body, embeddedResponse.body)).toBe(0);
+
+    downloadResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, false);
+    expect(downloadResponse.status).toBe(200);
+    expect(Buffer.compare(downloadResponse.body, plainResponse.body)).toBe(0);
+  });
+
+  test('Invalid embed flag', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const downloadResponse = await downloadBook(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, 'invalid');
+    expect(downloadResponse.status).toBe(400);
+    expect(downloadResponse.text).toBe(INVALID_EMBED_FLAG);
+  });
+
   test('Non-existent book', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -829,6 +897,241 @@ describe('Delete book api key', () => {
   });
 });
 
+describe('Replace book JWT', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwt = registerResponse.body.jwt_token;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const addAnnotationResponse = await addAnnotation(bookId, ALICE_NOTE, { jwt });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const updateStateResponse = await updateState(bookId, ALICE_STATE, { jwt });
+    expect(updateStateResponse.status).toBe(204);
+
+    const createShelfResponse = await createShelf(randomString(20), userId, { jwt });
+    expect(createShelfResponse.status).toBe(200);
+    const shelfId = createShelfResponse.text;
+
+    const addBookToShelfResponse = await addBookToShelf(shelfId, bookId, { jwt });
+    expect(addBookToShelfResponse.status).toBe(204);
+
+    const coverResponse = await getCover(bookId, { jwt });
+    expect(coverResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook(bookId, 'Alices_Adventures_in_Wonderland_Revised.epub', { jwt });
+    expect(replaceResponse.status).toBe(204);
+
+    const downloadResponse = await downloadBook(bookId, { jwt }, false);
+    expect(downloadResponse.status).toBe(200);
+
+    const getFileMetadataResponse = await getBookFileMetadata(bookId, { jwt });
+    expect(getFileMetadataResponse.status).toBe(200);
+    expect(getFileMetadataResponse.body.file_size).toBe(downloadResponse.body.length);
+
+    // The revised chapter starts with a new paragraph, so the annotated text moved down by one
+    const annotationResponse = await getAnnotation(bookId, addAnnotationResponse.text, { jwt });
+    expect(annotationResponse.status).toBe(200);
+    expect(annotationResponse.body).toEqual({ ...ALICE_NOTE, annotation_id: addAnnotationResponse.text, annotation_type: 'Highlight', start_tag: 'kobo.75.1', end_tag: 'kobo.75.2', tags: [], orphaned: false, text: expect.any(String), created_at: expect.any(Number), updated_at: expect.any(Number) });
+
+    // The chapter with the reading location was left alone
+    const getStateResponse = await getState(bookId, { jwt });
+    expect(getStateResponse.status).toBe(200);
+    expect(getStateResponse.body).toEqual(ALICE_STATE);
+
+    const listBooksResponse = await listBooksFromShelf(shelfId, { jwt });
+    expect(listBooksResponse.status).toBe(200);
+    expect(listBooksResponse.body).toEqual([bookId]);
+
+    const coverResponse2 = await getCover(bookId, { jwt });
+    expect(coverResponse2.status).toBe(200);
+    expect(coverResponse2.body).toEqual(coverResponse.body);
+  });
+
+  test('Text not found', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwt = registerResponse.body.jwt_token;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const addAnnotationResponse = await addAnnotation(bookId, ALICE_NOTE, { jwt });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const updateStateResponse = await updateState(bookId, ALICE_STATE, { jwt });
+    expect(updateStateResponse.status).toBe(204);
+
+    const replaceResponse = await replaceBook(bookId, 'The_Great_Gatsby.epub', { jwt });
+    expect(replaceResponse.status).toBe(204);
+
+    const annotationResponse = await getAnnotation(bookId, addAnnotationResponse.text, { jwt });
+    expect(annotationResponse.status).toBe(200);
+    expect(annotationResponse.body).toEqual({ ...ALICE_NOTE, annotation_id: addAnnotationResponse.text, annotation_type: 'Highlight', tags: [], orphaned: true, text: expect.any(String), created_at: expect.any(Number), updated_at: expect.any(Number) });
+
+    const getStateResponse = await getState(bookId, { jwt });
+    expect(getStateResponse.status).toBe(200);
+    expect(getStateResponse.body).toEqual({ statistics: ALICE_STATE.statistics });
+  });
+
+  test('Same file', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(replaceResponse.status).toBe(204);
+  });
+
+  test('File of another book', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const uploadResponse2 = await uploadBook(userId, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse2.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse.body.jwt_token });
+    expect(replaceResponse.status).toBe(409);
+    expect(replaceResponse.text).toBe(BOOK_CONFLICT);
+  });
+
+  test('Invalid book', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'This_is_not_an_epub.txt', { jwt: registerResponse.body.jwt_token });
+    expect(replaceResponse.status).toBe(400);
+    expect(replaceResponse.text).toBe(INVALID_BOOK);
+  });
+
+  test('Non-existent book', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook('non-existent', 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(replaceResponse.status).toBe(404);
+    expect(replaceResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse2.body.jwt_token });
+    expect(replaceResponse.status).toBe(404);
+    expect(replaceResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse2.body.jwt_token });
+    expect(replaceResponse.status).toBe(204);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub');
+    expect(replaceResponse.status).toBe(401);
+    expect(replaceResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Replace book api key', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub', { apiKey: createApiKeyResponse.body.key });
+    expect(replaceResponse.status).toBe(204);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create', 'Read', 'Delete'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub', { apiKey: createApiKeyResponse.body.key });
+    expect(replaceResponse.status).toBe(403);
+    expect(replaceResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Expired key', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const timestamp = Date.now() + 1000;
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update'], timestamp, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    // Wait for the key to expire
+    await wait(1.5);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'The_Wonderful_Wizard_of_Oz.epub', { apiKey: createApiKeyResponse.body.key });
+    expect(replaceResponse.status).toBe(401);
+    expect(replaceResponse.text).toBe(INVALID_API_KEY);
+  });
+});
+
 describe('Search books JWT', () => {
   test('Simple', async () => {
     const { response: registerResponse, username } = await registerUser();
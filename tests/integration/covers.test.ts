@@ -2,8 +2,9 @@ import fs from 'fs';
 import path from 'path';
 import { BOOK_NOT_FOUND, uploadBook } from '../utils/books.js';
 import { COVERS_DIR, FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
-import { addCover, COVER_CONFLICT, COVER_NOT_FOUND, deleteCover, getCover, INVALID_COVER, updateCover } from '../utils/covers.js';
-import { createApiKey, registerUser } from '../utils/users.js';
+import { addCover, COVER_CONFLICT, COVER_NOT_FOUND, deleteCover, EMBEDDED_COVER_NOT_FOUND, extractCover, getCover, imageSize, INVALID_COVER, INVALID_COVER_FORMAT, INVALID_COVER_SIZE, updateCover } from '../utils/covers.js';
+import { addMetadata, EXAMPLE_METADATA } from '../utils/metadata.js';
+import { createApiKey, patchPreferences, registerUser } from '../utils/users.js';
 
 describe('Get cover JWT', () => {
   test('Simple', async () => {
@@ -26,7 +27,97 @@ describe('Get cover JWT', () => {
     expect(cover).toEqual(downloadResponse.body);
   });
 
-  test('Non-existent cover', async () => {
+  test('Without automatic metadata', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // The embedded cover is extracted during the upload
+    const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    let coverPath = path.join(COVERS_DIR, 'Alices_Adventures_in_Wonderland.jpeg');
+    let cover = fs.readFileSync(coverPath);
+
+    expect(cover).toEqual(downloadResponse.body);
+  });
+
+  test('Resized', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for cover to be extracted
+    await wait(1);
+
+    let downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { width: 200 });
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.headers['content-type']).toBe('image/jpeg');
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 200, height: 276 });
+
+    downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { width: 200, height: 200 });
+    expect(downloadResponse.status).toBe(200);
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 145, height: 200 });
+
+    // Covers are never scaled up
+    downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { width: 5000 });
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.body).toEqual(fs.readFileSync(path.join(COVERS_DIR, 'Alices_Adventures_in_Wonderland.jpeg')));
+  });
+
+  test('Format', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for cover to be extracted
+    await wait(1);
+
+    let downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { width: 100, format: 'png' });
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.headers['content-type']).toBe('image/png');
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 100, height: 138 });
+
+    downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { format: 'webp' });
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.headers['content-type']).toBe('image/webp');
+    expect(downloadResponse.body.subarray(8, 12).toString()).toBe('WEBP');
+  });
+
+  test('Invalid variant', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    let downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { width: 0 });
+    expect(downloadResponse.status).toBe(400);
+    expect(downloadResponse.text).toBe(INVALID_COVER_SIZE);
+
+    downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { height: 'tall' });
+    expect(downloadResponse.status).toBe(400);
+    expect(downloadResponse.text).toBe(INVALID_COVER_SIZE);
+
+    downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, { format: 'gif' });
+    expect(downloadResponse.status).toBe(400);
+    expect(downloadResponse.text).toBe(INVALID_COVER_FORMAT);
+  });
+
+  test('Placeholder cover', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
     const userId = registerResponse.body.user_id;
@@ -39,8 +130,43 @@ describe('Get cover JWT', () => {
     await wait(1);
 
     const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
-    expect(downloadResponse.status).toBe(404);
-    expect(downloadResponse.text).toBe(COVER_NOT_FOUND);
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.headers['content-type']).toBe('image/jpeg');
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 600, height: 900 });
+  });
+
+  test('Placeholder follows metadata', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    // This epub does not contain a cover
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Give chance for any cover to be extracted
+    await wait(1);
+
+    const placeholderResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(placeholderResponse.status).toBe(200);
+
+    const metadataResponse = await addMetadata(uploadResponse.text, EXAMPLE_METADATA, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(204);
+
+    const changedResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(changedResponse.status).toBe(200);
+    expect(changedResponse.body).not.toEqual(placeholderResponse.body);
+
+    const addResponse = await addCover(uploadResponse.text, 'Generic.jpeg', { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    let coverPath = path.join(COVERS_DIR, 'Generic.jpeg');
+    let cover = fs.readFileSync(coverPath);
+
+    expect(cover).toEqual(downloadResponse.body);
   });
 
   test('Non-existent book', async () => {
@@ -130,7 +256,7 @@ describe('Get cover api key', () => {
     expect(cover).toEqual(downloadResponse.body);
   });
 
-  test('Non-existent cover', async () => {
+  test('Placeholder cover', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
     const userId = registerResponse.body.user_id;
@@ -146,8 +272,9 @@ describe('Get cover api key', () => {
     expect(createApiKeyResponse.status).toBe(200);
 
     const downloadResponse = await getCover(uploadResponse.text, { apiKey: createApiKeyResponse.body.key });
-    expect(downloadResponse.status).toBe(404);
-    expect(downloadResponse.text).toBe(COVER_NOT_FOUND);
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.headers['content-type']).toBe('image/jpeg');
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 600, height: 900 });
   });
 
   test('Non-existent book', async () => {
@@ -606,9 +733,10 @@ describe('Delete cover JWT', () => {
     const deleteResponse = await deleteCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
     expect(deleteResponse.status).toBe(204);
 
+    // A placeholder is generated in place of the deleted cover
     const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
-    expect(downloadResponse.status).toBe(404);
-    expect(downloadResponse.text).toBe(COVER_NOT_FOUND);
+    expect(downloadResponse.status).toBe(200);
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 600, height: 900 });
   });
 
   test('Non-existent cover', async () => {
@@ -709,9 +837,10 @@ describe('Delete cover api key', () => {
     const deleteResponse = await deleteCover(uploadResponse.text, { apiKey: createApiKeyResponse.body.key });
     expect(deleteResponse.status).toBe(204);
 
+    // A placeholder is generated in place of the deleted cover
     const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
-    expect(downloadResponse.status).toBe(404);
-    expect(downloadResponse.text).toBe(COVER_NOT_FOUND);
+    expect(downloadResponse.status).toBe(200);
+    expect(imageSize(downloadResponse.body)).toEqual({ width: 600, height: 900 });
   });
 
   test('Non-existent cover', async () => {
@@ -1176,3 +1305,147 @@ describe('Update cover api key', () => {
     }
   });
 });
+
+describe('Extract cover JWT', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const updateResponse = await updateCover(uploadResponse.text, 'Generic.jpeg', { jwt: registerResponse.body.jwt_token });
+    expect(updateResponse.status).toBe(204);
+
+    const extractResponse = await extractCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(extractResponse.status).toBe(204);
+
+    const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    let coverPath = path.join(COVERS_DIR, 'Alices_Adventures_in_Wonderland.jpeg');
+    let cover = fs.readFileSync(coverPath);
+
+    expect(cover).toEqual(downloadResponse.body);
+  });
+
+  test('Deleted cover', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const deleteResponse = await deleteCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(deleteResponse.status).toBe(204);
+
+    const extractResponse = await extractCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(extractResponse.status).toBe(204);
+
+    const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    let coverPath = path.join(COVERS_DIR, 'Alices_Adventures_in_Wonderland.jpeg');
+    let cover = fs.readFileSync(coverPath);
+
+    expect(cover).toEqual(downloadResponse.body);
+  });
+
+  test('No embedded cover', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    // This epub does not contain a cover
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const extractResponse = await extractCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(extractResponse.status).toBe(404);
+    expect(extractResponse.text).toBe(EMBEDDED_COVER_NOT_FOUND);
+  });
+
+  test('Non-existent book', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const extractResponse = await extractCover('non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(extractResponse.status).toBe(404);
+    expect(extractResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const extractResponse = await extractCover(uploadResponse.text, { jwt: registerResponse2.body.jwt_token });
+    expect(extractResponse.status).toBe(404);
+    expect(extractResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const extractResponse = await extractCover(uploadResponse.text);
+    expect(extractResponse.status).toBe(401);
+    expect(extractResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Extract cover api key', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const deleteResponse = await deleteCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(deleteResponse.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const extractResponse = await extractCover(uploadResponse.text, { apiKey: createApiKeyResponse.body.key });
+    expect(extractResponse.status).toBe(204);
+
+    const downloadResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    let coverPath = path.join(COVERS_DIR, 'Alices_Adventures_in_Wonderland.jpeg');
+    let cover = fs.readFileSync(coverPath);
+
+    expect(cover).toEqual(downloadResponse.body);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create', 'Read', 'Delete'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const extractResponse = await extractCover(uploadResponse.text, { apiKey: createApiKeyResponse.body.key });
+    expect(extractResponse.status).toBe(403);
+    expect(extractResponse.text).toBe(FORBIDDEN);
+  });
+});
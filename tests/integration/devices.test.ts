@@ -0,0 +1,440 @@
+import { uploadBook } from '../utils/books.js';
+import { FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
+import { DEVICE_CONFLICT, DEVICE_NOT_FOUND, getDevice, INVALID_DEVICE_NAME, INVALID_DEVICE_SYNC_TOKEN, INVALID_DEVICE_TYPE, listDevices, patchDevice, registerDevice, removeDevice } from '../utils/devices.js';
+import { sync } from '../utils/sync.js';
+import { createApiKey, INVALID_TOKEN, loginUser, refreshToken, registerUser, USER_NOT_FOUND } from '../utils/users.js';
+
+describe('Register device', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, '  Kobo Libra  ', 'Ereader', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+    const deviceId = registerDeviceResponse.text;
+
+    const getDeviceResponse = await getDevice(userId, deviceId, { jwt: registerResponse.body.jwt_token });
+    expect(getDeviceResponse.status).toBe(200);
+    expect(getDeviceResponse.body).toEqual({
+      device_id: deviceId,
+      owner_id: userId,
+      name: 'Kobo Libra',
+      device_type: 'Ereader',
+      sync_token: -1,
+      pending_changes: 0,
+      last_seen: null,
+      created_at: expect.any(Number)
+    });
+  });
+
+  test('API key', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const createApiKeyResponse = await createApiKey(userId, 'Kobo', ['Read', 'Create'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const registerDeviceResponse = await registerDevice(userId, 'Kobo', 'Ereader', { apiKey: createApiKeyResponse.body.key });
+    expect(registerDeviceResponse.status).toBe(200);
+  });
+
+  test('Session already registered', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const registerDeviceResponse2 = await registerDevice(userId, 'Other phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse2.status).toBe(409);
+    expect(registerDeviceResponse2.text).toBe(DEVICE_CONFLICT);
+  });
+
+  test('Invalid name', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let registerDeviceResponse = await registerDevice(userId, '   ', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(400);
+    expect(registerDeviceResponse.text).toBe(INVALID_DEVICE_NAME);
+
+    registerDeviceResponse = await registerDevice(userId, 'a'.repeat(51), 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(400);
+    expect(registerDeviceResponse.text).toBe(INVALID_DEVICE_NAME);
+  });
+
+  test('Invalid type', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Toaster', 'Toaster', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(400);
+    expect(registerDeviceResponse.text).toBe(INVALID_DEVICE_TYPE);
+  });
+
+  test('Different user', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    // Not even admins can register a session that is not theirs
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse2.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(403);
+    expect(registerDeviceResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone');
+    expect(registerDeviceResponse.status).toBe(401);
+    expect(registerDeviceResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('List devices', () => {
+  test('Simple', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const registerDeviceResponse2 = await registerDevice(userId, 'Kobo', 'Ereader', { jwt: loginResponse.body.jwt_token });
+    expect(registerDeviceResponse2.status).toBe(200);
+
+    const listDevicesResponse = await listDevices(userId, { jwt: registerResponse.body.jwt_token });
+    expect(listDevicesResponse.status).toBe(200);
+    expect(listDevicesResponse.body.map((d: any) => d.device_id)).toEqual([registerDeviceResponse.text, registerDeviceResponse2.text]);
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const listDevicesResponse2 = await listDevices(registerResponse2.body.user_id, { jwt: registerResponse2.body.jwt_token });
+    expect(listDevicesResponse2.status).toBe(200);
+    expect(listDevicesResponse2.body).toEqual([]);
+  });
+
+  test('Pending changes', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+
+    const registerDeviceResponse = await registerDevice(userId, 'Kobo', 'Ereader', { jwt: loginResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+    const deviceId = registerDeviceResponse.text;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for cover and metadata to be extracted
+    await wait(1);
+
+    let getDeviceResponse = await getDevice(userId, deviceId, { jwt: registerResponse.body.jwt_token });
+    expect(getDeviceResponse.status).toBe(200);
+    expect(getDeviceResponse.body.pending_changes).toBeGreaterThan(0);
+    expect(getDeviceResponse.body.last_seen).toBeNull();
+
+    const syncResponse = await sync(userId, undefined, { jwt: loginResponse.body.jwt_token });
+    expect(syncResponse.status).toBe(200);
+
+    const patchDeviceResponse = await patchDevice(userId, deviceId, { sync_token: syncResponse.body.new_sync_token }, { jwt: loginResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(204);
+
+    getDeviceResponse = await getDevice(userId, deviceId, { jwt: registerResponse.body.jwt_token });
+    expect(getDeviceResponse.status).toBe(200);
+    expect(getDeviceResponse.body.pending_changes).toBe(0);
+    expect(getDeviceResponse.body.sync_token).toBe(syncResponse.body.new_sync_token);
+    expect(typeof getDeviceResponse.body.last_seen).toBe('number');
+  });
+
+  test('Non-existent user', async () => {
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const listDevicesResponse = await listDevices('non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(listDevicesResponse.status).toBe(404);
+    expect(listDevicesResponse.text).toBe(USER_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const listDevicesResponse = await listDevices(userId, { jwt: registerResponse2.body.jwt_token });
+    expect(listDevicesResponse.status).toBe(403);
+    expect(listDevicesResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const listDevicesResponse = await listDevices(userId, { jwt: registerResponse2.body.jwt_token });
+    expect(listDevicesResponse.status).toBe(200);
+    expect(listDevicesResponse.body.map((d: any) => d.device_id)).toEqual([registerDeviceResponse.text]);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const listDevicesResponse = await listDevices(registerResponse.body.user_id);
+    expect(listDevicesResponse.status).toBe(401);
+    expect(listDevicesResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Update device', () => {
+  test('Rename', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+    const deviceId = registerDeviceResponse.text;
+
+    const patchDeviceResponse = await patchDevice(userId, deviceId, { name: 'Tablet', device_type: 'Tablet' }, { jwt: registerResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(204);
+
+    const getDeviceResponse = await getDevice(userId, deviceId, { jwt: registerResponse.body.jwt_token });
+    expect(getDeviceResponse.status).toBe(200);
+    expect(getDeviceResponse.body.name).toBe('Tablet');
+    expect(getDeviceResponse.body.device_type).toBe('Tablet');
+  });
+
+  test('Invalid changes', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+    const deviceId = registerDeviceResponse.text;
+
+    let patchDeviceResponse = await patchDevice(userId, deviceId, { name: '' }, { jwt: registerResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(400);
+    expect(patchDeviceResponse.text).toBe(INVALID_DEVICE_NAME);
+
+    patchDeviceResponse = await patchDevice(userId, deviceId, { device_type: 'Toaster' }, { jwt: registerResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(400);
+    expect(patchDeviceResponse.text).toBe(INVALID_DEVICE_TYPE);
+
+    // Devices cannot acknowledge changes that do not exist yet
+    patchDeviceResponse = await patchDevice(userId, deviceId, { sync_token: Number.MAX_SAFE_INTEGER }, { jwt: registerResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(400);
+    expect(patchDeviceResponse.text).toBe(INVALID_DEVICE_SYNC_TOKEN);
+
+    patchDeviceResponse = await patchDevice(userId, deviceId, { sync_token: -2 }, { jwt: registerResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(400);
+    expect(patchDeviceResponse.text).toBe(INVALID_DEVICE_SYNC_TOKEN);
+  });
+
+  test('Non-existent device', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const patchDeviceResponse = await patchDevice(registerResponse.body.user_id, 'non-existent', { name: 'Phone' }, { jwt: registerResponse.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(404);
+    expect(patchDeviceResponse.text).toBe(DEVICE_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    let patchDeviceResponse = await patchDevice(userId, registerDeviceResponse.text, { name: 'Mine' }, { jwt: registerResponse2.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(403);
+    expect(patchDeviceResponse.text).toBe(FORBIDDEN);
+
+    // Devices are only reachable through their owner
+    patchDeviceResponse = await patchDevice(registerResponse2.body.user_id, registerDeviceResponse.text, { name: 'Mine' }, { jwt: registerResponse2.body.jwt_token });
+    expect(patchDeviceResponse.status).toBe(404);
+    expect(patchDeviceResponse.text).toBe(DEVICE_NOT_FOUND);
+  });
+});
+
+describe('Sync cursor', () => {
+  test('Resume without a sync token', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Kobo', 'Ereader', { jwt: jwtToken2 });
+    expect(registerDeviceResponse.status).toBe(200);
+    const deviceId = registerDeviceResponse.text;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: jwtToken });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    // Wait for cover and metadata to be extracted
+    await wait(1);
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.file).toEqual([bookId]);
+    const newSyncToken = syncResponse.body.new_sync_token;
+
+    // The device moved past what it was sent, so nothing comes up again
+    syncResponse = await sync(userId, undefined, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.new_sync_token).toBe(newSyncToken);
+    expect(syncResponse.body.unsynced_books.file).toEqual([]);
+
+    let getDeviceResponse = await getDevice(userId, deviceId, { jwt: jwtToken });
+    expect(getDeviceResponse.status).toBe(200);
+    expect(getDeviceResponse.body.sync_token).toBe(newSyncToken);
+
+    // Acknowledging an older sync token goes over those changes again
+    const patchDeviceResponse = await patchDevice(userId, deviceId, { sync_token: -1 }, { jwt: jwtToken2 });
+    expect(patchDeviceResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, undefined, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.file).toEqual([bookId]);
+
+    // Providing a sync token moves the device to the one it was sent
+    syncResponse = await sync(userId, -1, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+
+    getDeviceResponse = await getDevice(userId, deviceId, { jwt: jwtToken });
+    expect(getDeviceResponse.status).toBe(200);
+    expect(getDeviceResponse.body.sync_token).toBe(syncResponse.body.new_sync_token);
+  });
+});
+
+describe('Remove device', () => {
+  test('Session', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: loginResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+    const deviceId = registerDeviceResponse.text;
+
+    const removeDeviceResponse = await removeDevice(userId, deviceId, { jwt: registerResponse.body.jwt_token });
+    expect(removeDeviceResponse.status).toBe(204);
+
+    const getDeviceResponse = await getDevice(userId, deviceId, { jwt: registerResponse.body.jwt_token });
+    expect(getDeviceResponse.status).toBe(404);
+    expect(getDeviceResponse.text).toBe(DEVICE_NOT_FOUND);
+
+    // The removed session can no longer be refreshed, unlike the others
+    const refreshResponse = await refreshToken(loginResponse.body.refresh_token);
+    expect(refreshResponse.status).toBe(401);
+    expect(refreshResponse.text).toBe(INVALID_TOKEN);
+
+    const refreshResponse2 = await refreshToken(registerResponse.body.refresh_token);
+    expect(refreshResponse2.status).toBe(200);
+
+    // Its access token cannot be used to sync again either
+    const syncResponse = await sync(userId, undefined, { jwt: loginResponse.body.jwt_token });
+    expect(syncResponse.status).toBe(401);
+    expect(syncResponse.text).toBe(INVALID_TOKEN);
+  });
+
+  test('API key', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const createApiKeyResponse = await createApiKey(userId, 'Kobo', ['Read', 'Create'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const registerDeviceResponse = await registerDevice(userId, 'Kobo', 'Ereader', { apiKey: createApiKeyResponse.body.key });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const removeDeviceResponse = await removeDevice(userId, registerDeviceResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(removeDeviceResponse.status).toBe(204);
+
+    const syncResponse = await sync(userId, undefined, { apiKey: createApiKeyResponse.body.key });
+    expect(syncResponse.status).toBe(401);
+    expect(syncResponse.text).toBe(INVALID_API_KEY);
+  });
+
+  test('Non-existent device', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const removeDeviceResponse = await removeDevice(registerResponse.body.user_id, 'non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(removeDeviceResponse.status).toBe(404);
+    expect(removeDeviceResponse.text).toBe(DEVICE_NOT_FOUND);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const removeDeviceResponse = await removeDevice(userId, registerDeviceResponse.text, { jwt: registerResponse2.body.jwt_token });
+    expect(removeDeviceResponse.status).toBe(204);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const registerDeviceResponse = await registerDevice(userId, 'Phone', 'Phone', { jwt: registerResponse.body.jwt_token });
+    expect(registerDeviceResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Reader', ['Read'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const removeDeviceResponse = await removeDevice(userId, registerDeviceResponse.text, { apiKey: createApiKeyResponse.body.key });
+    expect(removeDeviceResponse.status).toBe(403);
+    expect(removeDeviceResponse.text).toBe(FORBIDDEN);
+  });
+});
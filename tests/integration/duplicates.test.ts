@@ -0,0 +1,428 @@
+import { addAnnotation, ALICE_BOOKMARK, ALICE_NOTE, getAnnotation, listAnnotations } from '../utils/annotations.js';
+import { BOOK_NOT_FOUND, uploadBook } from '../utils/books.js';
+import { FORBIDDEN, INVALID_API_KEY, randomString, UNAUTHORIZED, wait } from '../utils/common.js';
+import { INVALID_MERGE_REQUEST, listDuplicates, mergeBooks } from '../utils/duplicates.js';
+import { addMetadata, EXAMPLE_METADATA } from '../utils/metadata.js';
+import { addBookToShelf, createShelf, listBooksFromShelf } from '../utils/shelves.js';
+import { ALICE_STATE, getState, updateState } from '../utils/state.js';
+import { createApiKey, patchPreferences, registerUser, USER_NOT_FOUND } from '../utils/users.js';
+
+async function setupLibrary() {
+  const { response: registerResponse } = await registerUser();
+  expect(registerResponse.status).toBe(200);
+  const userId = registerResponse.body.user_id;
+  const jwt = registerResponse.body.jwt_token;
+
+  const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt });
+  expect(patchPreferencesResponse.status).toBe(204);
+
+  const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt });
+  expect(uploadResponse.status).toBe(200);
+  const uploadResponse2 = await uploadBook(userId, 'The_Wonderful_Wizard_of_Oz.epub', { jwt });
+  expect(uploadResponse2.status).toBe(200);
+
+  return { userId, jwt, bookId: uploadResponse.text, bookId2: uploadResponse2.text };
+}
+
+describe('List duplicates JWT', () => {
+  test('No duplicates', async () => {
+    const { userId, jwt } = await setupLibrary();
+
+    const listResponse = await listDuplicates(userId, { jwt });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toEqual([]);
+  });
+
+  test('Same ISBN, title and author', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const addMetadataResponse = await addMetadata(bookId, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse.status).toBe(204);
+    const addMetadataResponse2 = await addMetadata(bookId2, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse2.status).toBe(204);
+
+    const listResponse = await listDuplicates(userId, { jwt });
+    expect(listResponse.status).toBe(200);
+
+    const [first, second] = [bookId, bookId2].sort();
+    expect(listResponse.body).toEqual([{ book_id: first, duplicate_id: second, reasons: ['isbn', 'title_author'] }]);
+  });
+
+  test('Normalized ISBN, title and author', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const addMetadataResponse = await addMetadata(bookId, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse.status).toBe(204);
+
+    const metadata = {
+      title: 'to kill a mockingbird!',
+      isbn: '0-06-112008-1',
+      contributors: [{ name: 'HARPER LEE', role: 'author' }, { name: 'Someone Else', role: 'Illustrator' }]
+    };
+    const addMetadataResponse2 = await addMetadata(bookId2, metadata, { jwt });
+    expect(addMetadataResponse2.status).toBe(204);
+
+    const listResponse = await listDuplicates(undefined, { jwt });
+    expect(listResponse.status).toBe(200);
+
+    const [first, second] = [bookId, bookId2].sort();
+    expect(listResponse.body).toEqual([{ book_id: first, duplicate_id: second, reasons: ['isbn', 'title_author'] }]);
+  });
+
+  test('Same title by different authors', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const addMetadataResponse = await addMetadata(bookId, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse.status).toBe(204);
+
+    const metadata = { title: EXAMPLE_METADATA.title, contributors: [{ name: 'Someone Else', role: 'Author' }] };
+    const addMetadataResponse2 = await addMetadata(bookId2, metadata, { jwt });
+    expect(addMetadataResponse2.status).toBe(204);
+
+    const listResponse = await listDuplicates(userId, { jwt });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toEqual([]);
+  });
+
+  test('Different user without permission', async () => {
+    const { userId } = await setupLibrary();
+
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const listResponse = await listDuplicates(userId, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(403);
+    expect(listResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user with permission', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const addMetadataResponse = await addMetadata(bookId, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse.status).toBe(204);
+    const addMetadataResponse2 = await addMetadata(bookId2, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse2.status).toBe(204);
+
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const listResponse = await listDuplicates(userId, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+  });
+
+  test('Non-existent user', async () => {
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const listResponse = await listDuplicates('non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(404);
+    expect(listResponse.text).toBe(USER_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { userId } = await setupLibrary();
+
+    const listResponse = await listDuplicates(userId);
+    expect(listResponse.status).toBe(401);
+    expect(listResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('List duplicates API key', () => {
+  test('Simple', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const addMetadataResponse = await addMetadata(bookId, EXAMPLE_METADATA, { jwt });
+    expect(addMetadataResponse.status).toBe(204);
+    const addMetadataResponse2 = await addMetadata(bookId2, { isbn: EXAMPLE_METADATA.isbn }, { jwt });
+    expect(addMetadataResponse2.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const listResponse = await listDuplicates(undefined, { apiKey: createApiKeyResponse.body.key });
+    expect(listResponse.status).toBe(200);
+
+    const [first, second] = [bookId, bookId2].sort();
+    expect(listResponse.body).toEqual([{ book_id: first, duplicate_id: second, reasons: ['isbn'] }]);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { userId, jwt } = await setupLibrary();
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create', 'Update', 'Delete'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const listResponse = await listDuplicates(userId, { apiKey: createApiKeyResponse.body.key });
+    expect(listResponse.status).toBe(403);
+    expect(listResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Expired key', async () => {
+    const { userId, jwt } = await setupLibrary();
+
+    const timestamp = Date.now() + 1000;
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read'], timestamp, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    // Wait for the key to expire
+    await wait(1.5);
+
+    const listResponse = await listDuplicates(userId, { apiKey: createApiKeyResponse.body.key });
+    expect(listResponse.status).toBe(401);
+    expect(listResponse.text).toBe(INVALID_API_KEY);
+  });
+});
+
+describe('Merge books JWT', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwt = registerResponse.body.jwt_token;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const uploadResponse2 = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(uploadResponse2.status).toBe(200);
+    const duplicateId = uploadResponse2.text;
+
+    const updateStateResponse = await updateState(duplicateId, ALICE_STATE, { jwt });
+    expect(updateStateResponse.status).toBe(204);
+
+    const addAnnotationResponse = await addAnnotation(duplicateId, ALICE_NOTE, { jwt });
+    expect(addAnnotationResponse.status).toBe(200);
+    const annotationId = addAnnotationResponse.text;
+
+    const createShelfResponse = await createShelf(randomString(20), userId, { jwt });
+    expect(createShelfResponse.status).toBe(200);
+    const shelfId = createShelfResponse.text;
+
+    const addBookToShelfResponse = await addBookToShelf(shelfId, duplicateId, { jwt });
+    expect(addBookToShelfResponse.status).toBe(204);
+
+    const mergeResponse = await mergeBooks(bookId, duplicateId, { jwt });
+    expect(mergeResponse.status).toBe(204);
+
+    // The location belongs to the duplicate's file, so only the statistics are kept
+    const getStateResponse = await getState(bookId, { jwt });
+    expect(getStateResponse.status).toBe(200);
+    expect(getStateResponse.body).toEqual({ statistics: ALICE_STATE.statistics });
+
+    const listAnnotationsResponse = await listAnnotations(bookId, { jwt });
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body).toEqual([annotationId]);
+
+    const listBooksResponse = await listBooksFromShelf(shelfId, { jwt });
+    expect(listBooksResponse.status).toBe(200);
+    expect(listBooksResponse.body).toEqual([bookId]);
+
+    const getDuplicateStateResponse = await getState(duplicateId, { jwt });
+    expect(getDuplicateStateResponse.status).toBe(404);
+    expect(getDuplicateStateResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different files', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwt = registerResponse.body.jwt_token;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland_Revised.epub', { jwt });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const uploadResponse2 = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(uploadResponse2.status).toBe(200);
+    const duplicateId = uploadResponse2.text;
+
+    // The revised chapter starts with a new paragraph, so the same text is one paragraph further down
+    const addAnnotationResponse = await addAnnotation(bookId, { ...ALICE_NOTE, start_tag: 'kobo.75.1', end_tag: 'kobo.75.2', note: 'Kept note' }, { jwt });
+    expect(addAnnotationResponse.status).toBe(200);
+    const annotationId = addAnnotationResponse.text;
+
+    const addAnnotationResponse2 = await addAnnotation(duplicateId, { ...ALICE_NOTE, tags: ['favourite'] }, { jwt });
+    expect(addAnnotationResponse2.status).toBe(200);
+
+    const addAnnotationResponse3 = await addAnnotation(duplicateId, ALICE_BOOKMARK, { jwt });
+    expect(addAnnotationResponse3.status).toBe(200);
+    const bookmarkId = addAnnotationResponse3.text;
+
+    const mergeResponse = await mergeBooks(bookId, duplicateId, { jwt });
+    expect(mergeResponse.status).toBe(204);
+
+    const listAnnotationsResponse = await listAnnotations(bookId, { jwt });
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body.sort()).toEqual([annotationId, bookmarkId].sort());
+
+    // The copy of an annotation the book already had is folded into it
+    const getAnnotationResponse = await getAnnotation(bookId, annotationId, { jwt });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.note).toBe(`Kept note\n\n${ALICE_NOTE.note}`);
+    expect(getAnnotationResponse.body.tags).toEqual(['favourite']);
+
+    const getAnnotationResponse2 = await getAnnotation(bookId, bookmarkId, { jwt });
+    expect(getAnnotationResponse2.status).toBe(200);
+    expect(getAnnotationResponse2.body.start_tag).toBe('kobo.75.1');
+    expect(getAnnotationResponse2.body.orphaned).toBe(false);
+  });
+
+  test('Annotations not in the kept file', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwt = registerResponse.body.jwt_token;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const uploadResponse2 = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(uploadResponse2.status).toBe(200);
+    const duplicateId = uploadResponse2.text;
+
+    const addAnnotationResponse = await addAnnotation(duplicateId, ALICE_NOTE, { jwt });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const mergeResponse = await mergeBooks(bookId, duplicateId, { jwt });
+    expect(mergeResponse.status).toBe(204);
+
+    const getAnnotationResponse = await getAnnotation(bookId, addAnnotationResponse.text, { jwt });
+    expect(getAnnotationResponse.status).toBe(200);
+    expect(getAnnotationResponse.body.orphaned).toBe(true);
+  });
+
+  test('Book already in shelf', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const createShelfResponse = await createShelf(randomString(20), userId, { jwt });
+    expect(createShelfResponse.status).toBe(200);
+    const shelfId = createShelfResponse.text;
+
+    const addBookToShelfResponse = await addBookToShelf(shelfId, bookId, { jwt });
+    expect(addBookToShelfResponse.status).toBe(204);
+    const addBookToShelfResponse2 = await addBookToShelf(shelfId, bookId2, { jwt });
+    expect(addBookToShelfResponse2.status).toBe(204);
+
+    const mergeResponse = await mergeBooks(bookId, bookId2, { jwt });
+    expect(mergeResponse.status).toBe(204);
+
+    const listBooksResponse = await listBooksFromShelf(shelfId, { jwt });
+    expect(listBooksResponse.status).toBe(200);
+    expect(listBooksResponse.body).toEqual([bookId]);
+  });
+
+  test('Same book', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const mergeResponse = await mergeBooks(bookId, bookId, { jwt });
+    expect(mergeResponse.status).toBe(400);
+    expect(mergeResponse.text).toBe(INVALID_MERGE_REQUEST);
+  });
+
+  test('Invalid request', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const mergeResponse = await mergeBooks(bookId, 5, { jwt });
+    expect(mergeResponse.status).toBe(400);
+    expect(mergeResponse.text).toBe(INVALID_MERGE_REQUEST);
+  });
+
+  test('Non-existent duplicate', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const mergeResponse = await mergeBooks(bookId, 'non-existent', { jwt });
+    expect(mergeResponse.status).toBe(404);
+    expect(mergeResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Duplicate from different user', async () => {
+    const { jwt, bookId } = await setupLibrary();
+    const { bookId: otherBookId } = await setupLibrary();
+
+    const mergeResponse = await mergeBooks(bookId, otherBookId, { jwt });
+    expect(mergeResponse.status).toBe(404);
+    expect(mergeResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different users with permission', async () => {
+    const { bookId } = await setupLibrary();
+    const { bookId: otherBookId } = await setupLibrary();
+
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const mergeResponse = await mergeBooks(bookId, otherBookId, { jwt: registerResponse.body.jwt_token });
+    expect(mergeResponse.status).toBe(403);
+    expect(mergeResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user without permission', async () => {
+    const { bookId, bookId2 } = await setupLibrary();
+
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const mergeResponse = await mergeBooks(bookId, bookId2, { jwt: registerResponse.body.jwt_token });
+    expect(mergeResponse.status).toBe(404);
+    expect(mergeResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { bookId, bookId2 } = await setupLibrary();
+
+    const mergeResponse = await mergeBooks(bookId, bookId2);
+    expect(mergeResponse.status).toBe(401);
+    expect(mergeResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Merge books API key', () => {
+  test('Simple', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update', 'Delete'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const mergeResponse = await mergeBooks(bookId, bookId2, { apiKey: createApiKeyResponse.body.key });
+    expect(mergeResponse.status).toBe(204);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create', 'Read', 'Update'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const mergeResponse = await mergeBooks(bookId, bookId2, { apiKey: createApiKeyResponse.body.key });
+    expect(mergeResponse.status).toBe(403);
+    expect(mergeResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Expired key', async () => {
+    const { userId, jwt, bookId, bookId2 } = await setupLibrary();
+
+    const timestamp = Date.now() + 1000;
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update', 'Delete'], timestamp, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    // Wait for the key to expire
+    await wait(1.5);
+
+    const mergeResponse = await mergeBooks(bookId, bookId2, { apiKey: createApiKeyResponse.body.key });
+    expect(mergeResponse.status).toBe(401);
+    expect(mergeResponse.text).toBe(INVALID_API_KEY);
+  });
+});
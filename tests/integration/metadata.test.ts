@@ -1,6 +1,38 @@
+import fs from 'fs';
+import path from 'path';
 import { BOOK_NOT_FOUND, uploadBook } from '../utils/books.js';
-import { FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
-import { addMetadata, addMetadataRequest, ALICE_METADATA, deleteMetadata, EXAMPLE_METADATA, getMetadata, INVALID_METADATA, listMetadataRequests, METADATA_CONFLICT, METADATA_NOT_FOUND, patchMetadata, updateMetadata } from '../utils/metadata.js';
+import { COVERS_DIR, FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
+import { deleteCover, getCover, imageSize } from '../utils/covers.js';
+import {
+  addMetadata,
+  addMetadataRequest,
+  ALICE_METADATA,
+  applyMetadataCandidate,
+  cancelMetadataRequest,
+  CANDIDATE_NOT_FOUND,
+  deleteMetadata,
+  EXAMPLE_METADATA,
+  getMetadata,
+  getMetadataCandidateCover,
+  getMetadataRequest,
+  INVALID_CANDIDATE_SELECTION,
+  INVALID_CANDIDATE_STATUS,
+  INVALID_JOB_STATUS,
+  INVALID_METADATA,
+  INVALID_METADATA_FIELD,
+  INVALID_PROVENANCE_FLAG,
+  listMetadataCandidates,
+  listMetadataRequests,
+  METADATA_CONFLICT,
+  METADATA_NOT_FOUND,
+  METADATA_REQUEST_NOT_FOUND,
+  METADATA_REQUEST_NOT_QUEUED,
+  patchMetadata,
+  rejectMetadataCandidate,
+  updateMetadata,
+  updateMetadataLocks,
+  updateMetadataRequest
+} from '../utils/metadata.js';
 import { createApiKey, INVALID_PROVIDERS, patchPreferences, registerUser } from '../utils/users.js';
 
 describe('Get metadata JWT', () => {
@@ -21,6 +53,48 @@ describe('Get metadata JWT', () => {
     expect(downloadResponse.body).toEqual(ALICE_METADATA);
   });
 
+  test('Provenance', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const patchResponse = await patchMetadata(uploadResponse.text, { title: 'title test' }, { jwt: registerResponse.body.jwt_token });
+    expect(patchResponse.status).toBe(204);
+
+    const downloadResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(downloadResponse.status).toBe(200);
+
+    const { provenance, ...metadata } = downloadResponse.body;
+    expect(metadata).toEqual({ ...ALICE_METADATA, title: 'title test' });
+    expect(Object.keys(provenance).sort()).toEqual(Object.keys(ALICE_METADATA).sort());
+    expect(provenance.title.source).toBe('user');
+    expect(provenance.isbn.source).toBe('epub_metadata_extractor');
+    expect(provenance.isbn.locked).toBe(false);
+    expect(typeof provenance.isbn.updated_at).toBe('number');
+  });
+
+  test('Invalid provenance flag', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const downloadResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, 'maybe');
+    expect(downloadResponse.status).toBe(400);
+    expect(downloadResponse.text).toBe(INVALID_PROVENANCE_FLAG);
+  });
+
   test('Disabled auto-fetch', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -1414,6 +1488,218 @@ describe('Patch metadata api key', () => {
   });
 });
 
+describe('Update metadata locks JWT', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title', 'series'], { jwt: registerResponse.body.jwt_token });
+    expect(lockResponse.status).toBe(204);
+
+    const downloadResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.body.provenance.title.locked).toBe(true);
+    expect(downloadResponse.body.provenance.series).toEqual({ locked: true });
+    expect(downloadResponse.body.provenance.isbn.locked).toBe(false);
+
+    const unlockResponse = await updateMetadataLocks(uploadResponse.text, [], { jwt: registerResponse.body.jwt_token });
+    expect(unlockResponse.status).toBe(204);
+
+    const downloadResponse2 = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(downloadResponse2.status).toBe(200);
+    expect(downloadResponse2.body.provenance.title.locked).toBe(false);
+    expect(downloadResponse2.body.provenance.series).toBeUndefined();
+  });
+
+  test('Locked fields survive metadata fetching', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const updateResponse = await updateMetadata(uploadResponse.text, { title: 'title test', language: 'pt' }, { jwt: registerResponse.body.jwt_token });
+    expect(updateResponse.status).toBe(204);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title', 'publisher'], { jwt: registerResponse.body.jwt_token });
+    expect(lockResponse.status).toBe(204);
+
+    const metadataRequestResponse = await addMetadataRequest(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(metadataRequestResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const downloadResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(downloadResponse.status).toBe(200);
+
+    const { provenance, ...metadata } = downloadResponse.body;
+    expect(metadata).toEqual({ ...ALICE_METADATA, title: 'title test' });
+    expect(provenance.title).toEqual({ source: 'user', updated_at: provenance.title.updated_at, locked: true });
+    expect(provenance.language.source).toBe('epub_metadata_extractor');
+    expect(provenance.publisher).toEqual({ locked: true });
+  });
+
+  test('Locked fields before metadata exists', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title'], { jwt: registerResponse.body.jwt_token });
+    expect(lockResponse.status).toBe(204);
+
+    const metadataRequestResponse = await addMetadataRequest(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(metadataRequestResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const downloadResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+
+    let expectedMetadata: any = structuredClone(ALICE_METADATA);
+    delete expectedMetadata.title;
+
+    expect(downloadResponse.body).toEqual(expectedMetadata);
+  });
+
+  test('Invalid field', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title', 'invalid'], { jwt: registerResponse.body.jwt_token });
+    expect(lockResponse.status).toBe(400);
+    expect(lockResponse.text).toBe(INVALID_METADATA_FIELD);
+  });
+
+  test('Non-existent book', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks('non-existent', ['title'], { jwt: registerResponse.body.jwt_token });
+    expect(lockResponse.status).toBe(404);
+    expect(lockResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title'], { jwt: registerResponse2.body.jwt_token });
+    expect(lockResponse.status).toBe(404);
+    expect(lockResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title'], { jwt: registerResponse2.body.jwt_token });
+    expect(lockResponse.status).toBe(204);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title']);
+    expect(lockResponse.status).toBe(401);
+    expect(lockResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Update metadata locks api key', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title'], { apiKey: createApiKeyResponse.body.key });
+    expect(lockResponse.status).toBe(204);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create', 'Read', 'Delete'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title'], { apiKey: createApiKeyResponse.body.key });
+    expect(lockResponse.status).toBe(403);
+    expect(lockResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Expired api key', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const timestamp = Date.now() + 1000;
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Update'], timestamp, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    // Wait for the key to expire
+    await wait(1.5);
+
+    const lockResponse = await updateMetadataLocks(uploadResponse.text, ['title'], { apiKey: createApiKeyResponse.body.key });
+    expect(lockResponse.status).toBe(401);
+    expect(lockResponse.text).toBe(INVALID_API_KEY);
+  });
+});
+
 describe('Add metadata request JWT', () => {
   test('Simple', async () => {
     const { response: registerResponse } = await registerUser();
@@ -1439,6 +1725,29 @@ describe('Add metadata request JWT', () => {
     expect(getResponse.status).toBe(200);
   });
 
+  test('Merge policy', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    // The preferred provider is not used, so the title falls back to provider priority
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, { title: 'goodreads_metadata_scraper', genres: 'longest' });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const metadataRequestResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(metadataRequestResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const downloadResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(downloadResponse.status).toBe(200);
+    expect(downloadResponse.body).toEqual(ALICE_METADATA);
+  });
+
   test('Invalid providers', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -1786,6 +2095,56 @@ describe('List metadata requests JWT', () => {
     expect(getResponse.body).toEqual([]);
   });
 
+  test('Finished requests', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1.5);
+
+    let getResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body).toEqual([]);
+
+    getResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'succeeded');
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.length).toBe(1);
+    expect(getResponse.body[0].user_id).toBe(userId);
+    expect(getResponse.body[0].book_id).toBe(uploadResponse.text);
+    expect(getResponse.body[0].providers).toEqual(['epub_metadata_extractor']);
+    expect(getResponse.body[0].status).toBe('succeeded');
+    expect(getResponse.body[0].reason).toBeUndefined();
+    expect(getResponse.body[0].finished_at).toBeGreaterThanOrEqual(getResponse.body[0].started_at);
+
+    getResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'succeeded', 'non-existent');
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body).toEqual([]);
+
+    getResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'failed');
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body).toEqual([]);
+  });
+
+  test('Invalid status', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const getResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'invalid');
+    expect(getResponse.status).toBe(400);
+    expect(getResponse.text).toBe(INVALID_JOB_STATUS);
+  });
+
   test('Different user without permission', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -1931,3 +2290,734 @@ describe('List metadata requests api key', () => {
     expect(getResponse.text).toEqual(INVALID_API_KEY);
   });
 });
+
+describe('Metadata request queue JWT', () => {
+  test('Cancel request', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Keep the book busy so the next request stays queued
+    let addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'queued');
+    expect(listResponse.status).toBe(200);
+    const jobId = listResponse.body.find((j: any) => j.providers[0] === 'epub_metadata_extractor').job_id;
+
+    let cancelResponse = await cancelMetadataRequest(jobId, { jwt: registerResponse.body.jwt_token });
+    expect(cancelResponse.status).toBe(204);
+
+    cancelResponse = await cancelMetadataRequest(jobId, { jwt: registerResponse.body.jwt_token });
+    expect(cancelResponse.status).toBe(409);
+    expect(cancelResponse.text).toBe(METADATA_REQUEST_NOT_QUEUED);
+
+    const getResponse = await getMetadataRequest(jobId, { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.status).toBe('cancelled');
+    expect(getResponse.body.position).toBeUndefined();
+
+    // Wait for the queue to move on
+    await wait(2.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+  });
+
+  test('Reprioritize request', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Keep the book busy so the next requests stay queued
+    let addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    addResponse = await addMetadataRequest(uploadResponse.text, ['test_command_plugin'], { jwt: registerResponse.body.jwt_token }, 5);
+    expect(addResponse.status).toBe(204);
+
+    let listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'queued');
+    expect(listResponse.status).toBe(200);
+    let first = listResponse.body.find((j: any) => j.providers[0] === 'epub_metadata_extractor');
+    let second = listResponse.body.find((j: any) => j.providers[0] === 'test_command_plugin');
+    expect(first.priority).toBe(0);
+    expect(second.priority).toBe(5);
+    expect(second.position).toBeLessThan(first.position);
+
+    const updateResponse = await updateMetadataRequest(first.job_id, 10, { jwt: registerResponse.body.jwt_token });
+    expect(updateResponse.status).toBe(204);
+
+    listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'queued');
+    expect(listResponse.status).toBe(200);
+    first = listResponse.body.find((j: any) => j.job_id === first.job_id);
+    second = listResponse.body.find((j: any) => j.job_id === second.job_id);
+    expect(first.priority).toBe(10);
+    expect(first.position).toBeLessThan(second.position);
+
+    // Wait for the queue to move on
+    await wait(2.5);
+
+    const getResponse = await getMetadataRequest(first.job_id, { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.status).toBe('succeeded');
+
+    const updateFinishedResponse = await updateMetadataRequest(first.job_id, 1, { jwt: registerResponse.body.jwt_token });
+    expect(updateFinishedResponse.status).toBe(409);
+    expect(updateFinishedResponse.text).toBe(METADATA_REQUEST_NOT_QUEUED);
+  });
+
+  test('Bounded priority', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Keep the book busy so the next requests stay queued
+    let addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Users cannot get ahead of everyone else's requests
+    addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token }, 1000000);
+    expect(addResponse.status).toBe(204);
+
+    addResponse = await addMetadataRequest(uploadResponse.text, ['test_command_plugin'], { jwt: registerResponse.body.jwt_token }, -1000000);
+    expect(addResponse.status).toBe(204);
+
+    let listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'queued');
+    expect(listResponse.status).toBe(200);
+    let first = listResponse.body.find((j: any) => j.providers[0] === 'epub_metadata_extractor');
+    const second = listResponse.body.find((j: any) => j.providers[0] === 'test_command_plugin');
+    expect(first.priority).toBe(10);
+    expect(second.priority).toBe(0);
+
+    const updateResponse = await updateMetadataRequest(second.job_id, 1000000, { jwt: registerResponse.body.jwt_token });
+    expect(updateResponse.status).toBe(204);
+
+    // Admins are not bound
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const updateResponse2 = await updateMetadataRequest(first.job_id, 1000000, { jwt: registerResponse2.body.jwt_token });
+    expect(updateResponse2.status).toBe(204);
+
+    listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'queued');
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.find((j: any) => j.job_id === second.job_id).priority).toBe(10);
+    first = listResponse.body.find((j: any) => j.job_id === first.job_id);
+    expect(first.priority).toBe(1000000);
+  });
+
+  test('Different books in parallel', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const uploadResponse2 = await uploadBook(userId, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse2.status).toBe(200);
+
+    let addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    addResponse = await addMetadataRequest(uploadResponse2.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // The second book does not wait for the slow request on the first one
+    await wait(0.8);
+
+    let listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'running');
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+    expect(listResponse.body[0].book_id).toBe(uploadResponse.text);
+
+    listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'succeeded');
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+    expect(listResponse.body[0].book_id).toBe(uploadResponse2.text);
+
+    const metadataResponse = await getMetadata(uploadResponse2.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(200);
+  });
+
+  test('Non-existent request', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const getResponse = await getMetadataRequest('non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(404);
+    expect(getResponse.text).toBe(METADATA_REQUEST_NOT_FOUND);
+
+    const cancelResponse = await cancelMetadataRequest('non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(cancelResponse.status).toBe(404);
+    expect(cancelResponse.text).toBe(METADATA_REQUEST_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const jobId = listResponse.body[0].job_id;
+
+    const getResponse = await getMetadataRequest(jobId, { jwt: registerResponse2.body.jwt_token });
+    expect(getResponse.status).toBe(404);
+    expect(getResponse.text).toBe(METADATA_REQUEST_NOT_FOUND);
+
+    const updateResponse = await updateMetadataRequest(jobId, 10, { jwt: registerResponse2.body.jwt_token });
+    expect(updateResponse.status).toBe(404);
+    expect(updateResponse.text).toBe(METADATA_REQUEST_NOT_FOUND);
+
+    const cancelResponse = await cancelMetadataRequest(jobId, { jwt: registerResponse2.body.jwt_token });
+    expect(cancelResponse.status).toBe(404);
+    expect(cancelResponse.text).toBe(METADATA_REQUEST_NOT_FOUND);
+  });
+
+  test('Different user with permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const { response: registerResponse2 } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse2.status).toBe(200);
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1.5);
+
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'succeeded');
+    expect(listResponse.status).toBe(200);
+    const jobId = listResponse.body[0].job_id;
+
+    const getResponse = await getMetadataRequest(jobId, { jwt: registerResponse2.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.job_id).toBe(jobId);
+  });
+
+  test('No auth', async () => {
+    const getResponse = await getMetadataRequest('non-existent');
+    expect(getResponse.status).toBe(401);
+    expect(getResponse.text).toBe(UNAUTHORIZED);
+
+    const cancelResponse = await cancelMetadataRequest('non-existent');
+    expect(cancelResponse.status).toBe(401);
+    expect(cancelResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Metadata request queue api key', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read', 'Update'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    let addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { apiKey: createApiKeyResponse.body.key });
+    expect(addResponse.status).toBe(204);
+
+    addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { apiKey: createApiKeyResponse.body.key });
+    expect(addResponse.status).toBe(204);
+
+    const listResponse = await listMetadataRequests(userId, { apiKey: createApiKeyResponse.body.key }, 'queued');
+    expect(listResponse.status).toBe(200);
+    const jobId = listResponse.body.find((j: any) => j.providers[0] === 'epub_metadata_extractor').job_id;
+
+    const getResponse = await getMetadataRequest(jobId, { apiKey: createApiKeyResponse.body.key });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.status).toBe('queued');
+    expect(getResponse.body.position).toBeGreaterThanOrEqual(1);
+
+    const updateResponse = await updateMetadataRequest(jobId, 3, { apiKey: createApiKeyResponse.body.key });
+    expect(updateResponse.status).toBe(204);
+
+    const cancelResponse = await cancelMetadataRequest(jobId, { apiKey: createApiKeyResponse.body.key });
+    expect(cancelResponse.status).toBe(204);
+  });
+
+  test('Missing capabilities', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const jobId = listResponse.body[0].job_id;
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const getResponse = await getMetadataRequest(jobId, { apiKey: createApiKeyResponse.body.key });
+    expect(getResponse.status).toBe(403);
+    expect(getResponse.text).toBe(FORBIDDEN);
+
+    const cancelResponse = await cancelMetadataRequest(jobId, { apiKey: createApiKeyResponse.body.key });
+    expect(cancelResponse.status).toBe(403);
+    expect(cancelResponse.text).toBe(FORBIDDEN);
+  });
+});
+
+describe('Metadata candidates JWT', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    // Nothing is written to the book until a candidate is applied
+    const getResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(404);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+
+    const candidate = listResponse.body[0];
+    expect(candidate.provider).toBe('epub_metadata_extractor');
+    expect(candidate.status).toBe('pending');
+    expect(candidate.metadata).toEqual(ALICE_METADATA);
+    expect(candidate.has_cover).toBe(true);
+    expect(candidate.diff.title).toEqual({ candidate: ALICE_METADATA.title });
+
+    const coverResponse = await getMetadataCandidateCover(uploadResponse.text, candidate.candidate_id, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(200);
+
+    const coverPath = path.join(COVERS_DIR, 'Alices_Adventures_in_Wonderland.jpeg');
+    const cover = fs.readFileSync(coverPath);
+    expect(cover).toEqual(coverResponse.body);
+  });
+
+  test('Apply whole candidate', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    let listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const candidateId = listResponse.body[0].candidate_id;
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(applyResponse.status).toBe(204);
+
+    const getResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.title).toBe(ALICE_METADATA.title);
+    expect(getResponse.body.provenance.title.source).toBe('epub_metadata_extractor');
+
+    const coverResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(200);
+
+    listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toEqual([]);
+
+    listResponse = await listMetadataCandidates(uploadResponse.text, 'applied', { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+    expect(listResponse.body[0].status).toBe('applied');
+  });
+
+  test('Apply selected fields', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Drop the cover extracted during the upload
+    const deleteResponse = await deleteCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(deleteResponse.status).toBe(204);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const candidateId = listResponse.body[0].candidate_id;
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, ['title'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(applyResponse.status).toBe(204);
+
+    const getResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body).toEqual({ title: ALICE_METADATA.title });
+
+    // The cover is only applied when explicitly selected, so a placeholder is served instead
+    const coverResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(200);
+    expect(imageSize(coverResponse.body)).toEqual({ width: 600, height: 900 });
+  });
+
+  test('Reject candidate', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    let addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    let listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const candidateId = listResponse.body[0].candidate_id;
+
+    const rejectResponse = await rejectMetadataCandidate(uploadResponse.text, candidateId, { jwt: registerResponse.body.jwt_token });
+    expect(rejectResponse.status).toBe(204);
+
+    const coverResponse = await getMetadataCandidateCover(uploadResponse.text, candidateId, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(404);
+
+    // A rejected candidate is not proposed again
+    addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    await wait(1);
+
+    listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body).toEqual([]);
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(applyResponse.status).toBe(400);
+    expect(applyResponse.text).toBe(INVALID_CANDIDATE_STATUS);
+  });
+
+  test('Invalid selection', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const candidateId = listResponse.body[0].candidate_id;
+
+    let applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, ['invalid'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(applyResponse.status).toBe(400);
+    expect(applyResponse.text).toBe(INVALID_METADATA_FIELD);
+
+    applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, [], false, { jwt: registerResponse.body.jwt_token });
+    expect(applyResponse.status).toBe(400);
+    expect(applyResponse.text).toBe(INVALID_CANDIDATE_SELECTION);
+  });
+
+  test('Invalid status', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, 'invalid', { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(400);
+    expect(listResponse.text).toBe(INVALID_CANDIDATE_STATUS);
+  });
+
+  test('Non-existent candidate', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, 'non-existent', undefined, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(applyResponse.status).toBe(404);
+    expect(applyResponse.text).toBe(CANDIDATE_NOT_FOUND);
+
+    const rejectResponse = await rejectMetadataCandidate(uploadResponse.text, 'non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(rejectResponse.status).toBe(404);
+    expect(rejectResponse.text).toBe(CANDIDATE_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const candidateId = listResponse.body[0].candidate_id;
+
+    const { response: registerResponse2 } = await registerUser();
+    expect(registerResponse2.status).toBe(200);
+
+    const otherListResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse2.body.jwt_token });
+    expect(otherListResponse.status).toBe(404);
+    expect(otherListResponse.text).toBe(BOOK_NOT_FOUND);
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, undefined, undefined, { jwt: registerResponse2.body.jwt_token });
+    expect(applyResponse.status).toBe(404);
+    expect(applyResponse.text).toBe(BOOK_NOT_FOUND);
+
+    const rejectResponse = await rejectMetadataCandidate(uploadResponse.text, candidateId, { jwt: registerResponse2.body.jwt_token });
+    expect(rejectResponse.status).toBe(404);
+    expect(rejectResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text);
+    expect(listResponse.status).toBe(401);
+    expect(listResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Metadata candidates api key', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read', 'Update'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { apiKey: createApiKeyResponse.body.key });
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, listResponse.body[0].candidate_id, undefined, undefined, { apiKey: createApiKeyResponse.body.key });
+    expect(applyResponse.status).toBe(204);
+
+    const getResponse = await getMetadata(uploadResponse.text, { apiKey: createApiKeyResponse.body.key });
+    expect(getResponse.status).toBe(200);
+    expect(getResponse.body.title).toBe(ALICE_METADATA.title);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token }, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be extracted
+    await wait(1);
+
+    const listResponse = await listMetadataCandidates(uploadResponse.text, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(listResponse.status).toBe(200);
+    const candidateId = listResponse.body[0].candidate_id;
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const applyResponse = await applyMetadataCandidate(uploadResponse.text, candidateId, undefined, undefined, { apiKey: createApiKeyResponse.body.key });
+    expect(applyResponse.status).toBe(403);
+    expect(applyResponse.text).toBe(FORBIDDEN);
+
+    const rejectResponse = await rejectMetadataCandidate(uploadResponse.text, candidateId, { apiKey: createApiKeyResponse.body.key });
+    expect(rejectResponse.status).toBe(403);
+    expect(rejectResponse.text).toBe(FORBIDDEN);
+  });
+});
+
+describe('Scheduled metadata refresh', () => {
+  test('Missing metadata', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // Books are left alone while automatic metadata is disabled
+    await wait(1.5);
+
+    let metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+
+    patchPreferencesResponse = await patchPreferences(userId, undefined, true, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    // Wait for the scheduler to pick up the book
+    await wait(2.5);
+
+    metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(200);
+
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'succeeded');
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+    expect(listResponse.body[0].book_id).toBe(uploadResponse.text);
+    expect(listResponse.body[0].priority).toBe(-1);
+  });
+
+  test('Opted out', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    patchPreferencesResponse = await patchPreferences(userId, undefined, true, { jwt: registerResponse.body.jwt_token }, undefined, undefined, 0);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    await wait(2.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'succeeded');
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(0);
+  });
+});
@@ -0,0 +1,311 @@
+import fs from 'fs';
+import http from 'http';
+import path from 'path';
+import { uploadBook } from '../utils/books.js';
+import { COVERS_DIR, wait } from '../utils/common.js';
+import { getCover } from '../utils/covers.js';
+import { addMetadataRequest, getMetadata, listMetadataRequests } from '../utils/metadata.js';
+import { COMMAND_PLUGIN_METADATA, GOOGLE_BOOKS_METADATA, HTTP_PLUGIN_METADATA, OPEN_LIBRARY_METADATA, startMockProviders } from '../utils/providers.js';
+import { getPreferences, INVALID_PROVIDERS, patchPreferences, registerUser } from '../utils/users.js';
+
+let mockServer: http.Server;
+
+beforeAll(async () => {
+  mockServer = await startMockProviders();
+});
+
+afterAll(() => {
+  mockServer.close();
+});
+
+describe('Open Library provider', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['open_library_metadata_provider'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(200);
+    expect(metadataResponse.body).toEqual(OPEN_LIBRARY_METADATA);
+
+    const coverResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(200);
+
+    const coverPath = path.join(COVERS_DIR, 'Generic.jpeg');
+    const cover = fs.readFileSync(coverPath);
+    expect(cover).toEqual(coverResponse.body);
+  });
+
+  test('Provenance', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['open_library_metadata_provider', 'epub_metadata_extractor'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(metadataResponse.status).toBe(200);
+    expect(metadataResponse.body.page_count).toBe(OPEN_LIBRARY_METADATA.page_count);
+    expect(metadataResponse.body.provenance.page_count.source).toBe('open_library_metadata_provider');
+    expect(metadataResponse.body.provenance.title.source).toBe('open_library_metadata_provider');
+  });
+
+  test('No match', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['open_library_metadata_provider'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+  });
+
+  test('Select as default provider', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, ['open_library_metadata_provider'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_providers).toEqual(['open_library_metadata_provider']);
+  });
+});
+
+describe('Google Books provider', () => {
+  test('Simple', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['google_books_metadata_provider'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(200);
+    expect(metadataResponse.body).toEqual(GOOGLE_BOOKS_METADATA);
+
+    const coverResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(200);
+
+    const coverPath = path.join(COVERS_DIR, 'Generic.jpeg');
+    const cover = fs.readFileSync(coverPath);
+    expect(cover).toEqual(coverResponse.body);
+  });
+
+  test('Language matching', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['google_books_metadata_provider'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    // The first result is a German edition, but the book is in English
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(200);
+    expect(metadataResponse.body.title).toBe('The Wonderful Wizard of Oz');
+    expect(metadataResponse.body.language).toBe('en');
+  });
+
+  test('No match', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    // This epub has no title or identifiers to search with
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['google_books_metadata_provider'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+  });
+});
+
+describe('Metadata plugins', () => {
+  test('HTTP plugin', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['test_http_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(200);
+    expect(metadataResponse.body).toEqual(HTTP_PLUGIN_METADATA);
+
+    const coverResponse = await getCover(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(coverResponse.status).toBe(200);
+
+    const coverPath = path.join(COVERS_DIR, 'Generic.jpeg');
+    const cover = fs.readFileSync(coverPath);
+    expect(cover).toEqual(coverResponse.body);
+  });
+
+  test('Command plugin', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['test_command_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token }, true);
+    expect(metadataResponse.status).toBe(200);
+    expect(metadataResponse.body.title).toBe(COMMAND_PLUGIN_METADATA.title);
+    expect(metadataResponse.body.page_count).toBe(COMMAND_PLUGIN_METADATA.page_count);
+    expect(metadataResponse.body.provenance.title.source).toBe('test_command_plugin');
+  });
+
+  test('No match', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'The_Wonderful_Wizard_of_Oz.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['test_http_plugin', 'test_command_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    // Wait for metadata to be fetched
+    await wait(1.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+  });
+
+  test('Timeout', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    // This plugin takes longer to answer than its configured timeout
+    const addResponse = await addMetadataRequest(uploadResponse.text, ['test_slow_plugin'], { jwt: registerResponse.body.jwt_token });
+    expect(addResponse.status).toBe(204);
+
+    await wait(2.5);
+
+    const metadataResponse = await getMetadata(uploadResponse.text, { jwt: registerResponse.body.jwt_token });
+    expect(metadataResponse.status).toBe(404);
+
+    // The plugin is retried once before the request is marked as failed
+    const listResponse = await listMetadataRequests(userId, { jwt: registerResponse.body.jwt_token }, 'failed');
+    expect(listResponse.status).toBe(200);
+    expect(listResponse.body.length).toBe(1);
+    expect(listResponse.body[0].reason).toBe('test_slow_plugin: the provider timed out');
+  });
+
+  test('Select as default provider', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, ['test_http_plugin', 'epub_metadata_extractor'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    const getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_providers).toEqual(['test_http_plugin', 'epub_metadata_extractor']);
+  });
+
+  test('Unknown plugin', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, ['unknown_plugin'], undefined, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(400);
+    expect(patchPreferencesResponse.text).toBe(INVALID_PROVIDERS);
+  });
+});
@@ -1,11 +1,11 @@
 import { addAnnotation, ALICE_NOTE, deleteAnnotation, patchAnnotation } from '../utils/annotations.js';
 import { deleteBook, uploadBook } from '../utils/books.js';
 import { FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
-import { deleteCover, updateCover } from '../utils/covers.js';
-import { deleteMetadata, EXAMPLE_METADATA, patchMetadata, updateMetadata } from '../utils/metadata.js';
-import { addBookToShelf, createShelf, deleteBookFromShelf, deleteShelf } from '../utils/shelves.js';
-import { ALICE_STATE, patchState, updateState } from '../utils/state.js';
-import { INVALID_SYNC_TOKEN, sync } from '../utils/sync.js';
+import { addCover, deleteCover, updateCover } from '../utils/covers.js';
+import { addMetadata, deleteMetadata, EXAMPLE_METADATA, getMetadata, patchMetadata, updateMetadata } from '../utils/metadata.js';
+import { addBookToShelf, createShelf, deleteBookFromShelf, deleteShelf, getShelfMetadata } from '../utils/shelves.js';
+import { ALICE_STATE, getState, patchState, updateState } from '../utils/state.js';
+import { INVALID_EXPAND_FLAG, INVALID_LIMIT, INVALID_SYNC_TOKEN, sync } from '../utils/sync.js';
 import { createApiKey, loginUser, registerUser, USER_NOT_FOUND } from '../utils/users.js';
 
 describe('Sync JWT', () => {
@@ -50,9 +50,9 @@ describe('Sync JWT', () => {
       new_sync_token: currentSyncToken,
       unsynced_books: {
         file: [],
-        // metadata and cover are asynchronous
+        // metadata is asynchronous, while the cover is extracted during the upload
         metadata: [bookId],
-        cover: [bookId],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -101,7 +101,7 @@ describe('Sync JWT', () => {
       unsynced_books: {
         file: [],
         metadata: [bookId2],
-        cover: [bookId2],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -122,7 +122,7 @@ describe('Sync JWT', () => {
       unsynced_books: {
         file: [],
         metadata: [bookId, bookId2],
-        cover: [bookId, bookId2],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -331,7 +331,7 @@ describe('Sync JWT', () => {
       unsynced_books: {
         file: [],
         metadata: [bookId],
-        cover: [bookId],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -410,7 +410,7 @@ describe('Sync JWT', () => {
 
     expect(syncResponse.body).toEqual(expectedResponse);
 
-    // This book has no metadata nor cover
+    // This book has no metadata nor cover, so it gets a placeholder
     const uploadResponse2 = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: jwtToken });
     expect(uploadResponse2.status).toBe(200);
     const bookId2 = uploadResponse2.text;
@@ -430,7 +430,7 @@ describe('Sync JWT', () => {
       unsynced_books: {
         file: [bookId2, bookId3],
         metadata: [bookId3],
-        cover: [bookId3],
+        cover: [bookId2, bookId3],
         state: [],
         annotations: [],
         deleted: [bookId]
@@ -756,6 +756,47 @@ describe('Sync JWT', () => {
     expect(syncResponse.body).toEqual(expectedResponse);
   });
 
+  test('Placeholder cover', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    // This epub does not contain a cover
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: jwtToken });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+    await wait(1);
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.cover).toEqual([bookId]);
+    let currentSyncToken = syncResponse.body.new_sync_token;
+
+    // The placeholder is drawn again with the new title
+    const addMetadataResponse = await addMetadata(bookId, EXAMPLE_METADATA, { jwt: jwtToken });
+    expect(addMetadataResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.cover).toEqual([bookId]);
+    currentSyncToken = syncResponse.body.new_sync_token;
+
+    const addCoverResponse = await addCover(bookId, 'Generic.jpeg', { jwt: jwtToken });
+    expect(addCoverResponse.status).toBe(204);
+
+    const deleteCoverResponse = await deleteCover(bookId, { jwt: jwtToken });
+    expect(deleteCoverResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.cover).toEqual([bookId]);
+  });
+
   test('Changed state', async () => {
     const { response: registerResponse, username, password } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -1110,7 +1151,7 @@ describe('Sync JWT', () => {
       unsynced_books: {
         file: [],
         metadata: [uploadResponse.text],
-        cover: [uploadResponse.text],
+        cover: [],
         state: [],
         annotations: [],
         deleted: [] as string[]
@@ -1133,7 +1174,7 @@ describe('Sync JWT', () => {
       unsynced_books: {
         file: [],
         metadata: [uploadResponse2.text],
-        cover: [uploadResponse2.text],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -1158,6 +1199,310 @@ describe('Sync JWT', () => {
     expect(syncResponse.text).toBe(INVALID_SYNC_TOKEN);
   });
 
+  test('Sync token ahead of the change log', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: registerResponse.body.jwt_token });
+    expect(uploadResponse.status).toBe(200);
+
+    let syncResponse = await sync(userId, undefined, { jwt: registerResponse.body.jwt_token });
+    expect(syncResponse.status).toBe(200);
+    const latestSyncToken = syncResponse.body.new_sync_token;
+
+    syncResponse = await sync(userId, latestSyncToken + 1000, { jwt: registerResponse.body.jwt_token });
+    expect(syncResponse.status).toBe(400);
+    expect(syncResponse.text).toBe(INVALID_SYNC_TOKEN);
+
+    syncResponse = await sync(userId, -2, { jwt: registerResponse.body.jwt_token });
+    expect(syncResponse.status).toBe(400);
+    expect(syncResponse.text).toBe(INVALID_SYNC_TOKEN);
+
+    syncResponse = await sync(userId, latestSyncToken, { jwt: registerResponse.body.jwt_token });
+    expect(syncResponse.status).toBe(200);
+  });
+
+  test('Expanded books', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: jwtToken });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    // Wait for cover and metadata to be extracted
+    await wait(1);
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 }, true);
+    expect(syncResponse.status).toBe(200);
+    let currentSyncToken = syncResponse.body.new_sync_token;
+    expect(syncResponse.body.continuation_token).toBeUndefined();
+    expect(syncResponse.body.shelves).toEqual([]);
+
+    const metadataResponse = await getMetadata(bookId, { jwt: jwtToken });
+    expect(metadataResponse.status).toBe(200);
+    const stateResponse = await getState(bookId, { jwt: jwtToken });
+    expect(stateResponse.status).toBe(200);
+
+    // A new book carries everything
+    expect(syncResponse.body.books).toHaveLength(1);
+    expect(syncResponse.body.books[0].book_id).toBe(bookId);
+    expect(syncResponse.body.books[0].metadata).toEqual(metadataResponse.body);
+    expect(syncResponse.body.books[0].state).toEqual(stateResponse.body);
+    expect(syncResponse.body.books[0].annotations).toEqual([]);
+    expect(typeof syncResponse.body.books[0].cover_hash).toBe('string');
+
+    const addAnnotationResponse = await addAnnotation(bookId, ALICE_NOTE, { jwt: jwtToken });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 }, true);
+    expect(syncResponse.status).toBe(200);
+    currentSyncToken = syncResponse.body.new_sync_token;
+
+    // Other changes only carry what changed
+    expect(syncResponse.body.books).toHaveLength(1);
+    expect(syncResponse.body.books[0].book_id).toBe(bookId);
+    expect(syncResponse.body.books[0].metadata).toBeUndefined();
+    expect(syncResponse.body.books[0].state).toBeUndefined();
+    expect(syncResponse.body.books[0].cover_hash).toBeUndefined();
+    expect(syncResponse.body.books[0].annotations).toHaveLength(1);
+    expect(syncResponse.body.books[0].annotations[0].annotation_id).toBe(addAnnotationResponse.text);
+
+    const stateUpdateResponse = await updateState(bookId, ALICE_STATE, { jwt: jwtToken });
+    expect(stateUpdateResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 }, true);
+    expect(syncResponse.status).toBe(200);
+    currentSyncToken = syncResponse.body.new_sync_token;
+
+    expect(syncResponse.body.books).toEqual([{ book_id: bookId, state: ALICE_STATE }]);
+
+    const deleteResponse = await deleteBook(bookId, { jwt: jwtToken });
+    expect(deleteResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 }, true);
+    expect(syncResponse.status).toBe(200);
+
+    // Deleted books carry nothing
+    expect(syncResponse.body.unsynced_books.deleted).toEqual([bookId]);
+    expect(syncResponse.body.books).toEqual([]);
+  });
+
+  test('Expanded shelves', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: jwtToken });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const createShelfResponse = await createShelf('Expanded shelf', userId, { jwt: jwtToken });
+    expect(createShelfResponse.status).toBe(200);
+    const shelfId = createShelfResponse.text;
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 }, true);
+    expect(syncResponse.status).toBe(200);
+    let currentSyncToken = syncResponse.body.new_sync_token;
+
+    let shelfResponse = await getShelfMetadata(shelfId, { jwt: jwtToken });
+    expect(shelfResponse.status).toBe(200);
+
+    expect(syncResponse.body.shelves).toEqual([{ shelf_id: shelfId, metadata: shelfResponse.body }]);
+
+    const addBookResponse = await addBookToShelf(shelfId, bookId, { jwt: jwtToken });
+    expect(addBookResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 }, true);
+    expect(syncResponse.status).toBe(200);
+
+    expect(syncResponse.body.books).toEqual([]);
+    expect(syncResponse.body.shelves).toEqual([{ shelf_id: shelfId, contents: [bookId] }]);
+  });
+
+  test('Paginated sync', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    const shelfIds = [];
+    for (const name of ['First shelf', 'Second shelf', 'Third shelf']) {
+      const createShelfResponse = await createShelf(name, userId, { jwt: jwtToken });
+      expect(createShelfResponse.status).toBe(200);
+      shelfIds.push(createShelfResponse.text);
+    }
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 }, undefined, 2);
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_shelves.metadata).toEqual(shelfIds.slice(0, 2));
+    expect(syncResponse.body.continuation_token).toBe(syncResponse.body.new_sync_token);
+
+    syncResponse = await sync(userId, syncResponse.body.continuation_token, { jwt: jwtToken2 }, undefined, 2);
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_shelves.metadata).toEqual(shelfIds.slice(2));
+    expect(syncResponse.body.continuation_token).toBeUndefined();
+
+    syncResponse = await sync(userId, syncResponse.body.new_sync_token, { jwt: jwtToken2 }, undefined, 2);
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_shelves.metadata).toEqual([]);
+    expect(syncResponse.body.continuation_token).toBeUndefined();
+  });
+
+  test('Full resync', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: jwtToken });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const createShelfResponse = await createShelf('Resync shelf', userId, { jwt: jwtToken });
+    expect(createShelfResponse.status).toBe(200);
+    const shelfId = createShelfResponse.text;
+
+    // Wait for cover and metadata to be extracted
+    await wait(1);
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    const currentSyncToken = syncResponse.body.new_sync_token;
+    expect(syncResponse.body.full_resync_required).toBeUndefined();
+
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+
+    // Wait for the change log to be pruned past the retention period
+    await wait(8);
+
+    const loginResponse2 = await loginUser(username, password);
+    expect(loginResponse2.status).toBe(200);
+
+    syncResponse = await sync(userId, undefined, { jwt: loginResponse2.body.jwt_token });
+    expect(syncResponse.status).toBe(200);
+
+    expect(syncResponse.body).toEqual({
+      new_sync_token: currentSyncToken,
+      full_resync_required: true,
+      unsynced_books: {
+        file: [bookId],
+        metadata: [],
+        cover: [],
+        state: [],
+        annotations: [],
+        deleted: []
+      },
+      unsynced_shelves: {
+        metadata: [shelfId],
+        contents: [shelfId],
+        deleted: []
+      }
+    });
+
+    // Devices that were up to date carry on as usual
+    syncResponse = await sync(userId, currentSyncToken, { jwt: jwtToken2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.full_resync_required).toBeUndefined();
+    expect(syncResponse.body.new_sync_token).toBe(currentSyncToken);
+  }, 15000);
+
+  test('Full resync in pages', async () => {
+    const { response: registerResponse, username, password } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+    const jwtToken = registerResponse.body.jwt_token;
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt: jwtToken });
+    expect(uploadResponse.status).toBe(200);
+    const bookId = uploadResponse.text;
+
+    const shelfIds = [];
+    for (const name of ['First resync shelf', 'Second resync shelf']) {
+      const createShelfResponse = await createShelf(name, userId, { jwt: jwtToken });
+      expect(createShelfResponse.status).toBe(200);
+      shelfIds.push(createShelfResponse.text);
+    }
+    shelfIds.sort();
+
+    // Wait for the change log to be pruned past the retention period
+    await wait(8);
+
+    const loginResponse = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwtToken2 = loginResponse.body.jwt_token;
+
+    // Books come first, then shelves
+    let syncResponse = await sync(userId, undefined, { jwt: jwtToken2 }, true, 2);
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.full_resync_required).toBe(true);
+    expect(syncResponse.body.unsynced_books.file).toEqual([bookId]);
+    expect(syncResponse.body.unsynced_shelves.metadata).toEqual(shelfIds.slice(0, 1));
+    expect(syncResponse.body.books.map((book: any) => book.book_id)).toEqual([bookId]);
+    expect(syncResponse.body.shelves.map((shelf: any) => shelf.shelf_id)).toEqual(shelfIds.slice(0, 1));
+    expect(syncResponse.body.continuation_token).toBe(syncResponse.body.new_sync_token);
+    const resyncToken = syncResponse.body.new_sync_token;
+
+    syncResponse = await sync(userId, syncResponse.body.continuation_token, { jwt: jwtToken2 }, true, 2);
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.full_resync_required).toBe(true);
+    expect(syncResponse.body.new_sync_token).toBe(resyncToken);
+    expect(syncResponse.body.unsynced_books.file).toEqual([]);
+    expect(syncResponse.body.unsynced_shelves.metadata).toEqual(shelfIds.slice(1));
+    expect(syncResponse.body.continuation_token).toBeUndefined();
+
+    // Once every page was served, the device carries on from the change log
+    syncResponse = await sync(userId, syncResponse.body.new_sync_token, { jwt: jwtToken2 }, true, 2);
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.full_resync_required).toBeUndefined();
+    expect(syncResponse.body.unsynced_shelves.metadata).toEqual([]);
+  }, 15000);
+
+  test('Invalid expand flag', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let syncResponse = await sync(userId, undefined, { jwt: registerResponse.body.jwt_token }, 'not valid');
+    expect(syncResponse.status).toBe(400);
+    expect(syncResponse.text).toBe(INVALID_EXPAND_FLAG);
+  });
+
+  test('Invalid limit', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let syncResponse = await sync(userId, undefined, { jwt: registerResponse.body.jwt_token }, undefined, 0);
+    expect(syncResponse.status).toBe(400);
+    expect(syncResponse.text).toBe(INVALID_LIMIT);
+
+    syncResponse = await sync(userId, undefined, { jwt: registerResponse.body.jwt_token }, undefined, 'not valid');
+    expect(syncResponse.status).toBe(400);
+    expect(syncResponse.text).toBe(INVALID_LIMIT);
+  });
+
   test('Non-existent user', async () => {
     const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
     expect(registerResponse.status).toBe(200);
@@ -1249,9 +1594,9 @@ describe('Sync api key', () => {
       new_sync_token: currentSyncToken,
       unsynced_books: {
         file: [],
-        // metadata and cover are asynchronous
+        // metadata is asynchronous, while the cover is extracted during the upload
         metadata: [bookId],
-        cover: [bookId],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -1300,7 +1645,7 @@ describe('Sync api key', () => {
       unsynced_books: {
         file: [],
         metadata: [bookId2],
-        cover: [bookId2],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -1321,7 +1666,7 @@ describe('Sync api key', () => {
       unsynced_books: {
         file: [],
         metadata: [bookId, bookId2],
-        cover: [bookId, bookId2],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -1538,7 +1883,7 @@ describe('Sync api key', () => {
       unsynced_books: {
         file: [],
         metadata: [bookId],
-        cover: [bookId],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -1617,7 +1962,7 @@ describe('Sync api key', () => {
 
     expect(syncResponse.body).toEqual(expectedResponse);
 
-    // This book has no metadata nor cover
+    // This book has no metadata nor cover, so it gets a placeholder
     const uploadResponse2 = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt: jwtToken });
     expect(uploadResponse2.status).toBe(200);
     const bookId2 = uploadResponse2.text;
@@ -1637,7 +1982,7 @@ describe('Sync api key', () => {
       unsynced_books: {
         file: [bookId2, bookId3],
         metadata: [bookId3],
-        cover: [bookId3],
+        cover: [bookId2, bookId3],
         state: [],
         annotations: [],
         deleted: [bookId]
@@ -2325,7 +2670,7 @@ describe('Sync api key', () => {
       unsynced_books: {
         file: [],
         metadata: [uploadResponse.text],
-        cover: [uploadResponse.text],
+        cover: [],
         state: [],
         annotations: [],
         deleted: [] as string[]
@@ -2348,7 +2693,7 @@ describe('Sync api key', () => {
       unsynced_books: {
         file: [],
         metadata: [uploadResponse2.text],
-        cover: [uploadResponse2.text],
+        cover: [],
         state: [],
         annotations: [],
         deleted: []
@@ -0,0 +1,466 @@
+import { addAnnotation, ALICE_NOTE, listAnnotations } from '../utils/annotations.js';
+import { BOOK_ID_CONFLICT, BOOK_IN_TRASH, BOOK_NOT_FOUND, deleteBook, downloadBook, replaceBook, searchBooks, uploadBook } from '../utils/books.js';
+import { FORBIDDEN, INVALID_API_KEY, UNAUTHORIZED, wait } from '../utils/common.js';
+import { addBookToShelf, createShelf, deleteShelf, getShelfMetadata, listBooksFromShelf, SHELF_IN_TRASH, SHELF_NAME_CONFLICT, SHELF_NOT_FOUND, updateShelf } from '../utils/shelves.js';
+import { ALICE_STATE, getState, updateState } from '../utils/state.js';
+import { sync } from '../utils/sync.js';
+import { getTrash, restoreBook, restoreShelf, TRASH_RETENTION } from '../utils/trash.js';
+import { createApiKey, loginUser, registerUser, USER_NOT_FOUND } from '../utils/users.js';
+
+async function setupLibrary() {
+  const { response: registerResponse, username, password } = await registerUser();
+  expect(registerResponse.status).toBe(200);
+  const userId = registerResponse.body.user_id;
+  const jwt = registerResponse.body.jwt_token;
+
+  const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+  expect(uploadResponse.status).toBe(200);
+
+  const createShelfResponse = await createShelf('Favorites', userId, { jwt });
+  expect(createShelfResponse.status).toBe(200);
+
+  const addBookToShelfResponse = await addBookToShelf(createShelfResponse.text, uploadResponse.text, { jwt });
+  expect(addBookToShelfResponse.status).toBe(204);
+
+  return { userId, username, password, jwt, bookId: uploadResponse.text, shelfId: createShelfResponse.text };
+}
+
+describe('Get trash JWT', () => {
+  test('Empty', async () => {
+    const { userId, jwt } = await setupLibrary();
+
+    const getTrashResponse = await getTrash(userId, { jwt });
+    expect(getTrashResponse.status).toBe(200);
+    expect(getTrashResponse.body).toEqual({ books: [], shelves: [] });
+  });
+
+  test('Deleted book and shelf', async () => {
+    const { jwt, bookId, shelfId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    const getTrashResponse = await getTrash(undefined, { jwt });
+    expect(getTrashResponse.status).toBe(200);
+
+    const [book] = getTrashResponse.body.books;
+    const [shelf] = getTrashResponse.body.shelves;
+    expect(getTrashResponse.body.books.length).toBe(1);
+    expect(getTrashResponse.body.shelves.length).toBe(1);
+
+    expect(book.book_id).toBe(bookId);
+    expect(book.purge_at - book.deleted_at).toBe(TRASH_RETENTION);
+    expect(shelf.shelf_id).toBe(shelfId);
+    expect(shelf.name).toBe('Favorites');
+    expect(shelf.purge_at - shelf.deleted_at).toBe(TRASH_RETENTION);
+  });
+
+  test('Different user without permission', async () => {
+    const { userId } = await setupLibrary();
+
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const getTrashResponse = await getTrash(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getTrashResponse.status).toBe(403);
+    expect(getTrashResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Different user with permission', async () => {
+    const { userId, jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const getTrashResponse = await getTrash(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getTrashResponse.status).toBe(200);
+    expect(getTrashResponse.body.books.length).toBe(1);
+  });
+
+  test('Non-existent user', async () => {
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const getTrashResponse = await getTrash('non-existent', { jwt: registerResponse.body.jwt_token });
+    expect(getTrashResponse.status).toBe(404);
+    expect(getTrashResponse.text).toBe(USER_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { userId } = await setupLibrary();
+
+    const getTrashResponse = await getTrash(userId);
+    expect(getTrashResponse.status).toBe(401);
+    expect(getTrashResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Get trash API key', () => {
+  test('Simple', async () => {
+    const { userId, jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const getTrashResponse = await getTrash(undefined, { apiKey: createApiKeyResponse.body.key });
+    expect(getTrashResponse.status).toBe(200);
+    expect(getTrashResponse.body.books.length).toBe(1);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { userId, jwt } = await setupLibrary();
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create', 'Update', 'Delete'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const getTrashResponse = await getTrash(userId, { apiKey: createApiKeyResponse.body.key });
+    expect(getTrashResponse.status).toBe(403);
+    expect(getTrashResponse.text).toBe(FORBIDDEN);
+  });
+
+  test('Expired key', async () => {
+    const { userId, jwt } = await setupLibrary();
+
+    const timestamp = Date.now() + 1000;
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read'], timestamp, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    // Wait for the key to expire
+    await wait(1.5);
+
+    const getTrashResponse = await getTrash(userId, { apiKey: createApiKeyResponse.body.key });
+    expect(getTrashResponse.status).toBe(401);
+    expect(getTrashResponse.text).toBe(INVALID_API_KEY);
+  });
+});
+
+describe('Restore book JWT', () => {
+  test('Simple', async () => {
+    const { jwt, bookId, shelfId } = await setupLibrary();
+
+    const updateStateResponse = await updateState(bookId, ALICE_STATE, { jwt });
+    expect(updateStateResponse.status).toBe(204);
+    const addAnnotationResponse = await addAnnotation(bookId, ALICE_NOTE, { jwt });
+    expect(addAnnotationResponse.status).toBe(200);
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const restoreResponse = await restoreBook(bookId, { jwt });
+    expect(restoreResponse.status).toBe(204);
+
+    const downloadResponse = await downloadBook(bookId, { jwt });
+    expect(downloadResponse.status).toBe(200);
+
+    const getStateResponse = await getState(bookId, { jwt });
+    expect(getStateResponse.status).toBe(200);
+    expect(getStateResponse.body).toEqual(ALICE_STATE);
+
+    const listAnnotationsResponse = await listAnnotations(bookId, { jwt });
+    expect(listAnnotationsResponse.status).toBe(200);
+    expect(listAnnotationsResponse.body).toEqual([addAnnotationResponse.text]);
+
+    const listBooksResponse = await listBooksFromShelf(shelfId, { jwt });
+    expect(listBooksResponse.status).toBe(200);
+    expect(listBooksResponse.body).toEqual([bookId]);
+
+    const getTrashResponse = await getTrash(undefined, { jwt });
+    expect(getTrashResponse.status).toBe(200);
+    expect(getTrashResponse.body.books).toEqual([]);
+  });
+
+  test('Hidden while in trash', async () => {
+    const { username, jwt, bookId, shelfId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const downloadResponse = await downloadBook(bookId, { jwt });
+    expect(downloadResponse.status).toBe(404);
+    expect(downloadResponse.text).toBe(BOOK_NOT_FOUND);
+
+    const searchResponse = await searchBooks(username, undefined, undefined, undefined, undefined, { jwt });
+    expect(searchResponse.status).toBe(200);
+    expect(searchResponse.body.book_ids).toEqual([]);
+
+    const listBooksResponse = await listBooksFromShelf(shelfId, { jwt });
+    expect(listBooksResponse.status).toBe(200);
+    expect(listBooksResponse.body).toEqual([]);
+
+    const getShelfMetadataResponse = await getShelfMetadata(shelfId, { jwt });
+    expect(getShelfMetadataResponse.status).toBe(200);
+    expect(getShelfMetadataResponse.body.book_count).toBe(0);
+  });
+
+  test('Book and book id kept while in trash', async () => {
+    const { userId, jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const uploadResponse = await uploadBook(userId, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(uploadResponse.status).toBe(409);
+    expect(uploadResponse.text).toBe(BOOK_IN_TRASH);
+
+    const uploadResponse2 = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt }, bookId);
+    expect(uploadResponse2.status).toBe(409);
+    expect(uploadResponse2.text).toBe(BOOK_ID_CONFLICT);
+  });
+
+  test('Upload after trash', async () => {
+    const { userId, jwt, bookId } = await setupLibrary();
+
+    const uploadResponse = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt });
+    expect(uploadResponse.status).toBe(200);
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const replaceResponse = await replaceBook(uploadResponse.text, 'Alices_Adventures_in_Wonderland.epub', { jwt });
+    expect(replaceResponse.status).toBe(409);
+    expect(replaceResponse.text).toBe(BOOK_IN_TRASH);
+
+    const restoreResponse = await restoreBook(bookId, { jwt });
+    expect(restoreResponse.status).toBe(204);
+
+    const deleteBookResponse2 = await deleteBook(uploadResponse.text, { jwt });
+    expect(deleteBookResponse2.status).toBe(204);
+
+    const uploadResponse2 = await uploadBook(userId, 'The_Great_Gatsby.epub', { jwt });
+    expect(uploadResponse2.status).toBe(409);
+    expect(uploadResponse2.text).toBe(BOOK_IN_TRASH);
+  });
+
+  test('Synced devices', async () => {
+    const { userId, username, password, jwt, bookId, shelfId } = await setupLibrary();
+
+    const { response: loginResponse } = await loginUser(username, password);
+    expect(loginResponse.status).toBe(200);
+    const jwt2 = loginResponse.body.jwt_token;
+
+    await wait(1);
+
+    let syncResponse = await sync(userId, undefined, { jwt: jwt2 });
+    expect(syncResponse.status).toBe(200);
+    const syncToken = syncResponse.body.new_sync_token;
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, syncToken, { jwt: jwt2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.deleted).toEqual([bookId]);
+
+    const restoreResponse = await restoreBook(bookId, { jwt });
+    expect(restoreResponse.status).toBe(204);
+
+    syncResponse = await sync(userId, syncToken, { jwt: jwt2 });
+    expect(syncResponse.status).toBe(200);
+    expect(syncResponse.body.unsynced_books.file).toEqual([bookId]);
+    expect(syncResponse.body.unsynced_books.state).toEqual([bookId]);
+    expect(syncResponse.body.unsynced_books.deleted).toEqual([]);
+    expect(syncResponse.body.unsynced_shelves.contents).toEqual([shelfId]);
+  });
+
+  test('Book not in trash', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const restoreResponse = await restoreBook(bookId, { jwt });
+    expect(restoreResponse.status).toBe(404);
+    expect(restoreResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Non-existent book', async () => {
+    const { jwt } = await setupLibrary();
+
+    const restoreResponse = await restoreBook('non-existent', { jwt });
+    expect(restoreResponse.status).toBe(404);
+    expect(restoreResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const restoreResponse = await restoreBook(bookId, { jwt: registerResponse.body.jwt_token });
+    expect(restoreResponse.status).toBe(404);
+    expect(restoreResponse.text).toBe(BOOK_NOT_FOUND);
+  });
+
+  test('Different user with permission', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const { response: registerResponse } = await registerUser(undefined, undefined, true, process.env.ADMIN_KEY);
+    expect(registerResponse.status).toBe(200);
+
+    const restoreResponse = await restoreBook(bookId, { jwt: registerResponse.body.jwt_token });
+    expect(restoreResponse.status).toBe(204);
+  });
+
+  test('No auth', async () => {
+    const { jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const restoreResponse = await restoreBook(bookId);
+    expect(restoreResponse.status).toBe(401);
+    expect(restoreResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Restore book API key', () => {
+  test('Simple', async () => {
+    const { userId, jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const restoreResponse = await restoreBook(bookId, { apiKey: createApiKeyResponse.body.key });
+    expect(restoreResponse.status).toBe(204);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { userId, jwt, bookId } = await setupLibrary();
+
+    const deleteBookResponse = await deleteBook(bookId, { jwt });
+    expect(deleteBookResponse.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read', 'Update', 'Delete'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const restoreResponse = await restoreBook(bookId, { apiKey: createApiKeyResponse.body.key });
+    expect(restoreResponse.status).toBe(403);
+    expect(restoreResponse.text).toBe(FORBIDDEN);
+  });
+});
+
+describe('Restore shelf JWT', () => {
+  test('Simple', async () => {
+    const { jwt, bookId, shelfId } = await setupLibrary();
+
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    let getShelfMetadataResponse = await getShelfMetadata(shelfId, { jwt });
+    expect(getShelfMetadataResponse.status).toBe(404);
+    expect(getShelfMetadataResponse.text).toBe(SHELF_NOT_FOUND);
+
+    const restoreResponse = await restoreShelf(shelfId, { jwt });
+    expect(restoreResponse.status).toBe(204);
+
+    getShelfMetadataResponse = await getShelfMetadata(shelfId, { jwt });
+    expect(getShelfMetadataResponse.status).toBe(200);
+    expect(getShelfMetadataResponse.body.book_count).toBe(1);
+
+    const listBooksResponse = await listBooksFromShelf(shelfId, { jwt });
+    expect(listBooksResponse.status).toBe(200);
+    expect(listBooksResponse.body).toEqual([bookId]);
+  });
+
+  test('Name kept while in trash', async () => {
+    const { userId, jwt, shelfId } = await setupLibrary();
+
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    const createShelfResponse = await createShelf('Favorites', userId, { jwt });
+    expect(createShelfResponse.status).toBe(409);
+    expect(createShelfResponse.text).toBe(SHELF_IN_TRASH);
+
+    const createShelfResponse2 = await createShelf('Read later', userId, { jwt });
+    expect(createShelfResponse2.status).toBe(200);
+
+    const updateShelfResponse = await updateShelf(createShelfResponse2.text, 'Favorites', { jwt });
+    expect(updateShelfResponse.status).toBe(409);
+    expect(updateShelfResponse.text).toBe(SHELF_IN_TRASH);
+
+    const restoreResponse = await restoreShelf(shelfId, { jwt });
+    expect(restoreResponse.status).toBe(204);
+
+    const createShelfResponse3 = await createShelf('Favorites', userId, { jwt });
+    expect(createShelfResponse3.status).toBe(409);
+    expect(createShelfResponse3.text).toBe(SHELF_NAME_CONFLICT);
+  });
+
+  test('Shelf not in trash', async () => {
+    const { jwt, shelfId } = await setupLibrary();
+
+    const restoreResponse = await restoreShelf(shelfId, { jwt });
+    expect(restoreResponse.status).toBe(404);
+    expect(restoreResponse.text).toBe(SHELF_NOT_FOUND);
+  });
+
+  test('Different user without permission', async () => {
+    const { jwt, shelfId } = await setupLibrary();
+
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+
+    const restoreResponse = await restoreShelf(shelfId, { jwt: registerResponse.body.jwt_token });
+    expect(restoreResponse.status).toBe(404);
+    expect(restoreResponse.text).toBe(SHELF_NOT_FOUND);
+  });
+
+  test('No auth', async () => {
+    const { jwt, shelfId } = await setupLibrary();
+
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    const restoreResponse = await restoreShelf(shelfId);
+    expect(restoreResponse.status).toBe(401);
+    expect(restoreResponse.text).toBe(UNAUTHORIZED);
+  });
+});
+
+describe('Restore shelf API key', () => {
+  test('Simple', async () => {
+    const { userId, jwt, shelfId } = await setupLibrary();
+
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Create'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const restoreResponse = await restoreShelf(shelfId, { apiKey: createApiKeyResponse.body.key });
+    expect(restoreResponse.status).toBe(204);
+  });
+
+  test('Wrong capabilities', async () => {
+    const { userId, jwt, shelfId } = await setupLibrary();
+
+    const deleteShelfResponse = await deleteShelf(shelfId, { jwt });
+    expect(deleteShelfResponse.status).toBe(204);
+
+    const createApiKeyResponse = await createApiKey(userId, 'Test Key', ['Read', 'Update', 'Delete'], undefined, { jwt });
+    expect(createApiKeyResponse.status).toBe(200);
+
+    const restoreResponse = await restoreShelf(shelfId, { apiKey: createApiKeyResponse.body.key });
+    expect(restoreResponse.status).toBe(403);
+    expect(restoreResponse.text).toBe(FORBIDDEN);
+  });
+});
@@ -11,8 +11,10 @@ import {
   INVALID_ADMIN_KEY,
   INVALID_CAPABILITIES,
   INVALID_CREDENTIALS,
+  INVALID_MERGE_POLICY,
   INVALID_PREFERENCES,
   INVALID_PROVIDERS,
+  INVALID_REFRESH_AGE,
   INVALID_TIMESTAMP,
   INVALID_TOKEN,
   INVALID_USERNAME_PASSWORD,
@@ -651,6 +653,8 @@ describe('Get preferences', () => {
     expect(getPreferencesResponse.body.metadata_providers).toEqual(['epub_metadata_extractor']);
     expect(getPreferencesResponse.body).toHaveProperty('automatic_metadata');
     expect(getPreferencesResponse.body.automatic_metadata).toEqual(true);
+    expect(getPreferencesResponse.body).toHaveProperty('metadata_review');
+    expect(getPreferencesResponse.body.metadata_review).toEqual(false);
   });
 
   test('Non-existent user', async () => {
@@ -746,6 +750,45 @@ describe('Update preferences', () => {
     expect(updatePreferencesResponse.text).toBe(INVALID_PROVIDERS);
   });
 
+  test('Merge policy', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_merge_policy).toEqual({});
+
+    const mergePolicy = { title: 'epub_metadata_extractor', description: 'goodreads_metadata_scraper', genres: 'most_complete', subtitle: 'longest' };
+    const updatePreferencesResponse = await updatePreferences(userId, ['epub_metadata_extractor'], true, { jwt: registerResponse.body.jwt_token }, mergePolicy);
+    expect(updatePreferencesResponse.status).toBe(204);
+
+    const getPreferencesResponse2 = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse2.status).toBe(200);
+    expect(getPreferencesResponse2.body.metadata_merge_policy).toEqual(mergePolicy);
+
+    const updatePreferencesResponse2 = await updatePreferences(userId, ['epub_metadata_extractor'], true, { jwt: registerResponse.body.jwt_token });
+    expect(updatePreferencesResponse2.status).toBe(204);
+
+    const getPreferencesResponse3 = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse3.status).toBe(200);
+    expect(getPreferencesResponse3.body.metadata_merge_policy).toEqual({});
+  });
+
+  test('Invalid merge policy', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let updatePreferencesResponse = await updatePreferences(userId, ['epub_metadata_extractor'], true, { jwt: registerResponse.body.jwt_token }, { title: 'invalid policy' });
+    expect(updatePreferencesResponse.status).toBe(400);
+    expect(updatePreferencesResponse.text).toBe(INVALID_MERGE_POLICY);
+
+    updatePreferencesResponse = await updatePreferences(userId, ['epub_metadata_extractor'], true, { jwt: registerResponse.body.jwt_token }, { invalid_field: 'longest' });
+    expect(updatePreferencesResponse.status).toBe(400);
+    expect(updatePreferencesResponse.text).toBe(INVALID_MERGE_POLICY);
+  });
+
   test('Missing metadata preference', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
@@ -845,6 +888,79 @@ describe('Patch preferences', () => {
     expect(patchPreferencesResponse.text).toBe(INVALID_PROVIDERS);
   });
 
+  test('Merge policy', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const mergePolicy = { description: 'longest' };
+    let patchPreferencesResponse = await patchPreferences(userId, undefined, undefined, { jwt: registerResponse.body.jwt_token }, mergePolicy);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    let getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_providers).toEqual(['epub_metadata_extractor']);
+    expect(getPreferencesResponse.body.automatic_metadata).toEqual(true);
+    expect(getPreferencesResponse.body.metadata_merge_policy).toEqual(mergePolicy);
+
+    patchPreferencesResponse = await patchPreferences(userId, undefined, false, { jwt: registerResponse.body.jwt_token });
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_merge_policy).toEqual(mergePolicy);
+  });
+
+  test('Invalid merge policy', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, undefined, { jwt: registerResponse.body.jwt_token }, { cover: 'longest' });
+    expect(patchPreferencesResponse.status).toBe(400);
+    expect(patchPreferencesResponse.text).toBe(INVALID_MERGE_POLICY);
+  });
+
+  test('Refresh age', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_refresh_age).toBeNull();
+
+    let patchPreferencesResponse = await patchPreferences(userId, undefined, undefined, { jwt: registerResponse.body.jwt_token }, undefined, undefined, 86400);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.metadata_refresh_age).toBe(86400);
+    expect(getPreferencesResponse.body.automatic_metadata).toEqual(true);
+
+    patchPreferencesResponse = await patchPreferences(userId, undefined, undefined, { jwt: registerResponse.body.jwt_token }, undefined, undefined, -1);
+    expect(patchPreferencesResponse.status).toBe(400);
+    expect(patchPreferencesResponse.text).toBe(INVALID_REFRESH_AGE);
+  });
+
+  test('Embed metadata', async () => {
+    const { response: registerResponse } = await registerUser();
+    expect(registerResponse.status).toBe(200);
+    const userId = registerResponse.body.user_id;
+
+    let getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.embed_metadata).toBe(false);
+
+    const patchPreferencesResponse = await patchPreferences(userId, undefined, undefined, { jwt: registerResponse.body.jwt_token }, undefined, undefined, undefined, true);
+    expect(patchPreferencesResponse.status).toBe(204);
+
+    getPreferencesResponse = await getPreferences(userId, { jwt: registerResponse.body.jwt_token });
+    expect(getPreferencesResponse.status).toBe(200);
+    expect(getPreferencesResponse.body.embed_metadata).toBe(true);
+    expect(getPreferencesResponse.body.automatic_metadata).toEqual(true);
+  });
+
   test('Empty body', async () => {
     const { response: registerResponse } = await registerUser();
     expect(registerResponse.status).toBe(200);
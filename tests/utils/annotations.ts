@@ -4,6 +4,12 @@ import { SERVER_URL } from './common.js';
 export const INVALID_ANNOTATION = 'The provided annotation is invalid.';
 export const ANNOTATION_NOT_FOUND = 'The requested annotation does not exist or is not accessible.';
 export const ANNOTATION_CONFLICT = 'An annotation in this position already exists.';
+export const INVALID_ANNOTATION_TYPE = 'The requested annotation type is invalid.';
+export const INVALID_TAGS = 'The provided tags are invalid.';
+export const INVALID_TIMESTAMP = 'The provided timestamp is invalid.';
+export const INVALID_EXPORT_FORMAT = 'The requested export format is invalid.';
+export const INVALID_IMPORT_FORMAT = 'The requested import format is invalid.';
+export const INVALID_IMPORT_FILE = 'The provided annotations file could not be read.';
 
 export const ALICE_NOTE = {
   source: 'OEBPS/229714655232534212_11-h-10.htm.xhtml',
@@ -14,6 +20,41 @@ export const ALICE_NOTE = {
   note: 'I loved this part!'
 };
 
+export const ALICE_BOOKMARK = {
+  annotation_type: 'Bookmark',
+  source: 'OEBPS/229714655232534212_11-h-10.htm.xhtml',
+  start_tag: 'kobo.74.1',
+  start_char: 0
+};
+
+export const ALICE_CLIPPINGS = [
+  "Alice's Adventures in Wonderland (Carroll, Lewis)",
+  '- Your Highlight on page 1 | Location 40-42 | Added on Monday, 3 March 2025 21:04:12',
+  '',
+  'There was nothing so very remarkable in that; nor did Alice think it so very much out of the way to hear the Rabbit say to itself, "Oh dear! Oh dear! I shall be late!"',
+  '==========',
+  "Alice's Adventures in Wonderland (Carroll, Lewis)",
+  '- Your Note on page 1 | Location 42 | Added on Monday, 3 March 2025 21:04:30',
+  '',
+  'Poor rabbit',
+  '==========',
+  "Alice's Adventures in Wonderland (Carroll, Lewis)",
+  '- Your Bookmark on page 2 | Location 57 | Added on Monday, 3 March 2025 21:05:02',
+  '',
+  '',
+  '==========',
+  "Alice's Adventures in Wonderland (Carroll, Lewis)",
+  '- Your Highlight on page 3 | Location 80-81 | Added on Monday, 3 March 2025 21:06:44',
+  '',
+  'This sentence is not in the book at all.',
+  '==========',
+  'Some Other Book (Someone)',
+  '- Your Highlight on page 9 | Location 120-121 | Added on Tuesday, 4 March 2025 08:15:00',
+  '',
+  'Hello there.',
+  '=========='
+].join('\r\n');
+
 export async function addAnnotation(book_id: string, annotation: any, auth?: { jwt?: string; apiKey?: string }) {
   let req = request(SERVER_URL).post(`/books/${book_id}/annotations`);
 
@@ -32,22 +73,23 @@ export async function getAnnotation(book_id: string, annotation_id: string, auth
   return req.send();
 }
 
-export async function listAnnotations(book_id: string, auth?: { jwt?: string; apiKey?: string }) {
+export async function listAnnotations(book_id: string, auth?: { jwt?: string; apiKey?: string }, type?: string) {
   let req = request(SERVER_URL).get(`/books/${book_id}/annotations`);
 
+  if (type) req = req.query({ type: type });
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
 
   return req.send();
 }
 
-export async function patchAnnotation(book_id: string, annotation_id: string, note: string, auth?: { jwt?: string; apiKey?: string }) {
+export async function patchAnnotation(book_id: string, annotation_id: string, note: string | undefined, auth?: { jwt?: string; apiKey?: string }, changes?: { color?: string; style?: string; tags?: string[] }) {
   let req = request(SERVER_URL).patch(`/books/${book_id}/annotations/${annotation_id}`);
 
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
 
-  return req.send({ note: note });
+  return req.send({ note: note, ...changes });
 }
 
 export async function deleteAnnotation(book_id: string, annotation_id: string, auth?: { jwt?: string; apiKey?: string }) {
@@ -58,3 +100,46 @@ export async function deleteAnnotation(book_id: string, annotation_id: string, a
 
   return req.send();
 }
+
+export async function exportBookAnnotations(book_id: string, format?: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/books/${book_id}/annotations/export`);
+
+  if (format) req = req.query({ format: format });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function exportAnnotations(user_id?: string, format?: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/annotations/export`);
+
+  if (user_id) req = req.query({ user_id: user_id });
+  if (format) req = req.query({ format: format });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function importAnnotations(data: string, format?: string, book_id?: string, user_id?: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/annotations/import`);
+
+  if (format) req = req.query({ format: format });
+  if (book_id) req = req.query({ book_id: book_id });
+  if (user_id) req = req.query({ user_id: user_id });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.set('Content-Type', 'text/plain').send(data);
+}
+
+export async function searchAnnotations(params: { user_id?: string; q?: string; book_id?: string; tag?: string; type?: string; from?: any; to?: any; page?: any; size?: any }, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/annotations`);
+
+  req = req.query(params);
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
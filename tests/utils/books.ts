@@ -5,11 +5,13 @@ import { BOOK_DIR } from './common.js';
 import { SERVER_URL } from './common.js';
 
 export const BOOK_CONFLICT = 'This book is already in your library.';
+export const BOOK_IN_TRASH = 'This book is in your trash, restore it instead.';
 export const BOOK_NOT_FOUND = 'The requested book does not exist or is not accessible.';
 export const INVALID_BOOK = 'The provided EPUB data is invalid.';
 export const INVALID_PAGINATION = 'The requested pagination is invalid.';
 export const INVALID_BOOK_ID = 'The provided book id is invalid.';
 export const BOOK_ID_CONFLICT = 'The provided book id is already in use.';
+export const INVALID_EMBED_FLAG = 'The provided metadata embedding flag is invalid.';
 
 const bookCache: Record<string, Buffer> = {};
 
@@ -39,9 +41,24 @@ export async function uploadBook(owner_id?: string, epub_name?: string, auth?: {
   return req.attach('epub', epubBuffer);
 }
 
-export async function downloadBook(book_id: string, auth?: { jwt?: string; apiKey?: string }) {
+export async function replaceBook(book_id: string, epub_name?: string, auth?: { jwt?: string; apiKey?: string }) {
+  if (epub_name === undefined) throw new Error('EPUB name is required.');
+
+  const epubBuffer = bookCache[epub_name!];
+  if (!epubBuffer) throw new Error(`EPUB file not preloaded: ${epub_name}`);
+
+  let req = request(SERVER_URL).put(`/books/${book_id}`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.attach('epub', epubBuffer);
+}
+
+export async function downloadBook(book_id: string, auth?: { jwt?: string; apiKey?: string }, embed_metadata?: any) {
   let req = request(SERVER_URL).get(`/books/${book_id}`);
 
+  if (embed_metadata !== undefined) req = req.query({ embed_metadata: embed_metadata });
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
 
@@ -7,6 +7,9 @@ import fs from 'fs';
 export const COVER_NOT_FOUND = 'The requested cover does not exist or is not accessible.';
 export const COVER_CONFLICT = 'This book already has a cover.';
 export const INVALID_COVER = 'The provided cover image is invalid.';
+export const INVALID_COVER_SIZE = 'The requested cover size is invalid.';
+export const INVALID_COVER_FORMAT = 'The requested cover format is invalid.';
+export const EMBEDDED_COVER_NOT_FOUND = 'The book does not contain a cover.';
 
 const imageCache: Record<string, Buffer> = {};
 
@@ -43,9 +46,11 @@ export async function updateCover(book_id: string, cover_name: string, auth?: {
   return req.send(coverBuffer);
 }
 
-export async function getCover(book_id: string, auth?: { jwt?: string; apiKey?: string }) {
+export async function getCover(book_id: string, auth?: { jwt?: string; apiKey?: string }, variant?: { width?: any; height?: any; format?: any }) {
   let req = request(SERVER_URL).get(`/books/${book_id}/cover`);
 
+  if (variant !== undefined) req = req.query(variant);
+
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
 
@@ -60,3 +65,27 @@ export async function deleteCover(book_id: string, auth?: { jwt?: string; apiKey
 
   return req.send();
 }
+
+export async function extractCover(book_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/books/${book_id}/cover/extract`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export function imageSize(image: Buffer): { width: number; height: number } {
+  // PNG keeps its dimensions in the IHDR chunk
+  if (image.subarray(1, 4).toString() === 'PNG') return { width: image.readUInt32BE(16), height: image.readUInt32BE(20) };
+
+  // JPEG keeps them in the start of frame segment
+  let position = 2;
+  while (position < image.length) {
+    const marker = image[position + 1];
+    if (marker >= 0xc0 && marker <= 0xc2) return { width: image.readUInt16BE(position + 7), height: image.readUInt16BE(position + 5) };
+    position += 2 + image.readUInt16BE(position + 2);
+  }
+
+  throw new Error('Unsupported image');
+}
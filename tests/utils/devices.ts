@@ -0,0 +1,53 @@
+import request from 'supertest';
+import { SERVER_URL } from './common.js';
+
+export const DEVICE_NOT_FOUND = 'The requested device does not exist or is not accessible.';
+export const DEVICE_CONFLICT = 'This session is already registered as a device.';
+export const INVALID_DEVICE_NAME = 'The provided device name is invalid.';
+export const INVALID_DEVICE_TYPE = 'The provided device type is invalid.';
+export const INVALID_DEVICE_SYNC_TOKEN = 'The provided sync token is invalid.';
+
+export async function registerDevice(user_id: string, name: any, device_type: any, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/users/${user_id}/devices`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send({ name, device_type });
+}
+
+export async function listDevices(user_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/users/${user_id}/devices`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function getDevice(user_id: string, device_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/users/${user_id}/devices/${device_id}`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function patchDevice(user_id: string, device_id: string, changes: { name?: any; device_type?: any; sync_token?: any }, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).patch(`/users/${user_id}/devices/${device_id}`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send(changes);
+}
+
+export async function removeDevice(user_id: string, device_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).delete(`/users/${user_id}/devices/${device_id}`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
@@ -0,0 +1,27 @@
+import request from 'supertest';
+import { SERVER_URL } from './common.js';
+
+export const INVALID_MERGE_REQUEST = 'The provided merge request is invalid.';
+
+export async function listDuplicates(user_id?: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/duplicates`);
+
+  if (user_id) req = req.query({ user_id: user_id });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function mergeBooks(book_id: string, duplicate_id: any, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/books/${book_id}/merge`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  const body = {
+    duplicate_id: duplicate_id
+  };
+
+  return req.send(body);
+}
@@ -4,6 +4,14 @@ import { SERVER_URL } from './common.js';
 export const METADATA_NOT_FOUND = 'The requested metadata does not exist or is not accessible.';
 export const METADATA_CONFLICT = 'This book already has metadata.';
 export const INVALID_METADATA = 'The provided metadata is invalid.';
+export const INVALID_METADATA_FIELD = 'The provided metadata field is invalid.';
+export const INVALID_PROVENANCE_FLAG = 'The provided provenance flag is invalid.';
+export const CANDIDATE_NOT_FOUND = 'The requested metadata candidate does not exist or is not accessible.';
+export const INVALID_CANDIDATE_STATUS = 'The provided candidate status is invalid.';
+export const INVALID_JOB_STATUS = 'The provided metadata request status is invalid.';
+export const METADATA_REQUEST_NOT_FOUND = 'The requested metadata request does not exist or is not accessible.';
+export const METADATA_REQUEST_NOT_QUEUED = 'This metadata request is no longer queued.';
+export const INVALID_CANDIDATE_SELECTION = 'The provided candidate selection is invalid.';
 
 export const EXAMPLE_METADATA = {
   title: 'To Kill a Mockingbird',
@@ -68,9 +76,10 @@ export async function patchMetadata(book_id: string, metadata: any, auth?: { jwt
   return req.send(metadata);
 }
 
-export async function getMetadata(book_id: string, auth?: { jwt?: string; apiKey?: string }) {
+export async function getMetadata(book_id: string, auth?: { jwt?: string; apiKey?: string }, provenance?: any) {
   let req = request(SERVER_URL).get(`/books/${book_id}/metadata`);
 
+  if (provenance !== undefined) req = req.query({ provenance: provenance });
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
 
@@ -86,7 +95,57 @@ export async function deleteMetadata(book_id: string, auth?: { jwt?: string; api
   return req.send();
 }
 
-export async function addMetadataRequest(book_id: string, providers?: string[], auth?: { jwt?: string; apiKey?: string }) {
+export async function updateMetadataLocks(book_id: string, locked_fields: any, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).put(`/books/${book_id}/metadata/locks`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send({ locked_fields });
+}
+
+export async function listMetadataCandidates(book_id: string, status?: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/books/${book_id}/metadata/candidates`);
+
+  if (status !== undefined) req = req.query({ status: status });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function getMetadataCandidateCover(book_id: string, candidate_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/books/${book_id}/metadata/candidates/${candidate_id}/cover`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function applyMetadataCandidate(book_id: string, candidate_id: string, fields?: string[], cover?: boolean, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/books/${book_id}/metadata/candidates/${candidate_id}/apply`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  const body: any = {};
+  if (fields !== undefined) body.fields = fields;
+  if (cover !== undefined) body.cover = cover;
+
+  return req.send(body);
+}
+
+export async function rejectMetadataCandidate(book_id: string, candidate_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/books/${book_id}/metadata/candidates/${candidate_id}/reject`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function addMetadataRequest(book_id: string, providers?: string[], auth?: { jwt?: string; apiKey?: string }, priority?: number) {
   let req = request(SERVER_URL).post(`/metadata-requests`);
 
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
@@ -95,14 +154,44 @@ export async function addMetadataRequest(book_id: string, providers?: string[],
   const body: any = {};
   body.book_id = book_id;
   if (providers !== undefined) body.metadata_providers = providers;
+  if (priority !== undefined) body.priority = priority;
 
   return req.send(body);
 }
 
-export async function listMetadataRequests(user_id?: string, auth?: { jwt?: string; apiKey?: string }) {
+export async function listMetadataRequests(user_id?: string, auth?: { jwt?: string; apiKey?: string }, status?: string, book_id?: string) {
   let req = request(SERVER_URL).get(`/metadata-requests`);
 
   if (user_id) req = req.query({ user_id: user_id });
+  if (status) req = req.query({ status: status });
+  if (book_id) req = req.query({ book_id: book_id });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function getMetadataRequest(job_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/metadata-requests/${job_id}`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function updateMetadataRequest(job_id: string, priority: number, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).patch(`/metadata-requests/${job_id}`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send({ priority });
+}
+
+export async function cancelMetadataRequest(job_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).delete(`/metadata-requests/${job_id}`);
+
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
 
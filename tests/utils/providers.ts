@@ -0,0 +1,173 @@
+import fs from 'fs';
+import http from 'http';
+import path from 'path';
+import { COVERS_DIR } from './common.js';
+
+export const MOCK_PROVIDERS_PORT = 5001;
+
+export const OPEN_LIBRARY_METADATA = {
+  title: "Alice's Adventures in Wonderland",
+  subtitle: 'Illustrated Edition',
+  description: 'Alice follows a white rabbit down a rabbit hole into a fantastical world.',
+  publisher: 'Macmillan',
+  publication_date: 1214524800000,
+  isbn: '9780000000002',
+  contributors: [
+    {
+      name: 'Lewis Carroll',
+      role: 'Author'
+    }
+  ],
+  genres: ["Children's stories", 'Fantasy fiction'],
+  series: {
+    title: 'Alice',
+    number: 1
+  },
+  page_count: 192,
+  language: 'en'
+};
+
+export const GOOGLE_BOOKS_METADATA = {
+  title: "Alice's Adventures in Wonderland",
+  description: 'A girl falls down a rabbit hole.',
+  publisher: 'Penguin',
+  publication_date: 1212278400000,
+  isbn: '9780000000019',
+  contributors: [
+    {
+      name: 'Lewis Carroll',
+      role: 'Author'
+    }
+  ],
+  genres: ['Fiction'],
+  page_count: 200,
+  language: 'en'
+};
+
+export const HTTP_PLUGIN_METADATA = {
+  title: "Alice's Adventures in Wonderland",
+  publisher: 'In-house Catalogue',
+  page_count: 120
+};
+
+export const COMMAND_PLUGIN_METADATA = {
+  title: 'Alice in Wonderland',
+  publisher: 'Catalogue Press',
+  page_count: 150
+};
+
+const GOOGLE_BOOKS_COVER_URL = `http://localhost:${MOCK_PROVIDERS_PORT}/books/content?id=GB1&zoom=1&edge=curl`;
+
+const GOOGLE_BOOKS_VOLUMES: Record<string, any[]> = {
+  "Alice's Adventures in Wonderland": [
+    {
+      volumeInfo: {
+        title: "Alice's Adventures in Wonderland",
+        authors: ['Lewis Carroll'],
+        publisher: 'Penguin',
+        publishedDate: '2008-06',
+        description: 'A girl falls down a rabbit hole.',
+        industryIdentifiers: [
+          { type: 'ISBN_10', identifier: '0000000011' },
+          { type: 'ISBN_13', identifier: '9780000000019' }
+        ],
+        pageCount: 200,
+        categories: ['Fiction'],
+        language: 'en',
+        imageLinks: { thumbnail: GOOGLE_BOOKS_COVER_URL }
+      }
+    }
+  ],
+  'The Wonderful Wizard of Oz': [
+    {
+      volumeInfo: {
+        title: 'Der Zauberer von Oz',
+        authors: ['L. Frank Baum'],
+        language: 'de'
+      }
+    },
+    {
+      volumeInfo: {
+        title: 'The Wonderful Wizard of Oz',
+        authors: ['L. Frank Baum'],
+        language: 'en'
+      }
+    }
+  ]
+};
+
+const OPEN_LIBRARY_ROUTES: Record<string, any> = {
+  '/books/OL1M.json': {
+    title: "Alice's Adventures in Wonderland",
+    subtitle: 'Illustrated Edition',
+    publishers: ['Macmillan'],
+    publish_date: 'June 27, 2008',
+    isbn_13: ['9780000000002'],
+    authors: [{ key: '/authors/OL1A' }],
+    works: [{ key: '/works/OL1W' }],
+    series: ['Alice (1)'],
+    languages: [{ key: '/languages/eng' }],
+    number_of_pages: 192,
+    covers: [1]
+  },
+  '/works/OL1W.json': {
+    description: { type: '/type/text', value: 'Alice follows a white rabbit down a rabbit hole into a fantastical world.' },
+    subjects: ['Fantasy fiction', "Children's stories"]
+  },
+  '/authors/OL1A.json': { name: 'Lewis Carroll' }
+};
+
+function openLibrarySearch(url: URL) {
+  if (url.searchParams.get('title') !== "Alice's Adventures in Wonderland") return { docs: [] };
+  return { docs: [{ cover_edition_key: 'OL1M', edition_key: ['OL1M'] }] };
+}
+
+function googleBooksSearch(url: URL) {
+  const query = url.searchParams.get('q') ?? '';
+  const title = Object.keys(GOOGLE_BOOKS_VOLUMES).find((t) => query.startsWith(`intitle:${t}`));
+  return { totalItems: title ? GOOGLE_BOOKS_VOLUMES[title].length : 0, items: title ? GOOGLE_BOOKS_VOLUMES[title] : undefined };
+}
+
+function httpPlugin(body: any, authorization?: string) {
+  if (authorization !== 'Bearer plugin_token' || body.title !== "Alice's Adventures in Wonderland") return undefined;
+  return { metadata: HTTP_PLUGIN_METADATA, cover_url: `http://localhost:${MOCK_PROVIDERS_PORT}/b/id/1-L.jpg` };
+}
+
+export function startMockProviders(): Promise<http.Server> {
+  const server = http.createServer((req, res) => {
+    const url = new URL(req.url ?? '/', `http://localhost:${MOCK_PROVIDERS_PORT}`);
+
+    if (url.pathname === '/plugin') {
+      let data = '';
+      req.on('data', (chunk) => (data += chunk));
+      req.on('end', () => {
+        const body = httpPlugin(JSON.parse(data), req.headers.authorization);
+        res.writeHead(body ? 200 : 404, { 'Content-Type': 'application/json' });
+        res.end(body ? JSON.stringify(body) : undefined);
+      });
+      return;
+    }
+
+    if (url.pathname === '/b/id/1-L.jpg' || url.pathname === '/books/content') {
+      res.writeHead(200, { 'Content-Type': 'image/jpeg' });
+      res.end(fs.readFileSync(path.join(COVERS_DIR, 'Generic.jpeg')));
+      return;
+    }
+
+    let body;
+    if (url.pathname === '/search.json') body = openLibrarySearch(url);
+    else if (url.pathname === '/books/v1/volumes') body = googleBooksSearch(url);
+    else body = OPEN_LIBRARY_ROUTES[url.pathname];
+
+    if (body === undefined) {
+      res.writeHead(404);
+      res.end();
+      return;
+    }
+
+    res.writeHead(200, { 'Content-Type': 'application/json' });
+    res.end(JSON.stringify(body));
+  });
+
+  return new Promise((resolve) => server.listen(MOCK_PROVIDERS_PORT, () => resolve(server)));
+}
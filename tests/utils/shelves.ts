@@ -3,6 +3,7 @@ import { SERVER_URL } from './common.js';
 
 export const INVALID_SHELF_NAME = 'The provided shelf name is invalid.';
 export const SHELF_NAME_CONFLICT = 'There is already a shelf with this name in your library.';
+export const SHELF_IN_TRASH = 'There is a shelf with this name in your trash, restore it instead.';
 export const SHELF_NOT_FOUND = 'The requested shelf does not exist or is not accessible.';
 export const SHELF_BOOK_CONFLICT = 'The provided book is already present in this shelf.';
 export const SHELF_BOOK_NOT_FOUND = 'The provided book does not exist in this shelf, or is not accessible.';
@@ -2,11 +2,15 @@ import request from 'supertest';
 import { SERVER_URL } from './common.js';
 
 export const INVALID_SYNC_TOKEN = 'The provided sync token is invalid.';
+export const INVALID_EXPAND_FLAG = 'The provided expand flag is invalid.';
+export const INVALID_LIMIT = 'The requested limit is invalid.';
 
-export async function sync(user_id?: string, sync_token?: any, auth?: { jwt?: string; apiKey?: string }) {
+export async function sync(user_id?: string, sync_token?: any, auth?: { jwt?: string; apiKey?: string }, expand?: any, limit?: any) {
   let req = request(SERVER_URL).get(`/sync`);
 
   if (sync_token) req = req.query({ sync_token });
+  if (expand !== undefined) req = req.query({ expand });
+  if (limit !== undefined) req = req.query({ limit });
   if (user_id) req = req.query({ user_id: user_id });
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
   if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
@@ -0,0 +1,32 @@
+import request from 'supertest';
+import { SERVER_URL } from './common.js';
+
+export const TRASH_RETENTION = 2592000000;
+
+export async function getTrash(user_id?: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).get(`/trash`);
+
+  if (user_id) req = req.query({ user_id: user_id });
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function restoreBook(book_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/books/${book_id}/restore`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
+
+export async function restoreShelf(shelf_id: string, auth?: { jwt?: string; apiKey?: string }) {
+  let req = request(SERVER_URL).post(`/shelves/${shelf_id}/restore`);
+
+  if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
+  if (auth?.apiKey) req = req.set('api-key', auth.apiKey);
+
+  return req.send();
+}
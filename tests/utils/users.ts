@@ -15,6 +15,8 @@ export const INVALID_TIMESTAMP = 'Expiration timestamp is invalid or incorrectly
 export const INVALID_PROVIDERS = 'Invalid or unsupported metadata provider selection.';
 export const MISSING_METADATA_PREFERENCE = 'Automatic metadata preference must be present.';
 export const INVALID_PREFERENCES = 'Invalid or unsupported preferences provided.';
+export const INVALID_MERGE_POLICY = 'Invalid or unsupported metadata merge policy.';
+export const INVALID_REFRESH_AGE = 'Invalid metadata refresh age.';
 export const INVALID_TOKEN = 'The provided token is invalid.';
 export const TOKEN_NOT_FOUND = 'The refresh token was not found or cannot be accessed.';
 
@@ -121,7 +123,7 @@ export async function getPreferences(user_id: string, auth?: { jwt?: string; api
   return req.send();
 }
 
-export async function updatePreferences(user_id: string, providers?: string[], automatic_metadata?: boolean, auth?: { jwt?: string; apiKey?: string }) {
+export async function updatePreferences(user_id: string, providers?: string[], automatic_metadata?: boolean, auth?: { jwt?: string; apiKey?: string }, metadata_merge_policy?: any, metadata_review?: boolean, metadata_refresh_age?: number, embed_metadata?: boolean) {
   let req = request(SERVER_URL).put(`/users/${user_id}/preferences`);
 
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
@@ -130,11 +132,15 @@ export async function updatePreferences(user_id: string, providers?: string[], a
   const body: any = {};
   if (providers !== undefined) body.metadata_providers = providers;
   if (automatic_metadata !== undefined) body.automatic_metadata = automatic_metadata;
+  if (metadata_merge_policy !== undefined) body.metadata_merge_policy = metadata_merge_policy;
+  if (metadata_review !== undefined) body.metadata_review = metadata_review;
+  if (metadata_refresh_age !== undefined) body.metadata_refresh_age = metadata_refresh_age;
+  if (embed_metadata !== undefined) body.embed_metadata = embed_metadata;
 
   return req.send(body);
 }
 
-export async function patchPreferences(user_id: string, providers?: string[], automatic_metadata?: boolean, auth?: { jwt?: string; apiKey?: string }) {
+export async function patchPreferences(user_id: string, providers?: string[], automatic_metadata?: boolean, auth?: { jwt?: string; apiKey?: string }, metadata_merge_policy?: any, metadata_review?: boolean, metadata_refresh_age?: number, embed_metadata?: boolean) {
   let req = request(SERVER_URL).patch(`/users/${user_id}/preferences`);
 
   if (auth?.jwt) req = req.auth(auth.jwt, { type: 'bearer' });
@@ -143,6 +149,10 @@ export async function patchPreferences(user_id: string, providers?: string[], au
   const body: any = {};
   if (providers !== undefined) body.metadata_providers = providers;
   if (automatic_metadata !== undefined) body.automatic_metadata = automatic_metadata;
+  if (metadata_merge_policy !== undefined) body.metadata_merge_policy = metadata_merge_policy;
+  if (metadata_review !== undefined) body.metadata_review = metadata_review;
+  if (metadata_refresh_age !== undefined) body.metadata_refresh_age = metadata_refresh_age;
+  if (embed_metadata !== undefined) body.embed_metadata = embed_metadata;
 
   return req.send(body);
 }